@@ -0,0 +1,214 @@
+//! Recording configuration changes to the audit log, and browsing it
+use ayaya_db::{
+    data::{
+        audit::{AuditFilter, AuditRecord},
+        voice_analytics::VoiceWindow,
+    },
+    entity::audit_log,
+};
+use poise::serenity_prelude::{self as serenity, Mentionable};
+use snafu::ResultExt;
+use time::OffsetDateTime;
+
+use crate::{
+    CommandResult, Context,
+    error::{DataManagerSnafu, GeneralSerenitySnafu},
+    stats::WindowChoice,
+    utils::{GuildInfo, LINES_PER_PAGE, paginate},
+};
+
+/// Longest before or after value shown in the mirror channel
+const MAX_MIRRORED_VALUE_LEN: usize = 500;
+
+/// Record a change to the configuration of the guild the command was called in, and mirror it
+/// to the guild's audit channel if it has one. Failures are logged, the change already happened.
+pub(crate) async fn audit(
+    ctx: Context<'_>,
+    action: &str,
+    before: Option<String>,
+    after: Option<String>,
+) {
+    record(ctx, GuildInfo::guild_id_or_0(ctx), action, before, after).await;
+}
+
+/// Record a change that is not about a guild, such as dashboard access
+pub(crate) async fn audit_global(
+    ctx: Context<'_>,
+    action: &str,
+    before: Option<String>,
+    after: Option<String>,
+) {
+    record(ctx, 0, action, before, after).await;
+}
+
+async fn record(
+    ctx: Context<'_>,
+    server_id: u64,
+    action: &str,
+    before: Option<String>,
+    after: Option<String>,
+) {
+    let record = AuditRecord {
+        server_id,
+        actor_id: ctx.author().id.get(),
+        action: action.to_string(),
+        before,
+        after,
+    };
+    let data_manager = &ctx.data().data_manager;
+    let entry = match data_manager
+        .audit()
+        .record(&record, OffsetDateTime::now_utc())
+        .await
+    {
+        Ok(entry) => entry,
+        Err(error) => {
+            tracing::error!("Failed to record audit entry {action} of guild {server_id}: {error}");
+            return;
+        }
+    };
+    if server_id == 0 {
+        return;
+    }
+
+    let channel_id = match data_manager.guild_settings().get_settings(server_id).await {
+        Ok(config) => config.audit_channel_id,
+        Err(error) => {
+            tracing::error!("Failed to get the audit channel of guild {server_id}: {error}");
+            return;
+        }
+    };
+    let Some(channel_id) = channel_id.map(serenity::ChannelId::new) else {
+        return;
+    };
+    // settings saved before channels were checked may point to another guild
+    let in_guild = ctx
+        .cache()
+        .guild(serenity::GuildId::new(server_id))
+        .is_some_and(|guild| guild.channels.contains_key(&channel_id));
+    if !in_guild {
+        tracing::warn!("Audit channel {channel_id} is not a channel of guild {server_id}");
+        return;
+    }
+    if let Err(error) = channel_id
+        .send_message(
+            ctx.http(),
+            serenity::CreateMessage::new().embed(audit_embed(&entry)),
+        )
+        .await
+    {
+        tracing::warn!("Failed to mirror audit entry {action} of guild {server_id}: {error}");
+    }
+}
+
+fn audit_embed(entry: &audit_log::Model) -> serenity::CreateEmbed<'static> {
+    let value = |value: &Option<String>| match value {
+        Some(value) if value.chars().count() > MAX_MIRRORED_VALUE_LEN => format!(
+            "{}…",
+            value
+                .chars()
+                .take(MAX_MIRRORED_VALUE_LEN - 1)
+                .collect::<String>()
+        ),
+        Some(value) => value.clone(),
+        None => "*none*".to_string(),
+    };
+    serenity::CreateEmbed::default()
+        .title(format!("Configuration change: {}", entry.action))
+        .description(format!(
+            "By {}",
+            serenity::UserId::new(entry.actor_id as u64).mention()
+        ))
+        .field("Before", value(&entry.before), false)
+        .field("After", value(&entry.after), false)
+        .timestamp(serenity::Timestamp::now())
+}
+
+/// Page through the configuration changes of the server, newest first.
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    ephemeral,
+    required_permissions = "MANAGE_GUILD",
+    rename = "audit",
+    category = "Admin Commands"
+)]
+pub async fn audit_log(
+    ctx: Context<'_>,
+    #[description = "Only changes whose action starts with this, eg: permissions"] action: Option<
+        String,
+    >,
+    #[description = "Only changes by this user"] user: Option<serenity::User>,
+    #[description = "Only changes in this time window"] window: Option<WindowChoice>,
+    #[description = "Show changes made outside of servers instead. Owner only."] global: Option<
+        bool,
+    >,
+) -> CommandResult {
+    ctx.defer_ephemeral().await.context(GeneralSerenitySnafu)?;
+    let global = global.unwrap_or(false);
+    if global && !ctx.framework().options().owners.contains(&ctx.author().id) {
+        ctx.reply("Only the bot's owners can see changes made outside of servers.")
+            .await
+            .context(GeneralSerenitySnafu)?;
+        return Ok(());
+    }
+    let filter = AuditFilter {
+        server_id: if global {
+            0
+        } else {
+            GuildInfo::guild_id_or_0(ctx)
+        },
+        actor_id: user.map(|user| user.id.get()),
+        action: action.map(|action| action.trim().to_string()),
+        since: window.and_then(|window| VoiceWindow::from(window).since(OffsetDateTime::now_utc())),
+    };
+
+    let audit_manager = ctx.data().data_manager.audit();
+    let page_size = LINES_PER_PAGE as u64;
+    let first_page = audit_manager
+        .get_audit_page(&filter, 0, page_size)
+        .await
+        .context(DataManagerSnafu)?;
+    if first_page.total == 0 {
+        ctx.reply("No configuration changes match the filters.")
+            .await
+            .context(GeneralSerenitySnafu)?;
+        return Ok(());
+    }
+
+    let page_count = first_page.total.div_ceil(page_size) as usize;
+    let title = format!("Audit log, {} changes", first_page.total);
+    paginate(ctx, &title, page_count, async |page| {
+        let entries = if page == 0 {
+            first_page.entries.clone()
+        } else {
+            audit_manager
+                .get_audit_page(&filter, page as u64, page_size)
+                .await
+                .context(DataManagerSnafu)?
+                .entries
+        };
+        Ok(entries
+            .iter()
+            .map(audit_line)
+            .collect::<Vec<_>>()
+            .join("\n"))
+    })
+    .await
+}
+
+fn audit_line(entry: &audit_log::Model) -> String {
+    let change = match (&entry.before, &entry.after) {
+        (Some(before), Some(after)) => format!("{before} → {after}"),
+        (None, Some(after)) => format!("→ {after}"),
+        (Some(before), None) => format!("{before} →"),
+        (None, None) => String::new(),
+    };
+    format!(
+        "<t:{}:f> {} **{}** {change}",
+        entry.created_at.unix_timestamp(),
+        serenity::UserId::new(entry.actor_id as u64).mention(),
+        entry.action
+    )
+}
@@ -1,7 +1,15 @@
 //! Command reserved for admins or specific users
+pub(crate) mod audit;
+mod permission_transfer;
+mod permissions;
+mod restrictions;
+
+use ayaya_db::error::DataError;
 use poise::serenity_prelude as serenity;
 use snafu::ResultExt;
 
+use audit::audit;
+
 use crate::{
     CommandResult, Commands, Context,
     error::{DataManagerSnafu, GeneralSerenitySnafu},
@@ -13,7 +21,15 @@ pub fn admin_commands() -> Commands {
         restrict_command_role(),
         restrict_category_role(),
         allow_user_command(),
-        list_command_restrictions(),
+        remove_command_role(),
+        remove_category_role(),
+        remove_user_command(),
+        edit_command_role(),
+        edit_category_role(),
+        edit_user_command(),
+        restrictions::list_command_restrictions(),
+        permissions::permissions(),
+        audit::audit_log(),
     ]
 }
 
@@ -24,6 +40,7 @@ pub fn admin_commands() -> Commands {
     slash_command,
     prefix_command,
     guild_only,
+    required_permissions = "MANAGE_GUILD",
     rename = "setrcr",
     category = "Admin Commands"
 )]
@@ -42,6 +59,13 @@ pub async fn restrict_command_role(
 
     match model.await {
         Ok(res) => {
+            audit(
+                ctx,
+                "permissions.command_role.add",
+                None,
+                Some(format!("command `{}` role {}", res.command, role.id)),
+            )
+            .await;
             ctx.reply(format!(
                 "Command restriction added for role `{}` & command `{}`.",
                 role.name, res.command
@@ -68,6 +92,7 @@ pub async fn restrict_command_role(
     slash_command,
     prefix_command,
     guild_only,
+    required_permissions = "MANAGE_GUILD",
     rename = "setrcatr",
     category = "Admin Commands"
 )]
@@ -86,6 +111,13 @@ pub async fn restrict_category_role(
 
     match model.await {
         Ok(res) => {
+            audit(
+                ctx,
+                "permissions.category_role.add",
+                None,
+                Some(format!("category `{}` role {}", res.category, role.id)),
+            )
+            .await;
             ctx.reply(format!(
                 "Category restriction added for role `{}` & category `{}`.",
                 role.name, res.category
@@ -113,6 +145,7 @@ pub async fn restrict_category_role(
     slash_command,
     prefix_command,
     guild_only,
+    required_permissions = "MANAGE_GUILD",
     rename = "setausercom",
     category = "Admin Commands"
 )]
@@ -132,6 +165,13 @@ pub async fn allow_user_command(
 
     match model.await {
         Ok(res) => {
+            audit(
+                ctx,
+                "permissions.user_allowed.add",
+                None,
+                Some(format!("command `{}` user {}", res.command, user.id)),
+            )
+            .await;
             ctx.reply(format!(
                 "User allowance added for user `{}` & command `{}`.",
                 user.name, res.command
@@ -154,89 +194,301 @@ pub async fn allow_user_command(
     Ok(())
 }
 
+/// Remove a command restriction added with `setrcr`
 #[poise::command(
     slash_command,
     prefix_command,
     guild_only,
-    rename = "lcr",
+    required_permissions = "MANAGE_GUILD",
+    rename = "rmrcr",
     category = "Admin Commands"
 )]
-pub async fn list_command_restrictions(
+pub async fn remove_command_role(
     ctx: Context<'_>,
     #[autocomplete = "autocomplete_command_names"] command: String,
+    role: serenity::Role,
 ) -> CommandResult {
     ctx.defer().await.context(GeneralSerenitySnafu)?;
     let mut data_manager = ctx.data().data_manager.clone();
     let guild_id = GuildInfo::guild_id_or_0(ctx);
-    // TODO: properly store command categories
-    let command_category =
-        if let Some(Some(category)) = ctx.data().command_categories_map.get(&command) {
-            category.clone()
-        } else {
-            "Uncategorized".to_string()
-        };
-
-    let allowed_users = data_manager
+
+    let removed = data_manager
         .permissions_mut()
-        .findall_user_allowed(guild_id, &command)
+        .delete_command_role_restriction(guild_id, &role.id, &command)
         .await
         .context(DataManagerSnafu)?;
 
-    let required_roles_command = data_manager
+    let msg = if removed {
+        audit(
+            ctx,
+            "permissions.command_role.remove",
+            Some(format!("command `{command}` role {}", role.id)),
+            None,
+        )
+        .await;
+        format!(
+            "Command restriction removed for role `{}` & command `{command}`.",
+            role.name
+        )
+    } else {
+        format!(
+            "Role `{}` is not required for command `{command}`.",
+            role.name
+        )
+    };
+    ctx.reply(msg).await.context(GeneralSerenitySnafu)?;
+    Ok(())
+}
+
+/// Remove a category restriction added with `setrcatr`
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    rename = "rmrcatr",
+    category = "Admin Commands"
+)]
+pub async fn remove_category_role(
+    ctx: Context<'_>,
+    #[autocomplete = "autocomplete_command_categories"] category: String,
+    role: serenity::Role,
+) -> CommandResult {
+    ctx.defer().await.context(GeneralSerenitySnafu)?;
+    let mut data_manager = ctx.data().data_manager.clone();
+    let guild_id = GuildInfo::guild_id_or_0(ctx);
+
+    let removed = data_manager
         .permissions_mut()
-        .find_command_roles_allowed(guild_id, &command)
+        .delete_category_role_restriction(guild_id, &role.id, &category)
         .await
         .context(DataManagerSnafu)?;
 
-    let required_roles_category = data_manager
+    let msg = if removed {
+        audit(
+            ctx,
+            "permissions.category_role.remove",
+            Some(format!("category `{category}` role {}", role.id)),
+            None,
+        )
+        .await;
+        format!(
+            "Category restriction removed for role `{}` & category `{category}`.",
+            role.name
+        )
+    } else {
+        format!(
+            "Role `{}` is not required for category `{category}`.",
+            role.name
+        )
+    };
+    ctx.reply(msg).await.context(GeneralSerenitySnafu)?;
+    Ok(())
+}
+
+/// Remove a user allowance added with `setausercom`
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    rename = "rmausercom",
+    category = "Admin Commands"
+)]
+pub async fn remove_user_command(
+    ctx: Context<'_>,
+    #[autocomplete = "autocomplete_command_names"] command: String,
+    user: serenity::User,
+) -> CommandResult {
+    ctx.defer().await.context(GeneralSerenitySnafu)?;
+    let mut data_manager = ctx.data().data_manager.clone();
+    let guild_id = GuildInfo::guild_id_or_0(ctx);
+
+    let removed = data_manager
         .permissions_mut()
-        .find_category_roles_allowed(guild_id, &command_category)
+        .delete_command_user_allowed(guild_id, user.id.get(), &command)
         .await
         .context(DataManagerSnafu)?;
 
-    let mut message = serenity::MessageBuilder::default();
-    message = message.push_line(format!("# Restrictions info for command: {}", &command).as_str());
+    let msg = if removed {
+        audit(
+            ctx,
+            "permissions.user_allowed.remove",
+            Some(format!("command `{command}` user {}", user.id)),
+            None,
+        )
+        .await;
+        format!(
+            "User allowance removed for user `{}` & command `{command}`.",
+            user.name
+        )
+    } else {
+        format!(
+            "User `{}` has no allowance for command `{command}`.",
+            user.name
+        )
+    };
+    ctx.reply(msg).await.context(GeneralSerenitySnafu)?;
+    Ok(())
+}
 
-    message = message.push_line("### Allowed Users");
-    for (i, model) in allowed_users.iter().enumerate() {
-        let user = serenity::UserId::new(model.user_id as u64)
-            .to_user(ctx)
-            .await
-            .context(GeneralSerenitySnafu)?;
-        message = message.push_line(format!("{}. {}", i + 1, user.name).as_str());
-    }
-    message = message.push_line("### Command Roles");
-    let roles = serenity::GuildId::new(guild_id)
-        .roles(ctx.http())
-        .await
-        .context(GeneralSerenitySnafu)?;
-    for (i, model) in required_roles_command.iter().enumerate() {
-        let role = if let Some(role) = roles.get(&serenity::RoleId::new(model.role_id as u64)) {
-            role.name.to_string()
-        } else {
-            "Unknown Role".to_string()
-        };
-        message = message.push_line(format!("{}. {}", i + 1, role).as_str());
-    }
-    message = message.push_line("### Category Roles");
-    for (i, model) in required_roles_category.iter().enumerate() {
-        let role = if let Some(role) = roles.get(&serenity::RoleId::new(model.role_id as u64)) {
-            role.name.to_string()
-        } else {
-            "Unknown Role".to_string()
-        };
-        message = message.push_line(format!("{}. {}", i + 1, role).as_str());
-    }
+/// Replace the role required for a command
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    rename = "editrcr",
+    category = "Admin Commands"
+)]
+pub async fn edit_command_role(
+    ctx: Context<'_>,
+    #[autocomplete = "autocomplete_command_names"] command: String,
+    old_role: serenity::Role,
+    new_role: serenity::Role,
+) -> CommandResult {
+    ctx.defer().await.context(GeneralSerenitySnafu)?;
+    let mut data_manager = ctx.data().data_manager.clone();
+    let guild_id = GuildInfo::guild_id_or_0(ctx);
 
-    let embed = serenity::CreateEmbed::default().description(message.to_string());
-    ctx.send(poise::CreateReply::default().embed(embed).reply(true))
-        .await
-        .context(GeneralSerenitySnafu)?;
+    let model = data_manager
+        .permissions_mut()
+        .edit_command_role_restriction(guild_id, &command, &old_role.id, &new_role.id);
+
+    let msg = match model.await {
+        Ok(res) => {
+            audit(
+                ctx,
+                "permissions.command_role.edit",
+                Some(format!("command `{command}` role {}", old_role.id)),
+                Some(format!("command `{}` role {}", res.command, new_role.id)),
+            )
+            .await;
+            format!(
+                "Command `{}` now requires role `{}` instead of `{}`.",
+                res.command, new_role.name, old_role.name
+            )
+        }
+        Err(DataError::NotFound { .. }) => format!(
+            "Role `{}` is not required for command `{command}`.",
+            old_role.name
+        ),
+        Err(DataError::NewCommandRoleRestrictionDuplicate) => format!(
+            "Role `{}` is already required for command `{command}`.",
+            new_role.name
+        ),
+        Err(e) => return Err(e).context(DataManagerSnafu),
+    };
+    ctx.reply(msg).await.context(GeneralSerenitySnafu)?;
+    Ok(())
+}
+
+/// Replace the role required for a command category
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    rename = "editrcatr",
+    category = "Admin Commands"
+)]
+pub async fn edit_category_role(
+    ctx: Context<'_>,
+    #[autocomplete = "autocomplete_command_categories"] category: String,
+    old_role: serenity::Role,
+    new_role: serenity::Role,
+) -> CommandResult {
+    ctx.defer().await.context(GeneralSerenitySnafu)?;
+    let mut data_manager = ctx.data().data_manager.clone();
+    let guild_id = GuildInfo::guild_id_or_0(ctx);
 
+    let model = data_manager
+        .permissions_mut()
+        .edit_category_role_restriction(guild_id, &category, &old_role.id, &new_role.id);
+
+    let msg = match model.await {
+        Ok(res) => {
+            audit(
+                ctx,
+                "permissions.category_role.edit",
+                Some(format!("category `{category}` role {}", old_role.id)),
+                Some(format!("category `{}` role {}", res.category, new_role.id)),
+            )
+            .await;
+            format!(
+                "Category `{}` now requires role `{}` instead of `{}`.",
+                res.category, new_role.name, old_role.name
+            )
+        }
+        Err(DataError::NotFound { .. }) => format!(
+            "Role `{}` is not required for category `{category}`.",
+            old_role.name
+        ),
+        Err(DataError::NewCategoryRoleRestrictionDuplicate) => format!(
+            "Role `{}` is already required for category `{category}`.",
+            new_role.name
+        ),
+        Err(e) => return Err(e).context(DataManagerSnafu),
+    };
+    ctx.reply(msg).await.context(GeneralSerenitySnafu)?;
+    Ok(())
+}
+
+/// Move a user allowance to another command
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    rename = "editausercom",
+    category = "Admin Commands"
+)]
+pub async fn edit_user_command(
+    ctx: Context<'_>,
+    user: serenity::User,
+    #[autocomplete = "autocomplete_command_names"] command: String,
+    #[autocomplete = "autocomplete_command_names"] new_command: String,
+) -> CommandResult {
+    ctx.defer().await.context(GeneralSerenitySnafu)?;
+    let mut data_manager = ctx.data().data_manager.clone();
+    let guild_id = GuildInfo::guild_id_or_0(ctx);
+
+    let model = data_manager.permissions_mut().edit_command_user_allowed(
+        guild_id,
+        user.id.get(),
+        &command,
+        &new_command,
+    );
+
+    let msg = match model.await {
+        Ok(res) => {
+            audit(
+                ctx,
+                "permissions.user_allowed.edit",
+                Some(format!("command `{command}` user {}", user.id)),
+                Some(format!("command `{}` user {}", res.command, user.id)),
+            )
+            .await;
+            format!(
+                "User `{}` is now allowed to use command `{}` instead of `{command}`.",
+                user.name, res.command
+            )
+        }
+        Err(DataError::NotFound { .. }) => format!(
+            "User `{}` has no allowance for command `{command}`.",
+            user.name
+        ),
+        Err(DataError::NewCommandAllowedUserDuplicate) => format!(
+            "User `{}` is already allowed to use command `{new_command}`.",
+            user.name
+        ),
+        Err(e) => return Err(e).context(DataManagerSnafu),
+    };
+    ctx.reply(msg).await.context(GeneralSerenitySnafu)?;
     Ok(())
 }
 
-async fn autocomplete_command_categories<'a>(
+pub(crate) async fn autocomplete_command_categories<'a>(
     ctx: Context<'_>,
     partial: &'_ str,
 ) -> serenity::CreateAutocompleteResponse<'a> {
@@ -0,0 +1,261 @@
+//! Exporting the role restrictions and user allowances of a server to a file, and importing them
+//! into another server
+use ayaya_db::data::permission_transfer::{
+    GuildNames, ImportPlan, PERMISSION_EXPORT_VERSION, PermissionExport, PermissionRow,
+};
+use poise::serenity_prelude::{self as serenity, Mentionable};
+use snafu::ResultExt;
+
+use super::audit::audit;
+use crate::{
+    CommandResult, Context,
+    error::{BotError, DataManagerSnafu, DownloadAttachmentSnafu, GeneralSerenitySnafu},
+    utils::{GuildInfo, get_guild},
+};
+
+/// Most changes listed in the import preview
+const MAX_PREVIEW_LINES: usize = 30;
+
+/// Export the role restrictions and user allowances of this server to a file.
+///
+/// Import it in another server with `/permissions import`. Deny rules and channel limits are not
+/// exported.
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    category = "Admin Commands"
+)]
+pub async fn export(ctx: Context<'_>) -> CommandResult {
+    ctx.defer().await.context(GeneralSerenitySnafu)?;
+    let guild_id = GuildInfo::guild_id_or_0(ctx);
+    let mut data_manager = ctx.data().data_manager.clone();
+    let rows = data_manager
+        .permissions_mut()
+        .guild_permission_rows(guild_id)
+        .await
+        .context(DataManagerSnafu)?;
+    let (mut names, _) = guild_names(ctx)?;
+    // members missing from the cache are looked up, so the export names every user
+    for row in &rows {
+        if let PermissionRow::AllowedUser { user_id, .. } = row
+            && !names.users.iter().any(|(id, _)| id == user_id)
+            && let Ok(user) = serenity::UserId::new(*user_id).to_user(ctx.http()).await
+        {
+            names.users.push((*user_id, user.name.to_string()));
+        }
+    }
+    let export = PermissionExport::from_rows(&rows, &names);
+    let json = serde_json::to_string_pretty(&export).unwrap_or_default();
+
+    let attachment = serenity::CreateAttachment::bytes(
+        json.into_bytes(),
+        format!("permissions-{guild_id}.json"),
+    );
+    ctx.send(
+        poise::CreateReply::default()
+            .content(format!("Exported {} rules.", rows.len()))
+            .attachment(attachment),
+    )
+    .await
+    .context(GeneralSerenitySnafu)?;
+    Ok(())
+}
+
+/// Import rules exported with `/permissions export`, matching roles and users by name.
+///
+/// Shows the rules that would be added first, and only adds them once confirmed. Existing rules
+/// are kept.
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    category = "Admin Commands"
+)]
+pub async fn import(
+    ctx: Context<'_>,
+    #[description = "A file made by /permissions export"] file: serenity::Attachment,
+) -> CommandResult {
+    ctx.defer().await.context(GeneralSerenitySnafu)?;
+    let guild_id = GuildInfo::guild_id_or_0(ctx);
+    let downloaded = file.download().await.context(DownloadAttachmentSnafu)?;
+    let export = match serde_json::from_slice::<PermissionExport>(&downloaded) {
+        Ok(export) if export.version > PERMISSION_EXPORT_VERSION => {
+            ctx.reply("This file was exported by a newer Ayaya, it can't be imported here.")
+                .await
+                .context(GeneralSerenitySnafu)?;
+            return Ok(());
+        }
+        Ok(export) => export,
+        Err(error) => {
+            ctx.reply(format!(
+                "This file is not a permission export: {error}. Make one with /permissions export."
+            ))
+            .await
+            .context(GeneralSerenitySnafu)?;
+            return Ok(());
+        }
+    };
+
+    let mut data_manager = ctx.data().data_manager.clone();
+    let existing = data_manager
+        .permissions_mut()
+        .guild_permission_rows(guild_id)
+        .await
+        .context(DataManagerSnafu)?;
+    let (names, every_member_cached) = guild_names(ctx)?;
+    let plan = export.plan_import(&names, &existing);
+    let preview = plan_preview(&plan, every_member_cached);
+    if plan.to_add.is_empty() {
+        ctx.send(poise::CreateReply::default().embed(preview.title("Nothing to import")))
+            .await
+            .context(GeneralSerenitySnafu)?;
+        return Ok(());
+    }
+
+    let ctx_id = ctx.id();
+    let apply_id = format!("{ctx_id}_apply");
+    let cancel_id = format!("{ctx_id}_cancel");
+    let buttons = vec![
+        serenity::CreateButton::new(&apply_id)
+            .label("Import")
+            .style(serenity::ButtonStyle::Success),
+        serenity::CreateButton::new(&cancel_id)
+            .label("Cancel")
+            .style(serenity::ButtonStyle::Secondary),
+    ];
+    let reply = ctx
+        .send(
+            poise::CreateReply::default()
+                .embed(preview.clone().title("Import preview"))
+                .components(vec![serenity::CreateComponent::ActionRow(
+                    serenity::CreateActionRow::Buttons(buttons.into()),
+                )]),
+        )
+        .await
+        .context(GeneralSerenitySnafu)?;
+
+    let author_id = ctx.author().id;
+    let press = serenity::collector::ComponentInteractionCollector::new(ctx.serenity_context())
+        .filter(move |press| {
+            press.user.id == author_id && press.data.custom_id.starts_with(&ctx_id.to_string())
+        })
+        .timeout(std::time::Duration::from_mins(2))
+        .await;
+    let title = match press {
+        Some(press) if press.data.custom_id == apply_id => {
+            press
+                .create_response(ctx.http(), serenity::CreateInteractionResponse::Acknowledge)
+                .await
+                .context(GeneralSerenitySnafu)?;
+            let mut added = 0;
+            for row in &plan.to_add {
+                if data_manager
+                    .permissions_mut()
+                    .add_permission_row(guild_id, row)
+                    .await
+                    .context(DataManagerSnafu)?
+                {
+                    added += 1;
+                }
+            }
+            tracing::info!("Imported {added} permission rules into guild {guild_id}");
+            audit(
+                ctx,
+                "permissions.import",
+                None,
+                Some(format!("{added} rules from {}", file.filename)),
+            )
+            .await;
+            format!("Imported {added} rules")
+        }
+        Some(press) => {
+            press
+                .create_response(ctx.http(), serenity::CreateInteractionResponse::Acknowledge)
+                .await
+                .context(GeneralSerenitySnafu)?;
+            "Import canceled".to_string()
+        }
+        None => "Import timed out, nothing was imported".to_string(),
+    };
+    reply
+        .edit(
+            ctx,
+            poise::CreateReply::default()
+                .embed(preview.title(title))
+                .components(vec![]),
+        )
+        .await
+        .context(GeneralSerenitySnafu)?;
+    Ok(())
+}
+
+/// The roles and members of the guild from the cache, and whether every member is cached
+fn guild_names(ctx: Context<'_>) -> Result<(GuildNames, bool), BotError> {
+    let guild = get_guild(ctx)?;
+    let names = GuildNames {
+        roles: guild
+            .roles
+            .iter()
+            .map(|role| (role.id.get(), role.name.to_string()))
+            .collect(),
+        users: guild
+            .members
+            .iter()
+            .map(|member| (member.user.id.get(), member.user.name.to_string()))
+            .collect(),
+    };
+    let every_member_cached = guild.members.len() as u64 >= guild.member_count;
+    Ok((names, every_member_cached))
+}
+
+/// The changes of an import. Users are only matched among cached members, so unmatched users
+/// may still be in the guild when `every_member_cached` is false.
+fn plan_preview(plan: &ImportPlan, every_member_cached: bool) -> serenity::CreateEmbed<'static> {
+    let mut lines = plan
+        .to_add
+        .iter()
+        .map(|row| match row {
+            PermissionRow::CommandRole { command, role_id } => format!(
+                "+ command `{command}` requires {}",
+                serenity::RoleId::new(*role_id).mention()
+            ),
+            PermissionRow::CategoryRole { category, role_id } => format!(
+                "+ category `{category}` requires {}",
+                serenity::RoleId::new(*role_id).mention()
+            ),
+            PermissionRow::AllowedUser { command, user_id } => format!(
+                "+ command `{command}` allows {}",
+                serenity::UserId::new(*user_id).mention()
+            ),
+        })
+        .chain(
+            plan.unresolved
+                .iter()
+                .map(|description| format!("? {description}, no match here")),
+        )
+        .collect::<Vec<_>>();
+    let hidden = lines.len().saturating_sub(MAX_PREVIEW_LINES);
+    lines.truncate(MAX_PREVIEW_LINES);
+    if hidden > 0 {
+        lines.push(format!("And {hidden} more."));
+    }
+    if !every_member_cached && !plan.unresolved.is_empty() {
+        lines.push(
+            "Only cached members were matched, users of this server missing from the cache were \
+             skipped."
+                .to_string(),
+        );
+    }
+
+    serenity::CreateEmbed::default()
+        .description(lines.join("\n"))
+        .footer(serenity::CreateEmbedFooter::new(format!(
+            "{} to add, {} already here, {} without a matching role or user",
+            plan.to_add.len(),
+            plan.already_present,
+            plan.unresolved.len()
+        )))
+}
@@ -0,0 +1,277 @@
+//! Deny rules, channel scopes, explanations of permission decisions and permission transfers
+use ayaya_db::{
+    data::permission_rules::{ChannelKind, DenyTarget, RuleScope},
+    error::DataError,
+};
+use poise::serenity_prelude as serenity;
+use serenity::Mentionable;
+use snafu::ResultExt;
+
+use super::{
+    audit::audit,
+    autocomplete_command_categories,
+    permission_transfer::{export, import},
+};
+use crate::{
+    CommandResult, Context,
+    error::{DataManagerSnafu, GeneralSerenitySnafu},
+    utils::{GuildInfo, autocomplete_command_names, command_permission, describe_rule},
+};
+
+/// Deny rules and channel limits of commands. This command must be called with a subcommand.
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    subcommands("deny", "channel", "explain", "export", "import"),
+    category = "Admin Commands"
+)]
+pub async fn permissions(_ctx: Context<'_>) -> CommandResult {
+    Ok(())
+}
+
+/// Deny a user or role a command or command category, even if a role would allow it.
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    category = "Admin Commands"
+)]
+pub async fn deny(
+    ctx: Context<'_>,
+    #[description = "The command to deny"]
+    #[autocomplete = "autocomplete_command_names"]
+    command: Option<String>,
+    #[description = "The command category to deny"]
+    #[autocomplete = "autocomplete_command_categories"]
+    category: Option<String>,
+    #[description = "The user to deny"] user: Option<serenity::User>,
+    #[description = "The role to deny"] role: Option<serenity::Role>,
+    #[description = "Remove the deny rule instead"] remove: Option<bool>,
+) -> CommandResult {
+    ctx.defer().await.context(GeneralSerenitySnafu)?;
+    let Some((scope, name)) = rule_scope(command, category) else {
+        ctx.reply("Give either a command or a command category.")
+            .await
+            .context(GeneralSerenitySnafu)?;
+        return Ok(());
+    };
+    let (target, target_name) = match (user, role) {
+        (Some(user), None) => (DenyTarget::User(user.id.get()), user.mention().to_string()),
+        (None, Some(role)) => (DenyTarget::Role(role.id.get()), role.mention().to_string()),
+        _ => {
+            ctx.reply("Give either a user or a role.")
+                .await
+                .context(GeneralSerenitySnafu)?;
+            return Ok(());
+        }
+    };
+    let mut data_manager = ctx.data().data_manager.clone();
+    let guild_id = GuildInfo::guild_id_or_0(ctx);
+
+    let message = if remove.unwrap_or(false) {
+        let removed = data_manager
+            .permissions_mut()
+            .delete_deny_rule(guild_id, target, scope, &name)
+            .await
+            .context(DataManagerSnafu)?;
+        if removed {
+            audit(
+                ctx,
+                &format!("permissions.deny.{}.remove", scope.as_str()),
+                Some(format!("`{name}` {} {}", target.kind(), target.id())),
+                None,
+            )
+            .await;
+            format!(
+                "{target_name} is no longer denied the {} `{name}`.",
+                scope.as_str()
+            )
+        } else {
+            format!(
+                "{target_name} was not denied the {} `{name}`.",
+                scope.as_str()
+            )
+        }
+    } else {
+        match data_manager
+            .permissions_mut()
+            .new_deny_rule(guild_id, target, scope, &name)
+            .await
+        {
+            Ok(_) => {
+                audit(
+                    ctx,
+                    &format!("permissions.deny.{}.add", scope.as_str()),
+                    None,
+                    Some(format!("`{name}` {} {}", target.kind(), target.id())),
+                )
+                .await;
+                format!(
+                    "{target_name} is now denied the {} `{name}`.",
+                    scope.as_str()
+                )
+            }
+            Err(DataError::DuplicateEntry { .. }) => {
+                format!(
+                    "{target_name} is already denied the {} `{name}`.",
+                    scope.as_str()
+                )
+            }
+            Err(e) => return Err(e).context(DataManagerSnafu),
+        }
+    };
+    ctx.reply(message).await.context(GeneralSerenitySnafu)?;
+    Ok(())
+}
+
+/// Limit a command or command category to channels. Each call adds a channel to the list.
+///
+/// With text channels, the command can only be called from one of them. With voice channels,
+/// the caller must be in one of them.
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    category = "Admin Commands"
+)]
+pub async fn channel(
+    ctx: Context<'_>,
+    #[description = "The command to limit"]
+    #[autocomplete = "autocomplete_command_names"]
+    command: Option<String>,
+    #[description = "The command category to limit"]
+    #[autocomplete = "autocomplete_command_categories"]
+    category: Option<String>,
+    #[description = "The text channel the command can be called from"]
+    #[channel_types("Text")]
+    text_channel: Option<serenity::GuildChannel>,
+    #[description = "The voice channel the caller must be in"]
+    #[channel_types("Voice", "Stage")]
+    voice_channel: Option<serenity::GuildChannel>,
+    #[description = "Remove the channel from the list instead"] remove: Option<bool>,
+) -> CommandResult {
+    ctx.defer().await.context(GeneralSerenitySnafu)?;
+    let Some((scope, name)) = rule_scope(command, category) else {
+        ctx.reply("Give either a command or a command category.")
+            .await
+            .context(GeneralSerenitySnafu)?;
+        return Ok(());
+    };
+    let (kind, channel_id) = match (text_channel, voice_channel) {
+        (Some(channel), None) => (ChannelKind::Text, channel.id),
+        (None, Some(channel)) => (ChannelKind::Voice, channel.id),
+        _ => {
+            ctx.reply("Give either a text channel or a voice channel.")
+                .await
+                .context(GeneralSerenitySnafu)?;
+            return Ok(());
+        }
+    };
+    let mut data_manager = ctx.data().data_manager.clone();
+    let guild_id = GuildInfo::guild_id_or_0(ctx);
+    let channel_name = channel_id.mention();
+
+    let message = if remove.unwrap_or(false) {
+        let removed = data_manager
+            .permissions_mut()
+            .delete_channel_scope(guild_id, scope, &name, channel_id.get())
+            .await
+            .context(DataManagerSnafu)?;
+        if removed {
+            audit(
+                ctx,
+                &format!("permissions.channel.{}.remove", scope.as_str()),
+                Some(format!("`{name}` {} channel {channel_id}", kind.as_str())),
+                None,
+            )
+            .await;
+            format!(
+                "The {} `{name}` is no longer limited to {channel_name}.",
+                scope.as_str()
+            )
+        } else {
+            format!(
+                "The {} `{name}` was not limited to {channel_name}.",
+                scope.as_str()
+            )
+        }
+    } else {
+        match data_manager
+            .permissions_mut()
+            .new_channel_scope(guild_id, scope, &name, kind, channel_id.get())
+            .await
+        {
+            Ok(_) => {
+                audit(
+                    ctx,
+                    &format!("permissions.channel.{}.add", scope.as_str()),
+                    None,
+                    Some(format!("`{name}` {} channel {channel_id}", kind.as_str())),
+                )
+                .await;
+                format!(
+                    "The {} `{name}` is now limited to {channel_name}.",
+                    scope.as_str()
+                )
+            }
+            Err(DataError::DuplicateEntry { .. }) => {
+                format!(
+                    "The {} `{name}` is already limited to {channel_name}.",
+                    scope.as_str()
+                )
+            }
+            Err(e) => return Err(e).context(DataManagerSnafu),
+        }
+    };
+    ctx.reply(message).await.context(GeneralSerenitySnafu)?;
+    Ok(())
+}
+
+/// Show whether a user may call a command from this channel, and which rule decided.
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    required_permissions = "MANAGE_GUILD",
+    category = "Admin Commands"
+)]
+pub async fn explain(
+    ctx: Context<'_>,
+    #[description = "The user calling the command"] user: serenity::User,
+    #[description = "The command"]
+    #[autocomplete = "autocomplete_command_names"]
+    command: String,
+) -> CommandResult {
+    ctx.defer().await.context(GeneralSerenitySnafu)?;
+    let command_category =
+        if let Some(Some(category)) = ctx.data().command_categories_map.get(&command) {
+            category.clone()
+        } else {
+            "Unknown".to_string()
+        };
+
+    let rule = command_permission(ctx, user.id, &command, &command_category).await?;
+    let verdict = if rule.allows() { "may" } else { "may not" };
+    ctx.reply(format!(
+        "{} **{verdict}** use `{command}` in {}: {}.",
+        user.mention(),
+        ctx.channel_id().mention(),
+        describe_rule(&rule)
+    ))
+    .await
+    .context(GeneralSerenitySnafu)?;
+    Ok(())
+}
+
+/// The scope of a rule given either a command or a category
+fn rule_scope(command: Option<String>, category: Option<String>) -> Option<(RuleScope, String)> {
+    match (command, category) {
+        (Some(command), None) => Some((RuleScope::Command, command)),
+        (None, Some(category)) => Some((RuleScope::Category, category)),
+        _ => None,
+    }
+}
@@ -0,0 +1,403 @@
+//! Interactive panel listing the restrictions of a command or category, to add required roles
+//! and remove entries without typing out each command
+use ayaya_db::{
+    data::permission_rules::{ChannelKind, DenyTarget, RuleScope},
+    error::DataError,
+};
+use poise::serenity_prelude::{self as serenity, Mentionable};
+use snafu::ResultExt;
+
+use super::{audit::audit, autocomplete_command_categories};
+use crate::{
+    CommandResult, Context,
+    error::{BotError, DataManagerSnafu, GeneralSerenitySnafu},
+    utils::{GuildInfo, autocomplete_command_names},
+};
+
+/// Most entries shown with a remove button, to stay under the component limit of a message
+const MAX_PANEL_ENTRIES: usize = 10;
+
+/// A restriction shown in the panel
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PanelEntry {
+    RequiredRole(u64),
+    AllowedUser(u64),
+    Deny(DenyTarget),
+    Channel { kind: ChannelKind, channel_id: u64 },
+}
+
+impl PanelEntry {
+    fn describe(self) -> String {
+        match self {
+            Self::RequiredRole(role_id) => {
+                format!("Requires {}", serenity::RoleId::new(role_id).mention())
+            }
+            Self::AllowedUser(user_id) => {
+                format!("Always allows {}", serenity::UserId::new(user_id).mention())
+            }
+            Self::Deny(DenyTarget::User(user_id)) => {
+                format!("Denies {}", serenity::UserId::new(user_id).mention())
+            }
+            Self::Deny(DenyTarget::Role(role_id)) => {
+                format!("Denies {}", serenity::RoleId::new(role_id).mention())
+            }
+            Self::Channel {
+                kind: ChannelKind::Text,
+                channel_id,
+            } => format!(
+                "Only from {}",
+                serenity::ChannelId::new(channel_id).mention()
+            ),
+            Self::Channel {
+                kind: ChannelKind::Voice,
+                channel_id,
+            } => format!(
+                "Only while in {}",
+                serenity::ChannelId::new(channel_id).mention()
+            ),
+        }
+    }
+}
+
+/// Show and edit the restrictions of a command or command category.
+///
+/// Pick roles to require them, or press the button next to an entry to remove it.
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    ephemeral,
+    required_permissions = "MANAGE_GUILD",
+    rename = "lcr",
+    category = "Admin Commands"
+)]
+pub async fn list_command_restrictions(
+    ctx: Context<'_>,
+    #[description = "The command"]
+    #[autocomplete = "autocomplete_command_names"]
+    command: Option<String>,
+    #[description = "The command category"]
+    #[autocomplete = "autocomplete_command_categories"]
+    category: Option<String>,
+) -> CommandResult {
+    ctx.defer_ephemeral().await.context(GeneralSerenitySnafu)?;
+    let (scope, name) = match (command, category) {
+        (Some(command), None) => (RuleScope::Command, command),
+        (None, Some(category)) => (RuleScope::Category, category),
+        _ => {
+            ctx.reply("Give either a command or a command category.")
+                .await
+                .context(GeneralSerenitySnafu)?;
+            return Ok(());
+        }
+    };
+    let guild_id = GuildInfo::guild_id_or_0(ctx);
+    let ctx_id = ctx.id();
+    let add_role_id = format!("{ctx_id}_add_role");
+    let remove_prefix = format!("{ctx_id}_remove_");
+
+    let mut entries = load_entries(ctx, guild_id, scope, &name).await?;
+    let reply = poise::CreateReply::default()
+        .components(panel(
+            ctx,
+            scope,
+            &name,
+            &entries,
+            &add_role_id,
+            &remove_prefix,
+        ))
+        .flags(serenity::MessageFlags::EPHEMERAL | serenity::MessageFlags::IS_COMPONENTS_V2);
+    ctx.send(reply).await.context(GeneralSerenitySnafu)?;
+
+    let author_id = ctx.author().id;
+    while let Some(press) =
+        serenity::collector::ComponentInteractionCollector::new(ctx.serenity_context())
+            .filter(move |press| {
+                press.user.id == author_id && press.data.custom_id.starts_with(&ctx_id.to_string())
+            })
+            .timeout(std::time::Duration::from_mins(3))
+            .await
+    {
+        if press.data.custom_id == add_role_id {
+            if let serenity::ComponentInteractionDataKind::RoleSelect { values } = &press.data.kind
+            {
+                for role_id in values.iter() {
+                    add_required_role(ctx, guild_id, scope, &name, *role_id).await?;
+                }
+            }
+        } else if let Some(entry) = press
+            .data
+            .custom_id
+            .strip_prefix(&remove_prefix)
+            .and_then(|index| index.parse::<usize>().ok())
+            .and_then(|index| entries.get(index).copied())
+        {
+            remove_entry(ctx, guild_id, scope, &name, entry).await?;
+        } else {
+            continue;
+        }
+
+        entries = load_entries(ctx, guild_id, scope, &name).await?;
+        let response = serenity::CreateInteractionResponseMessage::new().components(panel(
+            ctx,
+            scope,
+            &name,
+            &entries,
+            &add_role_id,
+            &remove_prefix,
+        ));
+        press
+            .create_response(
+                ctx.http(),
+                serenity::CreateInteractionResponse::UpdateMessage(response),
+            )
+            .await
+            .context(GeneralSerenitySnafu)?;
+    }
+    Ok(())
+}
+
+/// Every restriction of the command or category. A command's entries don't include its
+/// category's ones.
+async fn load_entries(
+    ctx: Context<'_>,
+    guild_id: u64,
+    scope: RuleScope,
+    name: &str,
+) -> Result<Vec<PanelEntry>, BotError> {
+    let mut data_manager = ctx.data().data_manager.clone();
+    let permissions = data_manager.permissions_mut();
+    let mut entries = Vec::new();
+
+    match scope {
+        RuleScope::Command => {
+            let roles = permissions
+                .find_command_roles_allowed(guild_id, name)
+                .await
+                .context(DataManagerSnafu)?;
+            entries.extend(
+                roles
+                    .iter()
+                    .map(|model| PanelEntry::RequiredRole(model.role_id as u64)),
+            );
+            let users = permissions
+                .findall_user_allowed(guild_id, name)
+                .await
+                .context(DataManagerSnafu)?;
+            entries.extend(
+                users
+                    .iter()
+                    .map(|model| PanelEntry::AllowedUser(model.user_id as u64)),
+            );
+        }
+        RuleScope::Category => {
+            let roles = permissions
+                .find_category_roles_allowed(guild_id, name)
+                .await
+                .context(DataManagerSnafu)?;
+            entries.extend(
+                roles
+                    .iter()
+                    .map(|model| PanelEntry::RequiredRole(model.role_id as u64)),
+            );
+        }
+    }
+
+    let denies = permissions
+        .find_deny_rules(guild_id, scope, name)
+        .await
+        .context(DataManagerSnafu)?;
+    entries.extend(denies.iter().filter_map(|model| {
+        DenyTarget::from_parts(&model.target_kind, model.target_id as u64).map(PanelEntry::Deny)
+    }));
+    let channels = permissions
+        .find_channel_scopes(guild_id, scope, name)
+        .await
+        .context(DataManagerSnafu)?;
+    entries.extend(channels.iter().filter_map(|model| {
+        ChannelKind::parse(&model.channel_kind).map(|kind| PanelEntry::Channel {
+            kind,
+            channel_id: model.channel_id as u64,
+        })
+    }));
+
+    Ok(entries)
+}
+
+async fn add_required_role(
+    ctx: Context<'_>,
+    guild_id: u64,
+    scope: RuleScope,
+    name: &str,
+    role_id: serenity::RoleId,
+) -> CommandResult {
+    let mut data_manager = ctx.data().data_manager.clone();
+    let permissions = data_manager.permissions_mut();
+    let result = match scope {
+        RuleScope::Command => permissions
+            .new_command_role_restriction(guild_id, &role_id, name)
+            .await
+            .map(|_| ()),
+        RuleScope::Category => permissions
+            .new_category_role_restriction(guild_id, &role_id, name)
+            .await
+            .map(|_| ()),
+    };
+    match result {
+        Ok(()) => {
+            audit(
+                ctx,
+                &format!("permissions.{}_role.add", scope.as_str()),
+                None,
+                Some(format!("{} `{name}` role {role_id}", scope.as_str())),
+            )
+            .await;
+            Ok(())
+        }
+        // picking a role that is already required changes nothing
+        Err(
+            DataError::NewCommandRoleRestrictionDuplicate
+            | DataError::NewCategoryRoleRestrictionDuplicate,
+        ) => Ok(()),
+        Err(e) => Err(e).context(DataManagerSnafu),
+    }
+}
+
+async fn remove_entry(
+    ctx: Context<'_>,
+    guild_id: u64,
+    scope: RuleScope,
+    name: &str,
+    entry: PanelEntry,
+) -> CommandResult {
+    let mut data_manager = ctx.data().data_manager.clone();
+    let permissions = data_manager.permissions_mut();
+    let (removed, action, before) = match entry {
+        PanelEntry::RequiredRole(role_id) => {
+            let role_id = serenity::RoleId::new(role_id);
+            let removed = match scope {
+                RuleScope::Command => {
+                    permissions
+                        .delete_command_role_restriction(guild_id, &role_id, name)
+                        .await
+                }
+                RuleScope::Category => {
+                    permissions
+                        .delete_category_role_restriction(guild_id, &role_id, name)
+                        .await
+                }
+            }
+            .context(DataManagerSnafu)?;
+            (
+                removed,
+                format!("permissions.{}_role.remove", scope.as_str()),
+                format!("{} `{name}` role {role_id}", scope.as_str()),
+            )
+        }
+        PanelEntry::AllowedUser(user_id) => {
+            let removed = permissions
+                .delete_command_user_allowed(guild_id, user_id, name)
+                .await
+                .context(DataManagerSnafu)?;
+            (
+                removed,
+                "permissions.user_allowed.remove".to_string(),
+                format!("command `{name}` user {user_id}"),
+            )
+        }
+        PanelEntry::Deny(target) => {
+            let removed = permissions
+                .delete_deny_rule(guild_id, target, scope, name)
+                .await
+                .context(DataManagerSnafu)?;
+            (
+                removed,
+                format!("permissions.deny.{}.remove", scope.as_str()),
+                format!("`{name}` {} {}", target.kind(), target.id()),
+            )
+        }
+        PanelEntry::Channel { kind, channel_id } => {
+            let removed = permissions
+                .delete_channel_scope(guild_id, scope, name, channel_id)
+                .await
+                .context(DataManagerSnafu)?;
+            (
+                removed,
+                format!("permissions.channel.{}.remove", scope.as_str()),
+                format!("`{name}` {} channel {channel_id}", kind.as_str()),
+            )
+        }
+    };
+    if removed {
+        audit(ctx, &action, Some(before), None).await;
+    }
+    Ok(())
+}
+
+fn panel(
+    ctx: Context<'_>,
+    scope: RuleScope,
+    name: &str,
+    entries: &[PanelEntry],
+    add_role_id: &str,
+    remove_prefix: &str,
+) -> Vec<serenity::CreateComponent<'static>> {
+    let mut heading = format!("## Restrictions of {} `{name}`", scope.as_str());
+    if scope == RuleScope::Command
+        && let Some(Some(category)) = ctx.data().command_categories_map.get(name)
+    {
+        heading.push_str(&format!(
+            "\nIts category `{category}` can have restrictions of its own."
+        ));
+    }
+    let mut components = vec![serenity::CreateContainerComponent::TextDisplay(
+        serenity::CreateTextDisplay::new(heading),
+    )];
+
+    if entries.is_empty() {
+        components.push(serenity::CreateContainerComponent::TextDisplay(
+            serenity::CreateTextDisplay::new("Nothing restricts it, anyone can use it."),
+        ));
+    }
+    for (index, entry) in entries.iter().take(MAX_PANEL_ENTRIES).enumerate() {
+        let remove_button = serenity::CreateButton::new(format!("{remove_prefix}{index}"))
+            .label("Remove")
+            .style(serenity::ButtonStyle::Danger);
+        components.push(serenity::CreateContainerComponent::Section(
+            serenity::CreateSection::new(
+                vec![serenity::CreateSectionComponent::TextDisplay(
+                    serenity::CreateTextDisplay::new(entry.describe()),
+                )],
+                serenity::CreateSectionAccessory::Button(remove_button),
+            ),
+        ));
+    }
+    if entries.len() > MAX_PANEL_ENTRIES {
+        components.push(serenity::CreateContainerComponent::TextDisplay(
+            serenity::CreateTextDisplay::new(format!(
+                "-# And {} more, remove some to see them.",
+                entries.len() - MAX_PANEL_ENTRIES
+            )),
+        ));
+    }
+
+    let role_select = serenity::CreateSelectMenu::new(
+        add_role_id.to_string(),
+        serenity::CreateSelectMenuKind::Role {
+            default_roles: None,
+        },
+    )
+    .placeholder("Require roles")
+    .min_values(1)
+    .max_values(5);
+    components.push(serenity::CreateContainerComponent::Separator(
+        serenity::CreateSeparator::new(true),
+    ));
+    components.push(serenity::CreateContainerComponent::ActionRow(
+        serenity::CreateActionRow::SelectMenu(role_select),
+    ));
+
+    vec![serenity::CreateComponent::Container(
+        serenity::CreateContainer::new(components).accent_color(serenity::Colour::BLUE),
+    )]
+}
@@ -0,0 +1,426 @@
+//! Live events of the guilds, for dashboards that would otherwise have to poll.
+//!
+//! `GET /api/events` is a server-sent event stream of JSON messages, each with a `type` and the
+//! `guild_id` it happened in. Browsers' `EventSource` can't send headers, so on this route only
+//! the dashboard token can also be given as `?token=`. `?guilds=1,2` subscribes to some guilds
+//! only, by default the stream has every guild the user is a cached member of.
+//!
+//! Voice and command events are only sent to users who can view their channels, so events of
+//! threads are never sent. Track events need the `nowplaying` command and queue events the
+//! `queue` command, as for the player API. The token, the memberships and these permissions are
+//! checked again every minute, and the stream closes once the token is revoked or the user is in
+//! none of its guilds anymore.
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
+
+use ::serenity::futures::{Stream, stream};
+use axum::{
+    Extension,
+    extract::{Query, State},
+    response::sse::{Event, KeepAlive, Sse},
+};
+use poise::serenity_prelude as serenity;
+use serde::{Deserialize, Serialize, Serializer};
+use tokio::{
+    sync::{Mutex as TokioMutex, broadcast, broadcast::error::RecvError},
+    time::{Instant, Interval, interval_at},
+};
+
+use super::player::{PlayerError, TrackResponse, check_member_permission, playback_channel_id};
+use crate::{
+    AxumState, Data,
+    auth_http::middleware::{AuthUser, is_still_authorized},
+};
+
+/// Events kept for subscribers that fall behind, older ones are skipped
+const EVENT_CAPACITY: usize = 256;
+/// Time between two checks of the token and memberships of an open stream
+const RECHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Something that happened in a guild
+#[derive(Debug, Clone)]
+pub struct DashboardEvent {
+    pub guild_id: serenity::GuildId,
+    pub kind: DashboardEventKind,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DashboardEventKind {
+    /// A track started playing, also sent when a paused track resumes
+    TrackStart { track: TrackResponse },
+    /// A track finished, was skipped or was stopped
+    TrackEnd { track: TrackResponse },
+    /// Tracks were added, removed or reordered
+    QueueChanged { length: usize },
+    VoiceJoin {
+        #[serde(serialize_with = "as_string")]
+        user_id: u64,
+        #[serde(serialize_with = "as_string")]
+        channel_id: u64,
+    },
+    VoiceLeave {
+        #[serde(serialize_with = "as_string")]
+        user_id: u64,
+        #[serde(serialize_with = "as_string")]
+        channel_id: u64,
+    },
+    VoiceMove {
+        #[serde(serialize_with = "as_string")]
+        user_id: u64,
+        #[serde(serialize_with = "as_string")]
+        from_channel_id: u64,
+        #[serde(serialize_with = "as_string")]
+        to_channel_id: u64,
+    },
+    /// A command was called. Not sent for ephemeral, owner and configuration commands.
+    CommandInvoked {
+        #[serde(serialize_with = "as_string")]
+        user_id: u64,
+        #[serde(serialize_with = "as_string")]
+        channel_id: u64,
+        command: String,
+    },
+}
+
+/// Ids are sent as strings, JavaScript numbers can't hold them
+fn as_string<S: Serializer>(id: &u64, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(id)
+}
+
+impl DashboardEventKind {
+    /// The voice event of a member going from one voice channel to another, if they did
+    pub fn voice_update(
+        user_id: serenity::UserId,
+        from: Option<serenity::ChannelId>,
+        to: Option<serenity::ChannelId>,
+    ) -> Option<Self> {
+        let user_id = user_id.get();
+        match (from, to) {
+            (None, Some(to)) => Some(Self::VoiceJoin {
+                user_id,
+                channel_id: to.get(),
+            }),
+            (Some(from), None) => Some(Self::VoiceLeave {
+                user_id,
+                channel_id: from.get(),
+            }),
+            (Some(from), Some(to)) if from != to => Some(Self::VoiceMove {
+                user_id,
+                from_channel_id: from.get(),
+                to_channel_id: to.get(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Channels a subscriber must be able to view to get the event
+    fn channel_ids(&self) -> Vec<u64> {
+        match self {
+            Self::VoiceJoin { channel_id, .. }
+            | Self::VoiceLeave { channel_id, .. }
+            | Self::CommandInvoked { channel_id, .. } => vec![*channel_id],
+            Self::VoiceMove {
+                from_channel_id,
+                to_channel_id,
+                ..
+            } => vec![*from_channel_id, *to_channel_id],
+            Self::TrackStart { .. } | Self::TrackEnd { .. } | Self::QueueChanged { .. } => {
+                Vec::new()
+            }
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct EventMessage<'a> {
+    guild_id: String,
+    #[serde(flatten)]
+    kind: &'a DashboardEventKind,
+}
+
+/// Sends the events of every guild to the open event streams
+#[derive(Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<Arc<DashboardEvent>>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(EVENT_CAPACITY);
+        Self { sender }
+    }
+}
+
+impl EventBus {
+    /// Send an event to the streams, dropped when none are open
+    pub fn publish(&self, guild_id: serenity::GuildId, kind: DashboardEventKind) {
+        if self.sender.receiver_count() == 0 {
+            return;
+        }
+        let _ = self
+            .sender
+            .send(Arc::new(DashboardEvent { guild_id, kind }));
+    }
+
+    /// Send the length of a guild's queue after it changed
+    pub fn queue_changed(&self, guild_id: serenity::GuildId, length: usize) {
+        self.publish(guild_id, DashboardEventKind::QueueChanged { length });
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<DashboardEvent>> {
+        self.sender.subscribe()
+    }
+}
+
+/// Send the length of a guild's queue after it changed. This locks the call, which must not be
+/// locked already.
+pub(crate) async fn publish_queue_changed(data: &Data, guild_id: serenity::GuildId) {
+    if let Some(call) = data.songbird.get(guild_id) {
+        let length = call.lock().await.queue().len();
+        data.events.queue_changed(guild_id, length);
+    }
+}
+
+#[derive(Deserialize)]
+pub struct EventsQuery {
+    /// Comma separated ids of the guilds to stream, every guild of the user by default
+    pub guilds: Option<String>,
+}
+
+/// The user of an event stream, and what they can see of each guild they get the events of
+struct Subscriber {
+    auth_user: AuthUser,
+    guilds: HashMap<serenity::GuildId, GuildAccess>,
+    /// Whether the guilds were picked with `?guilds=`. Members missing from the cache are then
+    /// fetched, instead of leaving the guild out.
+    picked_guilds: bool,
+}
+
+impl Subscriber {
+    fn user_id(&self) -> serenity::UserId {
+        serenity::UserId::new(self.auth_user.user_id as u64)
+    }
+
+    /// Whether the user is a member of the event's guild who may use the player command showing
+    /// the event, or who can view each of its channels
+    fn can_see(&self, serenity: &serenity::Context, event: &DashboardEvent) -> bool {
+        let Some(access) = self.guilds.get(&event.guild_id) else {
+            return false;
+        };
+        match event.kind {
+            DashboardEventKind::TrackStart { .. } | DashboardEventKind::TrackEnd { .. } => {
+                return access.now_playing;
+            }
+            DashboardEventKind::QueueChanged { .. } => return access.queue,
+            _ => {}
+        }
+        let channel_ids = event.kind.channel_ids();
+        if channel_ids.is_empty() {
+            return true;
+        }
+        let Some(guild) = serenity.cache.guild(event.guild_id) else {
+            return false;
+        };
+        channel_ids.into_iter().all(|channel_id| {
+            guild
+                .channels
+                .get(&serenity::ChannelId::new(channel_id))
+                .is_some_and(|channel| {
+                    guild
+                        .user_permissions_in(channel, &access.member)
+                        .view_channel()
+                })
+        })
+    }
+
+    /// Check the token again and refresh the members and their permissions, false once the
+    /// stream should close
+    async fn recheck(&mut self, data: &Data, serenity: &serenity::Context) -> bool {
+        if !is_still_authorized(&data.data_manager, &self.auth_user).await {
+            tracing::info!(
+                "Closing the event stream of user {}, its token is no longer valid",
+                self.auth_user.user_id
+            );
+            return false;
+        }
+        let guild_ids = self.guilds.keys().copied().collect::<Vec<_>>();
+        for guild_id in guild_ids {
+            match member_of(serenity, guild_id, self.user_id(), self.picked_guilds).await {
+                Some(member) => {
+                    let access = GuildAccess::new(data, serenity, guild_id, member).await;
+                    self.guilds.insert(guild_id, access);
+                }
+                None => {
+                    self.guilds.remove(&guild_id);
+                }
+            }
+        }
+        !self.guilds.is_empty()
+    }
+}
+
+/// The member of a subscriber in a guild, and the player commands they may use there
+struct GuildAccess {
+    member: serenity::Member,
+    /// Whether they may use `nowplaying`, and get the track events
+    now_playing: bool,
+    /// Whether they may use `queue`, and get the queue events
+    queue: bool,
+}
+
+impl GuildAccess {
+    async fn new(
+        data: &Data,
+        serenity: &serenity::Context,
+        guild_id: serenity::GuildId,
+        member: serenity::Member,
+    ) -> Self {
+        let channel_id = playback_channel_id(data, guild_id)
+            .await
+            .map_or(0, |channel_id| channel_id.get());
+        let allowed = async |command: &str| {
+            check_member_permission(data, serenity, guild_id, &member, command, channel_id)
+                .await
+                .is_ok()
+        };
+        let now_playing = allowed("nowplaying").await;
+        let queue = allowed("queue").await;
+        Self {
+            member,
+            now_playing,
+            queue,
+        }
+    }
+}
+
+/// The member of the user in the guild from the cache, else from the API if `fetch` is set
+async fn member_of(
+    serenity: &serenity::Context,
+    guild_id: serenity::GuildId,
+    user_id: serenity::UserId,
+    fetch: bool,
+) -> Option<serenity::Member> {
+    let cached = serenity
+        .cache
+        .guild(guild_id)
+        .and_then(|guild| guild.members.get(&user_id).cloned());
+    if cached.is_some() || !fetch {
+        return cached;
+    }
+    guild_id.member(serenity, user_id).await.ok()
+}
+
+struct EventStream {
+    receiver: broadcast::Receiver<Arc<DashboardEvent>>,
+    subscriber: Subscriber,
+    recheck: Interval,
+    data: Arc<Data>,
+    serenity: serenity::Context,
+}
+
+/// GET /api/events - Stream the events of the user's guilds
+pub async fn events_handler(
+    State(state): State<Arc<TokioMutex<AxumState>>>,
+    Extension(auth_user): Extension<AuthUser>,
+    Query(query): Query<EventsQuery>,
+) -> Result<Sse<impl Stream<Item = Result<Event, axum::Error>>>, PlayerError> {
+    let data = state.lock().await.data.clone();
+    let serenity = data
+        .serenity_context
+        .get()
+        .cloned()
+        .ok_or(PlayerError::NotReady)?;
+    let user_id = serenity::UserId::new(auth_user.user_id as u64);
+
+    let picked_guilds = query
+        .guilds
+        .as_deref()
+        .map(str::trim)
+        .filter(|guilds| !guilds.is_empty());
+    let mut guilds = HashMap::new();
+    match picked_guilds {
+        Some(guilds) => {
+            for guild_id in parse_guild_ids(guilds)? {
+                if serenity.cache.guild(guild_id).is_none() {
+                    return Err(PlayerError::UnknownGuild);
+                }
+                let member = member_of(&serenity, guild_id, user_id, true)
+                    .await
+                    .ok_or(PlayerError::NotMember)?;
+                let access = GuildAccess::new(&data, &serenity, guild_id, member).await;
+                guilds.insert(guild_id, access);
+            }
+        }
+        None => {
+            for guild_id in serenity.cache.guilds() {
+                if let Some(member) = member_of(&serenity, guild_id, user_id, false).await {
+                    let access = GuildAccess::new(&data, &serenity, guild_id, member).await;
+                    guilds.insert(guild_id, access);
+                }
+            }
+        }
+    }
+    tracing::info!(
+        "User {user_id} opened an event stream of {} guilds",
+        guilds.len()
+    );
+
+    let events = EventStream {
+        receiver: data.events.subscribe(),
+        subscriber: Subscriber {
+            auth_user,
+            guilds,
+            picked_guilds: picked_guilds.is_some(),
+        },
+        recheck: interval_at(Instant::now() + RECHECK_INTERVAL, RECHECK_INTERVAL),
+        data,
+        serenity,
+    };
+    let stream = stream::unfold(events, |mut events| async move {
+        loop {
+            tokio::select! {
+                received = events.receiver.recv() => match received {
+                    Ok(event) if events.subscriber.can_see(&events.serenity, &event) => {
+                        let message = Event::default().json_data(EventMessage {
+                            guild_id: event.guild_id.to_string(),
+                            kind: &event.kind,
+                        });
+                        return Some((message, events));
+                    }
+                    Ok(_) => {}
+                    // the dashboard missed events, and should fetch the player state again
+                    Err(RecvError::Lagged(skipped)) => {
+                        let message = Event::default().event("lagged").data(skipped.to_string());
+                        return Some((Ok(message), events));
+                    }
+                    Err(RecvError::Closed) => return None,
+                },
+                _ = events.recheck.tick() => {
+                    if !events.subscriber.recheck(&events.data, &events.serenity).await {
+                        return None;
+                    }
+                }
+            }
+        }
+    });
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+fn parse_guild_ids(guilds: &str) -> Result<HashSet<serenity::GuildId>, PlayerError> {
+    guilds
+        .split(',')
+        .map(|guild_id| {
+            guild_id
+                .trim()
+                .parse::<u64>()
+                .ok()
+                .filter(|guild_id| *guild_id != 0)
+                .map(serenity::GuildId::new)
+                .ok_or_else(|| PlayerError::BadRequest(format!("Invalid guild id `{guild_id}`")))
+        })
+        .collect()
+}
@@ -1,3 +1,7 @@
 pub mod auth;
+pub mod events;
+pub mod player;
 
 pub use auth::auth_me_handler;
+pub use events::{EventBus, events_handler};
+pub use player::player_routes;
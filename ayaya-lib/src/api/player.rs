@@ -0,0 +1,702 @@
+//! Queue and playback control of a guild, for dashboards.
+//!
+//! Every endpoint is checked like the Discord command it stands for, eg: skipping needs the
+//! permissions of `skip` and counts against its cooldowns. Requests have no text channel of their
+//! own, so they count as sent from the text channel playback was started from.
+use std::{
+    collections::VecDeque,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use axum::{
+    Extension, Json, Router,
+    extract::{Path, State},
+    http::{StatusCode, header},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+};
+use ayaya_db::data::{
+    cooldowns::{CooldownCaller, CooldownManager, CooldownRule, applicable_cooldowns},
+    permission_rules::{Caller, PermissionRules, evaluate},
+};
+use poise::serenity_prelude as serenity;
+use serde::{Deserialize, Serialize};
+use songbird::tracks::{PlayMode, TrackHandle};
+use tokio::sync::Mutex as TokioMutex;
+
+use super::events::publish_queue_changed;
+use crate::{
+    AxumState, Data,
+    auth_http::middleware::AuthUser,
+    error::{BotError, ErrorCategory, UserFriendlyError},
+    settings::guild_cooldowns,
+    utils::{OptionExt, describe_rule, is_admin_of, songbird_channel_to_serenity_channel},
+    voice::{commands::play_command::enqueue_input, utils::YoutubeMetadata},
+};
+
+type ApiState = State<Arc<TokioMutex<AxumState>>>;
+
+/// Routes nested under `/api/guilds/{guild_id}/player`
+pub fn player_routes() -> Router<Arc<TokioMutex<AxumState>>> {
+    Router::new()
+        .route("/", get(now_playing))
+        .route("/queue", get(queue).post(enqueue).delete(clear))
+        .route("/queue/move", post(move_track))
+        .route("/skip", post(skip))
+        .route("/pause", post(pause))
+        .route("/resume", post(resume))
+        .route("/seek", post(seek))
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TrackResponse {
+    /// Position in the queue, the current track is 1
+    pub position: usize,
+    pub title: String,
+    pub channel: String,
+    pub url: Option<String>,
+    pub thumbnail: Option<String>,
+    pub duration_secs: Option<u64>,
+    pub requester_id: Option<String>,
+}
+
+impl TrackResponse {
+    pub(crate) fn new(position: usize, metadata: &YoutubeMetadata) -> Self {
+        Self {
+            position,
+            title: metadata.title.clone().unwrap_or_unknown(),
+            channel: metadata.channel.clone().unwrap_or_unknown(),
+            url: metadata.webpage_url.clone(),
+            thumbnail: metadata.thumbnail.clone(),
+            duration_secs: metadata.duration().map(|duration| duration.as_secs()),
+            requester_id: metadata.requester.as_ref().map(|user| user.id.to_string()),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct NowPlayingResponse {
+    pub track: Option<TrackResponse>,
+    pub position_secs: Option<u64>,
+    pub paused: bool,
+}
+
+#[derive(Deserialize)]
+pub struct EnqueueRequest {
+    /// A url, playlist url or search term
+    pub query: String,
+    /// Play after the current track instead of at the end of the queue
+    #[serde(default)]
+    pub next: bool,
+}
+
+#[derive(Deserialize)]
+pub struct SeekRequest {
+    pub position_secs: u64,
+}
+
+#[derive(Deserialize)]
+pub struct MoveRequest {
+    /// Position of the track to move, the current track (1) can't be moved
+    pub from: usize,
+    /// Position to move it to
+    pub to: usize,
+}
+
+/// GET /api/guilds/{id}/player - The current track and its position
+async fn now_playing(
+    State(state): ApiState,
+    Extension(auth_user): Extension<AuthUser>,
+    Path(guild_id): Path<u64>,
+) -> Result<Json<NowPlayingResponse>, PlayerError> {
+    let player = Player::authorize(&state, &auth_user, guild_id, "nowplaying").await?;
+    let Some(track) = player.current_track().await else {
+        return Ok(Json(NowPlayingResponse {
+            track: None,
+            position_secs: None,
+            paused: false,
+        }));
+    };
+    let info = track.get_info().await.ok();
+    Ok(Json(NowPlayingResponse {
+        track: Some(TrackResponse::new(1, &track.data::<YoutubeMetadata>())),
+        position_secs: info.as_ref().map(|info| info.position.as_secs()),
+        paused: info.is_some_and(|info| info.playing == PlayMode::Pause),
+    }))
+}
+
+/// GET /api/guilds/{id}/player/queue - Every track of the queue, the current one first
+async fn queue(
+    State(state): ApiState,
+    Extension(auth_user): Extension<AuthUser>,
+    Path(guild_id): Path<u64>,
+) -> Result<Json<Vec<TrackResponse>>, PlayerError> {
+    let player = Player::authorize(&state, &auth_user, guild_id, "queue").await?;
+    Ok(Json(player.queue_response().await?))
+}
+
+/// POST /api/guilds/{id}/player/queue - Add a url, playlist or search result to the queue
+async fn enqueue(
+    State(state): ApiState,
+    Extension(auth_user): Extension<AuthUser>,
+    Path(guild_id): Path<u64>,
+    Json(request): Json<EnqueueRequest>,
+) -> Result<(StatusCode, Json<Vec<TrackResponse>>), PlayerError> {
+    let command = if request.next { "play_next" } else { "play" };
+    let player = Player::authorize(&state, &auth_user, guild_id, command).await?;
+    let query = request.query.trim();
+    if query.is_empty() {
+        return Err(PlayerError::BadRequest("The query is empty".to_string()));
+    }
+    let call = player.call()?;
+
+    // notices go where playback was started, else to the chat of the voice channel
+    let notice_channel_id = match player.playback_channel_id {
+        Some(channel_id) => channel_id,
+        None => call
+            .lock()
+            .await
+            .current_channel()
+            .map(songbird_channel_to_serenity_channel)
+            .ok_or(PlayerError::NotInVoice)?,
+    };
+    let added = enqueue_input(
+        &player.data,
+        player.serenity.http.clone(),
+        player.guild_id,
+        notice_channel_id,
+        player.member.user.clone(),
+        query,
+        request.next,
+    )
+    .await?;
+    tracing::info!(
+        "User {} added {} tracks to the queue of guild {guild_id} from the API",
+        auth_user.user_id,
+        added.len()
+    );
+
+    // positions of the added tracks, they are at the end or right after the current track
+    let queue_len = call.lock().await.queue().len();
+    let first = if request.next && queue_len > added.len() {
+        2
+    } else {
+        queue_len.saturating_sub(added.len()) + 1
+    };
+    let tracks = added
+        .iter()
+        .enumerate()
+        .map(|(index, metadata)| TrackResponse::new(first + index, metadata))
+        .collect();
+    Ok((StatusCode::CREATED, Json(tracks)))
+}
+
+/// DELETE /api/guilds/{id}/player/queue - Remove every track after the current one
+async fn clear(
+    State(state): ApiState,
+    Extension(auth_user): Extension<AuthUser>,
+    Path(guild_id): Path<u64>,
+) -> Result<StatusCode, PlayerError> {
+    let player = Player::authorize(&state, &auth_user, guild_id, "clear").await?;
+    player.call()?.lock().await.queue().modify_queue(|queue| {
+        queue.truncate(1);
+    });
+    publish_queue_changed(&player.data, player.guild_id).await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// POST /api/guilds/{id}/player/queue/move - Move a track to another position of the queue
+async fn move_track(
+    State(state): ApiState,
+    Extension(auth_user): Extension<AuthUser>,
+    Path(guild_id): Path<u64>,
+    Json(request): Json<MoveRequest>,
+) -> Result<Json<Vec<TrackResponse>>, PlayerError> {
+    let player = Player::authorize(&state, &auth_user, guild_id, "queue_move").await?;
+    player
+        .call()?
+        .lock()
+        .await
+        .queue()
+        .modify_queue(|queue| move_in_queue(queue, request.from, request.to))?;
+    publish_queue_changed(&player.data, player.guild_id).await;
+    Ok(Json(player.queue_response().await?))
+}
+
+/// Move the track at position `from` of the queue to `to`, positions starting at 1
+fn move_in_queue<T>(queue: &mut VecDeque<T>, from: usize, to: usize) -> Result<(), PlayerError> {
+    if from <= 1 || to <= 1 {
+        return Err(PlayerError::BadRequest(
+            "The current track (position 1) can't be moved".to_string(),
+        ));
+    }
+    if from > queue.len() || to > queue.len() {
+        return Err(PlayerError::BadRequest(
+            "The position is not in the queue".to_string(),
+        ));
+    }
+    if let Some(track) = queue.remove(from - 1) {
+        queue.insert(to - 1, track);
+    }
+    Ok(())
+}
+
+/// POST /api/guilds/{id}/player/skip - Skip the current track
+async fn skip(
+    State(state): ApiState,
+    Extension(auth_user): Extension<AuthUser>,
+    Path(guild_id): Path<u64>,
+) -> Result<StatusCode, PlayerError> {
+    let player = Player::authorize(&state, &auth_user, guild_id, "skip").await?;
+    let call = player.call()?;
+    let queue = call.lock().await.queue().clone();
+    if queue.current().is_none() {
+        return Err(PlayerError::NothingPlaying);
+    }
+    queue
+        .skip()
+        .map_err(|error| PlayerError::Internal(error.to_string()))?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// POST /api/guilds/{id}/player/pause - Pause the current track
+async fn pause(
+    State(state): ApiState,
+    Extension(auth_user): Extension<AuthUser>,
+    Path(guild_id): Path<u64>,
+) -> Result<StatusCode, PlayerError> {
+    let player = Player::authorize(&state, &auth_user, guild_id, "pause").await?;
+    let track = player
+        .current_track()
+        .await
+        .ok_or(PlayerError::NothingPlaying)?;
+    track
+        .pause()
+        .map_err(|error| PlayerError::Internal(error.to_string()))?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// POST /api/guilds/{id}/player/resume - Resume the current track
+async fn resume(
+    State(state): ApiState,
+    Extension(auth_user): Extension<AuthUser>,
+    Path(guild_id): Path<u64>,
+) -> Result<StatusCode, PlayerError> {
+    let player = Player::authorize(&state, &auth_user, guild_id, "resume").await?;
+    let track = player
+        .current_track()
+        .await
+        .ok_or(PlayerError::NothingPlaying)?;
+    track
+        .play()
+        .map_err(|error| PlayerError::Internal(error.to_string()))?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// POST /api/guilds/{id}/player/seek - Seek forward in the current track, like the seek command
+async fn seek(
+    State(state): ApiState,
+    Extension(auth_user): Extension<AuthUser>,
+    Path(guild_id): Path<u64>,
+    Json(request): Json<SeekRequest>,
+) -> Result<StatusCode, PlayerError> {
+    let player = Player::authorize(&state, &auth_user, guild_id, "seek").await?;
+    let track = player
+        .current_track()
+        .await
+        .ok_or(PlayerError::NothingPlaying)?;
+    let duration = track
+        .data::<YoutubeMetadata>()
+        .duration()
+        .ok_or_else(|| PlayerError::BadRequest("The track has no duration".to_string()))?;
+    let max_position = duration.as_secs().saturating_sub(5);
+    let current_position = track
+        .get_info()
+        .await
+        .map_err(|error| PlayerError::Internal(error.to_string()))?
+        .position
+        .as_secs();
+    if request.position_secs < current_position {
+        return Err(PlayerError::BadRequest(format!(
+            "Can only seek forwards, the track is at {current_position}s"
+        )));
+    }
+    if request.position_secs > max_position {
+        return Err(PlayerError::BadRequest(format!(
+            "Can seek to at most {max_position}s"
+        )));
+    }
+    track
+        .seek(std::time::Duration::from_secs(request.position_secs))
+        .result()
+        .map_err(|error| PlayerError::Internal(error.to_string()))?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// A guild's player, for a member allowed the command of the request
+struct Player {
+    data: Arc<Data>,
+    serenity: serenity::Context,
+    guild_id: serenity::GuildId,
+    member: serenity::Member,
+    playback_channel_id: Option<serenity::GenericChannelId>,
+}
+
+impl Player {
+    /// Check that the user is a member of the guild, that the permission rules of the guild
+    /// allow them `command` unless they are an admin of the guild, and count the call against
+    /// the cooldowns of `command`
+    async fn authorize(
+        state: &TokioMutex<AxumState>,
+        auth_user: &AuthUser,
+        guild_id: u64,
+        command: &str,
+    ) -> Result<Self, PlayerError> {
+        let data = state.lock().await.data.clone();
+        let serenity = data
+            .serenity_context
+            .get()
+            .cloned()
+            .ok_or(PlayerError::NotReady)?;
+        if guild_id == 0 {
+            return Err(PlayerError::UnknownGuild);
+        }
+        let guild_id = serenity::GuildId::new(guild_id);
+        if serenity.cache.guild(guild_id).is_none() {
+            return Err(PlayerError::UnknownGuild);
+        }
+        let user_id = serenity::UserId::new(auth_user.user_id as u64);
+        let member = guild_id
+            .member(&serenity, user_id)
+            .await
+            .map_err(|_| PlayerError::NotMember)?;
+        let playback_channel_id = playback_channel_id(&data, guild_id).await;
+        let channel_id = playback_channel_id.map_or(0, |channel_id| channel_id.get());
+        check_member_permission(&data, &serenity, guild_id, &member, command, channel_id).await?;
+
+        // the same buckets as the command, so the API is no way around its cooldowns
+        let cooldowns = guild_cooldowns(&data, guild_id.get()).await?;
+        let caller = CooldownCaller {
+            guild_id: guild_id.get(),
+            user_id: user_id.get(),
+            channel_id,
+        };
+        check_cooldown(
+            &data.data_manager.cooldowns(),
+            &cooldowns,
+            caller,
+            command,
+            &command_category(&data, command),
+            Instant::now(),
+        )?;
+
+        Ok(Self {
+            data,
+            serenity,
+            guild_id,
+            member,
+            playback_channel_id,
+        })
+    }
+
+    fn call(&self) -> Result<Arc<TokioMutex<songbird::Call>>, PlayerError> {
+        self.data
+            .songbird
+            .get(self.guild_id)
+            .ok_or(PlayerError::NotInVoice)
+    }
+
+    async fn current_track(&self) -> Option<TrackHandle> {
+        let call = self.data.songbird.get(self.guild_id)?;
+        call.lock().await.queue().current()
+    }
+
+    async fn queue_response(&self) -> Result<Vec<TrackResponse>, PlayerError> {
+        let tracks = self.call()?.lock().await.queue().current_queue();
+        Ok(tracks
+            .iter()
+            .enumerate()
+            .map(|(index, track)| TrackResponse::new(index + 1, &track.data::<YoutubeMetadata>()))
+            .collect())
+    }
+}
+
+/// The channel the bot plays in, in which the API's commands are considered called
+pub(super) async fn playback_channel_id(
+    data: &Data,
+    guild_id: serenity::GuildId,
+) -> Option<serenity::GenericChannelId> {
+    data.playback_channel_map
+        .lock()
+        .await
+        .get(&guild_id)
+        .copied()
+}
+
+fn command_category(data: &Data, command: &str) -> String {
+    data.command_categories_map
+        .get(command)
+        .cloned()
+        .flatten()
+        .unwrap_or_else(|| "Unknown".to_string())
+}
+
+/// Refuse `command` to the member called from `channel_id`, unless they are an admin of the
+/// guild or its permission rules allow it
+pub(super) async fn check_member_permission(
+    data: &Data,
+    serenity: &serenity::Context,
+    guild_id: serenity::GuildId,
+    member: &serenity::Member,
+    command: &str,
+    channel_id: u64,
+) -> Result<(), PlayerError> {
+    let is_admin = serenity
+        .cache
+        .guild(guild_id)
+        .is_some_and(|guild| is_admin_of(&guild, member));
+    if is_admin {
+        return Ok(());
+    }
+
+    let user_id = member.user.id;
+    let rules = data
+        .data_manager
+        .clone()
+        .permissions_mut()
+        .find_permission_rules(
+            guild_id.get(),
+            user_id.get(),
+            command,
+            Some(&command_category(data, command)),
+        )
+        .await
+        .map_err(|error| PlayerError::Internal(error.to_string()))?;
+    let caller = Caller {
+        user_id: user_id.get(),
+        role_ids: member.roles.iter().map(|role_id| role_id.get()).collect(),
+        channel_id,
+        voice_channel_id: serenity.cache.guild(guild_id).and_then(|guild| {
+            guild
+                .voice_states
+                .get(&user_id)
+                .and_then(|state| state.channel_id)
+                .map(|channel_id| channel_id.get())
+        }),
+    };
+    check_permission(command, &rules, &caller)
+}
+
+/// Refuse `command` unless the permission rules allow it to the caller
+fn check_permission(
+    command: &str,
+    rules: &PermissionRules,
+    caller: &Caller,
+) -> Result<(), PlayerError> {
+    let rule = evaluate(rules, caller);
+    if !rule.allows() {
+        return Err(PlayerError::Denied(format!(
+            "Not allowed to use `{command}`: {}",
+            describe_rule(&rule)
+        )));
+    }
+    Ok(())
+}
+
+/// Count a call of `command` against its cooldowns and those of its category, refusing it if
+/// one of them is used up
+fn check_cooldown(
+    manager: &CooldownManager,
+    rules: &[CooldownRule],
+    caller: CooldownCaller,
+    command: &str,
+    category: &str,
+    now: Instant,
+) -> Result<(), PlayerError> {
+    let cooldowns = applicable_cooldowns(rules, command, category);
+    match manager.try_use(caller, &cooldowns, now) {
+        None => Ok(()),
+        Some(retry_after) => Err(PlayerError::OnCooldown(retry_after)),
+    }
+}
+
+#[derive(Debug)]
+pub enum PlayerError {
+    NotReady,
+    UnknownGuild,
+    NotMember,
+    Denied(String),
+    /// A cooldown of the command is used up, it frees up after the duration
+    OnCooldown(Duration),
+    NotInVoice,
+    NothingPlaying,
+    BadRequest(String),
+    Internal(String),
+}
+
+impl From<BotError> for PlayerError {
+    fn from(error: BotError) -> Self {
+        match error.category() {
+            ErrorCategory::UserMistake => PlayerError::BadRequest(error.to_string()),
+            _ => PlayerError::Internal(error.to_string()),
+        }
+    }
+}
+
+impl IntoResponse for PlayerError {
+    fn into_response(self) -> Response {
+        let (status, message) = match self {
+            PlayerError::NotReady => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Ayaya is still starting up".to_string(),
+            ),
+            PlayerError::UnknownGuild => (
+                StatusCode::NOT_FOUND,
+                "Ayaya is not in this server".to_string(),
+            ),
+            PlayerError::NotMember => (
+                StatusCode::FORBIDDEN,
+                "Not a member of this server".to_string(),
+            ),
+            PlayerError::Denied(message) => (StatusCode::FORBIDDEN, message),
+            PlayerError::OnCooldown(retry_after) => {
+                // whole seconds, rounded up so waiting that long is always enough
+                let secs = retry_after.as_secs_f64().ceil() as u64;
+                return (
+                    StatusCode::TOO_MANY_REQUESTS,
+                    [(header::RETRY_AFTER, secs.to_string())],
+                    format!("On cooldown, try again in {secs}s"),
+                )
+                    .into_response();
+            }
+            PlayerError::NotInVoice => (
+                StatusCode::CONFLICT,
+                "Ayaya is not in a voice channel of this server".to_string(),
+            ),
+            PlayerError::NothingPlaying => (StatusCode::CONFLICT, "Nothing is playing".to_string()),
+            PlayerError::BadRequest(message) => (StatusCode::BAD_REQUEST, message),
+            PlayerError::Internal(message) => {
+                tracing::error!("Error in the player API: {message}");
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Something went wrong".to_string(),
+                )
+            }
+        };
+
+        (status, message).into_response()
+    }
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used)]
+mod tests {
+    use ayaya_core::metrics::NoopMetrics;
+    use ayaya_db::data::{
+        cooldowns::CooldownBucket,
+        permission_rules::{DenyRule, DenyTarget, RuleScope},
+    };
+    use sea_orm::Database;
+
+    use super::*;
+
+    const USER_ID: u64 = 10;
+
+    fn caller() -> Caller {
+        Caller {
+            user_id: USER_ID,
+            role_ids: vec![20],
+            channel_id: 30,
+            voice_channel_id: Some(40),
+        }
+    }
+
+    #[test]
+    fn denied_callers_are_refused() {
+        assert!(check_permission("skip", &PermissionRules::default(), &caller()).is_ok());
+
+        let rules = PermissionRules {
+            denies: vec![DenyRule {
+                target: DenyTarget::User(USER_ID),
+                scope: RuleScope::Command,
+            }],
+            ..Default::default()
+        };
+        let Err(PlayerError::Denied(message)) = check_permission("skip", &rules, &caller()) else {
+            panic!("the denied user was allowed");
+        };
+        assert!(message.contains("`skip`"));
+
+        // a role the caller doesn't have
+        let rules = PermissionRules {
+            command_roles: vec![21],
+            ..Default::default()
+        };
+        assert!(matches!(
+            check_permission("skip", &rules, &caller()),
+            Err(PlayerError::Denied(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn calls_share_the_cooldowns_of_the_command() {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        let manager = CooldownManager::new(db, Arc::new(NoopMetrics));
+        let rules = [CooldownRule {
+            scope: RuleScope::Command,
+            name: "play".to_string(),
+            bucket: CooldownBucket::User,
+            uses: 2,
+            period: Duration::from_secs(30),
+        }];
+        let caller = CooldownCaller {
+            guild_id: 1,
+            user_id: USER_ID,
+            channel_id: 30,
+        };
+        let now = Instant::now();
+
+        // the command used the first call, from another channel
+        let cooldowns = applicable_cooldowns(&rules, "play", "Music");
+        let command_caller = CooldownCaller {
+            channel_id: 31,
+            ..caller
+        };
+        assert_eq!(manager.try_use(command_caller, &cooldowns, now), None);
+
+        assert!(check_cooldown(&manager, &rules, caller, "play", "Music", now).is_ok());
+        let Err(PlayerError::OnCooldown(retry_after)) =
+            check_cooldown(&manager, &rules, caller, "play", "Music", now)
+        else {
+            panic!("the call over the cooldown was allowed");
+        };
+        assert_eq!(retry_after, Duration::from_secs(30));
+
+        // other commands have their own cooldowns
+        assert!(check_cooldown(&manager, &rules, caller, "skip", "Music", now).is_ok());
+        let later = now + Duration::from_secs(30);
+        assert!(check_cooldown(&manager, &rules, caller, "play", "Music", later).is_ok());
+    }
+
+    #[test]
+    fn moves_stay_inside_the_queue() {
+        let mut queue = VecDeque::from([1, 2, 3, 4]);
+        move_in_queue(&mut queue, 4, 2).unwrap();
+        assert_eq!(queue, [1, 4, 2, 3]);
+        move_in_queue(&mut queue, 2, 4).unwrap();
+        assert_eq!(queue, [1, 2, 3, 4]);
+
+        // the current track stays first, and positions start at 1
+        for (from, to) in [(1, 3), (3, 1), (0, 2), (2, 0), (5, 2), (2, 5)] {
+            assert!(
+                matches!(
+                    move_in_queue(&mut queue, from, to),
+                    Err(PlayerError::BadRequest(_))
+                ),
+                "moved {from} to {to}"
+            );
+        }
+        assert_eq!(queue, [1, 2, 3, 4]);
+    }
+}
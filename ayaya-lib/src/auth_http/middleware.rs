@@ -8,6 +8,8 @@ use std::sync::Arc;
 use tokio::sync::Mutex as TokioMutex;
 
 use ayaya_core::auth::token::verify_token;
+use ayaya_db::data::DataManager;
+use uuid::Uuid;
 
 use crate::AxumState;
 
@@ -15,6 +17,8 @@ use crate::AxumState;
 #[derive(Clone, Debug)]
 pub struct AuthUser {
     pub user_id: i64,
+    /// The dashboard token the request was authenticated with
+    pub token_id: Uuid,
 }
 
 /// Auth middleware that validates Bearer tokens
@@ -26,58 +30,117 @@ impl AuthMiddleware {
         mut request: Request,
         next: Next,
     ) -> Result<Response, AuthError> {
-        // Extract Authorization header
-        let auth_header = request
-            .headers()
-            .get("Authorization")
-            .and_then(|h| h.to_str().ok())
-            .ok_or(AuthError::MissingToken)?;
-
-        // Check Bearer scheme
-        let token = auth_header
-            .strip_prefix("Bearer ")
-            .ok_or(AuthError::InvalidToken)?;
-
-        // Validate token against database
-        let state = state.lock().await;
-        let tokens = state
-            .data_manager
-            .list_active_tokens()
-            .await
-            .map_err(|_| AuthError::InvalidToken)?;
-
-        let mut authed_user = None;
-        let mut authed_token_id = None;
-        for token_model in tokens {
-            if verify_token(token, &token_model.token_hash)
-                && state
-                    .data_manager
-                    .is_allowlisted(token_model.user_id)
-                    .await
-                    .map_err(|_| AuthError::InvalidToken)?
-            {
-                authed_user = Some(token_model.user_id);
-                authed_token_id = Some(token_model.token_id);
-            }
-        }
+        let token = bearer_token(&request)?.ok_or(AuthError::MissingToken)?;
+        let auth_user = authenticate(&state, &token).await?;
 
-        let user_id = authed_user.ok_or(AuthError::InvalidToken)?;
+        // Add user to request extensions
+        request.extensions_mut().insert(auth_user);
 
-        // Update last used timestamp
-        if let Some(token_id) = authed_token_id {
-            let _ = state
-                .data_manager
-                .update_token_last_used_by_id(token_id)
-                .await;
-        }
+        Ok(next.run(request).await)
+    }
 
-        // Add user to request extensions
-        request.extensions_mut().insert(AuthUser { user_id });
+    /// Like [`Self::require_auth`], but the token can also be in a `token` query parameter. Only
+    /// for event streams, which browsers can't send headers with: query strings end up in logs.
+    pub async fn require_auth_or_query_token(
+        State(state): State<Arc<TokioMutex<AxumState>>>,
+        mut request: Request,
+        next: Next,
+    ) -> Result<Response, AuthError> {
+        let token = match bearer_token(&request)? {
+            Some(token) => token,
+            None => query_token(&request).ok_or(AuthError::MissingToken)?,
+        };
+        let auth_user = authenticate(&state, &token).await?;
+        request.extensions_mut().insert(auth_user);
 
         Ok(next.run(request).await)
     }
 }
 
+/// The token of the Authorization header, if there is one
+fn bearer_token(request: &Request) -> Result<Option<String>, AuthError> {
+    let Some(auth_header) = request
+        .headers()
+        .get("Authorization")
+        .and_then(|h| h.to_str().ok())
+    else {
+        return Ok(None);
+    };
+
+    // Check Bearer scheme
+    auth_header
+        .strip_prefix("Bearer ")
+        .map(|token| Some(token.to_string()))
+        .ok_or(AuthError::InvalidToken)
+}
+
+/// The `token` query parameter, if there is one
+fn query_token(request: &Request) -> Option<String> {
+    let query = request.uri().query()?;
+    url::form_urlencoded::parse(query.as_bytes())
+        .find(|(key, _)| key == "token")
+        .map(|(_, value)| value.into_owned())
+}
+
+/// The allowlisted user of an active token
+async fn authenticate(state: &TokioMutex<AxumState>, token: &str) -> Result<AuthUser, AuthError> {
+    // Validate token against database
+    let state = state.lock().await;
+    let tokens = state
+        .data_manager
+        .list_active_tokens()
+        .await
+        .map_err(|_| AuthError::InvalidToken)?;
+
+    let mut authed_user = None;
+    for token_model in tokens {
+        if verify_token(token, &token_model.token_hash)
+            && state
+                .data_manager
+                .is_allowlisted(token_model.user_id)
+                .await
+                .map_err(|_| AuthError::InvalidToken)?
+        {
+            authed_user = Some(AuthUser {
+                user_id: token_model.user_id,
+                token_id: token_model.token_id,
+            });
+        }
+    }
+
+    let auth_user = authed_user.ok_or(AuthError::InvalidToken)?;
+
+    // Update last used timestamp
+    let _ = state
+        .data_manager
+        .update_token_last_used_by_id(auth_user.token_id)
+        .await;
+
+    Ok(auth_user)
+}
+
+/// Whether the token of a request is still active and its user still allowlisted, for
+/// connections outliving the request, such as event streams
+pub async fn is_still_authorized(data_manager: &DataManager, auth_user: &AuthUser) -> bool {
+    let token_active = match data_manager.list_active_tokens().await {
+        Ok(tokens) => tokens
+            .iter()
+            .any(|token| token.token_id == auth_user.token_id),
+        Err(error) => {
+            tracing::error!(
+                "Failed to check the token of user {}: {error}",
+                auth_user.user_id
+            );
+            false
+        }
+    };
+    token_active
+        && data_manager
+            .is_allowlisted(auth_user.user_id)
+            .await
+            .unwrap_or(false)
+}
+
 #[derive(Debug)]
 pub enum AuthError {
     MissingToken,
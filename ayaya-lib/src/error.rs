@@ -1,12 +1,17 @@
 use ::serenity::all::CacheHttp;
 use poise::serenity_prelude as serenity;
 use snafu::Snafu;
-use tracing::error;
+use tracing::{error, info};
 
 use crate::{
     Data,
     metrics::ErrorType,
-    voice::{commands::soundboard::error::SoundboardError, error::MusicCommandError},
+    settings::{CooldownError, SettingsError},
+    stats::StatsError,
+    voice::{
+        commands::soundboard::error::SoundboardError, error::MusicCommandError,
+        native_soundboard::NativeSoundboardError, sound_pack::SoundPackError,
+    },
 };
 
 pub async fn error_handler(error: poise::FrameworkError<'_, Data, BotError>) {
@@ -45,6 +50,29 @@ pub async fn error_handler(error: poise::FrameworkError<'_, Data, BotError>) {
                 error!("Error sending error message: {}", e);
             }
         }
+        poise::FrameworkError::CommandCheckFailed {
+            error: Some(error),
+            ctx,
+            ..
+        } => {
+            ctx.data()
+                .metrics
+                .error(error.name(), ErrorType::Command)
+                .await;
+            let cmd = ctx.command().name.clone();
+            info!("Check of command ({}) failed: {}", cmd, error);
+
+            if let Err(e) = ctx
+                .send(
+                    poise::CreateReply::default()
+                        .embed(command_error_embed(cmd.to_string(), error))
+                        .ephemeral(true),
+                )
+                .await
+            {
+                error!("Error sending error message: {}", e);
+            }
+        }
         other => {
             if let Err(e) = poise::builtins::on_error(other).await {
                 error!("Error sending error message: {}", e);
@@ -96,6 +124,15 @@ pub enum BotError {
     #[snafu(transparent)]
     InitError { source: InitError },
 
+    #[snafu(transparent)]
+    SettingsError { source: SettingsError },
+
+    #[snafu(transparent)]
+    StatsError { source: StatsError },
+
+    #[snafu(transparent)]
+    CooldownError { source: CooldownError },
+
     #[snafu(display("Ayaya is unable to figure out her Guild ID."))]
     NoGuildId,
 
@@ -196,6 +233,9 @@ impl UserFriendlyError for BotError {
 
         match self {
             BotError::MusicCommandError { source } => source.help_text(),
+            BotError::SettingsError { source } => source.help_text(),
+            BotError::StatsError { source } => source.help_text(),
+            BotError::CooldownError { source } => source.help_text(),
             BotError::NoGuildId => "Ayaya is unable to figure out her Guild ID.",
             BotError::NoGuild => "Ayaya is has confused her current Guild",
             BotError::GuildCacheStale => "Cache is stale, please rejoin voice channels",
@@ -206,6 +246,9 @@ impl UserFriendlyError for BotError {
     fn category(&self) -> ErrorCategory {
         match self {
             BotError::MusicCommandError { source } => source.category(),
+            BotError::SettingsError { source } => source.category(),
+            BotError::StatsError { source } => source.category(),
+            BotError::CooldownError { source } => source.category(),
             BotError::NoGuildId => ErrorCategory::UserMistake,
             BotError::NoGuild => ErrorCategory::UserMistake,
             BotError::GuildCacheStale => ErrorCategory::UserMistake,
@@ -220,6 +263,9 @@ impl ErrorName for BotError {
         let name: &str = match self {
             BotError::MusicCommandError { source } => &source.name(),
             BotError::InitError { .. } => "init",
+            BotError::SettingsError { source } => &source.name(),
+            BotError::StatsError { source } => &source.name(),
+            BotError::CooldownError { source } => &source.name(),
             BotError::NoGuildId => "no_guild_id",
             BotError::NoGuild => "no_guild",
             BotError::GuildCacheStale => "guild_cache_stale",
@@ -253,6 +299,22 @@ impl From<SoundboardError> for BotError {
     }
 }
 
+impl From<SoundPackError> for BotError {
+    fn from(source: SoundPackError) -> Self {
+        Self::MusicCommandError {
+            source: MusicCommandError::SoundPackError { source },
+        }
+    }
+}
+
+impl From<NativeSoundboardError> for BotError {
+    fn from(source: NativeSoundboardError) -> Self {
+        Self::MusicCommandError {
+            source: MusicCommandError::NativeSoundboardError { source },
+        }
+    }
+}
+
 #[derive(Snafu, Debug)]
 #[snafu(visibility(pub))]
 pub enum InitError {
@@ -1,13 +1,25 @@
 use std::{
     io::{BufRead, BufReader},
-    sync::Arc,
+    sync::{Arc, atomic::Ordering},
 };
 
-use ayaya_db::data::voice::{VoiceSessionEndReason, VoiceStateUpdateInput};
+use ayaya_db::data::{
+    voice::{ObservedVoiceState, VoiceSessionEndReason, VoiceStateUpdateInput},
+    voice_feed::{FeedMessageValues, render_feed_message},
+};
 use serenity::all::{ActivityData, CacheHttp, Context, EventHandler, FullEvent};
 use time::OffsetDateTime;
 
-use crate::{Data, setup_cookies};
+use crate::{
+    Data,
+    api::events::DashboardEventKind,
+    scheduler::start_scheduler,
+    setup_cookies,
+    voice::{
+        always_on::rejoin_always_on_guilds,
+        bot_state::{handle_bot_voice_state_update, handle_channel_delete},
+    },
+};
 
 pub struct StartupHandler;
 
@@ -38,6 +50,8 @@ impl EventHandler for StartupHandler {
                     let mut user_id_lock = data.user_id.write().await;
                     *user_id_lock = bot_user_id;
                 }
+                // later ready events come with the same cache and http client
+                let _ = data.serenity_context.set(context.clone());
 
                 // TODO: handle this error
                 setup_cookies(
@@ -73,19 +87,71 @@ impl EventHandler for StartupHandler {
                     .for_each(|line| tracing::info!("yt-dlp setup: {}", line));
                 tracing::info!("yt-dlp checks done");
                 context.set_activity(Some(ActivityData::watching("Hoshimachi Suichan")));
+                start_scheduler(context);
+
+                // rejoin 24/7 channels after a restart or a new gateway session
+                let context = context.clone();
+                tokio::spawn(async move { rejoin_always_on_guilds(&context).await });
+            }
+            FullEvent::Resume { .. } => {
+                tracing::info!("Gateway session resumed, checking 24/7 channels and voice states");
+                let context = context.clone();
+                tokio::spawn(async move {
+                    rejoin_always_on_guilds(&context).await;
+                    let guild_ids = context.cache.guilds();
+                    reconcile_cached_voice_states(
+                        &context,
+                        &guild_ids,
+                        OffsetDateTime::now_utc(),
+                        VoiceSessionEndReason::Disconnect,
+                    )
+                    .await;
+                });
             }
             FullEvent::CacheReady { guilds, .. } => {
                 tracing::info!("Cached guild info is ready for {} guilds.", guilds.len());
-                reconcile_cached_voice_states(
-                    context,
-                    guilds.as_slice(),
-                    OffsetDateTime::now_utc(),
-                )
-                .await;
+                let data: Arc<Data> = context.data();
+                let now = OffsetDateTime::now_utc();
+                // the first time, sessions left open by the previous run end with the restart
+                let reason = if data.voice_reconciled.swap(true, Ordering::Relaxed) {
+                    VoiceSessionEndReason::Disconnect
+                } else {
+                    if let Err(error) = data
+                        .data_manager
+                        .voice()
+                        .close_all_open_voice_sessions(now, VoiceSessionEndReason::BotRestart)
+                        .await
+                    {
+                        tracing::error!(
+                            "Failed to close open voice sessions after restart: {error}"
+                        );
+                    }
+                    VoiceSessionEndReason::BotRestart
+                };
+                reconcile_cached_voice_states(context, guilds.as_slice(), now, reason).await;
+            }
+            FullEvent::GuildCreate { guild, .. } => {
+                // before the cache is ready, CacheReady reconciles every guild at once
+                let data: Arc<Data> = context.data();
+                if data.voice_reconciled.load(Ordering::Relaxed) {
+                    let observed = observed_voice_states(&data, guild.voice_states.iter()).await;
+                    reconcile_guild_voice_states(
+                        &data,
+                        guild.id,
+                        &observed,
+                        OffsetDateTime::now_utc(),
+                        VoiceSessionEndReason::Disconnect,
+                    )
+                    .await;
+                }
             }
             FullEvent::VoiceStateUpdate { old, new, .. } => {
+                handle_bot_voice_state_update(context, old.as_ref(), new).await;
                 persist_voice_state_update(context, old.as_ref(), new).await;
             }
+            FullEvent::ChannelDelete { channel, .. } => {
+                handle_channel_delete(context, channel).await;
+            }
             _ => {}
         }
     }
@@ -126,6 +192,11 @@ async fn persist_voice_state_update(
         start_is_estimated: old.is_none(),
     };
 
+    // members who opted out are neither announced, streamed nor recorded
+    let data: Arc<Data> = context.data();
+    if !is_recorded(&data, new.user_id).await {
+        return;
+    }
     {
         let context = context.clone();
         let input = input.clone();
@@ -133,7 +204,13 @@ async fn persist_voice_state_update(
             notify_channel(context, input).await;
         });
     }
-    let data: Arc<Data> = context.data();
+    if let Some(event) = DashboardEventKind::voice_update(
+        new.user_id,
+        old.and_then(|state| state.channel_id),
+        new.channel_id,
+    ) {
+        data.events.publish(guild_id, event);
+    }
     if let Err(error) = data
         .data_manager
         .voice()
@@ -144,60 +221,86 @@ async fn persist_voice_state_update(
     }
 }
 
+/// Reconcile the voice sessions of every guild with the voice states in the cache
 async fn reconcile_cached_voice_states(
     context: &Context,
     guild_ids: &[serenity::all::GuildId],
-    startup_time: OffsetDateTime,
+    observed_at: OffsetDateTime,
+    reason: VoiceSessionEndReason,
 ) {
     let data: Arc<Data> = context.data();
-    let voice_manager = data.data_manager.voice();
-
-    if let Err(error) = voice_manager
-        .close_all_open_voice_sessions(startup_time, VoiceSessionEndReason::BotRestart)
-        .await
-    {
-        tracing::error!("Failed to close open voice sessions during reconciliation: {error}");
-        return;
-    }
-
     for guild_id in guild_ids {
-        let active_voice_states = {
+        let voice_states = {
             let Some(guild) = guild_id.to_guild_cached(&context.cache) else {
                 continue;
             };
+            guild.voice_states.iter().cloned().collect::<Vec<_>>()
+        };
+        let observed = observed_voice_states(&data, voice_states.iter()).await;
+        reconcile_guild_voice_states(&data, *guild_id, &observed, observed_at, reason).await;
+    }
+}
+
+async fn reconcile_guild_voice_states(
+    data: &Data,
+    guild_id: serenity::all::GuildId,
+    observed: &[ObservedVoiceState],
+    observed_at: OffsetDateTime,
+    reason: VoiceSessionEndReason,
+) {
+    match data
+        .data_manager
+        .voice()
+        .reconcile_guild_voice_states(guild_id.get() as i64, observed, observed_at, reason)
+        .await
+    {
+        Ok(report) if report.closed > 0 || report.opened > 0 => tracing::info!(
+            "Reconciled voice sessions of guild {guild_id}: {} closed, {} opened",
+            report.closed,
+            report.opened
+        ),
+        Ok(_) => {}
+        Err(error) => {
+            tracing::error!("Failed to reconcile voice sessions of guild {guild_id}: {error}")
+        }
+    }
+}
 
-            guild
-                .voice_states
-                .iter()
-                .filter_map(|state| {
-                    let channel_id = state.channel_id?;
-                    Some((
-                        state.user_id.get() as i64,
-                        channel_id.get() as i64,
-                        serde_json::to_string(state).ok(),
-                    ))
-                })
-                .collect::<Vec<_>>()
+/// Members in a voice channel among the voice states, without the ones who opted out
+async fn observed_voice_states(
+    data: &Data,
+    voice_states: impl Iterator<Item = &serenity::all::VoiceState>,
+) -> Vec<ObservedVoiceState> {
+    let mut observed = Vec::new();
+    for state in voice_states {
+        let Some(channel_id) = state.channel_id else {
+            continue;
         };
+        if !is_recorded(data, state.user_id).await {
+            continue;
+        }
+        observed.push(ObservedVoiceState {
+            user_id: state.user_id.get() as i64,
+            channel_id: channel_id.get() as i64,
+            raw_state_json: serde_json::to_string(state).ok(),
+        });
+    }
+    observed
+}
 
-        for (user_id, channel_id, raw_state_json) in active_voice_states {
-            if let Err(error) = voice_manager
-                .ensure_open_voice_session(
-                    guild_id.get() as i64,
-                    user_id,
-                    channel_id,
-                    startup_time,
-                    raw_state_json,
-                )
-                .await
-            {
-                tracing::error!(
-                    "Failed to reconcile voice session for guild {} user {}: {}",
-                    guild_id,
-                    user_id,
-                    error
-                );
-            }
+/// Whether voice activity of the user is stored, ie: they didn't opt out. Errors count as not
+/// recorded, so an opted out member is never stored by mistake.
+async fn is_recorded(data: &Data, user_id: serenity::all::UserId) -> bool {
+    match data
+        .data_manager
+        .voice_privacy()
+        .is_opted_out(user_id.get())
+        .await
+    {
+        Ok(opted_out) => !opted_out,
+        Err(error) => {
+            tracing::error!("Failed to check voice opt out of user {user_id}: {error}");
+            false
         }
     }
 }
@@ -206,76 +309,80 @@ fn timestamp_to_offset_datetime(timestamp: serenity::all::Timestamp) -> Option<O
     OffsetDateTime::from_unix_timestamp(timestamp.unix_timestamp()).ok()
 }
 
+/// Post a voice activity notice to the guild's voice feed, if it has one and the update passes
+/// its filters
 async fn notify_channel(ctx: Context, input: VoiceStateUpdateInput) {
-    use serenity::all::{ChannelId, GuildId, UserId};
-
-    const BRD_CH_ID: ChannelId = ChannelId::new(1493505984591827038);
+    use serenity::all::{ChannelId, GuildId, Mentionable, UserId};
 
-    let brd_ch = BRD_CH_ID
-        .to_guild_channel(ctx.clone(), Some(GuildId::new(599271821862371338)))
+    let data: Arc<Data> = ctx.data();
+    let guild_id = GuildId::new(input.guild_id as u64);
+    let config = match data
+        .data_manager
+        .voice_feed()
+        .get_voice_feed(guild_id.get())
         .await
-        .expect("brd channel exists");
+    {
+        Ok(Some(config)) => config,
+        Ok(None) => return,
+        Err(error) => {
+            tracing::error!("Failed to get voice feed of guild {guild_id}: {error}");
+            return;
+        }
+    };
+    let Some(template) = config
+        .notice_kind(&input)
+        .and_then(|kind| config.template(kind))
+    else {
+        return;
+    };
 
-    let kind = input.classify();
-    let user = UserId::new(input.user_id as u64)
-        .to_user(ctx.clone())
-        .await
-        .expect("user exists");
-    let guild_id = GuildId::new(input.guild_id as u64);
     let guild_name = guild_id
-        .clone()
         .to_guild_cached(&ctx.cache)
-        .expect("guild is cached")
-        .name
-        .clone();
-
-    let msg = match kind {
-        ayaya_db::data::voice::VoiceEventKind::Join => {
-            let vc = ChannelId::new(input.to_channel_id.expect("a join") as u64)
-                .to_guild_channel(ctx.clone(), Some(guild_id))
-                .await
-                .unwrap();
-            let vc_name = vc.base.name.to_string();
-
-            format!(
-                "`{}` joined the channel `{}` in guild `{}`",
-                user.name, vc_name, guild_name
-            )
-        }
-        ayaya_db::data::voice::VoiceEventKind::Leave => {
-            let vc = ChannelId::new(input.from_channel_id.expect("a leave") as u64)
-                .to_guild_channel(ctx.clone(), Some(guild_id))
-                .await
-                .expect("channel exists");
-            format!(
-                "`{}` left the channel `{}` in guild `{}`",
-                user.name, vc.base.name, guild_name
-            )
-        }
-        ayaya_db::data::voice::VoiceEventKind::Move => {
-            let to_vc = ChannelId::new(input.to_channel_id.expect("a join") as u64)
-                .to_guild_channel(ctx.clone(), Some(guild_id))
-                .await
-                .expect("channel exists");
-            let from_vc = ChannelId::new(input.from_channel_id.expect("a join") as u64)
-                .to_guild_channel(ctx.clone(), Some(guild_id))
-                .await
-                .expect("channel exists");
-
-            format!(
-                "`{}` moved from `{}` to `{}` in guild `{}`",
-                user.name, from_vc.base.name, to_vc.base.name, guild_name
-            )
-        }
-        ayaya_db::data::voice::VoiceEventKind::StateChange => return,
+        .map(|guild| guild.name.to_string())
+        .unwrap_or_else(|| guild_id.to_string());
+    let user_id = UserId::new(input.user_id as u64);
+    let user_name = match user_id.to_user(&ctx).await {
+        Ok(user) => user.name.to_string(),
+        Err(_) => user_id.mention().to_string(),
     };
-    brd_ch
+    let from = match input.from_channel_id {
+        Some(channel_id) => Some(channel_name(&ctx, guild_id, channel_id).await),
+        None => None,
+    };
+    let to = match input.to_channel_id {
+        Some(channel_id) => Some(channel_name(&ctx, guild_id, channel_id).await),
+        None => None,
+    };
+
+    let msg = render_feed_message(
+        template,
+        &FeedMessageValues {
+            user: &user_name,
+            guild: &guild_name,
+            from: from.as_deref(),
+            to: to.as_deref(),
+        },
+    );
+    if let Err(error) = ChannelId::new(config.channel_id)
         .send_message(
             &ctx.http,
             serenity::all::CreateMessage::new().embed(embed_template(&ctx, &msg)),
         )
         .await
-        .unwrap();
+    {
+        tracing::warn!("Failed to post to the voice feed of guild {guild_id}: {error}");
+    }
+}
+
+/// Name of a voice channel, or its mention if it can't be fetched, eg: it was deleted
+async fn channel_name(ctx: &Context, guild_id: serenity::all::GuildId, channel_id: i64) -> String {
+    use serenity::all::{ChannelId, Mentionable};
+
+    let channel_id = ChannelId::new(channel_id as u64);
+    match channel_id.to_guild_channel(ctx, Some(guild_id)).await {
+        Ok(channel) => channel.base.name.to_string(),
+        Err(_) => channel_id.mention().to_string(),
+    }
 }
 
 fn embed_template<'a>(ctx: &Context, msg: &'a str) -> serenity::all::CreateEmbed<'a> {
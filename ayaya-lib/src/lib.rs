@@ -3,11 +3,15 @@
 #![deny(clippy::unwrap_used)]
 
 use std::{
+    borrow::Cow,
     collections::HashMap,
     io::Read,
     path::{Path, PathBuf},
     str::FromStr,
-    sync::{Arc, atomic::AtomicBool},
+    sync::{
+        Arc, OnceLock,
+        atomic::{AtomicBool, AtomicUsize},
+    },
 };
 
 use admin::admin_commands;
@@ -34,6 +38,7 @@ use poise::{
 use prometheus_client::{encoding::text::encode, registry::Registry};
 use reqwest::Client as HttpClient;
 use service::{AyayaDiscordBot, Discord, MetricsBasicAuth};
+use settings::settings_commands;
 use snafu::ResultExt;
 use stats::stats_commands;
 use time::{UtcOffset, format_description};
@@ -44,7 +49,10 @@ use tracing_error::ErrorLayer;
 use tracing_subscriber::{EnvFilter, fmt::time::OffsetTime, layer::SubscriberExt};
 use tracker::tracker;
 use utils::GuildInfo;
-use voice::voice_commands;
+use voice::{
+    native_soundboard::{DiscordSoundboardHttp, GuildSoundboardHttp},
+    voice_commands,
+};
 
 use crate::{error::*, voice::commands::music};
 
@@ -58,6 +66,8 @@ pub mod error;
 pub(crate) mod memes;
 pub(crate) mod metrics;
 pub(crate) mod owner;
+pub(crate) mod scheduler;
+pub(crate) mod settings;
 pub(crate) mod stats;
 pub(crate) mod tracker;
 pub(crate) mod utils;
@@ -69,6 +79,14 @@ pub type Context<'a> = poise::Context<'a, Data, BotError>;
 pub type Commands = Vec<poise::Command<Data, BotError>>;
 pub type CommandResult = Result<(), BotError>;
 
+/// Prefix of prefix commands in guilds without their own
+#[cfg(debug_assertions)]
+pub(crate) const DEFAULT_PREFIX: &str = "~";
+
+/// Prefix of prefix commands in guilds without their own
+#[cfg(not(debug_assertions))]
+pub(crate) const DEFAULT_PREFIX: &str = "aya";
+
 // User data, which is stored and accessible in all command invocations
 pub struct Data {
     http: HttpClient,
@@ -83,6 +101,23 @@ pub struct Data {
     data_dir: PathBuf,
     secret_key: String,
     linger_map: Arc<TokioMutex<HashMap<serenity::GuildId, Arc<AtomicBool>>>>,
+    always_on_map: Arc<TokioMutex<HashMap<serenity::GuildId, Arc<AtomicBool>>>>,
+    /// Text channel that started playback in each guild, for voice notifications
+    playback_channel_map: Arc<TokioMutex<HashMap<serenity::GuildId, serenity::GenericChannelId>>>,
+    idle_counter_map: Arc<TokioMutex<HashMap<serenity::GuildId, Arc<AtomicUsize>>>>,
+    /// Whether voice sessions were reconciled since startup. Later gateway gaps are reconciled
+    /// per guild.
+    voice_reconciled: AtomicBool,
+    /// Whether the scheduled jobs were started, they keep running across reconnects
+    scheduler_started: AtomicBool,
+    soundboard_http: Arc<dyn GuildSoundboardHttp>,
+    /// Leaves asked for and rejoins running, per guild
+    voice_flags: voice::bot_state::VoiceFlags,
+    /// Context of the gateway connection, set once the bot is ready. Used by the API, which
+    /// runs outside of events and commands.
+    serenity_context: OnceLock<serenity::Context>,
+    /// Player, voice and command events, streamed to dashboards
+    events: api::EventBus,
     #[expect(dead_code)]
     metrics_registry: Arc<TokioMutex<Registry>>,
     metrics: Metrics,
@@ -109,12 +144,9 @@ pub async fn ayayabot(
     let data_manager = DataManager::new(&db_url, Arc::new(metrics.clone()))
         .await
         .context(DataManagerSnafu)?;
-
-    #[cfg(debug_assertions)]
-    let prefix = "~";
-
-    #[cfg(not(debug_assertions))]
-    let prefix = "aya";
+    tokio::spawn(stats::retention::prune_voice_data_periodically(
+        data_manager.clone(),
+    ));
 
     let manager = songbird::Songbird::serenity();
 
@@ -128,7 +160,9 @@ pub async fn ayayabot(
         .options(poise::FrameworkOptions {
             commands,
             prefix_options: poise::PrefixFrameworkOptions {
-                prefix: Some(prefix.into()),
+                // resolved per guild, see guild_prefix
+                prefix: None,
+                dynamic_prefix: Some(|ctx| Box::pin(guild_prefix(ctx))),
                 mention_as_prefix: true,
                 case_insensitive_commands: true,
                 execute_untracked_edits: true,
@@ -177,8 +211,10 @@ pub async fn ayayabot(
         })
         .collect::<HashMap<_, _>>();
 
+    let http = HttpClient::new();
+    let soundboard_http = Arc::new(DiscordSoundboardHttp::new(http.clone(), token.clone()));
     let data = Arc::new(Data {
-        http: HttpClient::new(),
+        http,
         songbird: manager_clone,
         user_id: Default::default(),
         data_manager,
@@ -189,13 +225,22 @@ pub async fn ayayabot(
         ytdlp_config_path,
         data_dir,
         linger_map: Default::default(),
+        always_on_map: Default::default(),
+        playback_channel_map: Default::default(),
+        idle_counter_map: Default::default(),
+        voice_reconciled: AtomicBool::new(false),
+        scheduler_started: AtomicBool::new(false),
+        soundboard_http,
+        voice_flags: Default::default(),
+        serenity_context: OnceLock::new(),
+        events: Default::default(),
         secret_key,
         metrics_registry: metrics_registry_poise,
         metrics,
     });
 
     let discord = Discord {
-        data,
+        data: data.clone(),
         framework,
         token,
         intents,
@@ -205,14 +250,24 @@ pub async fn ayayabot(
     let axum_state = Arc::new(TokioMutex::new(AxumState {
         metrics_registry,
         data_manager: data_manager_axum,
+        data,
     }));
 
+    // browsers can't send headers with event streams, so only they take the token as a query
+    let event_routes = axum::Router::new()
+        .route("/events", axum::routing::get(api::events_handler))
+        .layer(axum::middleware::from_fn_with_state(
+            axum_state.clone(),
+            auth_http::middleware::AuthMiddleware::require_auth_or_query_token,
+        ));
     let api_routes = axum::Router::new()
         .route("/auth/me", axum::routing::get(api::auth_me_handler))
+        .nest("/guilds/:guild_id/player", api::player_routes())
         .layer(axum::middleware::from_fn_with_state(
             axum_state.clone(),
             auth_http::middleware::AuthMiddleware::require_auth,
-        ));
+        ))
+        .merge(event_routes);
 
     let cors_layer = build_cors_layer();
 
@@ -265,13 +320,54 @@ fn gen_commands() -> Vec<poise::Command<Data, BotError>> {
     commands.append(&mut owner_commands());
     commands.append(&mut stats_commands());
     commands.append(&mut admin_commands());
+    commands.append(&mut settings_commands());
     commands
 }
 
+/// Prefix of prefix commands where the message was sent, the guild's own or [`DEFAULT_PREFIX`]
+async fn guild_prefix(
+    ctx: poise::PartialContext<'_, Data, BotError>,
+) -> Result<Option<Cow<'static, str>>, BotError> {
+    let Some(guild_id) = ctx.guild_id else {
+        return Ok(Some(DEFAULT_PREFIX.into()));
+    };
+    let data: Arc<Data> = ctx.serenity_context.data();
+    let prefix = match data
+        .data_manager
+        .guild_settings()
+        .get_settings(guild_id.get())
+        .await
+    {
+        Ok(config) => config.prefix,
+        Err(e) => {
+            error!("Error getting guild settings, using the default prefix: {e}");
+            None
+        }
+    };
+    Ok(Some(prefix.map_or(DEFAULT_PREFIX.into(), Cow::Owned)))
+}
+
 /// Global checks applied to all commands, unless command is excluded
 async fn global_checks(ctx: poise::Context<'_, Data, BotError>) -> Result<bool, BotError> {
     // check if a command is allowed to be called
-    utils::check_command_allowed(ctx).await
+    if !utils::check_command_allowed(ctx).await? {
+        return Ok(false);
+    }
+    // calls over a cooldown of the command fail, and are answered with the time left
+    settings::check_command_cooldown(ctx).await?;
+    Ok(true)
+}
+
+/// Whether the call of the command can be seen in its channel, and so streamed to dashboards.
+/// Ephemeral, owner and configuration commands are kept private.
+fn is_public_command(command: &poise::Command<Data, BotError>) -> bool {
+    !command.ephemeral
+        && !command.owners_only
+        && command.required_permissions.is_empty()
+        && !matches!(
+            command.category.as_deref(),
+            Some("Admin Commands" | "Owner Commands" | "Settings" | "Dashboard")
+        )
 }
 
 async fn pre_command(ctx: poise::Context<'_, Data, BotError>) {
@@ -289,6 +385,24 @@ async fn pre_command(ctx: poise::Context<'_, Data, BotError>) {
     let author = ctx.author().clone();
     let channel_id = ctx.channel_id();
     let guild_id = GuildInfo::guild_id_or_0(ctx);
+    let is_public = ctx
+        .parent_commands()
+        .iter()
+        .copied()
+        .chain([ctx.command()])
+        .all(is_public_command);
+    if let Some(guild_id) = ctx.guild_id()
+        && is_public
+    {
+        ctx.data().events.publish(
+            guild_id,
+            api::events::DashboardEventKind::CommandInvoked {
+                user_id: author.id.get(),
+                channel_id: channel_id.get(),
+                command: command_name.clone(),
+            },
+        );
+    }
     info!(
         "Command \"{command_name}\" called from channel {channel_id} in guild {guild_id:?} by {} ({})",
         author.name, author
@@ -522,6 +636,7 @@ impl LokiOpts {
 pub struct AxumState {
     pub(crate) metrics_registry: Arc<TokioMutex<Registry>>,
     pub(crate) data_manager: DataManager,
+    pub(crate) data: Arc<Data>,
 }
 
 async fn metrics_handler(
@@ -19,6 +19,8 @@ pub struct Metrics {
     data_calls: Arc<Mutex<Family<DataAccessLabel, Counter>>>,
     /// Gauge tracking cache items
     cache_items: Arc<Mutex<Family<CacheLabel, Gauge>>>,
+    /// Cache hit and miss counter
+    cache_accesses: Arc<Mutex<Family<CacheAccessLabel, Counter>>>,
     /// Error counter
     errors: Arc<Mutex<Family<ErrorLabel, Counter>>>,
     // Data access latency
@@ -32,6 +34,7 @@ impl Metrics {
             command_calls: Default::default(),
             data_calls: Default::default(),
             cache_items: Default::default(),
+            cache_accesses: Default::default(),
             errors: Default::default(),
             data_access_latency: Arc::new(Mutex::new(Family::new_with_constructor(|| {
                 let data_time_buckets = [
@@ -74,6 +77,13 @@ impl Metrics {
         let cache_metrics = self.cache_items.lock().await;
         registry.register("cache", "Amount of items in cache", cache_metrics.clone());
 
+        let cache_access_metrics = self.cache_accesses.lock().await;
+        registry.register(
+            "cache_accesses",
+            "Count of cache hits and misses",
+            cache_access_metrics.clone(),
+        );
+
         let error_metrics = self.errors.lock().await;
         registry.register("errors", "Count of errors", error_metrics.clone());
 
@@ -116,6 +126,20 @@ impl Metrics {
             .set(len as i64);
     }
 
+    /// Increase the hit or miss counter of the specified cache
+    pub async fn cache_access(&self, cache_name: impl ToString, hit: bool) {
+        let cache_name = cache_name.to_string();
+        let result = if hit {
+            CacheResult::Hit
+        } else {
+            CacheResult::Miss
+        };
+        let metric = self.cache_accesses.lock().await;
+        metric
+            .get_or_create(&CacheAccessLabel { cache_name, result })
+            .inc();
+    }
+
     pub async fn error(&self, error_name: impl ToString, error_type: ErrorType) {
         let error_name = error_name.to_string();
         let metric = self.errors.lock().await;
@@ -181,6 +205,18 @@ pub struct CacheLabel {
     pub cache_name: String,
 }
 
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct CacheAccessLabel {
+    pub cache_name: String,
+    pub result: CacheResult,
+}
+
+#[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelValue)]
+pub enum CacheResult {
+    Hit,
+    Miss,
+}
+
 #[derive(Debug, Clone, Hash, PartialEq, Eq, EncodeLabelSet)]
 pub struct ErrorLabel {
     pub error_name: String,
@@ -205,6 +241,10 @@ impl MetricsSink for Metrics {
     async fn cache_len(&self, name: &str, len: usize) {
         self.cache_len(name, len).await;
     }
+
+    async fn cache_access(&self, name: &str, hit: bool) {
+        self.cache_access(name, hit).await;
+    }
 }
 
 /// Build data from git
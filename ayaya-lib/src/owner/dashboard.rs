@@ -5,9 +5,11 @@ use poise::serenity_prelude::Mentionable;
 use snafu::ResultExt;
 
 use ayaya_core::auth::token::{generate_token, hash_token};
+use ayaya_db::entity::dashboard_tokens;
 
 use crate::{
     CommandResult, Context,
+    admin::audit::audit_global,
     error::{BotError, DataManagerSnafu, GeneralSerenitySnafu},
 };
 
@@ -63,6 +65,13 @@ pub async fn add_user(
         .await
     {
         Ok(true) => {
+            audit_global(
+                ctx,
+                "dashboard.allowlist.add",
+                None,
+                Some(format!("user {}", user.id)),
+            )
+            .await;
             let msg = if let Some(n) = notes {
                 format!(
                     "Added {} to dashboard allowlist.\nNotes: {}",
@@ -101,6 +110,13 @@ pub async fn remove_user(
 
     match ctx.data().data_manager.remove_from_allowlist(user_id).await {
         Ok(true) => {
+            audit_global(
+                ctx,
+                "dashboard.allowlist.remove",
+                Some(format!("user {}", user.id)),
+                None,
+            )
+            .await;
             ctx.reply(format!(
                 "Removed {} from dashboard allowlist.\nAll their tokens have been revoked.",
                 user.mention()
@@ -234,6 +250,13 @@ pub async fn revoke_token(
                 .revoke_token(token.token_id)
                 .await
                 .context(DataManagerSnafu)?;
+            audit_global(
+                ctx,
+                "dashboard.token.revoke",
+                Some(token_summary(token)),
+                None,
+            )
+            .await;
 
             let user_mention = serenity::UserId::new(token.user_id as u64).mention();
             ctx.reply(format!(
@@ -289,7 +312,17 @@ pub async fn create_token(
         .create_dashboard_token_hash(user_id, token_hash, description.clone())
         .await
     {
-        Ok(_token_id) => {
+        Ok(token_id) => {
+            audit_global(
+                ctx,
+                "dashboard.token.create",
+                None,
+                Some(format!(
+                    "token {} `{description}`",
+                    &token_id.to_string()[..8]
+                )),
+            )
+            .await;
             ctx.reply(format!(
                 "**Dashboard Token Created**\n\n🔑 Token: `{}`\n\n⚠️ **Save this token immediately!** It will not be shown again.\n\nDescription: {}\n\nUse this token in the dashboard's login page.",
                 token, description
@@ -394,6 +427,13 @@ pub async fn revoke_my_token(
                 .revoke_token(token.token_id)
                 .await
                 .context(DataManagerSnafu)?;
+            audit_global(
+                ctx,
+                "dashboard.token.revoke",
+                Some(token_summary(token)),
+                None,
+            )
+            .await;
 
             ctx.reply(format!("Revoked token: `{}`", token.description))
                 .await
@@ -408,3 +448,13 @@ pub async fn revoke_my_token(
 
     Ok(())
 }
+
+/// Short description of a token for the audit log, never the token itself
+fn token_summary(token: &dashboard_tokens::Model) -> String {
+    format!(
+        "token {} `{}` of user {}",
+        &token.token_id.to_string()[..8],
+        token.description,
+        token.user_id
+    )
+}
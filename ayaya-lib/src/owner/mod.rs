@@ -1,22 +1,34 @@
 //! Contains commands reserved for the bot's owner: ie me.
 mod dashboard;
 
-use poise::serenity_prelude as serenity;
+use ayaya_db::{
+    data::{command_stats::CommandLogFilter, voice_analytics::VoiceWindow},
+    entity::command_call_log,
+};
+use poise::serenity_prelude::{self as serenity, Mentionable};
 use snafu::ResultExt;
+use time::OffsetDateTime;
 
 use crate::{
     CommandResult, Commands, Context,
+    admin::audit::audit_global,
     error::{
         BotError, DataManagerSnafu, DownloadAttachmentSnafu, ExternalAsyncCommandSnafu,
         GeneralSerenitySnafu,
     },
+    stats::{StatsError, WindowChoice},
+    utils::{LINES_PER_PAGE, autocomplete_command_names, paginate},
+    voice::commands::soundboard::send_sound_pack,
 };
 
 pub fn owner_commands() -> Commands {
     vec![
         command_log_raw(),
+        command_log(),
         upload_cookies(),
         dep_versions(),
+        export_sound_pack(),
+        export_voice_data(),
         dashboard::dashboard(),
     ]
 }
@@ -46,6 +58,87 @@ pub async fn command_log_raw(ctx: Context<'_>) -> Result<(), BotError> {
     Ok(())
 }
 
+/// Page through the command call log, newest first, with optional filters. Owner only.
+#[poise::command(
+    slash_command,
+    prefix_command,
+    owners_only,
+    ephemeral,
+    hide_in_help,
+    category = "Owner Commands"
+)]
+pub async fn command_log(
+    ctx: Context<'_>,
+    #[description = "Only calls by this user"] user: Option<serenity::User>,
+    #[description = "Only calls of this command"]
+    #[autocomplete = "autocomplete_command_names"]
+    command: Option<String>,
+    #[description = "Only calls in this server id, 0 for DMs"] server_id: Option<String>,
+    #[description = "Only calls in this time window"] window: Option<WindowChoice>,
+) -> CommandResult {
+    ctx.defer().await.context(GeneralSerenitySnafu)?;
+    let server_id = server_id
+        .map(|input| {
+            input
+                .trim()
+                .parse::<u64>()
+                .map_err(|_| StatsError::InvalidServerId { input })
+        })
+        .transpose()?;
+    let filter = CommandLogFilter {
+        server_id,
+        user_id: user.map(|user| user.id.get()),
+        command,
+        since: window.and_then(|window| VoiceWindow::from(window).since(OffsetDateTime::now_utc())),
+    };
+
+    let command_stats = ctx.data().data_manager.command_stats();
+    let page_size = LINES_PER_PAGE as u64;
+    let first_page = command_stats
+        .get_command_log_page(&filter, 0, page_size)
+        .await
+        .context(DataManagerSnafu)?;
+    if first_page.total == 0 {
+        ctx.reply("No command calls match the filters.")
+            .await
+            .context(GeneralSerenitySnafu)?;
+        return Ok(());
+    }
+
+    let page_count = first_page.total.div_ceil(page_size) as usize;
+    let title = format!("Command log, {} calls", first_page.total);
+    paginate(ctx, &title, page_count, async |page| {
+        let entries = if page == 0 {
+            first_page.entries.clone()
+        } else {
+            command_stats
+                .get_command_log_page(&filter, page as u64, page_size)
+                .await
+                .context(DataManagerSnafu)?
+                .entries
+        };
+        Ok(entries
+            .iter()
+            .map(command_log_line)
+            .collect::<Vec<_>>()
+            .join("\n"))
+    })
+    .await
+}
+
+fn command_log_line(entry: &command_call_log::Model) -> String {
+    let server = match entry.server_id {
+        0 => "DM".to_string(),
+        server_id => server_id.to_string(),
+    };
+    format!(
+        "<t:{}:f> {server} {} `{}`",
+        entry.command_time_stamp.unix_timestamp(),
+        serenity::UserId::new(entry.user_id as u64).mention(),
+        entry.command
+    )
+}
+
 #[poise::command(
     slash_command,
     prefix_command,
@@ -57,6 +150,7 @@ pub async fn command_log_raw(ctx: Context<'_>) -> Result<(), BotError> {
 pub async fn upload_cookies(ctx: Context<'_>, file: serenity::Attachment) -> CommandResult {
     ctx.defer().await.context(GeneralSerenitySnafu)?;
     let data_manager = ctx.data().data_manager.clone();
+    let file_name = file.filename.to_string();
     let file = match file.download().await {
         Ok(down) => {
             tracing::info!("downloaded file from discord");
@@ -74,6 +168,7 @@ pub async fn upload_cookies(ctx: Context<'_>, file: serenity::Attachment) -> Com
         tracing::error!("{e}");
         return Err(e).context(DataManagerSnafu);
     } else {
+        audit_global(ctx, "cookies.upload", None, Some(file_name)).await;
         ctx.reply("Uploaded").await.context(GeneralSerenitySnafu)?;
     }
 
@@ -117,3 +212,139 @@ pub async fn dep_versions(ctx: Context<'_>) -> CommandResult {
         .context(GeneralSerenitySnafu)?;
     Ok(())
 }
+
+/// Export a sound pack of a user's sounds, or every sound if no user is given. Owner only.
+#[poise::command(
+    slash_command,
+    prefix_command,
+    owners_only,
+    ephemeral,
+    hide_in_help,
+    category = "Owner Commands"
+)]
+pub async fn export_sound_pack(
+    ctx: Context<'_>,
+    #[description = "Only export sounds uploaded by this user"] user: Option<serenity::User>,
+) -> CommandResult {
+    ctx.defer().await.context(GeneralSerenitySnafu)?;
+    let sound_manager = ctx.data().data_manager.sounds();
+
+    let (sounds, filename) = match user {
+        Some(user) => (
+            sound_manager
+                .get_user_sounds(&user.id)
+                .await
+                .context(DataManagerSnafu)?,
+            format!("sounds-{}.zip", user.id),
+        ),
+        None => (
+            sound_manager
+                .get_all_sounds()
+                .await
+                .context(DataManagerSnafu)?,
+            "sounds-all.zip".to_string(),
+        ),
+    };
+
+    if sounds.is_empty() {
+        ctx.reply("No sounds to export.")
+            .await
+            .context(GeneralSerenitySnafu)?;
+        return Ok(());
+    }
+
+    send_sound_pack(ctx, &sounds, filename).await
+}
+
+/// Export everything the voice monitor stored about a user, eg: on their request. Owner only.
+#[poise::command(
+    slash_command,
+    prefix_command,
+    owners_only,
+    ephemeral,
+    hide_in_help,
+    category = "Owner Commands"
+)]
+pub async fn export_voice_data(ctx: Context<'_>, user: serenity::User) -> CommandResult {
+    ctx.defer().await.context(GeneralSerenitySnafu)?;
+    let voice_data = ctx
+        .data()
+        .data_manager
+        .voice_privacy()
+        .get_user_voice_data(user.id.get())
+        .await
+        .context(DataManagerSnafu)?;
+
+    // raw states are stored as JSON strings, embed them as objects when they parse
+    let raw_json = |raw: &Option<String>| {
+        raw.as_deref().map(|raw| {
+            serde_json::from_str::<serde_json::Value>(raw)
+                .unwrap_or_else(|_| serde_json::Value::String(raw.to_string()))
+        })
+    };
+    let sessions = voice_data
+        .sessions
+        .iter()
+        .map(|session| {
+            serde_json::json!({
+                "session_id": session.session_id.to_string(),
+                "guild_id": session.guild_id.to_string(),
+                "channel_id": session.channel_id.to_string(),
+                "joined_at": session.joined_at.to_string(),
+                "start_is_estimated": session.start_is_estimated,
+                "left_at": session.left_at.map(|left_at| left_at.to_string()),
+                "ended_reason": session.ended_reason,
+                "join_state": raw_json(&session.join_state_json),
+                "leave_state": raw_json(&session.leave_state_json),
+            })
+        })
+        .collect::<Vec<_>>();
+    let events = voice_data
+        .events
+        .iter()
+        .map(|event| {
+            serde_json::json!({
+                "event_id": event.event_id.to_string(),
+                "guild_id": event.guild_id.to_string(),
+                "event_kind": event.event_kind,
+                "from_channel_id": event.from_channel_id.map(|id| id.to_string()),
+                "to_channel_id": event.to_channel_id.map(|id| id.to_string()),
+                "occurred_at": event.occurred_at.to_string(),
+                "session_id": event.session_id.map(|id| id.to_string()),
+                "self_mute": event.self_mute,
+                "self_deaf": event.self_deaf,
+                "mute": event.mute,
+                "deaf": event.deaf,
+                "self_stream": event.self_stream,
+                "self_video": event.self_video,
+                "suppress": event.suppress,
+                "request_to_speak_at": event.request_to_speak_at.map(|at| at.to_string()),
+                "raw_state": raw_json(&event.raw_state_json),
+            })
+        })
+        .collect::<Vec<_>>();
+    let export = serde_json::json!({
+        "user_id": user.id.to_string(),
+        "opted_out_at": voice_data.opted_out_at.map(|at| at.to_string()),
+        "sessions": sessions,
+        "events": events,
+    });
+
+    let attachment = serenity::CreateAttachment::bytes(
+        export.to_string().into_bytes(),
+        format!("voice-data-{}.json", user.id),
+    );
+    ctx.send(
+        poise::CreateReply::default()
+            .content(format!(
+                "Voice data of {}: {} sessions, {} events.",
+                user.name,
+                voice_data.sessions.len(),
+                voice_data.events.len()
+            ))
+            .attachment(attachment),
+    )
+    .await
+    .context(GeneralSerenitySnafu)?;
+    Ok(())
+}
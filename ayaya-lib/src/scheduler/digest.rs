@@ -0,0 +1,168 @@
+//! Weekly digest posted to the channel picked by each guild
+use std::sync::Arc;
+
+use ayaya_db::data::{
+    digest::{DigestConfig, DigestContent},
+    voice_analytics::REPORT_OFFSET,
+};
+use poise::serenity_prelude::{self as serenity, Mentionable};
+use serenity::all::Context;
+use time::{Duration, OffsetDateTime};
+
+use crate::Data;
+
+/// Time between two checks for due digests
+const CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+/// New sounds listed by name, the rest are counted
+const LISTED_SOUNDS: usize = 20;
+
+/// Post the digests that are due every [`CHECK_INTERVAL`]. Runs until the bot stops.
+pub async fn post_digests_periodically(context: Context) {
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        post_due_digests(&context).await;
+    }
+}
+
+async fn post_due_digests(context: &Context) {
+    let data: Arc<Data> = context.data();
+    let digest_manager = data.data_manager.digest();
+    let now = OffsetDateTime::now_utc();
+    let due = match digest_manager.get_due_digests(now, REPORT_OFFSET).await {
+        Ok(due) => due,
+        Err(error) => {
+            tracing::error!("Failed to get due digests: {error}");
+            return;
+        }
+    };
+
+    for (guild_id, config) in due {
+        post_digest(context, guild_id, &config, now).await;
+        // a digest that can't be posted, eg: the channel was deleted, waits for the next week
+        if let Err(error) = digest_manager.mark_digest_posted(guild_id, now).await {
+            tracing::error!("Failed to mark the digest of guild {guild_id} as posted: {error}");
+        }
+    }
+}
+
+async fn post_digest(context: &Context, guild_id: u64, config: &DigestConfig, now: OffsetDateTime) {
+    let data: Arc<Data> = context.data();
+    let channel_id = serenity::ChannelId::new(config.channel_id);
+    // digests saved before channels were checked may point to another guild
+    let in_guild = context
+        .cache
+        .guild(serenity::GuildId::new(guild_id))
+        .is_some_and(|guild| guild.channels.contains_key(&channel_id));
+    if !in_guild {
+        tracing::warn!("Digest channel {channel_id} is not a channel of guild {guild_id}");
+        return;
+    }
+    let since = now - Duration::weeks(1);
+    let content = match data
+        .data_manager
+        .digest()
+        .build_digest(guild_id, config.sections, since, now)
+        .await
+    {
+        Ok(content) => content,
+        Err(error) => {
+            tracing::error!("Failed to build the digest of guild {guild_id}: {error}");
+            return;
+        }
+    };
+
+    let guild_name = serenity::GuildId::new(guild_id)
+        .to_guild_cached(&context.cache)
+        .map(|guild| guild.name.to_string());
+    let title = match guild_name {
+        Some(name) => format!("Weekly digest of {name}"),
+        None => "Weekly digest".to_string(),
+    };
+    match channel_id
+        .send_message(
+            &context.http,
+            serenity::CreateMessage::new().embed(digest_embed(title, &content)),
+        )
+        .await
+    {
+        Ok(_) => tracing::info!("Posted the weekly digest of guild {guild_id}"),
+        Err(error) => tracing::warn!("Failed to post the digest of guild {guild_id}: {error}"),
+    }
+}
+
+/// Embed with a field per section that has entries
+pub fn digest_embed(title: String, content: &DigestContent) -> serenity::CreateEmbed<'static> {
+    let mut embed = serenity::CreateEmbed::default()
+        .title(title)
+        .color(serenity::Color::ROHRKATZE_BLUE)
+        .timestamp(serenity::Timestamp::now());
+    if content.is_empty() {
+        return embed.description("Nothing happened this week.");
+    }
+
+    if !content.top_voice.is_empty() {
+        let lines = content
+            .top_voice
+            .iter()
+            .enumerate()
+            .map(|(i, (user_id, duration))| {
+                format!(
+                    "{}. {}: {}",
+                    i + 1,
+                    serenity::UserId::new(*user_id).mention(),
+                    format_duration(*duration)
+                )
+            })
+            .collect::<Vec<_>>();
+        embed = embed.field("Top voice members", lines.join("\n"), false);
+    }
+    if !content.top_songs.is_empty() {
+        let lines = content
+            .top_songs
+            .iter()
+            .enumerate()
+            .map(|(i, song)| {
+                let url = format!("https://www.youtube.com/watch?v={}", song.youtube_id);
+                let song_name = match &song.description {
+                    Some(description) => format!("[{description}]({url})"),
+                    None => url,
+                };
+                format!("{}. {song_name}: {} plays", i + 1, song.plays)
+            })
+            .collect::<Vec<_>>();
+        embed = embed.field("Top songs this week", lines.join("\n"), false);
+    }
+    if !content.top_commands.is_empty() {
+        let lines = content
+            .top_commands
+            .iter()
+            .enumerate()
+            .map(|(i, (command, count))| format!("{}. `{command}`: {count}", i + 1))
+            .collect::<Vec<_>>();
+        embed = embed.field("Most used commands this week", lines.join("\n"), false);
+    }
+    if !content.new_sounds.is_empty() {
+        let mut names = content
+            .new_sounds
+            .iter()
+            .take(LISTED_SOUNDS)
+            .map(|sound| format!("`{}`", sound.sound_name))
+            .collect::<Vec<_>>();
+        if content.new_sounds.len() > LISTED_SOUNDS {
+            names.push(format!(
+                "and {} more",
+                content.new_sounds.len() - LISTED_SOUNDS
+            ));
+        }
+        embed = embed.field("New sounds", names.join(", "), false);
+    }
+
+    embed.footer(serenity::CreateEmbedFooter::new("Ayaya Discord Bot"))
+}
+
+/// Whole minutes as "3h 20m"
+fn format_duration(duration: Duration) -> String {
+    let minutes = duration.whole_minutes().max(0) as u64;
+    humantime::format_duration(std::time::Duration::from_secs(minutes * 60)).to_string()
+}
@@ -0,0 +1,19 @@
+//! Background jobs that run on a schedule once the bot is connected
+use std::sync::{Arc, atomic::Ordering};
+
+use serenity::all::Context;
+
+use crate::Data;
+
+pub(crate) mod digest;
+
+/// Start the scheduled jobs. Only the first call starts them, later `Ready` events reuse the
+/// running jobs.
+pub fn start_scheduler(context: &Context) {
+    let data: Arc<Data> = context.data();
+    if data.scheduler_started.swap(true, Ordering::Relaxed) {
+        return;
+    }
+    tracing::info!("Starting scheduled jobs");
+    tokio::spawn(digest::post_digests_periodically(context.clone()));
+}
@@ -0,0 +1,314 @@
+//! Cooldowns limiting how often commands can be used, and their enforcement before every command
+use std::time::{Duration, Instant};
+
+use ayaya_db::data::{
+    cooldowns::{CooldownBucket, CooldownCaller, CooldownRule, applicable_cooldowns},
+    permission_rules::RuleScope,
+};
+use poise::serenity_prelude as serenity;
+use snafu::{ResultExt, Snafu};
+
+use super::{SettingsError, parse_duration};
+use crate::{
+    CommandResult, Context, Data,
+    admin::{audit::audit, autocomplete_command_categories},
+    error::{BotError, DataManagerSnafu, ErrorName, GeneralSerenitySnafu, UserFriendlyError},
+    utils::{GuildInfo, autocomplete_command_names, get_guild_id},
+};
+
+/// Most uses a cooldown can allow
+pub(super) const MAX_COOLDOWN_USES: u32 = 100;
+/// Longest cooldown period
+pub(super) const MAX_COOLDOWN_PERIOD: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Cooldowns of commands that are easy to spam, used until a guild sets its own for the same
+/// command and bucket: command, uses and period in seconds, per user
+const DEFAULT_COOLDOWNS: [(&str, u32, u64); 3] =
+    [("play", 5, 30), ("gay", 3, 60), ("upload_sound", 3, 300)];
+
+fn default_cooldowns() -> Vec<CooldownRule> {
+    DEFAULT_COOLDOWNS
+        .into_iter()
+        .map(|(command, uses, period_secs)| CooldownRule {
+            scope: RuleScope::Command,
+            name: command.to_string(),
+            bucket: CooldownBucket::User,
+            uses,
+            period: Duration::from_secs(period_secs),
+        })
+        .collect()
+}
+
+/// The guild's cooldowns, followed by the defaults so the guild's replace them
+pub(crate) async fn guild_cooldowns(
+    data: &Data,
+    guild_id: u64,
+) -> Result<Vec<CooldownRule>, BotError> {
+    let mut rules = data
+        .data_manager
+        .cooldowns()
+        .get_cooldowns(guild_id)
+        .await
+        .context(DataManagerSnafu)?;
+    rules.extend(default_cooldowns());
+    Ok(rules)
+}
+
+/// Check command counting the call against the cooldowns of the command and its category. A
+/// call over a cooldown fails with [`CooldownError::OnCooldown`].
+pub async fn check_command_cooldown(ctx: Context<'_>) -> Result<(), BotError> {
+    let command = &ctx.command().name;
+    let category = ctx.command().category.clone().unwrap_or("Unknown".into());
+    let guild_id = GuildInfo::guild_id_or_0(ctx);
+
+    let rules = guild_cooldowns(&ctx.data(), guild_id).await?;
+    let cooldowns = applicable_cooldowns(&rules, command, &category);
+    if cooldowns.is_empty() {
+        return Ok(());
+    }
+    let caller = CooldownCaller {
+        guild_id,
+        user_id: ctx.author().id.get(),
+        channel_id: ctx.channel_id().get(),
+    };
+    match ctx
+        .data()
+        .data_manager
+        .cooldowns()
+        .try_use(caller, &cooldowns, Instant::now())
+    {
+        None => Ok(()),
+        Some(retry_after) => Err(CooldownError::OnCooldown {
+            command: command.to_string(),
+            // whole seconds, rounded up so waiting that long is always enough
+            retry_after: Duration::from_secs(retry_after.as_secs_f64().ceil() as u64),
+        }
+        .into()),
+    }
+}
+
+/// Who shares the uses of a cooldown
+#[derive(Debug, Clone, Copy, Default, poise::ChoiceParameter)]
+pub enum BucketChoice {
+    #[default]
+    #[name = "Each user"]
+    User,
+    #[name = "Each channel"]
+    Channel,
+    #[name = "The whole server"]
+    Guild,
+}
+
+impl From<BucketChoice> for CooldownBucket {
+    fn from(value: BucketChoice) -> Self {
+        match value {
+            BucketChoice::User => CooldownBucket::User,
+            BucketChoice::Channel => CooldownBucket::Channel,
+            BucketChoice::Guild => CooldownBucket::Guild,
+        }
+    }
+}
+
+/// View or change how often a command or command category can be used.
+///
+/// Without a command or category, shows every cooldown. A period of 0s turns a cooldown off,
+/// which also lifts a default one.
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    ephemeral,
+    required_permissions = "MANAGE_GUILD",
+    category = "Settings"
+)]
+pub async fn cooldown(
+    ctx: Context<'_>,
+    #[description = "The command to limit"]
+    #[autocomplete = "autocomplete_command_names"]
+    command: Option<String>,
+    #[description = "The command category to limit"]
+    #[autocomplete = "autocomplete_command_categories"]
+    category: Option<String>,
+    #[description = "Who shares the uses, each user by default"] bucket: Option<BucketChoice>,
+    #[description = "How many uses are allowed per period"] uses: Option<u32>,
+    #[description = "The period, eg: 30s, 5m. 0s turns the cooldown off"] per: Option<String>,
+    #[description = "Remove the cooldown, going back to the default"] remove: Option<bool>,
+) -> CommandResult {
+    ctx.defer_ephemeral().await.context(GeneralSerenitySnafu)?;
+    let guild_id = get_guild_id(ctx)?;
+    let (scope, name) = match (command, category) {
+        (Some(command), None) => (RuleScope::Command, command.trim().to_string()),
+        (None, Some(category)) => (RuleScope::Category, category.trim().to_string()),
+        (None, None) => {
+            let rules = guild_cooldowns(&ctx.data(), guild_id.get()).await?;
+            ctx.send(poise::CreateReply::default().embed(cooldowns_embed(&rules, "Cooldowns")))
+                .await
+                .context(GeneralSerenitySnafu)?;
+            return Ok(());
+        }
+        (Some(_), Some(_)) => return Err(SettingsError::CooldownTargetRequired.into()),
+    };
+    let bucket = CooldownBucket::from(bucket.unwrap_or_default());
+    let cooldown_manager = ctx.data().data_manager.cooldowns();
+
+    if remove.unwrap_or(false) {
+        let removed = cooldown_manager
+            .delete_cooldown(guild_id.get(), scope, &name, bucket)
+            .await
+            .context(DataManagerSnafu)?;
+        let message = match removed {
+            Some(rule) => {
+                tracing::info!("Removed cooldown {rule:?} of guild {guild_id}");
+                audit(
+                    ctx,
+                    "settings.cooldown.remove",
+                    Some(describe_cooldown(&rule)),
+                    None,
+                )
+                .await;
+                format!("Cooldown of {} `{name}` removed.", scope.as_str())
+            }
+            None => format!(
+                "{} `{name}` has no cooldown for {}.",
+                capitalize(scope.as_str()),
+                bucket_name(bucket)
+            ),
+        };
+        ctx.reply(message).await.context(GeneralSerenitySnafu)?;
+        return Ok(());
+    }
+
+    let (Some(uses), Some(per)) = (uses, per) else {
+        return Err(SettingsError::CooldownLimitRequired.into());
+    };
+    let period = parse_duration(&per)?;
+    let in_range = period >= Duration::from_secs(1)
+        && period <= MAX_COOLDOWN_PERIOD
+        && (1..=MAX_COOLDOWN_USES).contains(&uses);
+    if !period.is_zero() && !in_range {
+        return Err(SettingsError::CooldownOutOfRange.into());
+    }
+    let rule = CooldownRule {
+        scope,
+        name,
+        bucket,
+        uses,
+        period: Duration::from_secs(period.as_secs()),
+    };
+    let previous = cooldown_manager
+        .set_cooldown(guild_id.get(), &rule)
+        .await
+        .context(DataManagerSnafu)?;
+    tracing::info!("Set cooldown {rule:?} of guild {guild_id}");
+    audit(
+        ctx,
+        "settings.cooldown",
+        previous.as_ref().map(describe_cooldown),
+        Some(describe_cooldown(&rule)),
+    )
+    .await;
+
+    let rules = guild_cooldowns(&ctx.data(), guild_id.get()).await?;
+    ctx.send(poise::CreateReply::default().embed(cooldowns_embed(&rules, "Cooldowns updated")))
+        .await
+        .context(GeneralSerenitySnafu)?;
+    Ok(())
+}
+
+fn bucket_name(bucket: CooldownBucket) -> &'static str {
+    match bucket {
+        CooldownBucket::User => "each user",
+        CooldownBucket::Channel => "each channel",
+        CooldownBucket::Guild => "the whole server",
+    }
+}
+
+fn capitalize(value: &str) -> String {
+    let mut chars = value.chars();
+    chars
+        .next()
+        .map(|first| first.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
+}
+
+fn describe_cooldown(rule: &CooldownRule) -> String {
+    let limit = if rule.period.is_zero() {
+        "no cooldown".to_string()
+    } else {
+        format!(
+            "{} uses per {}",
+            rule.uses,
+            humantime::format_duration(rule.period)
+        )
+    };
+    format!(
+        "{} `{}`: {limit} for {}",
+        rule.scope.as_str(),
+        rule.name,
+        bucket_name(rule.bucket)
+    )
+}
+
+fn cooldowns_embed(rules: &[CooldownRule], title: &str) -> serenity::CreateEmbed<'static> {
+    let defaults = default_cooldowns();
+    let lines = rules
+        .iter()
+        .enumerate()
+        .filter(|(index, rule)| {
+            // defaults the guild replaced are not in effect
+            !rules[..*index].iter().any(|other| {
+                other.scope == rule.scope && other.name == rule.name && other.bucket == rule.bucket
+            })
+        })
+        .map(|(_, rule)| {
+            let default = if defaults.contains(rule) {
+                " *(default)*"
+            } else {
+                ""
+            };
+            format!("- {}{default}", describe_cooldown(rule))
+        })
+        .collect::<Vec<_>>();
+
+    serenity::CreateEmbed::default()
+        .title(title)
+        .description(if lines.is_empty() {
+            "No cooldowns.".to_string()
+        } else {
+            lines.join("\n")
+        })
+}
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub))]
+pub enum CooldownError {
+    #[snafu(display(
+        "`{command}` is on cooldown, try again in {}.",
+        humantime::format_duration(*retry_after)
+    ))]
+    OnCooldown {
+        command: String,
+        retry_after: Duration,
+    },
+}
+
+impl ErrorName for CooldownError {
+    fn name(&self) -> String {
+        let name = match self {
+            CooldownError::OnCooldown { .. } => "on_cooldown",
+        };
+        format!("cooldown::{name}")
+    }
+}
+
+impl UserFriendlyError for CooldownError {
+    fn help_text(&self) -> &str {
+        match self {
+            CooldownError::OnCooldown { .. } => "Wait a moment before using the command again.",
+        }
+    }
+
+    fn category(&self) -> crate::error::ErrorCategory {
+        crate::error::ErrorCategory::UserMistake
+    }
+}
@@ -0,0 +1,285 @@
+//! General guild settings, viewed and changed by key
+use ayaya_db::data::guild_settings::{GuildConfig, GuildSettingKey};
+use poise::serenity_prelude as serenity;
+use serenity::Mentionable;
+use snafu::ResultExt;
+
+use super::{InvalidSettingValueSnafu, SettingsError, UnknownSettingSnafu};
+use crate::{
+    CommandResult, Context, DEFAULT_PREFIX,
+    admin::audit::audit,
+    error::{DataManagerSnafu, GeneralSerenitySnafu},
+    utils::get_guild_id,
+};
+
+/// Longest accepted command prefix
+const MAX_PREFIX_LEN: usize = 10;
+
+/// Show every general setting of the server.
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    ephemeral,
+    required_permissions = "MANAGE_GUILD",
+    category = "Settings"
+)]
+pub async fn view(ctx: Context<'_>) -> CommandResult {
+    ctx.defer_ephemeral().await.context(GeneralSerenitySnafu)?;
+    let guild_id = get_guild_id(ctx)?;
+    let config = ctx
+        .data()
+        .data_manager
+        .guild_settings()
+        .get_settings(guild_id.get())
+        .await
+        .context(DataManagerSnafu)?;
+
+    ctx.send(poise::CreateReply::default().embed(settings_embed(&config, "Server settings")))
+        .await
+        .context(GeneralSerenitySnafu)?;
+    Ok(())
+}
+
+/// Change a general setting of the server.
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    ephemeral,
+    required_permissions = "MANAGE_GUILD",
+    category = "Settings"
+)]
+pub async fn set(
+    ctx: Context<'_>,
+    #[description = "The setting to change"]
+    #[autocomplete = "autocomplete_setting_keys"]
+    key: String,
+    #[description = "The new value"] value: String,
+) -> CommandResult {
+    ctx.defer_ephemeral().await.context(GeneralSerenitySnafu)?;
+    let guild_id = get_guild_id(ctx)?;
+    let key = parse_key(&key)?;
+    let settings_manager = ctx.data().data_manager.guild_settings();
+
+    let mut config = settings_manager
+        .get_settings(guild_id.get())
+        .await
+        .context(DataManagerSnafu)?;
+    let before = setting_value(&config, key);
+    apply_value(&mut config, key, value.trim(), &guild_channel_ids(ctx))?;
+    settings_manager
+        .save_settings(guild_id.get(), &config)
+        .await
+        .context(DataManagerSnafu)?;
+    tracing::info!("Updated setting {} of guild {guild_id}", key.name());
+    audit(
+        ctx,
+        &format!("settings.{}", key.name()),
+        before,
+        setting_value(&config, key),
+    )
+    .await;
+
+    ctx.send(
+        poise::CreateReply::default().embed(settings_embed(&config, "Server settings updated")),
+    )
+    .await
+    .context(GeneralSerenitySnafu)?;
+    Ok(())
+}
+
+/// Put a general setting of the server back to its default.
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    ephemeral,
+    required_permissions = "MANAGE_GUILD",
+    category = "Settings"
+)]
+pub async fn reset(
+    ctx: Context<'_>,
+    #[description = "The setting to reset"]
+    #[autocomplete = "autocomplete_setting_keys"]
+    key: String,
+) -> CommandResult {
+    ctx.defer_ephemeral().await.context(GeneralSerenitySnafu)?;
+    let guild_id = get_guild_id(ctx)?;
+    let key = parse_key(&key)?;
+    let settings_manager = ctx.data().data_manager.guild_settings();
+
+    let mut config = settings_manager
+        .get_settings(guild_id.get())
+        .await
+        .context(DataManagerSnafu)?;
+    let before = setting_value(&config, key);
+    config.reset(key);
+    settings_manager
+        .save_settings(guild_id.get(), &config)
+        .await
+        .context(DataManagerSnafu)?;
+    tracing::info!("Reset setting {} of guild {guild_id}", key.name());
+    if before.is_some() {
+        audit(ctx, &format!("settings.{}", key.name()), before, None).await;
+    }
+
+    ctx.send(
+        poise::CreateReply::default().embed(settings_embed(&config, "Server settings updated")),
+    )
+    .await
+    .context(GeneralSerenitySnafu)?;
+    Ok(())
+}
+
+async fn autocomplete_setting_keys<'a>(
+    _ctx: Context<'_>,
+    partial: &str,
+) -> serenity::CreateAutocompleteResponse<'a> {
+    let partial = partial.to_lowercase();
+    let choices = GuildSettingKey::ALL
+        .into_iter()
+        .filter(|key| key.name().contains(&partial))
+        .map(|key| {
+            serenity::AutocompleteChoice::new(
+                format!("{}: {}", key.name(), key.description()),
+                key.name(),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    serenity::CreateAutocompleteResponse::new().set_choices(choices)
+}
+
+fn parse_key(key: &str) -> Result<GuildSettingKey, SettingsError> {
+    GuildSettingKey::parse(key.trim()).ok_or_else(|| {
+        UnknownSettingSnafu {
+            key: key.to_string(),
+        }
+        .build()
+    })
+}
+
+/// Parse a value of the setting into the config. Channels must be among `channel_ids`, the
+/// channels of the guild.
+fn apply_value(
+    config: &mut GuildConfig,
+    key: GuildSettingKey,
+    value: &str,
+    channel_ids: &[u64],
+) -> Result<(), SettingsError> {
+    let invalid = |reason: &str| {
+        InvalidSettingValueSnafu {
+            key: key.name(),
+            reason,
+        }
+        .build()
+    };
+
+    match key {
+        GuildSettingKey::Prefix => {
+            if value.is_empty()
+                || value.chars().count() > MAX_PREFIX_LEN
+                || value.chars().any(char::is_whitespace)
+            {
+                return Err(invalid(&format!(
+                    "a prefix is 1 to {MAX_PREFIX_LEN} characters without spaces"
+                )));
+            }
+            config.prefix = Some(value.to_string());
+        }
+        GuildSettingKey::MusicChannel => {
+            let channel_id = parse_channel(value)
+                .ok_or_else(|| invalid("give a channel mention, like #music"))?;
+            if !channel_ids.contains(&channel_id) {
+                return Err(invalid("the channel is not in this server"));
+            }
+            config.music_channel_id = Some(channel_id);
+        }
+        GuildSettingKey::AuditChannel => {
+            let channel_id = parse_channel(value)
+                .ok_or_else(|| invalid("give a channel mention, like #audit"))?;
+            if !channel_ids.contains(&channel_id) {
+                return Err(invalid("the channel is not in this server"));
+            }
+            config.audit_channel_id = Some(channel_id);
+        }
+        GuildSettingKey::PublicUploads => {
+            let public = match value.to_lowercase().as_str() {
+                "true" | "yes" | "on" => true,
+                "false" | "no" | "off" => false,
+                _ => return Err(invalid("give true or false")),
+            };
+            config.public_uploads = Some(public);
+        }
+    }
+    Ok(())
+}
+
+/// The stored value of a setting, if it is set
+fn setting_value(config: &GuildConfig, key: GuildSettingKey) -> Option<String> {
+    match key {
+        GuildSettingKey::Prefix => config.prefix.clone(),
+        GuildSettingKey::MusicChannel => config.music_channel_id.map(|id| id.to_string()),
+        GuildSettingKey::PublicUploads => config.public_uploads.map(|public| public.to_string()),
+        GuildSettingKey::AuditChannel => config.audit_channel_id.map(|id| id.to_string()),
+    }
+}
+
+/// Ids of the guild's channels, from the cache
+fn guild_channel_ids(ctx: Context<'_>) -> Vec<u64> {
+    ctx.guild()
+        .map(|guild| {
+            guild
+                .channels
+                .iter()
+                .map(|channel| channel.id.get())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// A channel mention or id
+fn parse_channel(value: &str) -> Option<u64> {
+    value
+        .strip_prefix("<#")
+        .and_then(|value| value.strip_suffix('>'))
+        .unwrap_or(value)
+        .parse::<u64>()
+        .ok()
+        .filter(|id| *id != 0)
+}
+
+fn settings_embed<'a>(config: &GuildConfig, title: &'a str) -> serenity::CreateEmbed<'a> {
+    let mut description = serenity::MessageBuilder::default();
+    for key in GuildSettingKey::ALL {
+        let value = match key {
+            GuildSettingKey::Prefix => match &config.prefix {
+                Some(prefix) => format!("`{prefix}`"),
+                None => format!("`{DEFAULT_PREFIX}` (default)"),
+            },
+            GuildSettingKey::MusicChannel => match config.music_channel_id {
+                Some(channel_id) => serenity::ChannelId::new(channel_id).mention().to_string(),
+                None => "the channel playback started from (default)".to_string(),
+            },
+            GuildSettingKey::PublicUploads => match config.public_uploads {
+                Some(public) => public.to_string(),
+                None => "true (default)".to_string(),
+            },
+            GuildSettingKey::AuditChannel => match config.audit_channel_id {
+                Some(channel_id) => serenity::ChannelId::new(channel_id).mention().to_string(),
+                None => "not mirrored (default)".to_string(),
+            },
+        };
+        description = description
+            .push_bold(format!("{}: ", key.name()))
+            .push_line(value);
+    }
+
+    serenity::CreateEmbed::default()
+        .title(title)
+        .description(description.build())
+        .footer(serenity::CreateEmbedFooter::new(
+            "Change with /settings set, go back to a default with /settings reset",
+        ))
+}
@@ -0,0 +1,1039 @@
+//! Per guild settings, edited by members with the Manage Server permission
+mod cooldowns;
+mod general;
+
+use std::time::Duration;
+
+use ayaya_db::data::{
+    digest::{DigestConfig, DigestSchedule},
+    idle::IdlePolicy,
+    voice_analytics::REPORT_OFFSET,
+    voice_feed::{FEED_PLACEHOLDERS, FeedIgnoreKind, VoiceFeedConfig},
+    voice_privacy::RetentionPolicy,
+};
+use poise::serenity_prelude as serenity;
+use serenity::Mentionable;
+use snafu::{ResultExt, Snafu};
+use time::{OffsetDateTime, Weekday};
+
+use crate::{
+    CommandResult, Commands, Context,
+    admin::audit::audit,
+    error::{DataManagerSnafu, ErrorName, GeneralSerenitySnafu, UserFriendlyError},
+    scheduler::digest::digest_embed,
+    utils::get_guild_id,
+};
+use cooldowns::cooldown;
+use general::{reset, set, view};
+
+pub(crate) use cooldowns::guild_cooldowns;
+pub use cooldowns::{CooldownError, check_command_cooldown};
+
+/// Shortest accepted interval between inactivity checks
+const MIN_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+/// Longest accepted inactivity timeout
+const MAX_TIMEOUT: Duration = Duration::from_secs(24 * 60 * 60);
+/// Longest accepted voice feed template
+const MAX_TEMPLATE_LEN: usize = 500;
+/// Longest accepted voice data retention, in days
+const MAX_RETENTION_DAYS: u32 = 3650;
+
+pub fn settings_commands() -> Commands {
+    vec![settings()]
+}
+
+/// Server settings. This command must be called with a subcommand.
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    subcommands(
+        "view",
+        "set",
+        "reset",
+        "idle",
+        "voice_feed",
+        "voice_feed_template",
+        "voice_feed_ignore",
+        "voice_retention",
+        "digest",
+        "digest_sections",
+        "digest_preview",
+        "cooldown"
+    ),
+    required_permissions = "MANAGE_GUILD",
+    category = "Settings"
+)]
+pub async fn settings(_ctx: Context<'_>) -> CommandResult {
+    Ok(())
+}
+
+/// View or change when Ayaya leaves an inactive voice channel.
+///
+/// Without arguments, shows the current settings. Changes apply the next time Ayaya joins.
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    ephemeral,
+    required_permissions = "MANAGE_GUILD",
+    category = "Settings"
+)]
+pub async fn idle(
+    ctx: Context<'_>,
+    #[description = "How long to stay while inactive, eg: 5m, 90s"] timeout: Option<String>,
+    #[description = "How often to check for inactivity, eg: 1m"] check_interval: Option<String>,
+    #[description = "Whether being alone in the channel counts as inactive"]
+    alone_is_inactive: Option<bool>,
+    #[description = "Whether a paused track counts as inactive"] paused_is_inactive: Option<bool>,
+    #[description = "Message sent when leaving. {timeout} is replaced with the timeout"]
+    leave_message: Option<String>,
+    #[description = "Go back to the default settings"] reset: Option<bool>,
+) -> CommandResult {
+    ctx.defer_ephemeral().await.context(GeneralSerenitySnafu)?;
+    let guild_id = get_guild_id(ctx)?;
+    let idle_manager = ctx.data().data_manager.idle();
+
+    if reset.unwrap_or(false) {
+        let before = idle_manager
+            .get_idle_policy(guild_id.get())
+            .await
+            .context(DataManagerSnafu)?;
+        idle_manager
+            .reset_idle_policy(guild_id.get())
+            .await
+            .context(DataManagerSnafu)?;
+        tracing::info!("Reset idle policy of guild {guild_id}");
+        let policy = IdlePolicy::default();
+        audit(
+            ctx,
+            "settings.idle",
+            Some(format!("{before:?}")),
+            Some(format!("{policy:?}")),
+        )
+        .await;
+        ctx.send(poise::CreateReply::default().embed(idle_embed(&policy, "Idle settings reset")))
+            .await
+            .context(GeneralSerenitySnafu)?;
+        return Ok(());
+    }
+
+    let mut policy = idle_manager
+        .get_idle_policy(guild_id.get())
+        .await
+        .context(DataManagerSnafu)?;
+    let before = policy.clone();
+    let changed = timeout.is_some()
+        || check_interval.is_some()
+        || alone_is_inactive.is_some()
+        || paused_is_inactive.is_some()
+        || leave_message.is_some();
+
+    if let Some(timeout) = timeout {
+        policy.timeout = parse_duration(&timeout)?;
+    }
+    if let Some(check_interval) = check_interval {
+        policy.check_interval = parse_duration(&check_interval)?;
+    }
+    if let Some(alone_is_inactive) = alone_is_inactive {
+        policy.alone_is_inactive = alone_is_inactive;
+    }
+    if let Some(paused_is_inactive) = paused_is_inactive {
+        policy.paused_is_inactive = paused_is_inactive;
+    }
+    if let Some(leave_message) = leave_message {
+        // an empty message goes back to the default one
+        let leave_message = leave_message.trim().to_string();
+        policy.leave_message = (!leave_message.is_empty()).then_some(leave_message);
+    }
+
+    if !changed {
+        ctx.send(poise::CreateReply::default().embed(idle_embed(&policy, "Idle settings")))
+            .await
+            .context(GeneralSerenitySnafu)?;
+        return Ok(());
+    }
+
+    validate_idle_policy(&policy)?;
+    idle_manager
+        .set_idle_policy(guild_id.get(), &policy)
+        .await
+        .context(DataManagerSnafu)?;
+    tracing::info!("Updated idle policy of guild {guild_id}: {policy:?}");
+    audit(
+        ctx,
+        "settings.idle",
+        Some(format!("{before:?}")),
+        Some(format!("{policy:?}")),
+    )
+    .await;
+
+    ctx.send(poise::CreateReply::default().embed(idle_embed(&policy, "Idle settings updated")))
+        .await
+        .context(GeneralSerenitySnafu)?;
+    Ok(())
+}
+
+/// View or change the voice activity feed, which posts join, leave and move notices.
+///
+/// Without arguments, shows the current settings.
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    ephemeral,
+    required_permissions = "MANAGE_GUILD",
+    category = "Settings"
+)]
+pub async fn voice_feed(
+    ctx: Context<'_>,
+    #[description = "The text channel to post notices to"]
+    #[channel_types("Text")]
+    channel: Option<serenity::GuildChannel>,
+    #[description = "Turn the feed on or off"] enabled: Option<bool>,
+    #[description = "Announce members joining a voice channel"] announce_join: Option<bool>,
+    #[description = "Announce members leaving a voice channel"] announce_leave: Option<bool>,
+    #[description = "Announce members moving between voice channels"] announce_move: Option<bool>,
+    #[description = "Remove the feed and its ignore lists"] remove: Option<bool>,
+) -> CommandResult {
+    ctx.defer_ephemeral().await.context(GeneralSerenitySnafu)?;
+    let guild_id = get_guild_id(ctx)?;
+    if let Some(channel) = &channel {
+        check_channel_of_guild(channel, guild_id)?;
+    }
+    let feed_manager = ctx.data().data_manager.voice_feed();
+
+    if remove.unwrap_or(false) {
+        let before = feed_manager
+            .get_voice_feed(guild_id.get())
+            .await
+            .context(DataManagerSnafu)?;
+        let removed = feed_manager
+            .delete_voice_feed(guild_id.get())
+            .await
+            .context(DataManagerSnafu)?;
+        let message = if removed {
+            tracing::info!("Removed voice feed of guild {guild_id}");
+            audit(
+                ctx,
+                "settings.voice_feed.remove",
+                before.map(|config| format!("{config:?}")),
+                None,
+            )
+            .await;
+            "Voice feed removed."
+        } else {
+            "The voice feed was not set up."
+        };
+        ctx.reply(message).await.context(GeneralSerenitySnafu)?;
+        return Ok(());
+    }
+
+    let existing = feed_manager
+        .get_voice_feed(guild_id.get())
+        .await
+        .context(DataManagerSnafu)?;
+    let before = existing.as_ref().map(|config| format!("{config:?}"));
+    let changed = channel.is_some()
+        || enabled.is_some()
+        || announce_join.is_some()
+        || announce_leave.is_some()
+        || announce_move.is_some();
+
+    let mut config = match (existing, &channel) {
+        (Some(config), _) => config,
+        (None, Some(channel)) => VoiceFeedConfig::new(channel.id.get()),
+        (None, None) if changed => return Err(SettingsError::VoiceFeedChannelRequired.into()),
+        (None, None) => {
+            ctx.reply("The voice feed is not set up. Pick a channel to set it up.")
+                .await
+                .context(GeneralSerenitySnafu)?;
+            return Ok(());
+        }
+    };
+
+    if let Some(channel) = channel {
+        config.channel_id = channel.id.get();
+    }
+    if let Some(enabled) = enabled {
+        config.enabled = enabled;
+    }
+    if let Some(announce_join) = announce_join {
+        config.announce_join = announce_join;
+    }
+    if let Some(announce_leave) = announce_leave {
+        config.announce_leave = announce_leave;
+    }
+    if let Some(announce_move) = announce_move {
+        config.announce_move = announce_move;
+    }
+    if !changed {
+        ctx.send(poise::CreateReply::default().embed(voice_feed_embed(&config, "Voice feed")))
+            .await
+            .context(GeneralSerenitySnafu)?;
+        return Ok(());
+    }
+
+    feed_manager
+        .save_voice_feed(guild_id.get(), &config)
+        .await
+        .context(DataManagerSnafu)?;
+    tracing::info!("Updated voice feed of guild {guild_id}: {config:?}");
+    audit(
+        ctx,
+        "settings.voice_feed",
+        before,
+        Some(format!("{config:?}")),
+    )
+    .await;
+
+    ctx.send(poise::CreateReply::default().embed(voice_feed_embed(&config, "Voice feed updated")))
+        .await
+        .context(GeneralSerenitySnafu)?;
+    Ok(())
+}
+
+/// Change the message of a kind of voice feed notice.
+///
+/// Templates can use {user}, {channel}, {from}, {to} and {guild}. Without a template, goes back
+/// to the default one.
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    ephemeral,
+    required_permissions = "MANAGE_GUILD",
+    category = "Settings"
+)]
+pub async fn voice_feed_template(
+    ctx: Context<'_>,
+    #[description = "The kind of notice"] kind: FeedNoticeChoice,
+    #[description = "The new template"] template: Option<String>,
+) -> CommandResult {
+    ctx.defer_ephemeral().await.context(GeneralSerenitySnafu)?;
+    let guild_id = get_guild_id(ctx)?;
+    let feed_manager = ctx.data().data_manager.voice_feed();
+
+    let mut config = feed_manager
+        .get_voice_feed(guild_id.get())
+        .await
+        .context(DataManagerSnafu)?
+        .ok_or(SettingsError::VoiceFeedChannelRequired)?;
+
+    let template = parse_template(template.unwrap_or_default())?;
+    let slot = match kind {
+        FeedNoticeChoice::Join => &mut config.join_template,
+        FeedNoticeChoice::Leave => &mut config.leave_template,
+        FeedNoticeChoice::Move => &mut config.move_template,
+    };
+    let before = std::mem::replace(slot, template.clone());
+
+    feed_manager
+        .save_voice_feed(guild_id.get(), &config)
+        .await
+        .context(DataManagerSnafu)?;
+    tracing::info!("Updated voice feed {kind} template of guild {guild_id}");
+    audit(
+        ctx,
+        &format!(
+            "settings.voice_feed.{}_template",
+            kind.to_string().to_lowercase()
+        ),
+        before,
+        template,
+    )
+    .await;
+
+    ctx.send(poise::CreateReply::default().embed(voice_feed_embed(&config, "Voice feed updated")))
+        .await
+        .context(GeneralSerenitySnafu)?;
+    Ok(())
+}
+
+/// Kinds of voice feed notices with a template
+#[derive(Debug, Clone, Copy, poise::ChoiceParameter, strum::Display)]
+pub enum FeedNoticeChoice {
+    Join,
+    Leave,
+    Move,
+}
+
+/// Hide the voice activity of a channel or member from the voice feed, or show it again.
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    ephemeral,
+    required_permissions = "MANAGE_GUILD",
+    category = "Settings"
+)]
+pub async fn voice_feed_ignore(
+    ctx: Context<'_>,
+    #[description = "The voice channel to ignore"]
+    #[channel_types("Voice", "Stage")]
+    channel: Option<serenity::GuildChannel>,
+    #[description = "The member to ignore"] user: Option<serenity::User>,
+    #[description = "Stop ignoring instead"] remove: Option<bool>,
+) -> CommandResult {
+    ctx.defer_ephemeral().await.context(GeneralSerenitySnafu)?;
+    let guild_id = get_guild_id(ctx)?;
+    if let Some(channel) = &channel {
+        check_channel_of_guild(channel, guild_id)?;
+    }
+    let feed_manager = ctx.data().data_manager.voice_feed();
+    let ignored = !remove.unwrap_or(false);
+
+    let mut targets = Vec::new();
+    if let Some(channel) = channel {
+        targets.push((
+            FeedIgnoreKind::Channel,
+            channel.id.get(),
+            channel.id.mention().to_string(),
+        ));
+    }
+    if let Some(user) = user {
+        targets.push((
+            FeedIgnoreKind::User,
+            user.id.get(),
+            user.id.mention().to_string(),
+        ));
+    }
+    if targets.is_empty() {
+        return Err(SettingsError::NothingToIgnore.into());
+    }
+
+    let mut lines = Vec::with_capacity(targets.len());
+    for (kind, target_id, mention) in targets {
+        let changed = feed_manager
+            .set_feed_ignored(guild_id.get(), kind, target_id, ignored)
+            .await
+            .context(DataManagerSnafu)?;
+        if changed {
+            let target = format!("{kind:?} {target_id}");
+            let (before, after) = if ignored {
+                (None, Some(target))
+            } else {
+                (Some(target), None)
+            };
+            audit(ctx, "settings.voice_feed.ignore", before, after).await;
+        }
+        lines.push(match (ignored, changed) {
+            (true, true) => format!("Now ignoring {mention}."),
+            (true, false) => format!("{mention} was already ignored."),
+            (false, true) => format!("No longer ignoring {mention}."),
+            (false, false) => format!("{mention} was not ignored."),
+        });
+    }
+    tracing::info!("Updated voice feed ignore list of guild {guild_id}");
+
+    ctx.reply(lines.join("\n"))
+        .await
+        .context(GeneralSerenitySnafu)?;
+    Ok(())
+}
+
+/// Refuse a channel of another server, which prefix calls can name
+fn check_channel_of_guild(
+    channel: &serenity::GuildChannel,
+    guild_id: serenity::GuildId,
+) -> Result<(), SettingsError> {
+    if channel.base.guild_id != guild_id {
+        return Err(SettingsError::ChannelNotInServer);
+    }
+    Ok(())
+}
+
+/// An empty template goes back to the default one
+fn parse_template(template: String) -> Result<Option<String>, SettingsError> {
+    let template = template.trim().to_string();
+    if template.chars().count() > MAX_TEMPLATE_LEN {
+        return Err(SettingsError::TemplateTooLong);
+    }
+    Ok((!template.is_empty()).then_some(template))
+}
+
+fn voice_feed_embed<'a>(config: &VoiceFeedConfig, title: &'a str) -> serenity::CreateEmbed<'a> {
+    let mentions = |ids: &[u64], mention: fn(u64) -> String| {
+        if ids.is_empty() {
+            "none".to_string()
+        } else {
+            ids.iter()
+                .map(|id| mention(*id))
+                .collect::<Vec<_>>()
+                .join(", ")
+        }
+    };
+    let template = |template: &Option<String>| template.as_deref().unwrap_or("default").to_string();
+
+    let description = serenity::MessageBuilder::default()
+        .push_bold("Channel: ")
+        .push_line(
+            serenity::ChannelId::new(config.channel_id)
+                .mention()
+                .to_string(),
+        )
+        .push_bold("Enabled: ")
+        .push_line(config.enabled.to_string())
+        .push_bold("Announce joins, leaves, moves: ")
+        .push_line(format!(
+            "{}, {}, {}",
+            config.announce_join, config.announce_leave, config.announce_move
+        ))
+        .push_bold("Join template: ")
+        .push_line_safe(template(&config.join_template))
+        .push_bold("Leave template: ")
+        .push_line_safe(template(&config.leave_template))
+        .push_bold("Move template: ")
+        .push_line_safe(template(&config.move_template))
+        .push_bold("Ignored channels: ")
+        .push_line(mentions(&config.ignored_channels, |id| {
+            serenity::ChannelId::new(id).mention().to_string()
+        }))
+        .push_bold("Ignored members: ")
+        .push_line(mentions(&config.ignored_users, |id| {
+            serenity::UserId::new(id).mention().to_string()
+        }))
+        .build();
+
+    serenity::CreateEmbed::default()
+        .title(title)
+        .description(description)
+        .footer(serenity::CreateEmbedFooter::new(format!(
+            "Template placeholders: {}",
+            FEED_PLACEHOLDERS.join(" ")
+        )))
+}
+
+/// View or change how long voice activity data is kept.
+///
+/// Without arguments, shows the current settings. Everything is kept until a retention is set, old
+/// data is then pruned a few times a day.
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    ephemeral,
+    required_permissions = "MANAGE_GUILD",
+    category = "Settings"
+)]
+pub async fn voice_retention(
+    ctx: Context<'_>,
+    #[description = "Days before the raw voice state data is cleared, 0 keeps it forever"]
+    #[max = 3650]
+    raw_state_days: Option<u32>,
+    #[description = "Days before voice history is deleted, 0 keeps it forever"]
+    #[max = 3650]
+    history_days: Option<u32>,
+) -> CommandResult {
+    ctx.defer_ephemeral().await.context(GeneralSerenitySnafu)?;
+    let guild_id = get_guild_id(ctx)?;
+    let privacy_manager = ctx.data().data_manager.voice_privacy();
+
+    let mut policy = privacy_manager
+        .get_retention_policy(guild_id.get())
+        .await
+        .context(DataManagerSnafu)?;
+    let before = policy;
+    if raw_state_days.is_none() && history_days.is_none() {
+        ctx.send(
+            poise::CreateReply::default().embed(retention_embed(&policy, "Voice data retention")),
+        )
+        .await
+        .context(GeneralSerenitySnafu)?;
+        return Ok(());
+    }
+
+    if let Some(raw_state_days) = raw_state_days {
+        policy.raw_state_days = (raw_state_days > 0).then_some(raw_state_days);
+    }
+    if let Some(history_days) = history_days {
+        policy.history_days = (history_days > 0).then_some(history_days);
+    }
+    if policy
+        .raw_state_days
+        .is_some_and(|days| days > MAX_RETENTION_DAYS)
+        || policy
+            .history_days
+            .is_some_and(|days| days > MAX_RETENTION_DAYS)
+    {
+        return Err(SettingsError::RetentionOutOfRange.into());
+    }
+
+    privacy_manager
+        .set_retention_policy(guild_id.get(), &policy)
+        .await
+        .context(DataManagerSnafu)?;
+    tracing::info!("Updated voice retention of guild {guild_id}: {policy:?}");
+    audit(
+        ctx,
+        "settings.voice_retention",
+        Some(format!("{before:?}")),
+        Some(format!("{policy:?}")),
+    )
+    .await;
+
+    ctx.send(
+        poise::CreateReply::default()
+            .embed(retention_embed(&policy, "Voice data retention updated")),
+    )
+    .await
+    .context(GeneralSerenitySnafu)?;
+    Ok(())
+}
+
+fn retention_embed<'a>(policy: &RetentionPolicy, title: &'a str) -> serenity::CreateEmbed<'a> {
+    let days = |days: Option<u32>| match days {
+        Some(days) => format!("{days} days"),
+        None => "forever".to_string(),
+    };
+    let description = serenity::MessageBuilder::default()
+        .push_bold("Raw voice state data: ")
+        .push_line(days(policy.raw_state_days))
+        .push_bold("Voice history: ")
+        .push_line(days(policy.history_days))
+        .push_italic_line("Members can opt out of voice tracking with /voice opt_out.")
+        .build();
+
+    serenity::CreateEmbed::default()
+        .title(title)
+        .description(description)
+}
+
+/// View or change the weekly digest, which posts the top voice members, songs, commands and new
+/// sounds of the week.
+///
+/// Without arguments, shows the current settings. Times are in UTC+8.
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    ephemeral,
+    required_permissions = "MANAGE_GUILD",
+    category = "Settings"
+)]
+pub async fn digest(
+    ctx: Context<'_>,
+    #[description = "The text channel to post the digest to"]
+    #[channel_types("Text")]
+    channel: Option<serenity::GuildChannel>,
+    #[description = "Turn the digest on or off"] enabled: Option<bool>,
+    #[description = "Day of the week to post on"] weekday: Option<WeekdayChoice>,
+    #[description = "Hour of the day to post at, in UTC+8"]
+    #[max = 23]
+    hour: Option<u8>,
+    #[description = "Remove the digest"] remove: Option<bool>,
+) -> CommandResult {
+    ctx.defer_ephemeral().await.context(GeneralSerenitySnafu)?;
+    let guild_id = get_guild_id(ctx)?;
+    if let Some(channel) = &channel {
+        check_channel_of_guild(channel, guild_id)?;
+    }
+    let digest_manager = ctx.data().data_manager.digest();
+
+    if remove.unwrap_or(false) {
+        let before = digest_manager
+            .get_digest(guild_id.get())
+            .await
+            .context(DataManagerSnafu)?;
+        digest_manager
+            .delete_digest(guild_id.get())
+            .await
+            .context(DataManagerSnafu)?;
+        tracing::info!("Removed digest of guild {guild_id}");
+        if let Some(before) = before {
+            audit(
+                ctx,
+                "settings.digest.remove",
+                Some(format!("{before:?}")),
+                None,
+            )
+            .await;
+        }
+        ctx.reply("Weekly digest removed.")
+            .await
+            .context(GeneralSerenitySnafu)?;
+        return Ok(());
+    }
+
+    let existing = digest_manager
+        .get_digest(guild_id.get())
+        .await
+        .context(DataManagerSnafu)?;
+    let before = existing.as_ref().map(|config| format!("{config:?}"));
+    let changed = channel.is_some() || enabled.is_some() || weekday.is_some() || hour.is_some();
+
+    let now = OffsetDateTime::now_utc();
+    let mut config = match (existing, &channel) {
+        (Some(config), _) => config,
+        // a new digest waits for its first scheduled time instead of posting right away
+        (None, Some(channel)) => DigestConfig {
+            last_posted_at: Some(now),
+            ..DigestConfig::new(channel.id.get())
+        },
+        (None, None) if changed => return Err(SettingsError::DigestChannelRequired.into()),
+        (None, None) => {
+            ctx.reply("The weekly digest is not set up. Pick a channel to set it up.")
+                .await
+                .context(GeneralSerenitySnafu)?;
+            return Ok(());
+        }
+    };
+
+    if let Some(channel) = channel {
+        config.channel_id = channel.id.get();
+    }
+    if let Some(enabled) = enabled {
+        config.enabled = enabled;
+    }
+    if let Some(weekday) = weekday {
+        config.schedule.weekday = weekday.into();
+    }
+    if let Some(hour) = hour {
+        config.schedule.hour = hour.min(23);
+    }
+    if weekday.is_some() || hour.is_some() {
+        // moving the schedule earlier in the week shouldn't post a digest right away
+        config.last_posted_at = Some(now);
+    }
+    if !changed {
+        ctx.send(
+            poise::CreateReply::default().embed(digest_settings_embed(&config, "Weekly digest")),
+        )
+        .await
+        .context(GeneralSerenitySnafu)?;
+        return Ok(());
+    }
+
+    digest_manager
+        .save_digest(guild_id.get(), &config)
+        .await
+        .context(DataManagerSnafu)?;
+    tracing::info!("Updated digest of guild {guild_id}: {config:?}");
+    audit(ctx, "settings.digest", before, Some(format!("{config:?}"))).await;
+
+    ctx.send(
+        poise::CreateReply::default()
+            .embed(digest_settings_embed(&config, "Weekly digest updated")),
+    )
+    .await
+    .context(GeneralSerenitySnafu)?;
+    Ok(())
+}
+
+/// Pick the sections of the weekly digest.
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    ephemeral,
+    required_permissions = "MANAGE_GUILD",
+    category = "Settings"
+)]
+pub async fn digest_sections(
+    ctx: Context<'_>,
+    #[description = "Members with the most time in voice"] voice: Option<bool>,
+    #[description = "Most played songs"] songs: Option<bool>,
+    #[description = "Most used commands"] commands: Option<bool>,
+    #[description = "New soundboard uploads"] sounds: Option<bool>,
+) -> CommandResult {
+    ctx.defer_ephemeral().await.context(GeneralSerenitySnafu)?;
+    let guild_id = get_guild_id(ctx)?;
+    let digest_manager = ctx.data().data_manager.digest();
+
+    let mut config = digest_manager
+        .get_digest(guild_id.get())
+        .await
+        .context(DataManagerSnafu)?
+        .ok_or(SettingsError::DigestChannelRequired)?;
+    let before = config.sections;
+
+    if let Some(voice) = voice {
+        config.sections.voice = voice;
+    }
+    if let Some(songs) = songs {
+        config.sections.songs = songs;
+    }
+    if let Some(commands) = commands {
+        config.sections.commands = commands;
+    }
+    if let Some(sounds) = sounds {
+        config.sections.sounds = sounds;
+    }
+
+    digest_manager
+        .save_digest(guild_id.get(), &config)
+        .await
+        .context(DataManagerSnafu)?;
+    tracing::info!(
+        "Updated digest sections of guild {guild_id}: {:?}",
+        config.sections
+    );
+    audit(
+        ctx,
+        "settings.digest_sections",
+        Some(format!("{before:?}")),
+        Some(format!("{:?}", config.sections)),
+    )
+    .await;
+
+    ctx.send(
+        poise::CreateReply::default()
+            .embed(digest_settings_embed(&config, "Weekly digest updated")),
+    )
+    .await
+    .context(GeneralSerenitySnafu)?;
+    Ok(())
+}
+
+/// Show what the weekly digest would post right now, without posting it.
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    ephemeral,
+    required_permissions = "MANAGE_GUILD",
+    category = "Settings"
+)]
+pub async fn digest_preview(ctx: Context<'_>) -> CommandResult {
+    ctx.defer_ephemeral().await.context(GeneralSerenitySnafu)?;
+    let guild_id = get_guild_id(ctx)?;
+    let digest_manager = ctx.data().data_manager.digest();
+
+    let config = digest_manager
+        .get_digest(guild_id.get())
+        .await
+        .context(DataManagerSnafu)?
+        .ok_or(SettingsError::DigestChannelRequired)?;
+    let now = OffsetDateTime::now_utc();
+    let content = digest_manager
+        .build_digest(
+            guild_id.get(),
+            config.sections,
+            now - time::Duration::weeks(1),
+            now,
+        )
+        .await
+        .context(DataManagerSnafu)?;
+
+    ctx.send(
+        poise::CreateReply::default()
+            .embed(digest_embed("Weekly digest preview".to_string(), &content)),
+    )
+    .await
+    .context(GeneralSerenitySnafu)?;
+    Ok(())
+}
+
+/// Days of the week for the digest schedule
+#[derive(Debug, Clone, Copy, poise::ChoiceParameter)]
+pub enum WeekdayChoice {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+impl From<WeekdayChoice> for Weekday {
+    fn from(value: WeekdayChoice) -> Self {
+        match value {
+            WeekdayChoice::Monday => Weekday::Monday,
+            WeekdayChoice::Tuesday => Weekday::Tuesday,
+            WeekdayChoice::Wednesday => Weekday::Wednesday,
+            WeekdayChoice::Thursday => Weekday::Thursday,
+            WeekdayChoice::Friday => Weekday::Friday,
+            WeekdayChoice::Saturday => Weekday::Saturday,
+            WeekdayChoice::Sunday => Weekday::Sunday,
+        }
+    }
+}
+
+fn digest_settings_embed<'a>(config: &DigestConfig, title: &'a str) -> serenity::CreateEmbed<'a> {
+    let DigestSchedule { weekday, hour } = config.schedule;
+    let next_post = config
+        .schedule
+        .last_due_at(OffsetDateTime::now_utc(), REPORT_OFFSET)
+        + time::Duration::weeks(1);
+    let sections = [
+        (config.sections.voice, "voice"),
+        (config.sections.songs, "songs"),
+        (config.sections.commands, "commands"),
+        (config.sections.sounds, "sounds"),
+    ]
+    .into_iter()
+    .filter_map(|(included, name)| included.then_some(name))
+    .collect::<Vec<_>>();
+
+    let description = serenity::MessageBuilder::default()
+        .push_bold("Channel: ")
+        .push_line(
+            serenity::ChannelId::new(config.channel_id)
+                .mention()
+                .to_string(),
+        )
+        .push_bold("Enabled: ")
+        .push_line(config.enabled.to_string())
+        .push_bold("Schedule: ")
+        .push_line(format!("every {weekday} at {hour:02}:00 UTC+8"))
+        .push_bold("Next post: ")
+        .push_line(format!("<t:{}:F>", next_post.unix_timestamp()))
+        .push_bold("Sections: ")
+        .push_line(if sections.is_empty() {
+            "none".to_string()
+        } else {
+            sections.join(", ")
+        })
+        .build();
+
+    serenity::CreateEmbed::default()
+        .title(title)
+        .description(description)
+}
+
+fn parse_duration(input: &str) -> Result<Duration, SettingsError> {
+    humantime::parse_duration(input.trim()).map_err(|_| SettingsError::InvalidDuration {
+        input: input.to_string(),
+    })
+}
+
+fn validate_idle_policy(policy: &IdlePolicy) -> Result<(), SettingsError> {
+    if policy.check_interval < MIN_CHECK_INTERVAL {
+        return Err(SettingsError::CheckIntervalTooShort);
+    }
+    if policy.timeout > MAX_TIMEOUT {
+        return Err(SettingsError::TimeoutTooLong);
+    }
+    if policy.timeout < policy.check_interval {
+        return Err(SettingsError::TimeoutShorterThanInterval);
+    }
+    Ok(())
+}
+
+fn idle_embed<'a>(policy: &IdlePolicy, title: &'a str) -> serenity::CreateEmbed<'a> {
+    let description = serenity::MessageBuilder::default()
+        .push_bold("Timeout: ")
+        .push_line(humantime::format_duration(policy.timeout).to_string())
+        .push_bold("Check interval: ")
+        .push_line(humantime::format_duration(policy.check_interval).to_string())
+        .push_bold("Alone counts as inactive: ")
+        .push_line(policy.alone_is_inactive.to_string())
+        .push_bold("Paused counts as inactive: ")
+        .push_line(policy.paused_is_inactive.to_string())
+        .push_bold("Leave message: ")
+        .push_line(policy.render_leave_message())
+        .build();
+
+    serenity::CreateEmbed::default()
+        .title(title)
+        .description(description)
+}
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub))]
+pub enum SettingsError {
+    #[snafu(display("\"{input}\" is not a duration."))]
+    InvalidDuration { input: String },
+
+    #[snafu(display(
+        "The check interval must be at least {}.",
+        humantime::format_duration(MIN_CHECK_INTERVAL)
+    ))]
+    CheckIntervalTooShort,
+
+    #[snafu(display(
+        "The timeout must be at most {}.",
+        humantime::format_duration(MAX_TIMEOUT)
+    ))]
+    TimeoutTooLong,
+
+    #[snafu(display("The timeout must not be shorter than the check interval."))]
+    TimeoutShorterThanInterval,
+
+    #[snafu(display("The voice feed is not set up yet. Pick a channel to set it up."))]
+    VoiceFeedChannelRequired,
+
+    #[snafu(display("Templates must be at most {MAX_TEMPLATE_LEN} characters."))]
+    TemplateTooLong,
+
+    #[snafu(display("Pick a channel or a member to ignore."))]
+    NothingToIgnore,
+
+    #[snafu(display("The channel is not in this server."))]
+    ChannelNotInServer,
+
+    #[snafu(display("Retention must be at most {MAX_RETENTION_DAYS} days, 0 keeps data forever."))]
+    RetentionOutOfRange,
+
+    #[snafu(display("The weekly digest is not set up yet. Pick a channel to set it up."))]
+    DigestChannelRequired,
+
+    #[snafu(display("There is no setting called \"{key}\"."))]
+    UnknownSetting { key: String },
+
+    #[snafu(display("Invalid value for {key}, {reason}."))]
+    InvalidSettingValue { key: String, reason: String },
+
+    #[snafu(display("Pick either a command or a category, not both."))]
+    CooldownTargetRequired,
+
+    #[snafu(display("Give both the number of uses and the period of the cooldown."))]
+    CooldownLimitRequired,
+
+    #[snafu(display(
+        "Cooldowns allow 1 to {} uses over 1s to {}.",
+        cooldowns::MAX_COOLDOWN_USES,
+        humantime::format_duration(cooldowns::MAX_COOLDOWN_PERIOD)
+    ))]
+    CooldownOutOfRange,
+}
+
+impl ErrorName for SettingsError {
+    fn name(&self) -> String {
+        let name = match self {
+            SettingsError::InvalidDuration { .. } => "invalid_duration",
+            SettingsError::CheckIntervalTooShort => "check_interval_too_short",
+            SettingsError::TimeoutTooLong => "timeout_too_long",
+            SettingsError::TimeoutShorterThanInterval => "timeout_shorter_than_interval",
+            SettingsError::VoiceFeedChannelRequired => "voice_feed_channel_required",
+            SettingsError::TemplateTooLong => "template_too_long",
+            SettingsError::NothingToIgnore => "nothing_to_ignore",
+            SettingsError::ChannelNotInServer => "channel_not_in_server",
+            SettingsError::RetentionOutOfRange => "retention_out_of_range",
+            SettingsError::DigestChannelRequired => "digest_channel_required",
+            SettingsError::UnknownSetting { .. } => "unknown_setting",
+            SettingsError::InvalidSettingValue { .. } => "invalid_setting_value",
+            SettingsError::CooldownTargetRequired => "cooldown_target_required",
+            SettingsError::CooldownLimitRequired => "cooldown_limit_required",
+            SettingsError::CooldownOutOfRange => "cooldown_out_of_range",
+        };
+        format!("settings::{name}")
+    }
+}
+
+impl UserFriendlyError for SettingsError {
+    fn help_text(&self) -> &str {
+        match self {
+            SettingsError::InvalidDuration { .. } => "Use durations like 30s, 5m or 1h 30m.",
+            SettingsError::CheckIntervalTooShort
+            | SettingsError::TimeoutTooLong
+            | SettingsError::TimeoutShorterThanInterval
+            | SettingsError::RetentionOutOfRange
+            | SettingsError::CooldownOutOfRange => "Pick values within the limits, then try again.",
+            SettingsError::VoiceFeedChannelRequired
+            | SettingsError::DigestChannelRequired
+            | SettingsError::NothingToIgnore
+            | SettingsError::CooldownLimitRequired => "Fill in the missing option, then try again.",
+            SettingsError::CooldownTargetRequired => "Remove one of the options, then try again.",
+            SettingsError::TemplateTooLong => "Shorten the template, then try again.",
+            SettingsError::ChannelNotInServer => "Pick a channel of this server, then try again.",
+            SettingsError::UnknownSetting { .. } => "Pick a setting from the suggestions.",
+            SettingsError::InvalidSettingValue { .. } => "Fix the value, then try again.",
+        }
+    }
+
+    fn category(&self) -> crate::error::ErrorCategory {
+        crate::error::ErrorCategory::UserMistake
+    }
+}
@@ -0,0 +1,214 @@
+//! Small PNG charts drawn in-process, without fonts or a plotting library. Text uses a built in
+//! 3x5 pixel font, so labels are limited to digits, latin letters and a few symbols.
+use ayaya_db::data::voice_analytics::Heatmap;
+
+type Rgb = [u8; 3];
+
+const BACKGROUND: Rgb = [0x2b, 0x2d, 0x31];
+const EMPTY: Rgb = [0x38, 0x3a, 0x40];
+const LOW: Rgb = [0x1f, 0x3b, 0x5c];
+const HIGH: Rgb = [0x5e, 0xc8, 0xff];
+const TEXT: Rgb = [0xdb, 0xde, 0xe1];
+
+const GLYPH_WIDTH: usize = 3;
+const GLYPH_HEIGHT: usize = 5;
+/// Text is drawn at twice the glyph size
+const TEXT_SCALE: usize = 2;
+const WEEKDAYS: [&str; 7] = ["MON", "TUE", "WED", "THU", "FRI", "SAT", "SUN"];
+
+/// Weekday by hour heatmap, Monday on top and midnight on the left
+pub fn render_heatmap(heatmap: &Heatmap) -> Result<Vec<u8>, png::EncodingError> {
+    const CELL: usize = 20;
+    const PITCH: usize = CELL + 2;
+    const LEFT: usize = 36;
+    const TOP: usize = 20;
+    const MARGIN: usize = 8;
+
+    let mut canvas = Canvas::new(LEFT + 24 * PITCH + MARGIN, TOP + 7 * PITCH + MARGIN);
+    let max = heatmap.iter().flatten().copied().fold(0.0_f64, f64::max);
+
+    for hour in 0..24 {
+        canvas.text(LEFT + hour * PITCH + 2, 4, &format!("{hour:02}"), TEXT);
+    }
+    for (weekday, row) in heatmap.iter().enumerate() {
+        let y = TOP + weekday * PITCH;
+        canvas.text(4, y + 5, WEEKDAYS[weekday], TEXT);
+        for (hour, minutes) in row.iter().enumerate() {
+            canvas.fill_rect(
+                LEFT + hour * PITCH,
+                y,
+                CELL,
+                CELL,
+                scale_color(*minutes, max),
+            );
+        }
+    }
+
+    canvas.encode()
+}
+
+/// Bar chart of the values from left to right, with the maximum written in the corner. Past one
+/// value per pixel, neighbouring values are averaged into one bar.
+pub fn render_bar_chart(values: &[f64], caption: &str) -> Result<Vec<u8>, png::EncodingError> {
+    const PLOT_WIDTH: usize = 600;
+    const PLOT_HEIGHT: usize = 160;
+    const LEFT: usize = 8;
+    const TOP: usize = 22;
+    const MARGIN: usize = 8;
+
+    let mut canvas = Canvas::new(LEFT + PLOT_WIDTH + MARGIN, TOP + PLOT_HEIGHT + MARGIN);
+    let values = downsample(values, PLOT_WIDTH);
+    let max = values.iter().copied().fold(0.0_f64, f64::max);
+    canvas.text(LEFT, 4, &format!("{caption} MAX {}", max.round()), TEXT);
+    canvas.fill_rect(LEFT, TOP + PLOT_HEIGHT - 1, PLOT_WIDTH, 1, EMPTY);
+
+    if !values.is_empty() && max > 0.0 {
+        let pitch = (PLOT_WIDTH / values.len()).max(1);
+        let width = if pitch > 2 { pitch - 1 } else { pitch };
+        for (i, value) in values.iter().enumerate() {
+            let height = ((value / max) * PLOT_HEIGHT as f64).round() as usize;
+            canvas.fill_rect(
+                LEFT + i * pitch,
+                TOP + PLOT_HEIGHT - height,
+                width,
+                height,
+                scale_color(*value, max),
+            );
+        }
+    }
+
+    canvas.encode()
+}
+
+/// Average the values into at most `buckets` values, each covering a run of neighbours
+fn downsample(values: &[f64], buckets: usize) -> Vec<f64> {
+    if values.len() <= buckets {
+        return values.to_vec();
+    }
+    (0..buckets)
+        .map(|bucket| {
+            let run =
+                &values[bucket * values.len() / buckets..(bucket + 1) * values.len() / buckets];
+            run.iter().sum::<f64>() / run.len() as f64
+        })
+        .collect()
+}
+
+/// Blend from [`LOW`] to [`HIGH`], with a square root so quiet cells stay visible
+fn scale_color(value: f64, max: f64) -> Rgb {
+    if value <= 0.0 || max <= 0.0 {
+        return EMPTY;
+    }
+    let t = (value / max).clamp(0.0, 1.0).sqrt();
+    let mut color = [0; 3];
+    for (i, channel) in color.iter_mut().enumerate() {
+        *channel = (LOW[i] as f64 + (HIGH[i] as f64 - LOW[i] as f64) * t).round() as u8;
+    }
+    color
+}
+
+/// RGB pixel buffer
+struct Canvas {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+    fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: BACKGROUND.repeat(width * height),
+        }
+    }
+
+    /// Fill a rectangle, clipped to the canvas
+    fn fill_rect(&mut self, x: usize, y: usize, width: usize, height: usize, color: Rgb) {
+        for row in y..(y + height).min(self.height) {
+            for column in x..(x + width).min(self.width) {
+                let offset = (row * self.width + column) * 3;
+                self.pixels[offset..offset + 3].copy_from_slice(&color);
+            }
+        }
+    }
+
+    /// Write text with the built in font. Unknown characters are left blank.
+    fn text(&mut self, x: usize, y: usize, text: &str, color: Rgb) {
+        let advance = (GLYPH_WIDTH + 1) * TEXT_SCALE;
+        for (i, character) in text.chars().enumerate() {
+            let rows = glyph(character);
+            for (row, bits) in rows.iter().enumerate() {
+                for column in 0..GLYPH_WIDTH {
+                    if bits & (1 << (GLYPH_WIDTH - 1 - column)) != 0 {
+                        self.fill_rect(
+                            x + i * advance + column * TEXT_SCALE,
+                            y + row * TEXT_SCALE,
+                            TEXT_SCALE,
+                            TEXT_SCALE,
+                            color,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    fn encode(&self) -> Result<Vec<u8>, png::EncodingError> {
+        let mut buffer = Vec::new();
+        let mut encoder = png::Encoder::new(&mut buffer, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels)?;
+        writer.finish()?;
+        Ok(buffer)
+    }
+}
+
+/// Rows of a 3x5 glyph, most significant bit on the left
+fn glyph(character: char) -> [u8; GLYPH_HEIGHT] {
+    match character.to_ascii_uppercase() {
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b110, 0b001, 0b010, 0b100, 0b111],
+        '3' => [0b110, 0b001, 0b010, 0b001, 0b110],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b110, 0b001, 0b110],
+        '6' => [0b011, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b010, 0b010, 0b010],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b110],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        _ => [0; GLYPH_HEIGHT],
+    }
+}
@@ -0,0 +1,169 @@
+//! Command usage reports of a server over time, built from the command call log
+use ayaya_db::data::command_stats::{CommandUsage, UsageWindow};
+use poise::serenity_prelude as serenity;
+use snafu::ResultExt;
+use time::OffsetDateTime;
+
+use super::{RenderChartSnafu, chart::render_bar_chart};
+use crate::{
+    CommandResult, Context,
+    error::{DataManagerSnafu, GeneralSerenitySnafu},
+    utils::{autocomplete_command_names, get_guild_id, paginate_lines},
+};
+
+/// Time window option of the command usage reports
+#[derive(Debug, Clone, Copy, Default, poise::ChoiceParameter)]
+pub enum UsageWindowChoice {
+    #[name = "Last 24 hours"]
+    Day,
+    #[name = "Last 7 days"]
+    Week,
+    #[default]
+    #[name = "Last 30 days"]
+    Month,
+    #[name = "Last 365 days"]
+    Year,
+    #[name = "All time"]
+    AllTime,
+}
+
+impl From<UsageWindowChoice> for UsageWindow {
+    fn from(value: UsageWindowChoice) -> Self {
+        match value {
+            UsageWindowChoice::Day => UsageWindow::Day,
+            UsageWindowChoice::Week => UsageWindow::Week,
+            UsageWindowChoice::Month => UsageWindow::Month,
+            UsageWindowChoice::Year => UsageWindow::Year,
+            UsageWindowChoice::AllTime => UsageWindow::AllTime,
+        }
+    }
+}
+
+/// Command usage reports. This command must be called with a subcommand.
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    subcommands("top", "trend"),
+    category = "Statistics"
+)]
+pub async fn command_stats(_ctx: Context<'_>) -> CommandResult {
+    Ok(())
+}
+
+/// Most used commands, with how many members used them and the change from the window before.
+#[poise::command(slash_command, prefix_command, guild_only, category = "Statistics")]
+pub async fn top(
+    ctx: Context<'_>,
+    #[description = "Time window, defaults to the last 30 days"] window: Option<UsageWindowChoice>,
+) -> CommandResult {
+    ctx.defer().await.context(GeneralSerenitySnafu)?;
+    let guild_id = get_guild_id(ctx)?;
+    let window = UsageWindow::from(window.unwrap_or_default());
+    let now = OffsetDateTime::now_utc();
+    let usage = ctx
+        .data()
+        .data_manager
+        .command_stats()
+        .get_command_usage(guild_id.get(), window, now)
+        .await
+        .context(DataManagerSnafu)?;
+    if usage.is_empty() {
+        ctx.reply(format!("No commands were used in the {}.", window.label()))
+            .await
+            .context(GeneralSerenitySnafu)?;
+        return Ok(());
+    }
+
+    let lines = usage
+        .iter()
+        .enumerate()
+        .map(|(i, usage)| {
+            let mut line = format!(
+                "{}. `{}`: {} calls by {} members",
+                i + 1,
+                usage.command,
+                usage.calls,
+                usage.unique_users
+            );
+            if window != UsageWindow::AllTime {
+                line.push_str(&format!(" ({})", format_change(usage)));
+            }
+            line
+        })
+        .collect::<Vec<_>>();
+    paginate_lines(ctx, &format!("Command usage, {}", window.label()), &lines).await
+}
+
+/// Calls over time as a chart, for one command or every command.
+#[poise::command(slash_command, prefix_command, guild_only, category = "Statistics")]
+pub async fn trend(
+    ctx: Context<'_>,
+    #[description = "The command, defaults to every command"]
+    #[autocomplete = "autocomplete_command_names"]
+    command: Option<String>,
+    #[description = "Time window, defaults to the last 30 days"] window: Option<UsageWindowChoice>,
+) -> CommandResult {
+    ctx.defer().await.context(GeneralSerenitySnafu)?;
+    let guild_id = get_guild_id(ctx)?;
+    let window = UsageWindow::from(window.unwrap_or_default());
+    let now = OffsetDateTime::now_utc();
+    let trend = ctx
+        .data()
+        .data_manager
+        .command_stats()
+        .get_command_trend(guild_id.get(), command.as_deref(), window, now)
+        .await
+        .context(DataManagerSnafu)?;
+    let total = trend
+        .as_ref()
+        .map_or(0, |trend| trend.counts.iter().sum::<u64>());
+    let Some(trend) = trend.filter(|_| total > 0) else {
+        ctx.reply(format!("No commands were used in the {}.", window.label()))
+            .await
+            .context(GeneralSerenitySnafu)?;
+        return Ok(());
+    };
+
+    let (_, bucket_label) = window.trend_bucket();
+    let mut description = format!("{total} calls by {} members", trend.unique_users);
+    if window != UsageWindow::AllTime {
+        description.push_str(&format!(", {} in the window before", trend.previous_calls));
+    }
+    description.push_str(&format!(". Each bar is {bucket_label}."));
+
+    let values = trend
+        .counts
+        .iter()
+        .map(|count| *count as f64)
+        .collect::<Vec<_>>();
+    let image = render_bar_chart(
+        &values,
+        &format!("CALLS PER {}", bucket_label.to_uppercase()),
+    )
+    .context(RenderChartSnafu)?;
+    let title = match &command {
+        Some(command) => format!("Calls of `{command}`, {}", window.label()),
+        None => format!("Command calls, {}", window.label()),
+    };
+    let filename = "command_trend.png";
+    let embed = serenity::CreateEmbed::default()
+        .title(title)
+        .description(description)
+        .image(format!("attachment://{filename}"));
+    ctx.send(poise::CreateReply::default().embed(embed).attachment(
+        serenity::CreateAttachment::bytes(image, filename.to_string()),
+    ))
+    .await
+    .context(GeneralSerenitySnafu)?;
+    Ok(())
+}
+
+/// Change from the window before, eg: "+25%"
+fn format_change(usage: &CommandUsage) -> String {
+    if usage.previous_calls == 0 {
+        return "new".to_string();
+    }
+    let change = (usage.calls as f64 - usage.previous_calls as f64) / usage.previous_calls as f64;
+    format!("{:+.0}%", change * 100.0)
+}
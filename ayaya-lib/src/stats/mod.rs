@@ -1,13 +1,18 @@
 //! Commands for stats
 //!
-use std::collections::HashMap;
+mod chart;
+mod commands;
+pub(crate) mod retention;
+mod voice;
+
+pub(crate) use voice::WindowChoice;
 
 use poise::serenity_prelude::{self as serenity, Mentionable};
-use snafu::ResultExt;
+use snafu::{ResultExt, Snafu};
 
 use crate::{
     CommandResult, Commands, Context,
-    error::{BotError, DataManagerSnafu, GeneralSerenitySnafu},
+    error::{BotError, DataManagerSnafu, ErrorName, GeneralSerenitySnafu, UserFriendlyError},
     utils::{GuildInfo, autocomplete_command_names, get_guild_name},
 };
 
@@ -15,7 +20,8 @@ pub fn stats_commands() -> Commands {
     vec![
         user_all_time_single(),
         server_all_time_single(),
-        server_voice_stats(),
+        voice::voice(),
+        commands::command_stats(),
     ]
 }
 
@@ -142,67 +148,38 @@ pub async fn server_all_time_single(
     Ok(())
 }
 
-/// A measure of no life
-#[poise::command(slash_command, prefix_command, category = "Statistics")]
-pub async fn server_voice_stats(
-    ctx: Context<'_>,
-    server_id: Option<serenity::GuildId>,
-) -> CommandResult {
-    ctx.defer().await.context(GeneralSerenitySnafu)?;
-
-    let data_manager = ctx.data().data_manager.clone();
-    let guild_id = if let Some(guild_id) = server_id {
-        guild_id.get()
-    } else {
-        GuildInfo::guild_id_or_0(ctx)
-    };
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub))]
+pub enum StatsError {
+    #[snafu(display("Failed to draw the chart: {source}"))]
+    RenderChart { source: png::EncodingError },
 
-    match data_manager
-        .voice()
-        .get_server_voice_sessions(guild_id)
-        .await
-    {
-        Ok(models) => {
-            // calculate length for each user
-            let mut map: std::collections::HashMap<u64, time::Duration> = HashMap::default();
-
-            for model in models {
-                if let Some(left_at) = model.left_at {
-                    let dur = left_at - model.joined_at;
-                    map.entry(model.user_id as u64)
-                        .and_modify(|e| {
-                            *e = e.saturating_add(dur);
-                        })
-                        .or_insert(dur);
-                } else {
-                    tracing::warn!("no left at time found, skipping");
-                }
-            }
+    #[snafu(display("\"{input}\" is not a server id."))]
+    InvalidServerId { input: String },
+}
 
-            let mut msg = serenity::MessageBuilder::default()
-                .push_line(format!("# Voicechat Stats: {guild_id}").as_str());
-
-            for (i, (user_id, dur)) in map.iter().enumerate() {
-                let i = i + 1;
-                let user = serenity::UserId::new(*user_id)
-                    .to_user(ctx)
-                    .await
-                    .context(GeneralSerenitySnafu)?;
-                let dur = humantime::format_duration(dur.unsigned_abs()).to_string();
-                let line = format!("{i}. {}: {}", user.name, dur);
-                msg = msg.push_line(line.as_str());
-            }
+impl ErrorName for StatsError {
+    fn name(&self) -> String {
+        let name = match self {
+            StatsError::RenderChart { .. } => "render_chart",
+            StatsError::InvalidServerId { .. } => "invalid_server_id",
+        };
+        format!("stats::{name}")
+    }
+}
 
-            ctx.reply(msg.to_string())
-                .await
-                .context(GeneralSerenitySnafu)?;
-        }
-        Err(_) => {
-            ctx.say("No data found")
-                .await
-                .context(GeneralSerenitySnafu)?;
+impl UserFriendlyError for StatsError {
+    fn help_text(&self) -> &str {
+        match self {
+            StatsError::RenderChart { .. } => "Try again later, or pick a smaller time window.",
+            StatsError::InvalidServerId { .. } => "Use the numeric id of the server, or 0 for DMs.",
         }
     }
 
-    Ok(())
+    fn category(&self) -> crate::error::ErrorCategory {
+        match self {
+            StatsError::RenderChart { .. } => crate::error::ErrorCategory::BotIssue,
+            StatsError::InvalidServerId { .. } => crate::error::ErrorCategory::UserMistake,
+        }
+    }
 }
@@ -0,0 +1,28 @@
+//! Background pruning of voice data past the retention policy of each guild
+use std::time::Duration;
+
+use ayaya_db::data::DataManager;
+use time::OffsetDateTime;
+use tracing::{error, info};
+
+/// Time between two prunes
+const PRUNE_INTERVAL: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// Prune voice data right away, then every [`PRUNE_INTERVAL`]. Runs until the bot stops.
+pub async fn prune_voice_data_periodically(data_manager: DataManager) {
+    let mut interval = tokio::time::interval(PRUNE_INTERVAL);
+    loop {
+        interval.tick().await;
+        match data_manager
+            .voice_privacy()
+            .prune_voice_data(OffsetDateTime::now_utc())
+            .await
+        {
+            Ok(report) => info!(
+                "Pruned voice data: {} raw states cleared, {} events and {} sessions deleted",
+                report.raw_cleared, report.events_deleted, report.sessions_deleted
+            ),
+            Err(e) => error!("Failed to prune voice data: {e}"),
+        }
+    }
+}
@@ -0,0 +1,766 @@
+//! Voice activity reports of a server, built from the recorded voice sessions
+use std::collections::BTreeSet;
+
+use ayaya_db::data::voice_analytics::{
+    CoPresence, REPORT_OFFSET, SessionSpan, VoiceStateTotals, VoiceWindow, activity_heatmap,
+    busiest_channels, busiest_index, clip_spans, concurrency_timeline, daily_peaks, hourly_totals,
+    member_totals, month_start, peak_concurrency, session_spans, voice_profile, week_start,
+    weekday_totals,
+};
+use poise::serenity_prelude::{self as serenity, Mentionable};
+use snafu::ResultExt;
+use time::OffsetDateTime;
+
+use super::{
+    RenderChartSnafu,
+    chart::{render_bar_chart, render_heatmap},
+};
+use crate::{
+    CommandResult, Context,
+    error::{BotError, DataManagerSnafu, GeneralSerenitySnafu},
+    utils::{get_guild_id, paginate_lines},
+};
+
+const WEEKDAY_NAMES: [&str; 7] = [
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+    "Sunday",
+];
+/// Channels listed by the channels report
+const TOP_CHANNELS: usize = 10;
+/// Members listed as companions in a profile
+const TOP_COMPANIONS: usize = 5;
+/// Members listed per ranking of the states command
+const TOP_STATE_MEMBERS: usize = 5;
+/// Members listed by the friends command
+const TOP_FRIENDS: usize = 10;
+
+/// File format of the co-presence graph export
+#[derive(Debug, Clone, Copy, Default, poise::ChoiceParameter)]
+pub enum GraphFormat {
+    #[default]
+    #[name = "JSON"]
+    Json,
+    #[name = "GraphML"]
+    GraphMl,
+}
+
+/// Time window option of the voice reports
+#[derive(Debug, Clone, Copy, Default, poise::ChoiceParameter)]
+pub enum WindowChoice {
+    #[name = "Last 24 hours"]
+    Day,
+    #[name = "Last 7 days"]
+    Week,
+    #[default]
+    #[name = "Last 30 days"]
+    Month,
+    #[name = "Last 365 days"]
+    Year,
+    #[name = "All time"]
+    AllTime,
+}
+
+impl From<WindowChoice> for VoiceWindow {
+    fn from(value: WindowChoice) -> Self {
+        match value {
+            WindowChoice::Day => VoiceWindow::Day,
+            WindowChoice::Week => VoiceWindow::Week,
+            WindowChoice::Month => VoiceWindow::Month,
+            WindowChoice::Year => VoiceWindow::Year,
+            WindowChoice::AllTime => VoiceWindow::AllTime,
+        }
+    }
+}
+
+/// Voice activity reports. This command must be called with a subcommand.
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    subcommands(
+        "leaderboard",
+        "activity",
+        "channels",
+        "peaks",
+        "me",
+        "friends",
+        "states",
+        "graph",
+        "opt_out",
+        "opt_in"
+    ),
+    category = "Statistics"
+)]
+pub async fn voice(_ctx: Context<'_>) -> CommandResult {
+    Ok(())
+}
+
+/// Who spent the most time in voice, including members still connected.
+#[poise::command(slash_command, prefix_command, guild_only, category = "Statistics")]
+pub async fn leaderboard(
+    ctx: Context<'_>,
+    #[description = "Time window, defaults to the last 30 days"] window: Option<WindowChoice>,
+    #[description = "Only count time in this channel"]
+    #[channel_types("Voice", "Stage")]
+    channel: Option<serenity::GuildChannel>,
+) -> CommandResult {
+    ctx.defer().await.context(GeneralSerenitySnafu)?;
+    let window = VoiceWindow::from(window.unwrap_or_default());
+    let spans = load_spans(ctx, window).await?;
+    let totals = member_totals(&spans, channel.as_ref().map(|channel| channel.id.get()));
+    if totals.is_empty() {
+        return reply_no_activity(ctx, window).await;
+    }
+
+    let author_id = ctx.author().id.get();
+    let lines = totals
+        .iter()
+        .enumerate()
+        .map(|(i, (user_id, duration))| {
+            let line = format!(
+                "{}. {}: {}",
+                i + 1,
+                serenity::UserId::new(*user_id).mention(),
+                format_minutes(duration.as_seconds_f64() / 60.0)
+            );
+            if *user_id == author_id {
+                format!("**{line}**")
+            } else {
+                line
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let title = match channel {
+        Some(channel) => format!(
+            "Voice leaderboard of {}, {}",
+            channel.base.name,
+            window.label()
+        ),
+        None => format!("Voice leaderboard, {}", window.label()),
+    };
+    paginate_lines(ctx, &title, &lines).await
+}
+
+/// When the server is in voice, as a heatmap of weekdays and hours.
+#[poise::command(slash_command, prefix_command, guild_only, category = "Statistics")]
+pub async fn activity(
+    ctx: Context<'_>,
+    #[description = "Time window, defaults to the last 30 days"] window: Option<WindowChoice>,
+) -> CommandResult {
+    ctx.defer().await.context(GeneralSerenitySnafu)?;
+    let window = VoiceWindow::from(window.unwrap_or_default());
+    let spans = load_spans(ctx, window).await?;
+    if spans.is_empty() {
+        return reply_no_activity(ctx, window).await;
+    }
+
+    let heatmap = activity_heatmap(&spans, REPORT_OFFSET);
+    let hourly = hourly_totals(&heatmap);
+    let weekdays = weekday_totals(&heatmap);
+    let total_minutes: f64 = weekdays.iter().sum();
+
+    let mut description = serenity::MessageBuilder::default()
+        .push_bold("Total time in voice: ")
+        .push_line(format_minutes(total_minutes));
+    if let Some(hour) = busiest_index(&hourly) {
+        description = description
+            .push_bold("Peak hour: ")
+            .push_line(format!("{hour:02}:00 - {:02}:00", (hour + 1) % 24));
+    }
+    if let Some(weekday) = busiest_index(&weekdays) {
+        description = description
+            .push_bold("Busiest day: ")
+            .push_line(WEEKDAY_NAMES[weekday]);
+    }
+
+    let image = render_heatmap(&heatmap).context(RenderChartSnafu)?;
+    send_report(
+        ctx,
+        format!("Voice activity, {}", window.label()),
+        description
+            .push_italic_line("Hours are in UTC+8. Brighter cells mean more time in voice.")
+            .build(),
+        image,
+        "voice_activity.png",
+    )
+    .await
+}
+
+/// The voice channels the server spends the most time in.
+#[poise::command(slash_command, prefix_command, guild_only, category = "Statistics")]
+pub async fn channels(
+    ctx: Context<'_>,
+    #[description = "Time window, defaults to the last 30 days"] window: Option<WindowChoice>,
+) -> CommandResult {
+    ctx.defer().await.context(GeneralSerenitySnafu)?;
+    let window = VoiceWindow::from(window.unwrap_or_default());
+    let spans = load_spans(ctx, window).await?;
+    if spans.is_empty() {
+        return reply_no_activity(ctx, window).await;
+    }
+
+    let channels = busiest_channels(&spans);
+    let total: f64 = channels
+        .iter()
+        .map(|(_, duration)| duration.as_seconds_f64())
+        .sum();
+
+    let mut description = serenity::MessageBuilder::default();
+    for (i, (channel_id, duration)) in channels.iter().take(TOP_CHANNELS).enumerate() {
+        let share = duration.as_seconds_f64() / total * 100.0;
+        description = description.push_line(format!(
+            "{}. {}: {} ({share:.0}%)",
+            i + 1,
+            serenity::ChannelId::new(*channel_id).mention(),
+            format_minutes(duration.as_seconds_f64() / 60.0),
+        ));
+    }
+
+    let embed = serenity::CreateEmbed::default()
+        .title(format!("Busiest voice channels, {}", window.label()))
+        .description(description.build());
+    ctx.send(poise::CreateReply::default().embed(embed))
+        .await
+        .context(GeneralSerenitySnafu)?;
+    Ok(())
+}
+
+/// The most members in voice at once, over time.
+#[poise::command(slash_command, prefix_command, guild_only, category = "Statistics")]
+pub async fn peaks(
+    ctx: Context<'_>,
+    #[description = "Time window, defaults to the last 30 days"] window: Option<WindowChoice>,
+) -> CommandResult {
+    ctx.defer().await.context(GeneralSerenitySnafu)?;
+    let window = VoiceWindow::from(window.unwrap_or_default());
+    let spans = load_spans(ctx, window).await?;
+    if spans.is_empty() {
+        return reply_no_activity(ctx, window).await;
+    }
+
+    let timeline = concurrency_timeline(&spans);
+    let days = daily_peaks(&timeline, REPORT_OFFSET);
+
+    let mut description = serenity::MessageBuilder::default();
+    if let Some((time, count)) = peak_concurrency(&timeline) {
+        description = description.push_bold("Highest: ").push_line(format!(
+            "{count} members on <t:{}:f>",
+            time.unix_timestamp()
+        ));
+    }
+
+    let mut busiest_days = days.clone();
+    busiest_days.sort_by(|(a_day, a), (b_day, b)| b.cmp(a).then(b_day.cmp(a_day)));
+    description = description.push_bold_line("Busiest days:");
+    for (day, count) in busiest_days.iter().take(5) {
+        description = description.push_line(format!("{day}: {count} members"));
+    }
+
+    let values = days
+        .iter()
+        .map(|(_, count)| *count as f64)
+        .collect::<Vec<_>>();
+    let image = render_bar_chart(&values, "DAILY PEAK").context(RenderChartSnafu)?;
+    send_report(
+        ctx,
+        format!("Members in voice at once, {}", window.label()),
+        description
+            .push_italic_line("One bar per day in UTC+8, oldest on the left.")
+            .build(),
+        image,
+        "voice_peaks.png",
+    )
+    .await
+}
+
+/// Your voice profile, or the one of another member.
+///
+/// Shows the time spent in voice, the longest session, the favorite channel, who you spend it \
+/// with and how many days in a row you showed up.
+#[poise::command(slash_command, prefix_command, guild_only, category = "Statistics")]
+pub async fn me(
+    ctx: Context<'_>,
+    #[description = "Selected user, defaults to you"] user: Option<serenity::User>,
+    #[description = "Time window, defaults to the last 30 days"] window: Option<WindowChoice>,
+) -> CommandResult {
+    ctx.defer().await.context(GeneralSerenitySnafu)?;
+    let user = user.unwrap_or_else(|| ctx.author().clone());
+    let window = VoiceWindow::from(window.unwrap_or_default());
+    let now = OffsetDateTime::now_utc();
+    let week = week_start(now, REPORT_OFFSET);
+    let month = month_start(now, REPORT_OFFSET);
+
+    // this week and this month are shown whatever the window is, so load enough for both
+    let since = window.since(now).map(|since| since.min(month.min(week)));
+    let all_spans = load_spans_since(ctx, since, now).await?;
+    let spans = clip_spans(&all_spans, window.since(now));
+
+    let user_id = user.id.get();
+    let profile = voice_profile(
+        &spans,
+        user_id,
+        now.to_offset(REPORT_OFFSET).date(),
+        REPORT_OFFSET,
+    );
+    if profile.session_count == 0 {
+        ctx.reply(format!(
+            "{} has no voice activity in the {}.",
+            user.display_name(),
+            window.label()
+        ))
+        .await
+        .context(GeneralSerenitySnafu)?;
+        return Ok(());
+    }
+
+    let user_minutes_since = |since| {
+        clip_spans(&all_spans, Some(since))
+            .iter()
+            .filter(|span| span.user_id == user_id)
+            .map(|span| span.duration().as_seconds_f64() / 60.0)
+            .sum::<f64>()
+    };
+
+    let mut description = serenity::MessageBuilder::default()
+        .push_bold("Total time: ")
+        .push_line(format_minutes(profile.total.as_seconds_f64() / 60.0))
+        .push_bold("This week: ")
+        .push_line(format_minutes(user_minutes_since(week)))
+        .push_bold("This month: ")
+        .push_line(format_minutes(user_minutes_since(month)))
+        .push_bold("Sessions: ")
+        .push_line(profile.session_count.to_string());
+    if let Some(longest) = profile.longest_session {
+        description = description
+            .push_bold("Longest session: ")
+            .push_line(format!(
+                "{} in {} on <t:{}:d>",
+                format_minutes(longest.duration().as_seconds_f64() / 60.0),
+                serenity::ChannelId::new(longest.channel_id).mention(),
+                longest.start.unix_timestamp()
+            ));
+    }
+    if let Some((channel_id, duration)) = profile.favorite_channel {
+        description = description
+            .push_bold("Favorite channel: ")
+            .push_line(format!(
+                "{} ({})",
+                serenity::ChannelId::new(channel_id).mention(),
+                format_minutes(duration.as_seconds_f64() / 60.0)
+            ));
+    }
+    description = description.push_bold("Streak: ").push_line(format!(
+        "{} days, longest {} days, active on {} days",
+        profile.current_streak, profile.longest_streak, profile.active_days
+    ));
+
+    if !profile.companions.is_empty() {
+        description = description.push_bold_line("Most time with:");
+        for (i, (companion_id, duration)) in
+            profile.companions.iter().take(TOP_COMPANIONS).enumerate()
+        {
+            description = description.push_line(format!(
+                "{}. {}: {}",
+                i + 1,
+                serenity::UserId::new(*companion_id).mention(),
+                format_minutes(duration.as_seconds_f64() / 60.0)
+            ));
+        }
+    }
+
+    let embed = serenity::CreateEmbed::default()
+        .title(format!(
+            "Voice profile of {}, {}",
+            user.display_name(),
+            window.label()
+        ))
+        .thumbnail(user.face())
+        .description(description.push_italic_line("Days are in UTC+8.").build());
+    ctx.send(poise::CreateReply::default().embed(embed))
+        .await
+        .context(GeneralSerenitySnafu)?;
+    Ok(())
+}
+
+/// Who you, or another member, spend the most time in voice with.
+#[poise::command(slash_command, prefix_command, guild_only, category = "Statistics")]
+pub async fn friends(
+    ctx: Context<'_>,
+    #[description = "Selected user, defaults to you"] user: Option<serenity::User>,
+    #[description = "Time window, defaults to the last 30 days"] window: Option<WindowChoice>,
+) -> CommandResult {
+    ctx.defer().await.context(GeneralSerenitySnafu)?;
+    let user = user.unwrap_or_else(|| ctx.author().clone());
+    let window = VoiceWindow::from(window.unwrap_or_default());
+    let pairs = load_co_presence(ctx, window).await?;
+
+    let user_id = user.id.get();
+    let friends = pairs
+        .iter()
+        .filter(|pair| pair.involves(user_id))
+        .take(TOP_FRIENDS)
+        .collect::<Vec<_>>();
+    if friends.is_empty() {
+        ctx.reply(format!(
+            "{} didn't share a voice channel with anyone in the {}.",
+            user.display_name(),
+            window.label()
+        ))
+        .await
+        .context(GeneralSerenitySnafu)?;
+        return Ok(());
+    }
+
+    let mut description = serenity::MessageBuilder::default();
+    for (i, pair) in friends.iter().enumerate() {
+        description = description.push_line(format!(
+            "{}. {}: {}",
+            i + 1,
+            serenity::UserId::new(pair.other(user_id)).mention(),
+            format_minutes(pair.overlap.as_seconds_f64() / 60.0)
+        ));
+    }
+
+    let embed = serenity::CreateEmbed::default()
+        .title(format!(
+            "Voice friends of {}, {}",
+            user.display_name(),
+            window.label()
+        ))
+        .thumbnail(user.face())
+        .description(description.build());
+    ctx.send(poise::CreateReply::default().embed(embed))
+        .await
+        .context(GeneralSerenitySnafu)?;
+    Ok(())
+}
+
+/// Time spent muted, deafened, streaming or on camera, for the server or a single member.
+#[poise::command(slash_command, prefix_command, guild_only, category = "Statistics")]
+pub async fn states(
+    ctx: Context<'_>,
+    #[description = "Selected user, defaults to the whole server"] user: Option<serenity::User>,
+    #[description = "Time window, defaults to the last 30 days"] window: Option<WindowChoice>,
+) -> CommandResult {
+    ctx.defer().await.context(GeneralSerenitySnafu)?;
+    let window = VoiceWindow::from(window.unwrap_or_default());
+    let guild_id = get_guild_id(ctx)?;
+    let now = OffsetDateTime::now_utc();
+    let totals = ctx
+        .data()
+        .data_manager
+        .voice()
+        .get_voice_state_totals(guild_id.get(), window.since(now), now)
+        .await
+        .context(DataManagerSnafu)?;
+
+    let (title, shown) = match &user {
+        Some(user) => (
+            format!(
+                "Voice states of {}, {}",
+                user.display_name(),
+                window.label()
+            ),
+            totals
+                .iter()
+                .find(|(user_id, _)| *user_id == user.id.get())
+                .map(|(_, user_totals)| *user_totals),
+        ),
+        None => {
+            let mut server = VoiceStateTotals::default();
+            for (_, user_totals) in &totals {
+                server.merge(user_totals);
+            }
+            (format!("Voice states, {}", window.label()), Some(server))
+        }
+    };
+    let Some(shown) = shown.filter(|shown| shown.in_voice.is_positive()) else {
+        return reply_no_activity(ctx, window).await;
+    };
+
+    let state_line = |name: &str, duration: time::Duration| {
+        let share = duration.as_seconds_f64() / shown.in_voice.as_seconds_f64() * 100.0;
+        format!(
+            "**{name}:** {} ({share:.0}%)",
+            format_minutes(duration.as_seconds_f64() / 60.0)
+        )
+    };
+    let mut description = serenity::MessageBuilder::default()
+        .push_bold("In voice: ")
+        .push_line(format_minutes(shown.in_voice.as_seconds_f64() / 60.0))
+        .push_line(state_line("Muted", shown.muted))
+        .push_line(state_line("Deafened", shown.deafened))
+        .push_line(state_line("Streaming", shown.streaming))
+        .push_line(state_line("On camera", shown.video));
+    if shown.suppressed.is_positive() {
+        description = description.push_line(state_line("Stage audience", shown.suppressed));
+    }
+
+    // for the server, also show who streams and uses their camera the most
+    let rankings: [(&str, fn(&VoiceStateTotals) -> time::Duration); 2] = [
+        ("Top streamers:", |totals| totals.streaming),
+        ("Top on camera:", |totals| totals.video),
+    ];
+    for (name, pick) in rankings.into_iter().filter(|_| user.is_none()) {
+        let mut ranked = totals
+            .iter()
+            .map(|(user_id, user_totals)| (*user_id, pick(user_totals)))
+            .filter(|(_, duration)| duration.is_positive())
+            .collect::<Vec<_>>();
+        if ranked.is_empty() {
+            continue;
+        }
+        ranked.sort_by(|(a_id, a), (b_id, b)| b.cmp(a).then(a_id.cmp(b_id)));
+        description = description.push_bold_line(name);
+        for (i, (user_id, duration)) in ranked.iter().take(TOP_STATE_MEMBERS).enumerate() {
+            description = description.push_line(format!(
+                "{}. {}: {}",
+                i + 1,
+                serenity::UserId::new(*user_id).mention(),
+                format_minutes(duration.as_seconds_f64() / 60.0)
+            ));
+        }
+    }
+
+    let embed = serenity::CreateEmbed::default()
+        .title(title)
+        .description(description.build());
+    ctx.send(poise::CreateReply::default().embed(embed))
+        .await
+        .context(GeneralSerenitySnafu)?;
+    Ok(())
+}
+
+/// Export who hangs out with whom in voice as a graph file.
+///
+/// Members are nodes, and edges are weighted by the minutes spent in the same channel.
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    owners_only,
+    ephemeral,
+    category = "Statistics"
+)]
+pub async fn graph(
+    ctx: Context<'_>,
+    #[description = "Time window, defaults to the last 30 days"] window: Option<WindowChoice>,
+    #[description = "File format, defaults to JSON"] format: Option<GraphFormat>,
+) -> CommandResult {
+    ctx.defer_ephemeral().await.context(GeneralSerenitySnafu)?;
+    let window = VoiceWindow::from(window.unwrap_or_default());
+    let pairs = load_co_presence(ctx, window).await?;
+    if pairs.is_empty() {
+        return reply_no_activity(ctx, window).await;
+    }
+
+    let guild_id = get_guild_id(ctx)?;
+    let (content, extension) = match format.unwrap_or_default() {
+        GraphFormat::Json => (graph_json(&pairs), "json"),
+        GraphFormat::GraphMl => (graph_graphml(&pairs), "graphml"),
+    };
+    let attachment = serenity::CreateAttachment::bytes(
+        content.into_bytes(),
+        format!("voice_graph_{guild_id}.{extension}"),
+    );
+    ctx.send(
+        poise::CreateReply::default()
+            .content(format!(
+                "Co-presence graph, {}: {} pairs of members.",
+                window.label(),
+                pairs.len()
+            ))
+            .attachment(attachment),
+    )
+    .await
+    .context(GeneralSerenitySnafu)?;
+    Ok(())
+}
+
+/// Stop recording your voice activity, and delete your voice history in every server.
+#[poise::command(slash_command, prefix_command, ephemeral, category = "Statistics")]
+pub async fn opt_out(
+    ctx: Context<'_>,
+    #[description = "Your voice history is deleted, this can't be undone"] confirm: bool,
+) -> CommandResult {
+    ctx.defer_ephemeral().await.context(GeneralSerenitySnafu)?;
+    if !confirm {
+        ctx.reply("Nothing changed. Set `confirm` to opt out and delete your voice history.")
+            .await
+            .context(GeneralSerenitySnafu)?;
+        return Ok(());
+    }
+
+    let user_id = ctx.author().id;
+    let deleted = ctx
+        .data()
+        .data_manager
+        .voice_privacy()
+        .opt_out(user_id.get(), OffsetDateTime::now_utc())
+        .await
+        .context(DataManagerSnafu)?;
+    tracing::info!("User {user_id} opted out of voice tracking, deleted {deleted} rows");
+
+    ctx.reply(format!(
+        "Your voice activity is no longer recorded, and {deleted} stored entries were deleted. \
+         Use `/voice opt_in` to be recorded again."
+    ))
+    .await
+    .context(GeneralSerenitySnafu)?;
+    Ok(())
+}
+
+/// Record your voice activity again after opting out.
+#[poise::command(slash_command, prefix_command, ephemeral, category = "Statistics")]
+pub async fn opt_in(ctx: Context<'_>) -> CommandResult {
+    ctx.defer_ephemeral().await.context(GeneralSerenitySnafu)?;
+    let user_id = ctx.author().id;
+    let was_opted_out = ctx
+        .data()
+        .data_manager
+        .voice_privacy()
+        .opt_in(user_id.get())
+        .await
+        .context(DataManagerSnafu)?;
+
+    let msg = if was_opted_out {
+        tracing::info!("User {user_id} opted back into voice tracking");
+        "Your voice activity is recorded again from now on."
+    } else {
+        "Your voice activity is already recorded."
+    };
+    ctx.reply(msg).await.context(GeneralSerenitySnafu)?;
+    Ok(())
+}
+
+/// Co-presence of the current server in the window
+async fn load_co_presence(
+    ctx: Context<'_>,
+    window: VoiceWindow,
+) -> Result<Vec<CoPresence>, BotError> {
+    let guild_id = get_guild_id(ctx)?;
+    let now = OffsetDateTime::now_utc();
+    ctx.data()
+        .data_manager
+        .voice()
+        .get_co_presence(guild_id.get(), window.since(now), now)
+        .await
+        .context(DataManagerSnafu)
+}
+
+/// Members of the pairs, sorted
+fn graph_nodes(pairs: &[CoPresence]) -> BTreeSet<u64> {
+    pairs
+        .iter()
+        .flat_map(|pair| [pair.user_a, pair.user_b])
+        .collect()
+}
+
+/// `{"nodes": [{"id": ...}], "edges": [{"source": ..., "target": ..., "minutes": ...}]}`, with
+/// ids as strings so they survive JSON number precision
+fn graph_json(pairs: &[CoPresence]) -> String {
+    let nodes = graph_nodes(pairs)
+        .into_iter()
+        .map(|user_id| serde_json::json!({ "id": user_id.to_string() }))
+        .collect::<Vec<_>>();
+    let edges = pairs
+        .iter()
+        .map(|pair| {
+            serde_json::json!({
+                "source": pair.user_a.to_string(),
+                "target": pair.user_b.to_string(),
+                "minutes": pair.overlap.whole_minutes(),
+            })
+        })
+        .collect::<Vec<_>>();
+    serde_json::json!({ "nodes": nodes, "edges": edges }).to_string()
+}
+
+/// Undirected GraphML with the overlap minutes as the edge weight
+fn graph_graphml(pairs: &[CoPresence]) -> String {
+    let mut out = String::from(concat!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>"#,
+        "\n",
+        r#"<graphml xmlns="http://graphml.graphdrawing.org/xmlns">"#,
+        "\n",
+        r#"  <key id="minutes" for="edge" attr.name="minutes" attr.type="long"/>"#,
+        "\n",
+        r#"  <graph id="voice" edgedefault="undirected">"#,
+        "\n",
+    ));
+    for user_id in graph_nodes(pairs) {
+        out.push_str(&format!("    <node id=\"{user_id}\"/>\n"));
+    }
+    for pair in pairs {
+        out.push_str(&format!(
+            "    <edge source=\"{}\" target=\"{}\"><data key=\"minutes\">{}</data></edge>\n",
+            pair.user_a,
+            pair.user_b,
+            pair.overlap.whole_minutes()
+        ));
+    }
+    out.push_str("  </graph>\n</graphml>\n");
+    out
+}
+
+/// Voice sessions of the current server, clipped to the window
+async fn load_spans(ctx: Context<'_>, window: VoiceWindow) -> Result<Vec<SessionSpan>, BotError> {
+    let now = OffsetDateTime::now_utc();
+    load_spans_since(ctx, window.since(now), now).await
+}
+
+/// Voice sessions of the current server, clipped to start no earlier than `since`
+async fn load_spans_since(
+    ctx: Context<'_>,
+    since: Option<OffsetDateTime>,
+    now: OffsetDateTime,
+) -> Result<Vec<SessionSpan>, BotError> {
+    let guild_id = get_guild_id(ctx)?;
+    let sessions = ctx
+        .data()
+        .data_manager
+        .voice()
+        .get_server_voice_sessions_since(guild_id.get(), since)
+        .await
+        .context(DataManagerSnafu)?;
+    Ok(session_spans(&sessions, since, now))
+}
+
+async fn reply_no_activity(ctx: Context<'_>, window: VoiceWindow) -> CommandResult {
+    ctx.reply(format!("No voice activity in the {}.", window.label()))
+        .await
+        .context(GeneralSerenitySnafu)?;
+    Ok(())
+}
+
+/// Reply with an embed showing an attached chart
+async fn send_report(
+    ctx: Context<'_>,
+    title: String,
+    description: String,
+    image: Vec<u8>,
+    filename: &str,
+) -> CommandResult {
+    let embed = serenity::CreateEmbed::default()
+        .title(title)
+        .description(description)
+        .image(format!("attachment://{filename}"));
+    ctx.send(poise::CreateReply::default().embed(embed).attachment(
+        serenity::CreateAttachment::bytes(image, filename.to_string()),
+    ))
+    .await
+    .context(GeneralSerenitySnafu)?;
+    Ok(())
+}
+
+/// Whole minutes as "3h 20m"
+fn format_minutes(minutes: f64) -> String {
+    let minutes = minutes.round().max(0.0) as u64;
+    humantime::format_duration(std::time::Duration::from_secs(minutes * 60)).to_string()
+}
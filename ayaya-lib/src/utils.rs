@@ -1,10 +1,11 @@
+use ayaya_db::data::permission_rules::{Caller, DecidingRule, RuleScope, evaluate};
 use poise::serenity_prelude::{self as serenity};
-use serenity::{Result as SerenityResult, model::channel::Message};
+use serenity::{Mentionable, Result as SerenityResult, model::channel::Message};
 use snafu::ResultExt;
 use tracing::error;
 
 use crate::{
-    BotError, Context,
+    BotError, CommandResult, Context,
     error::{DataManagerSnafu, GeneralSerenitySnafu},
     voice::error::MusicCommandError,
 };
@@ -155,6 +156,96 @@ impl ChannelInfo {
     }
 }
 
+/// Lines shown on each page by [`paginate_lines`]
+pub const LINES_PER_PAGE: usize = 10;
+
+/// Reply with the lines as an embed, split in pages with buttons to flip through them. The
+/// buttons stop working after a minute without presses.
+pub async fn paginate_lines(ctx: Context<'_>, title: &str, lines: &[String]) -> CommandResult {
+    let pages = lines.chunks(LINES_PER_PAGE).collect::<Vec<_>>();
+    paginate(ctx, title, pages.len(), async |page| {
+        Ok(pages
+            .get(page)
+            .map(|lines| lines.join("\n"))
+            .unwrap_or_default())
+    })
+    .await
+}
+
+/// Like [`paginate_lines`], with the description of each page built when it is shown. Useful
+/// when the pages are too many to load upfront.
+pub async fn paginate(
+    ctx: Context<'_>,
+    title: &str,
+    page_count: usize,
+    page_description: impl AsyncFn(usize) -> Result<String, BotError>,
+) -> CommandResult {
+    let ctx_id = ctx.id();
+    let prev_button_id = format!("{ctx_id}prev");
+    let next_button_id = format!("{ctx_id}next");
+
+    let page_count = page_count.max(1);
+    let page_embed = async |page: usize| -> Result<serenity::CreateEmbed<'static>, BotError> {
+        Ok(serenity::CreateEmbed::new()
+            .title(title.to_string())
+            .description(page_description(page).await?)
+            .footer(serenity::CreateEmbedFooter::new(format!(
+                "Page {}/{page_count}",
+                page + 1
+            ))))
+    };
+    let buttons = || {
+        let buttons = vec![
+            serenity::CreateButton::new(&prev_button_id).emoji('◀'),
+            serenity::CreateButton::new(&next_button_id).emoji('▶'),
+        ];
+        vec![serenity::CreateComponent::ActionRow(
+            serenity::CreateActionRow::Buttons(buttons.into()),
+        )]
+    };
+
+    let mut reply = poise::CreateReply::default().embed(page_embed(0).await?);
+    if page_count > 1 {
+        reply = reply.components(buttons());
+    }
+    ctx.send(reply).await.context(GeneralSerenitySnafu)?;
+    if page_count == 1 {
+        return Ok(());
+    }
+
+    // only the caller can turn the pages, others may not be allowed to see them
+    let author_id = ctx.author().id;
+    let mut current_page = 0;
+    while let Some(press) =
+        serenity::collector::ComponentInteractionCollector::new(ctx.serenity_context())
+            .filter(move |press| {
+                press.user.id == author_id && press.data.custom_id.starts_with(&ctx_id.to_string())
+            })
+            .timeout(std::time::Duration::from_secs(60))
+            .await
+    {
+        if press.data.custom_id == next_button_id {
+            current_page = (current_page + 1) % page_count;
+        } else if press.data.custom_id == prev_button_id {
+            current_page = current_page.checked_sub(1).unwrap_or(page_count - 1);
+        } else {
+            continue;
+        }
+
+        let response = serenity::CreateInteractionResponseMessage::new()
+            .embed(page_embed(current_page).await?)
+            .components(buttons());
+        press
+            .create_response(
+                ctx.http(),
+                serenity::CreateInteractionResponse::UpdateMessage(response),
+            )
+            .await
+            .context(GeneralSerenitySnafu)?;
+    }
+    Ok(())
+}
+
 /// Autocomplete function for command names
 pub async fn autocomplete_command_names<'a>(
     ctx: Context<'_>,
@@ -173,89 +264,160 @@ pub async fn autocomplete_command_names<'a>(
 
 /// Check command to determine if a commmand is allowed for a user.
 ///
-/// This checks for the following:
-/// 1. Whether a user is explicitly allowed.
-/// 2. Whether the user possesses a role that is allowed for the command.
-/// 3. Whether the user possesses a role that is allowed for the command category.
-///
-/// If the command is restricted, and the user does not meet the above requirement, then the
-/// command use is not allowed.
+/// The deny rules, channel scopes, user allowances and required roles of the command and its
+/// category are checked in the order documented in [`ayaya_db::data::permission_rules`]. The
+/// first matching rule decides, and a denied user is told which rule denied them. The guild owner
+/// and administrators are always allowed, so they can't lock themselves out.
 pub async fn check_command_allowed(ctx: Context<'_>) -> Result<bool, BotError> {
-    let mut data_manager = ctx.data().data_manager.clone();
-    let user_id = ctx.author().id.get();
-    let guild_id = GuildInfo::guild_id_or_0(ctx);
+    if is_guild_admin(ctx).await {
+        return Ok(true);
+    }
     let command = ctx.command().name.clone();
     let command_category = ctx.command().category.clone().unwrap_or("Unknown".into());
 
-    // TODO: cache
-
-    // check first if user is allowed to use the command
-    let user_allowed = data_manager
-        .permissions_mut()
-        .find_user_allowed(guild_id, user_id, &command)
-        .await
-        .context(DataManagerSnafu)?;
-    if let Some(_model) = user_allowed {
+    let rule = command_permission(ctx, ctx.author().id, &command, &command_category).await?;
+    if rule.allows() {
         return Ok(true);
     }
+    ctx.reply(format!(
+        "You are not allowed to use the command `{}`: {}.",
+        &command,
+        describe_rule(&rule)
+    ))
+    .await
+    .context(GeneralSerenitySnafu)?;
+    Ok(false)
+}
 
-    // check for roles. if present, then iter, else check for catgory role
-    let command_roles_allowed = data_manager
+/// Whether the author owns the guild of the context or has the Administrator permission in it
+async fn is_guild_admin(ctx: Context<'_>) -> bool {
+    let Some(member) = ctx.author_member().await else {
+        return false;
+    };
+    let Some(guild) = ctx.guild() else {
+        return false;
+    };
+    is_admin_of(&guild, &member)
+}
+
+/// Whether the member owns the guild or has the Administrator permission in it. They may use
+/// every command whatever the permission rules say, from Discord as from the API.
+pub fn is_admin_of(guild: &serenity::Guild, member: &serenity::Member) -> bool {
+    guild.owner_id == member.user.id || guild.member_permissions(member).administrator()
+}
+
+/// The rule deciding whether the user may call the command from the channel of the context.
+///
+/// The rules come from the permission cache, and the member's roles are only looked up when a
+/// rule depends on them, so unrestricted commands need neither a query nor a request.
+pub async fn command_permission(
+    ctx: Context<'_>,
+    user_id: serenity::UserId,
+    command: &str,
+    command_category: &str,
+) -> Result<DecidingRule, BotError> {
+    let mut data_manager = ctx.data().data_manager.clone();
+    let guild_id = GuildInfo::guild_id_or_0(ctx);
+
+    let rules = data_manager
         .permissions_mut()
-        .find_command_roles_allowed(guild_id, &command)
+        .find_permission_rules(guild_id, user_id.get(), command, Some(command_category))
         .await
         .context(DataManagerSnafu)?;
-    if !command_roles_allowed.is_empty() {
-        for role in command_roles_allowed {
-            let role_id = role.role_id as u64;
-            if ctx
-                .author()
-                .has_role(ctx, guild_id.into(), role_id.into())
-                .await
-                .context(GeneralSerenitySnafu)?
+
+    let mut caller = Caller {
+        user_id: user_id.get(),
+        channel_id: ctx.channel_id().get(),
+        ..Default::default()
+    };
+    if let Some(guild) = ctx.guild_id() {
+        if rules.uses_roles() {
+            // the author's member comes with the command, others from the cache or the api
+            let role_ids = if user_id == ctx.author().id
+                && let Some(member) = ctx.author_member().await
             {
-                return Ok(true);
-            }
-        }
-        // TODO: explanation
-        ctx.reply(format!(
-            "You are not allowed to use the command `{}` due not having the required roles.",
-            &command
-        ))
-        .await
-        .context(GeneralSerenitySnafu)?;
-        #[expect(clippy::needless_return)]
-        return Ok(false);
-    } else {
-        // check for category roles. if present, iter, else allow
-        let category_roles_allowed = data_manager
-            .permissions_mut()
-            .find_category_roles_allowed(guild_id, &command_category)
-            .await
-            .context(DataManagerSnafu)?;
-        if !category_roles_allowed.is_empty() {
-            for role in category_roles_allowed {
-                let role_id = role.role_id as u64;
-                if ctx
-                    .author()
-                    .has_role(ctx, guild_id.into(), role_id.into())
+                member.roles.iter().map(|role_id| role_id.get()).collect()
+            } else {
+                let member = guild
+                    .member(ctx, user_id)
                     .await
-                    .context(GeneralSerenitySnafu)?
-                {
-                    return Ok(true);
-                }
-            }
-            // TODO: explanation
-            ctx.reply(format!(
-                "You are not allowed to use the command `{}` due not having the required roles.",
-                &command
-            ))
-            .await
-            .context(GeneralSerenitySnafu)?;
-            return Ok(false);
+                    .context(GeneralSerenitySnafu)?;
+                member.roles.iter().map(|role_id| role_id.get()).collect()
+            };
+            caller.role_ids = role_ids;
         }
+        caller.voice_channel_id = ctx.guild().and_then(|guild| {
+            guild
+                .voice_states
+                .get(&user_id)
+                .and_then(|state| state.channel_id)
+                .map(|channel_id| channel_id.get())
+        });
+    }
+
+    Ok(evaluate(&rules, &caller))
+}
+
+/// Explains a permission decision, eg: "the role @DJ is required"
+pub fn describe_rule(rule: &DecidingRule) -> String {
+    fn mention_roles(role_ids: &[u64]) -> String {
+        role_ids
+            .iter()
+            .map(|role_id| serenity::RoleId::new(*role_id).mention().to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+    fn mention_channels(channel_ids: &[u64]) -> String {
+        channel_ids
+            .iter()
+            .map(|channel_id| serenity::ChannelId::new(*channel_id).mention().to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    match rule {
+        DecidingRule::UserDenied(scope) => {
+            format!("the user is denied the {}", scope_name(*scope))
+        }
+        DecidingRule::ChannelOutOfScope { scope, allowed } => format!(
+            "the {} can only be used in {}",
+            scope_name(*scope),
+            mention_channels(allowed)
+        ),
+        DecidingRule::VoiceChannelOutOfScope { scope, allowed } => format!(
+            "the {} can only be used while in {}",
+            scope_name(*scope),
+            mention_channels(allowed)
+        ),
+        DecidingRule::UserAllowed => "the user is allowed the command".to_string(),
+        DecidingRule::RoleDenied { role_id, scope } => format!(
+            "the role {} is denied the {}",
+            mention_roles(&[*role_id]),
+            scope_name(*scope)
+        ),
+        DecidingRule::CommandRole(role_id) => format!(
+            "the command requires a role, and the user has {}",
+            mention_roles(&[*role_id])
+        ),
+        DecidingRule::MissingCommandRole(role_ids) => format!(
+            "the command requires one of the roles {}",
+            mention_roles(role_ids)
+        ),
+        DecidingRule::CategoryRole(role_id) => format!(
+            "the category requires a role, and the user has {}",
+            mention_roles(&[*role_id])
+        ),
+        DecidingRule::MissingCategoryRole(role_ids) => format!(
+            "the category requires one of the roles {}",
+            mention_roles(role_ids)
+        ),
+        DecidingRule::Unrestricted => "the command is not restricted".to_string(),
+    }
+}
 
-        // allow the command if not restricted to a role
-        Ok(true)
+fn scope_name(scope: RuleScope) -> &'static str {
+    match scope {
+        RuleScope::Command => "command",
+        RuleScope::Category => "command category",
     }
 }
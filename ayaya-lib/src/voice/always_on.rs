@@ -0,0 +1,159 @@
+//! 24/7 mode: keep the bot in a voice channel across gateway reconnects, voice driver
+//! disconnects and restarts. The target channel is stored in the database, so it survives a
+//! restart.
+use std::{
+    sync::{Arc, atomic::AtomicBool},
+    time::Duration,
+};
+
+use ayaya_db::entity::always_on_channels;
+use poise::serenity_prelude as serenity;
+use serenity::Context as SerenityContext;
+use tracing::{error, info, warn};
+
+use crate::{Data, voice::commands::play_command::join::setup_joined_call};
+
+/// How many times a rejoin is attempted before giving up until the next reconnect
+const REJOIN_ATTEMPTS: u32 = 3;
+/// Wait before each attempt, multiplied by the attempt number
+const REJOIN_BACKOFF: Duration = Duration::from_secs(5);
+
+/// The live 24/7 flag of a guild, shared with its [`super::events::BotInactiveCounter`]
+pub async fn always_on_flag(data: &Data, guild_id: serenity::GuildId) -> Arc<AtomicBool> {
+    data.always_on_map
+        .lock()
+        .await
+        .entry(guild_id)
+        .or_default()
+        .clone()
+}
+
+/// Whether the bot currently has a live voice connection in the guild
+pub async fn is_connected(data: &Data, guild_id: serenity::GuildId) -> bool {
+    match data.songbird.get(guild_id) {
+        Some(call) => call.lock().await.current_connection().is_some(),
+        None => false,
+    }
+}
+
+/// Whether the bot has a live voice connection to the given channel
+async fn is_connected_to(data: &Data, guild_id: serenity::GuildId, channel_id: u64) -> bool {
+    match data.songbird.get(guild_id) {
+        Some(call) => {
+            let call = call.lock().await;
+            call.current_connection().is_some()
+                && call.current_channel().map(|channel| channel.get()) == Some(channel_id)
+        }
+        None => false,
+    }
+}
+
+/// Rejoin every 24/7 guild the bot is not connected to. Ran after Ready and Resume.
+pub async fn rejoin_always_on_guilds(ctx: &SerenityContext) {
+    let data: Arc<Data> = ctx.data();
+    let entries = match data.data_manager.always_on().list_always_on().await {
+        Ok(entries) => entries,
+        Err(e) => {
+            error!("Failed to list 24/7 guilds: {e}");
+            return;
+        }
+    };
+
+    for entry in entries {
+        let guild_id = serenity::GuildId::new(entry.guild_id as u64);
+        always_on_flag(&data, guild_id)
+            .await
+            .store(true, std::sync::atomic::Ordering::Relaxed);
+        if is_connected(&data, guild_id).await {
+            continue;
+        }
+        spawn_rejoin(ctx, guild_id);
+    }
+}
+
+/// Start rejoining the 24/7 channel of a guild in the background, unless a rejoin of the guild
+/// is already running. Ready, Resume and disconnects can all ask for one at the same time.
+pub fn spawn_rejoin(ctx: &SerenityContext, guild_id: serenity::GuildId) {
+    let data: Arc<Data> = ctx.data();
+    if !data.voice_flags.start_rejoin(guild_id) {
+        info!("A rejoin of guild {guild_id} is already running");
+        return;
+    }
+    let ctx = ctx.clone();
+    tokio::spawn(async move {
+        rejoin_with_retry(&ctx, guild_id).await;
+        data.voice_flags.finish_rejoin(guild_id);
+    });
+}
+
+/// Rejoin the 24/7 channel of a guild, retrying with a linear backoff. Stops early if 24/7 mode
+/// was turned off in the meantime.
+async fn rejoin_with_retry(ctx: &SerenityContext, guild_id: serenity::GuildId) {
+    let data: Arc<Data> = ctx.data();
+
+    for attempt in 1..=REJOIN_ATTEMPTS {
+        tokio::time::sleep(REJOIN_BACKOFF * attempt).await;
+
+        let entry = match data
+            .data_manager
+            .always_on()
+            .get_always_on(guild_id.get())
+            .await
+        {
+            Ok(Some(entry)) => entry,
+            Ok(None) => {
+                info!("24/7 mode was disabled in guild {guild_id}, not rejoining");
+                return;
+            }
+            Err(e) => {
+                warn!("Failed to get 24/7 channel of guild {guild_id}: {e}");
+                continue;
+            }
+        };
+
+        // the voice driver and the voice state update can both trigger a rejoin
+        if is_connected_to(&data, guild_id, entry.voice_channel_id as u64).await {
+            info!("Already back in the 24/7 channel of guild {guild_id}");
+            return;
+        }
+
+        match join_always_on(ctx, &entry).await {
+            Ok(()) => {
+                info!(
+                    "Rejoined 24/7 channel {} in guild {guild_id} on attempt {attempt}",
+                    entry.voice_channel_id
+                );
+                return;
+            }
+            Err(e) => warn!(
+                "Failed to rejoin 24/7 channel {} in guild {guild_id} on attempt {attempt}: {e}",
+                entry.voice_channel_id
+            ),
+        }
+    }
+
+    error!("Giving up rejoining the 24/7 channel in guild {guild_id}");
+}
+
+/// Join the 24/7 channel of a guild and set the call up the same way the join command does
+pub async fn join_always_on(
+    ctx: &SerenityContext,
+    entry: &always_on_channels::Model,
+) -> Result<(), songbird::error::JoinError> {
+    let data: Arc<Data> = ctx.data();
+    let guild_id = serenity::GuildId::new(entry.guild_id as u64);
+    let voice_channel_id = serenity::ChannelId::new(entry.voice_channel_id as u64);
+    let chat_channel_id = serenity::ChannelId::new(entry.text_channel_id as u64).into();
+
+    always_on_flag(&data, guild_id)
+        .await
+        .store(true, std::sync::atomic::Ordering::Relaxed);
+
+    let call = data.songbird.join(guild_id, voice_channel_id).await?;
+    let mut call = call.lock().await;
+    call.mute(false).await?;
+    call.deafen(true).await?;
+    setup_joined_call(&mut call, ctx, guild_id, chat_channel_id, true).await;
+
+    Ok(())
+}
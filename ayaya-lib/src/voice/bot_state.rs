@@ -0,0 +1,353 @@
+//! React to changes of the bot's own voice state made outside of its commands: being moved to
+//! another channel, disconnected by a moderator, or having its channel deleted. Keeps the
+//! [`songbird::Call`], the linger flag, the inactivity counter and the queue consistent, and tells
+//! the text channel that started playback what happened.
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex, atomic::Ordering},
+};
+
+use poise::serenity_prelude as serenity;
+use serenity::{Context as SerenityContext, Mentionable};
+use tracing::{error, info, warn};
+
+use crate::{
+    Data,
+    utils::check_msg,
+    voice::always_on::{always_on_flag, spawn_rejoin},
+};
+
+/// Per guild flags of the bot's own voice connection, kept apart from the call so they are
+/// right even while the call is being removed or set up
+#[derive(Default)]
+pub struct VoiceFlags {
+    /// Guilds the bot was asked to leave, by a command or the inactivity timeout
+    leave_requested: Mutex<HashSet<serenity::GuildId>>,
+    /// Guilds a 24/7 rejoin is running in
+    rejoining: Mutex<HashSet<serenity::GuildId>>,
+}
+
+impl VoiceFlags {
+    /// Mark the next disconnect of the guild as asked for. Set right before removing the call.
+    pub fn request_leave(&self, guild_id: serenity::GuildId) {
+        lock(&self.leave_requested).insert(guild_id);
+    }
+
+    /// Whether a leave was asked for in the guild, clearing the flag
+    pub fn take_leave_request(&self, guild_id: serenity::GuildId) -> bool {
+        lock(&self.leave_requested).remove(&guild_id)
+    }
+
+    /// Mark a rejoin as running in the guild. False if one already is, so it should not start.
+    pub fn start_rejoin(&self, guild_id: serenity::GuildId) -> bool {
+        lock(&self.rejoining).insert(guild_id)
+    }
+
+    pub fn finish_rejoin(&self, guild_id: serenity::GuildId) {
+        lock(&self.rejoining).remove(&guild_id);
+    }
+}
+
+/// The flags stay usable if a thread panicked while holding them
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+/// Handle a voice state update of the bot itself. Updates of other users are ignored.
+pub async fn handle_bot_voice_state_update(
+    ctx: &SerenityContext,
+    old: Option<&serenity::VoiceState>,
+    new: &serenity::VoiceState,
+) {
+    let data: Arc<Data> = ctx.data();
+    if new.user_id != *data.user_id.read().await {
+        return;
+    }
+    let Some(guild_id) = new
+        .guild_id
+        .or_else(|| old.and_then(|state| state.guild_id))
+    else {
+        return;
+    };
+
+    match (old.and_then(|state| state.channel_id), new.channel_id) {
+        (Some(from), Some(to)) if from != to => bot_moved(ctx, &data, guild_id, to).await,
+        (_, None) => bot_disconnected(ctx, &data, guild_id).await,
+        _ => {}
+    }
+}
+
+/// Handle a deleted channel. Only matters if it is the 24/7 channel, the channel the bot is
+/// connected to, or the text channel used for notifications.
+pub async fn handle_channel_delete(ctx: &SerenityContext, channel: &serenity::GuildChannel) {
+    let data: Arc<Data> = ctx.data();
+    let guild_id = channel.base.guild_id;
+
+    let mut playback_channels = data.playback_channel_map.lock().await;
+    if playback_channels.get(&guild_id) == Some(&serenity::GenericChannelId::from(channel.id)) {
+        playback_channels.remove(&guild_id);
+    }
+    drop(playback_channels);
+
+    let always_on_manager = data.data_manager.always_on();
+    match always_on_manager.get_always_on(guild_id.get()).await {
+        Ok(Some(entry)) if entry.voice_channel_id as u64 == channel.id.get() => {
+            if let Err(e) = always_on_manager.disable_always_on(guild_id.get()).await {
+                error!("Failed to disable 24/7 mode in guild {guild_id}: {e}");
+            }
+            always_on_flag(&data, guild_id)
+                .await
+                .store(false, Ordering::Relaxed);
+            info!(
+                "24/7 channel {} of guild {guild_id} was deleted",
+                channel.id
+            );
+            notify(
+                ctx,
+                &data,
+                guild_id,
+                "The 24/7 channel was deleted, 24/7 mode is now disabled.",
+            )
+            .await;
+        }
+        Ok(_) => {}
+        Err(e) => error!("Failed to get 24/7 channel of guild {guild_id}: {e}"),
+    }
+
+    let Some(call) = data.songbird.get(guild_id) else {
+        return;
+    };
+    let current_channel = call.lock().await.current_channel();
+    if current_channel.map(|channel| channel.get()) != Some(channel.id.get()) {
+        return;
+    }
+
+    info!(
+        "Voice channel {} of guild {guild_id} was deleted while connected",
+        channel.id
+    );
+    notify(
+        ctx,
+        &data,
+        guild_id,
+        &format!(
+            "The voice channel **{}** was deleted. The queue has been cleared.",
+            channel.base.name
+        ),
+    )
+    .await;
+    leave_call(&data, guild_id).await;
+}
+
+/// Remove the per guild state kept while the bot is in a call
+pub async fn forget_call_state(data: &Data, guild_id: serenity::GuildId) {
+    data.linger_map.lock().await.remove(&guild_id);
+    data.idle_counter_map.lock().await.remove(&guild_id);
+    data.playback_channel_map.lock().await.remove(&guild_id);
+}
+
+/// Dragged to another channel. The call follows on its own, so only the state around it changes.
+async fn bot_moved(
+    ctx: &SerenityContext,
+    data: &Data,
+    guild_id: serenity::GuildId,
+    to: serenity::ChannelId,
+) {
+    if data.songbird.get(guild_id).is_none() {
+        return;
+    }
+    info!("Moved to voice channel {to} in guild {guild_id}");
+
+    // the new channel gets a fresh timeout
+    if let Some(counter) = data.idle_counter_map.lock().await.get(&guild_id) {
+        counter.store(0, Ordering::Relaxed);
+    }
+
+    // the 24/7 channel stays the one picked with the command, rejoins go back to it
+    let always_on_channel = match data
+        .data_manager
+        .always_on()
+        .get_always_on(guild_id.get())
+        .await
+    {
+        Ok(entry) => entry
+            .map(|entry| serenity::ChannelId::new(entry.voice_channel_id as u64))
+            .filter(|channel_id| *channel_id != to),
+        Err(e) => {
+            error!("Failed to get the 24/7 channel of guild {guild_id}: {e}");
+            None
+        }
+    };
+    let mut message = format!("Ayaya was moved to {}.", to.mention());
+    if let Some(channel_id) = always_on_channel {
+        message.push_str(&format!(
+            " 24/7 mode still comes back to {} after a disconnect, use `/always_on` to change it.",
+            channel_id.mention()
+        ));
+    }
+    notify(ctx, data, guild_id, &message).await;
+}
+
+/// What to do after the bot left a voice channel
+#[derive(Debug, PartialEq, Eq)]
+enum DisconnectAction {
+    /// The leave was expected, only drop the state kept for the call
+    Forget,
+    /// Clear the queue and come back to the 24/7 channel
+    Rejoin,
+    /// Clear the queue and drop the call
+    Leave,
+}
+
+fn disconnect_action(leave_requested: bool, always_on: bool, has_call: bool) -> DisconnectAction {
+    if leave_requested {
+        DisconnectAction::Forget
+    } else if always_on {
+        DisconnectAction::Rejoin
+    } else if has_call {
+        DisconnectAction::Leave
+    } else {
+        DisconnectAction::Forget
+    }
+}
+
+/// Left the voice channel. Leaves the bot asked for are flagged in [`VoiceFlags`], any other
+/// leave was not requested, eg: a moderator disconnected the bot.
+async fn bot_disconnected(ctx: &SerenityContext, data: &Data, guild_id: serenity::GuildId) {
+    let leave_requested = data.voice_flags.take_leave_request(guild_id);
+    let always_on = !leave_requested
+        && match data
+            .data_manager
+            .always_on()
+            .get_always_on(guild_id.get())
+            .await
+        {
+            Ok(entry) => entry.is_some(),
+            Err(e) => {
+                error!("Failed to get 24/7 channel of guild {guild_id}: {e}");
+                false
+            }
+        };
+    let has_call = data.songbird.get(guild_id).is_some();
+
+    match disconnect_action(leave_requested, always_on, has_call) {
+        DisconnectAction::Forget => forget_call_state(data, guild_id).await,
+        DisconnectAction::Rejoin => {
+            warn!("Disconnected from voice in guild {guild_id} without a leave command");
+            notify(
+                ctx,
+                data,
+                guild_id,
+                "Ayaya was disconnected. The queue has been cleared, rejoining because 24/7 mode is on.",
+            )
+            .await;
+            if let Some(call) = data.songbird.get(guild_id) {
+                call.lock().await.queue().stop();
+                data.events.queue_changed(guild_id, 0);
+            }
+            spawn_rejoin(ctx, guild_id);
+        }
+        DisconnectAction::Leave => {
+            warn!("Disconnected from voice in guild {guild_id} without a leave command");
+            notify(
+                ctx,
+                data,
+                guild_id,
+                "Ayaya was disconnected. The queue has been cleared.",
+            )
+            .await;
+            leave_call(data, guild_id).await;
+        }
+    }
+}
+
+/// Clear the queue, drop the call and its state
+async fn leave_call(data: &Data, guild_id: serenity::GuildId) {
+    if let Some(call) = data.songbird.get(guild_id) {
+        call.lock().await.queue().stop();
+        data.events.queue_changed(guild_id, 0);
+    }
+    // the notification channel is read before this, so it can be forgotten now
+    forget_call_state(data, guild_id).await;
+    data.voice_flags.request_leave(guild_id);
+    if let Err(e) = data.songbird.remove(guild_id).await {
+        error!("Failed to remove call in guild {guild_id}: {e:?}");
+    }
+}
+
+/// Tell the text channel that started playback in the guild
+async fn notify(ctx: &SerenityContext, data: &Data, guild_id: serenity::GuildId, message: &str) {
+    let channel_id = data
+        .playback_channel_map
+        .lock()
+        .await
+        .get(&guild_id)
+        .copied();
+    if let Some(channel_id) = channel_id {
+        check_msg(channel_id.say(&ctx.http, message).await);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GUILD_ID: serenity::GuildId = serenity::GuildId::new(1);
+
+    #[test]
+    fn requested_leave_is_only_forgotten() {
+        assert_eq!(
+            disconnect_action(true, true, true),
+            DisconnectAction::Forget
+        );
+        assert_eq!(
+            disconnect_action(true, false, true),
+            DisconnectAction::Forget
+        );
+    }
+
+    #[test]
+    fn always_on_rejoins_after_unrequested_leave() {
+        assert_eq!(
+            disconnect_action(false, true, true),
+            DisconnectAction::Rejoin
+        );
+        assert_eq!(
+            disconnect_action(false, true, false),
+            DisconnectAction::Rejoin
+        );
+    }
+
+    #[test]
+    fn unrequested_leave_drops_the_call() {
+        assert_eq!(
+            disconnect_action(false, false, true),
+            DisconnectAction::Leave
+        );
+        assert_eq!(
+            disconnect_action(false, false, false),
+            DisconnectAction::Forget
+        );
+    }
+
+    #[test]
+    fn leave_request_is_taken_once() {
+        let flags = VoiceFlags::default();
+        assert!(!flags.take_leave_request(GUILD_ID));
+        flags.request_leave(GUILD_ID);
+        assert!(flags.take_leave_request(GUILD_ID));
+        assert!(!flags.take_leave_request(GUILD_ID));
+    }
+
+    #[test]
+    fn one_rejoin_per_guild() {
+        let flags = VoiceFlags::default();
+        assert!(flags.start_rejoin(GUILD_ID));
+        assert!(!flags.start_rejoin(GUILD_ID));
+        assert!(flags.start_rejoin(serenity::GuildId::new(2)));
+        flags.finish_rejoin(GUILD_ID);
+        assert!(flags.start_rejoin(GUILD_ID));
+    }
+}
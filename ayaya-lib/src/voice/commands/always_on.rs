@@ -0,0 +1,126 @@
+//! This module contains the 24/7 mode command
+use std::sync::atomic::Ordering;
+
+use poise::serenity_prelude as serenity;
+use serenity::Mentionable;
+use snafu::ResultExt;
+
+use crate::{
+    CommandResult, Context,
+    admin::audit::audit,
+    error::{DataManagerSnafu, GeneralSerenitySnafu},
+    utils::{ChannelInfo, GuildInfo, get_guild, get_guild_id},
+    voice::{
+        always_on::{always_on_flag, is_connected, join_always_on},
+        error::MusicCommandError,
+    },
+};
+
+/// 24/7 mode: Ayaya stays in a voice channel around the clock, and comes back after restarts.
+#[tracing::instrument(skip(ctx), fields(user_id = %ctx.author().id, guild_id = get_guild_id(ctx)?.get()))]
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    aliases("247"),
+    required_permissions = "MANAGE_GUILD",
+    category = "Music"
+)]
+pub async fn always_on(
+    ctx: Context<'_>,
+    #[description = "Turn 24/7 mode on or off"] enabled: bool,
+    #[description = "The voice channel to stay in. Defaults to your current voice channel"]
+    #[channel_types("Voice", "Stage")]
+    channel: Option<serenity::GuildChannel>,
+) -> CommandResult {
+    ctx.defer().await.context(GeneralSerenitySnafu)?;
+    let guild_id = get_guild_id(ctx)?;
+    let always_on_manager = ctx.data().data_manager.always_on();
+
+    if !enabled {
+        let was_enabled = always_on_manager
+            .disable_always_on(guild_id.get())
+            .await
+            .context(DataManagerSnafu)?;
+        always_on_flag(&ctx.data(), guild_id)
+            .await
+            .store(false, Ordering::Relaxed);
+
+        let message = if was_enabled {
+            tracing::info!("Disabled 24/7 mode in guild {guild_id}");
+            audit(
+                ctx,
+                "always_on",
+                Some("enabled".to_string()),
+                Some("disabled".to_string()),
+            )
+            .await;
+            "24/7 mode disabled. Ayaya will leave when idle again."
+        } else {
+            "24/7 mode was not enabled."
+        };
+        ctx.reply(message).await.context(GeneralSerenitySnafu)?;
+        return Ok(());
+    }
+
+    let guild_info = GuildInfo::from_ctx(ctx)?;
+    let voice_channel_id = match channel {
+        Some(channel) => channel.id,
+        None => get_guild(ctx)?
+            .voice_states
+            .get(&ctx.author().id)
+            .and_then(|state| state.channel_id)
+            .ok_or(MusicCommandError::UserVoiceNotJoined {
+                user: ctx.author().clone(),
+                guild_info: guild_info.clone(),
+            })?,
+    };
+
+    let entry = always_on_manager
+        .enable_always_on(
+            guild_id.get(),
+            voice_channel_id.get(),
+            ctx.channel_id().get(),
+            &ctx.author().id,
+        )
+        .await
+        .context(DataManagerSnafu)?;
+    tracing::info!("Enabled 24/7 mode in guild {guild_id} for channel {voice_channel_id}");
+    audit(
+        ctx,
+        "always_on",
+        None,
+        Some(format!("enabled in channel {voice_channel_id}")),
+    )
+    .await;
+
+    let current_channel = match ctx.data().songbird.get(guild_id) {
+        Some(call) => call.lock().await.current_channel(),
+        None => None,
+    };
+    let already_there = is_connected(&ctx.data(), guild_id).await
+        && current_channel.map(|channel| channel.get()) == Some(voice_channel_id.get());
+
+    if already_there {
+        always_on_flag(&ctx.data(), guild_id)
+            .await
+            .store(true, Ordering::Relaxed);
+    } else if let Err(e) = join_always_on(ctx.serenity_context(), &entry).await {
+        let voice_channel_info =
+            ChannelInfo::from_serenity_id(ctx, voice_channel_id.into(), true).await?;
+        return Err(MusicCommandError::FailedJoinCall {
+            source: e,
+            guild_info,
+            voice_channel_info,
+        }
+        .into());
+    }
+
+    ctx.reply(format!(
+        "24/7 mode enabled. Ayaya will stay in {} until told otherwise.",
+        voice_channel_id.mention()
+    ))
+    .await
+    .context(GeneralSerenitySnafu)?;
+    Ok(())
+}
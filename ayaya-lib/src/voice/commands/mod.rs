@@ -1,4 +1,5 @@
 use admin::*;
+use always_on::*;
 use play_command::*;
 use playback_control::*;
 use queue::*;
@@ -11,7 +12,8 @@ use crate::{
 };
 
 mod admin;
-mod play_command;
+mod always_on;
+pub(crate) mod play_command;
 mod playback_control;
 mod queue;
 pub(crate) mod soundboard;
@@ -19,6 +21,7 @@ pub(crate) mod soundboard;
 pub fn voice_commands() -> Commands {
     vec![
         join(),
+        always_on(),
         play(),
         leave(),
         queue(),
@@ -41,6 +44,10 @@ pub fn voice_commands() -> Commands {
         upload_sound(),
         play_sound(),
         rename_sound(),
+        export_sounds(),
+        import_sounds(),
+        import_native_sounds(),
+        push_native_sound(),
     ]
 }
 
@@ -1,24 +1,26 @@
 //! This module contains functions supporting the join command
-use std::{
-    sync::{
-        Arc,
-        atomic::{AtomicBool, AtomicUsize},
-    },
-    time::Duration,
+use std::sync::{
+    Arc,
+    atomic::{AtomicBool, AtomicUsize},
 };
 
 use ::serenity::futures::TryFutureExt as _;
+use ayaya_db::data::idle::IdlePolicy;
 use poise::serenity_prelude as serenity;
 use serenity::Mentionable;
 use snafu::ResultExt;
-use songbird::Event;
+use songbird::{CoreEvent, Event, TrackEvent};
 use tracing::{error, info, warn};
 
 use crate::{
-    Context,
+    Context, Data,
     error::{BotError, GeneralSerenitySnafu},
     utils::{ChannelInfo, GuildInfo, get_guild, get_guild_id},
-    voice::{error::MusicCommandError, events::BotInactiveCounter},
+    voice::{
+        always_on::always_on_flag,
+        error::MusicCommandError,
+        events::{BotInactiveCounter, TrackEventPublisher, VoiceLeaveCleanup},
+    },
 };
 
 /// Returns true if already in a channel, false if newly joined
@@ -37,14 +39,18 @@ pub async fn join_inner(
 
     let manager = ctx.data().songbird.clone();
 
+    // a call without a channel is left over from a disconnect, and is joined again below
+    let current_channel = match manager.get(guild_id) {
+        Some(call) => call.lock().await.current_channel(),
+        None => None,
+    };
+
     // check if we are already in a call
-    match manager.get(guild_id) {
+    match current_channel {
         // if already in call
-        Some(call) => {
+        Some(current_channel) => {
             let (voice_channel_name, voice_channel_id) = {
-                let call = call.lock().await;
-                let chan: u64 = call.current_channel().expect("bruh").get();
-                let channel_id = serenity::ChannelId::from(chan);
+                let channel_id = serenity::ChannelId::from(current_channel.get());
                 (
                     channel_id
                         .to_guild_channel(ctx.http(), Some(guild_id))
@@ -135,24 +141,14 @@ pub async fn join_inner(
                         })
                         .await?;
 
-                    let bot_user_id = { *ctx.data().user_id.read().await };
-                    let linger = Arc::new(AtomicBool::new(linger));
-
-                    // inactive counter bot
-                    call.add_global_event(
-                        Event::Periodic(Duration::from_secs(60), None),
-                        BotInactiveCounter {
-                            channel_id: chat_channel_id,
-                            counter: Arc::new(AtomicUsize::new(0)),
-                            guild_id,
-                            bot_user_id,
-                            manager: ctx.data().songbird.clone(),
-                            ctx: ctx.serenity_context().to_owned(),
-                            linger: linger.clone(),
-                        },
-                    );
-
-                    ctx.data().linger_map.lock().await.insert(guild_id, linger);
+                    setup_joined_call(
+                        &mut call,
+                        ctx.serenity_context(),
+                        guild_id,
+                        chat_channel_id,
+                        linger,
+                    )
+                    .await;
                 }
                 Err(e) => {
                     let voice_channel_info =
@@ -174,3 +170,98 @@ pub async fn join_inner(
         }
     }
 }
+
+/// Register the voice events and linger state of a freshly joined call.
+///
+/// This does not need a command context, so it is shared by the join command and the 24/7
+/// rejoin. Global events from a previous connection of the same call are dropped first.
+pub async fn setup_joined_call(
+    call: &mut songbird::Call,
+    ctx: &serenity::Context,
+    guild_id: serenity::GuildId,
+    chat_channel_id: serenity::GenericChannelId,
+    linger: bool,
+) {
+    let data: Arc<Data> = ctx.data();
+    let bot_user_id = { *data.user_id.read().await };
+    let linger = Arc::new(AtomicBool::new(linger));
+    let counter = Arc::new(AtomicUsize::new(0));
+    let always_on = always_on_flag(&data, guild_id).await;
+    let policy = match data
+        .data_manager
+        .idle()
+        .get_idle_policy(guild_id.get())
+        .await
+    {
+        Ok(policy) => policy,
+        Err(e) => {
+            error!("Error getting idle policy, using the default: {e}");
+            IdlePolicy::default()
+        }
+    };
+
+    // the guild's music channel, if set, replaces the channel playback started from
+    let chat_channel_id = match data
+        .data_manager
+        .guild_settings()
+        .get_settings(guild_id.get())
+        .await
+    {
+        Ok(config) => config
+            .music_channel_id
+            .map(|channel_id| serenity::ChannelId::new(channel_id).into())
+            .unwrap_or(chat_channel_id),
+        Err(e) => {
+            error!("Error getting guild settings, using the playback channel: {e}");
+            chat_channel_id
+        }
+    };
+
+    call.remove_all_global_events();
+    // a leave asked for before this join has happened already
+    data.voice_flags.take_leave_request(guild_id);
+
+    // inactive counter bot
+    call.add_global_event(
+        Event::Periodic(policy.check_interval, None),
+        BotInactiveCounter {
+            channel_id: chat_channel_id,
+            counter: counter.clone(),
+            guild_id,
+            bot_user_id,
+            manager: data.songbird.clone(),
+            ctx: ctx.to_owned(),
+            linger: linger.clone(),
+            always_on,
+            policy,
+        },
+    );
+
+    // cleanup, or rejoin in 24/7 mode, when the voice connection drops
+    call.add_global_event(
+        Event::Core(CoreEvent::DriverDisconnect),
+        VoiceLeaveCleanup {
+            guild_id,
+            ctx: ctx.to_owned(),
+        },
+    );
+
+    // track starts and ends, for dashboards
+    for (event, end) in [(TrackEvent::Play, false), (TrackEvent::End, true)] {
+        call.add_global_event(
+            Event::Track(event),
+            TrackEventPublisher {
+                guild_id,
+                events: data.events.clone(),
+                end,
+            },
+        );
+    }
+
+    data.linger_map.lock().await.insert(guild_id, linger);
+    data.idle_counter_map.lock().await.insert(guild_id, counter);
+    data.playback_channel_map
+        .lock()
+        .await
+        .insert(guild_id, chat_channel_id);
+}
@@ -19,6 +19,8 @@ pub mod join;
 mod play;
 mod youtube;
 
+pub(crate) use play::enqueue_input;
+
 /// Joins the voice channel the user is currently in. PARTY TIME!
 #[tracing::instrument(skip(ctx), fields(user_id = %ctx.author().id, guild_id = get_guild_id(ctx)?.get()))]
 #[poise::command(
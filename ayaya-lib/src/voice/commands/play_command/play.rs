@@ -10,7 +10,8 @@ use tokio::sync::Mutex;
 use tracing::{error, info};
 
 use crate::{
-    Context,
+    Context, Data,
+    api::events::publish_queue_changed,
     data::stats::StatsManager,
     error::{BotError, DataManagerSnafu, GeneralSerenitySnafu},
     utils::{GuildInfo, OptionExt, get_guild_id},
@@ -40,20 +41,26 @@ impl PlayParse {
             input.to_string()
         };
 
-        if new_input.starts_with("http") {
-            // TODO: err
-            let url = url::Url::parse(&new_input).expect("unable to parse url");
+        Self::from_input(&new_input)
+    }
+
+    /// Parse the input without looking up autocomplete values. Inputs starting with "http" that
+    /// are not urls are searched for.
+    pub fn from_input(input: &str) -> Self {
+        if input.starts_with("http")
+            && let Ok(url) = url::Url::parse(input)
+        {
             let pairs = url.query_pairs().filter(|(name, _)| !name.eq("si"));
             let mut url = url.clone();
             url.query_pairs_mut().clear().extend_pairs(pairs);
 
-            if new_input.contains("playlist") {
+            if input.contains("playlist") {
                 return Self::PlaylistUrl(url.to_string());
             }
 
             Self::Url(url.to_string())
         } else {
-            Self::Search(new_input.to_string())
+            Self::Search(input.to_string())
         }
     }
 
@@ -190,6 +197,70 @@ pub async fn play_inner(
     input_type.run(ctx, shuffle, next).await
 }
 
+/// Adds the input to the queue of a guild Ayaya is already in, without a command context. Now
+/// playing notices go to `notice_channel_id`. Returns the added tracks, in queue order.
+pub(crate) async fn enqueue_input(
+    data: &Data,
+    serenity_http: Arc<serenity::Http>,
+    guild_id: serenity::GuildId,
+    notice_channel_id: serenity::GenericChannelId,
+    user: serenity::User,
+    input: &str,
+    next: bool,
+) -> Result<Vec<YoutubeMetadata>, BotError> {
+    let call = data.songbird.get(guild_id);
+    if call.is_none() {
+        return Err(MusicCommandError::CallDoesNotExist.into());
+    }
+    let stats = data.data_manager.stats();
+    let mut sources = match PlayParse::from_input(input) {
+        PlayParse::Search(search) => vec![youtube::YoutubeDl::new_search(
+            data.http.clone(),
+            search,
+            Some(stats.clone()),
+        )],
+        PlayParse::Url(url) => vec![youtube::YoutubeDl::new(
+            data.http.clone(),
+            url,
+            Some(stats.clone()),
+        )],
+        PlayParse::PlaylistUrl(playlist_url) => {
+            youtube::YoutubeDl::new_playlist(data.http.clone(), playlist_url)
+                .await?
+                .0
+        }
+    };
+    if sources.is_empty() {
+        return Err(MusicCommandError::EmptySource.into());
+    }
+
+    // inserted next one by one, so the last source goes in first
+    if next {
+        sources.reverse();
+    }
+    let mut added = Vec::with_capacity(sources.len());
+    for source in sources {
+        added.push(
+            insert_source(
+                source,
+                call.clone(),
+                serenity_http.clone(),
+                notice_channel_id,
+                stats.clone(),
+                user.clone(),
+                guild_id,
+                next,
+            )
+            .await?,
+        );
+    }
+    if next {
+        added.reverse();
+    }
+    publish_queue_changed(data, guild_id).await;
+    Ok(added)
+}
+
 /// Inserts a youtube source, sets events and notifies the calling channel
 #[tracing::instrument(skip(ctx, call, sources))]
 async fn handle_sources(
@@ -254,6 +325,7 @@ async fn handle_sources(
             });
         }
     };
+    publish_queue_changed(ctx.data(), guild_info.guild_id).await;
 
     Ok(())
 }
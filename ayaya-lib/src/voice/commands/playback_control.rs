@@ -5,9 +5,10 @@ use snafu::ResultExt;
 
 use crate::{
     Context,
-    error::{BotError, GeneralSerenitySnafu},
+    error::{BotError, DataManagerSnafu, GeneralSerenitySnafu},
     utils::{ChannelInfo, GuildInfo, OptionExt, check_msg, get_guild_id},
     voice::{
+        always_on::always_on_flag,
         error::MusicCommandError,
         utils::{self, YoutubeMetadata, metadata_to_embed},
     },
@@ -119,6 +120,7 @@ pub async fn stop(ctx: Context<'_>) -> Result<(), BotError> {
         let handler = handler_lock.lock().await;
         let queue = handler.queue();
         queue.stop();
+        ctx.data().events.queue_changed(guild_info.guild_id, 0);
 
         check_msg(ctx.channel_id().say(ctx.http(), "queue cleared.").await);
     } else {
@@ -500,6 +502,19 @@ pub async fn leave(ctx: Context<'_>) -> Result<(), BotError> {
                 .await?
         };
 
+        // an explicit leave also ends 24/7 mode, otherwise Ayaya would come back on restart
+        let was_always_on = ctx
+            .data()
+            .data_manager
+            .always_on()
+            .disable_always_on(guild_info.guild_id.get())
+            .await
+            .context(DataManagerSnafu)?;
+        always_on_flag(&ctx.data(), guild_info.guild_id)
+            .await
+            .store(false, std::sync::atomic::Ordering::Relaxed);
+
+        ctx.data().voice_flags.request_leave(guild_info.guild_id);
         if let Err(e) = manager.remove(guild_info.guild_id).await {
             return Err(MusicCommandError::FailedLeaveCall {
                 source: e,
@@ -510,7 +525,12 @@ pub async fn leave(ctx: Context<'_>) -> Result<(), BotError> {
         }
 
         // TODO: replace with embeds
-        check_msg(ctx.channel_id().say(ctx.http(), "Left voice channel").await);
+        let message = if was_always_on {
+            "Left voice channel, 24/7 mode is now disabled"
+        } else {
+            "Left voice channel"
+        };
+        check_msg(ctx.channel_id().say(ctx.http(), message).await);
     } else {
         return Err(MusicCommandError::BotVoiceNotJoined { guild_info }.into());
     }
@@ -101,6 +101,9 @@ pub async fn delete(ctx: Context<'_>, queue_position: u64) -> Result<(), BotErro
                 if let Some(track) = queue.current_queue().get(index) {
                     let metadata = track.data::<YoutubeMetadata>();
                     if queue.dequeue(index).is_some() {
+                        ctx.data()
+                            .events
+                            .queue_changed(guild_info.guild_id, queue.len());
                         ctx.send(poise::CreateReply::default().embed(metadata_to_embed(
                             utils::EmbedOperation::DeleteFromQueue,
                             &metadata,
@@ -166,6 +169,9 @@ pub async fn clear(ctx: Context<'_>) -> Result<(), BotError> {
         lock.queue().modify_queue(|queue| {
             let _ = queue.split_off(0);
         });
+        ctx.data()
+            .events
+            .queue_changed(guild_info.guild_id, lock.queue().len());
 
         ctx.send(
             poise::CreateReply::default().embed(embed_template(utils::EmbedOperation::ClearQueue)),
@@ -250,6 +256,9 @@ pub async fn shuffle(ctx: Context<'_>) -> CommandResult {
                 // it is required to preserve the first element
                 queued.make_contiguous()[1..].shuffle(&mut rng);
             });
+            ctx.data()
+                .events
+                .queue_changed(guild_info.guild_id, queue.len());
             // TODO: pretty embeds
             ctx.say("Ayaya shuffled the queue!")
                 .await
@@ -439,6 +448,9 @@ pub async fn queue_move(
                 }
             })
             .expect("index was valid");
+        ctx.data()
+            .events
+            .queue_changed(guild_info.guild_id, queue_len);
 
         // notify
         let embed = metadata_to_embed(
@@ -1,4 +1,4 @@
-use std::{io::Read, sync::Arc};
+use std::{io::Read, path::Path, sync::Arc};
 
 use error::SoundboardError;
 use poise::serenity_prelude as serenity;
@@ -14,6 +14,7 @@ use crate::{
     utils::GuildInfo,
     voice::{
         error::MusicCommandError,
+        native_soundboard, sound_pack,
         utils::{EmbedOperation, embed_template},
     },
 };
@@ -32,15 +33,27 @@ pub async fn upload_sound(
     ctx: Context<'_>,
     #[description = "A memorable description for the sound"] description: String,
     file: serenity::Attachment,
-    #[description = "Whether others can use this sound. true or false."] public: Option<bool>,
+    #[description = "Whether others can use this sound. Defaults to the server's setting."]
+    public: Option<bool>,
 ) -> CommandResult {
     // TODO: logs
     ctx.defer_ephemeral().await.context(GeneralSerenitySnafu)?;
     let sound_manager = ctx.data().data_manager.sounds();
     let guild_id = GuildInfo::guild_id_or_0(ctx);
     let user_id = ctx.author().id;
-    // if not specified, the default is true
-    let public = public.unwrap_or(true);
+    // if not specified, the server's default, which is true unless changed
+    let public = match public {
+        Some(public) => public,
+        None => ctx
+            .data()
+            .data_manager
+            .guild_settings()
+            .get_settings(guild_id)
+            .await
+            .context(DataManagerSnafu)?
+            .public_uploads
+            .unwrap_or(true),
+    };
 
     // show the notice if its a public upload without the policy acceptance
     if public
@@ -99,11 +112,7 @@ pub async fn upload_sound(
     };
 
     // hash the original file to get an identifier
-    let mut hasher = sha1_smol::Sha1::new();
-    hasher.update(&downloaded_file);
-    let mut bytes = [0; 16];
-    bytes.copy_from_slice(&hasher.digest().bytes()[..16]);
-    let sound_id = uuid::Builder::from_sha1_bytes(bytes).into_uuid();
+    let sound_id = sound_id_from_bytes(&downloaded_file);
 
     // simplify ffmpeg usage by just using files
     let tempdir = tempfile::tempdir().expect("tempdir can be created");
@@ -115,28 +124,9 @@ pub async fn upload_sound(
 
     let outfile = tempdir.path().join(format!("{sound_id}.mp3"));
 
-    let (mut recv, send) = std::io::pipe().expect("unable to create anonymous pipe");
-
-    let mut command = tokio::process::Command::new("ffmpeg")
-        .args([
-            "-i",
-            temp_input_path.display().to_string().as_str(),
-            outfile.display().to_string().as_str(),
-        ])
-        .stdout(send.try_clone().expect("unable to clone pipe"))
-        .stderr(send)
-        .spawn()
-        .context(ExternalCommandSnafu)?;
-
-    let mut output = Vec::new();
-    recv.read_to_end(&mut output).context(IoSnafu)?;
-    tracing::debug!("ffmpeg{}", String::from_utf8(output).unwrap_or_default());
-
-    let success = command
-        .wait()
+    let success = convert_to_mp3(&temp_input_path, &outfile)
         .await
-        .expect("error waiting for command")
-        .success();
+        .context(ExternalCommandSnafu)?;
 
     if !success {
         ctx.reply("Unable to add sound, not an audio file?")
@@ -187,14 +177,10 @@ pub async fn play_sound(
 
     let guild_id = crate::utils::get_guild_id(ctx)?;
 
-    if join::join_inner(ctx, false, true).await? {
-        ctx.data()
-            .linger_map
-            .lock()
-            .await
-            .get_mut(&guild_id)
-            .expect("joined channel without linger map")
-            .store(true, std::sync::atomic::Ordering::Relaxed);
+    if join::join_inner(ctx, false, true).await?
+        && let Some(linger) = ctx.data().linger_map.lock().await.get(&guild_id)
+    {
+        linger.store(true, std::sync::atomic::Ordering::Relaxed);
         tracing::debug!("set channel to linger");
     };
 
@@ -264,6 +250,374 @@ pub async fn rename_sound(
     Ok(())
 }
 
+/// Export the sounds you uploaded as a pack, which can be imported back with import_sounds.
+#[poise::command(slash_command, prefix_command, ephemeral, category = "Soundboard")]
+pub async fn export_sounds(ctx: Context<'_>) -> CommandResult {
+    ctx.defer_ephemeral().await.context(GeneralSerenitySnafu)?;
+
+    let sounds = ctx
+        .data()
+        .data_manager
+        .sounds()
+        .get_user_sounds(&ctx.author().id)
+        .await
+        .context(DataManagerSnafu)?;
+
+    if sounds.is_empty() {
+        ctx.reply("You have not uploaded any sounds.")
+            .await
+            .context(GeneralSerenitySnafu)?;
+        return Ok(());
+    }
+
+    send_sound_pack(ctx, &sounds, format!("sounds-{}.zip", ctx.author().id)).await
+}
+
+/// Import a pack made by export_sounds. Sounds already present are skipped.
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    ephemeral,
+    category = "Soundboard"
+)]
+pub async fn import_sounds(
+    ctx: Context<'_>,
+    #[description = "A zip file created by the export_sounds command"] pack: serenity::Attachment,
+) -> CommandResult {
+    ctx.defer_ephemeral().await.context(GeneralSerenitySnafu)?;
+    let sound_manager = ctx.data().data_manager.sounds();
+    let guild_id = GuildInfo::guild_id_or_0(ctx);
+    let user_id = ctx.author().id;
+
+    let downloaded_pack = match pack.download().await {
+        Ok(down) => {
+            tracing::info!("downloaded sound pack from discord");
+            down
+        }
+        Err(e) => {
+            tracing::error!("error downloading sound pack");
+            return Err(e).context(DownloadAttachmentSnafu);
+        }
+    };
+    let sounds = sound_pack::read_sound_pack(&downloaded_pack)?;
+
+    let sound_dir = ctx.data().data_dir.join("sounds");
+    std::fs::create_dir_all(&sound_dir).context(FilesystemAccessSnafu {
+        path: sound_dir.clone(),
+    })?;
+
+    let mut imported = Vec::new();
+    let mut skipped = Vec::new();
+    let mut failed = Vec::new();
+    for sound in sounds {
+        let (entry, data) = sound?;
+        if !native_soundboard::is_mp3(&data) {
+            tracing::warn!("sound {} of the pack is not an mp3", entry.sound_id);
+            failed.push(entry.sound_name);
+            continue;
+        }
+
+        // like uploads, the id comes from the audio rather than from the pack, and the file is
+        // written before its row so a failed write leaves no sound without a file
+        let sound_id = sound_id_from_bytes(&data);
+        let path = sound_dir.join(format!("{sound_id}.mp3"));
+        if !path.exists() {
+            std::fs::write(&path, &data).context(FilesystemAccessSnafu { path: path.clone() })?;
+        }
+        let inserted = sound_manager
+            .import_sound(
+                &user_id,
+                guild_id,
+                sound_id,
+                entry.sound_name.clone(),
+                entry.public,
+            )
+            .await
+            .context(DataManagerSnafu)?;
+        if inserted {
+            imported.push(entry.sound_name);
+        } else {
+            skipped.push(entry.sound_name);
+        }
+    }
+    tracing::info!(
+        "Imported {} sounds ({} skipped, {} failed) from pack for user {}",
+        imported.len(),
+        skipped.len(),
+        failed.len(),
+        ctx.author()
+    );
+
+    let mut message = serenity::MessageBuilder::default()
+        .push_line(format!("Imported {} sound(s).", imported.len()).as_str());
+    for name in &imported {
+        message = message.push_line(format!("- {name}").as_str());
+    }
+    if !skipped.is_empty() {
+        message = message
+            .push_line(format!("Skipped {} sound(s) that already exist:", skipped.len()).as_str());
+        for name in &skipped {
+            message = message.push_line(format!("- {name}").as_str());
+        }
+    }
+    if !failed.is_empty() {
+        message = message.push_line(
+            format!("Skipped {} sound(s) that are not MP3 files:", failed.len()).as_str(),
+        );
+        for name in &failed {
+            message = message.push_line(format!("- {name}").as_str());
+        }
+    }
+    let embed = embed_template(EmbedOperation::SoundPackImported).description(message.build());
+    ctx.send(poise::CreateReply::default().embed(embed))
+        .await
+        .context(GeneralSerenitySnafu)?;
+
+    Ok(())
+}
+
+/// Build a pack out of the given sounds and send it as an attachment
+pub async fn send_sound_pack(
+    ctx: Context<'_>,
+    sounds: &[ayaya_db::entity::sounds::Model],
+    filename: String,
+) -> CommandResult {
+    let sound_dir = ctx.data().data_dir.join("sounds");
+    let (pack, missing) = sound_pack::build_sound_pack(&sound_dir, sounds)?;
+
+    let mut message = serenity::MessageBuilder::default().push_line(
+        format!(
+            "Exported {} sound(s).",
+            sounds.len().saturating_sub(missing.len())
+        )
+        .as_str(),
+    );
+    if !missing.is_empty() {
+        message = message.push_line(
+            format!("{} sound(s) have no file and were skipped:", missing.len()).as_str(),
+        );
+        for entry in &missing {
+            message = message.push_line(format!("- {}", entry.sound_name).as_str());
+        }
+    }
+
+    let reply = poise::CreateReply::default()
+        .embed(embed_template(EmbedOperation::SoundPackExported).description(message.build()))
+        .attachment(serenity::CreateAttachment::bytes(pack, filename));
+    ctx.send(reply).await.context(GeneralSerenitySnafu)?;
+
+    Ok(())
+}
+
+/// Import the sounds of this server's native soundboard into your sounds.
+///
+/// Sounds that were already imported or uploaded are skipped.
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    ephemeral,
+    category = "Soundboard"
+)]
+pub async fn import_native_sounds(
+    ctx: Context<'_>,
+    #[description = "Whether others can use the imported sounds. Defaults to false."]
+    public: Option<bool>,
+) -> CommandResult {
+    ctx.defer_ephemeral().await.context(GeneralSerenitySnafu)?;
+    let sound_manager = ctx.data().data_manager.sounds();
+    let guild_id = crate::utils::get_guild_id(ctx)?;
+    let public = public.unwrap_or(false);
+
+    let downloaded =
+        native_soundboard::download_guild_sounds(ctx.data().soundboard_http.as_ref(), guild_id)
+            .await?;
+
+    let sound_dir = ctx.data().data_dir.join("sounds");
+    std::fs::create_dir_all(&sound_dir).context(FilesystemAccessSnafu {
+        path: sound_dir.clone(),
+    })?;
+
+    let mut imported = Vec::new();
+    let mut skipped = Vec::new();
+    let mut failed = Vec::new();
+    for native in downloaded {
+        let sound_id = sound_id_from_bytes(&native.data);
+        let final_file_path = sound_dir.join(format!("{sound_id}.mp3"));
+
+        if !final_file_path.exists() {
+            if native_soundboard::is_mp3(&native.data) {
+                std::fs::write(&final_file_path, &native.data).context(FilesystemAccessSnafu {
+                    path: final_file_path.clone(),
+                })?;
+            } else {
+                // native sounds can also be ogg, which we convert like uploads
+                let tempdir = tempfile::tempdir().context(IoSnafu)?;
+                let temp_input_path = tempdir.path().join(&native.sound.sound_id);
+                std::fs::write(&temp_input_path, &native.data).context(FilesystemAccessSnafu {
+                    path: &temp_input_path,
+                })?;
+                let outfile = tempdir.path().join(format!("{sound_id}.mp3"));
+                if !convert_to_mp3(&temp_input_path, &outfile)
+                    .await
+                    .context(ExternalCommandSnafu)?
+                {
+                    tracing::warn!("unable to convert native sound {}", native.sound.sound_id);
+                    failed.push(native.sound.name);
+                    continue;
+                }
+                std::fs::copy(&outfile, &final_file_path).context(FilesystemAccessSnafu {
+                    path: final_file_path.clone(),
+                })?;
+            }
+        }
+
+        let inserted = sound_manager
+            .import_sound(
+                &ctx.author().id,
+                guild_id.get(),
+                sound_id,
+                native.sound.name.clone(),
+                public,
+            )
+            .await
+            .context(DataManagerSnafu)?;
+        if inserted {
+            imported.push(native.sound.name);
+        } else {
+            skipped.push(native.sound.name);
+        }
+    }
+    tracing::info!(
+        "Imported {} native sounds ({} skipped, {} failed) from guild {guild_id} for user {}",
+        imported.len(),
+        skipped.len(),
+        failed.len(),
+        ctx.author()
+    );
+
+    let mut message = serenity::MessageBuilder::default()
+        .push_line(format!("Imported {} sound(s).", imported.len()).as_str());
+    for name in &imported {
+        message = message.push_line(format!("- {name}").as_str());
+    }
+    if !skipped.is_empty() {
+        message = message
+            .push_line(format!("Skipped {} sound(s) that already exist:", skipped.len()).as_str());
+        for name in &skipped {
+            message = message.push_line(format!("- {name}").as_str());
+        }
+    }
+    if !failed.is_empty() {
+        message =
+            message.push_line(format!("Failed to convert {} sound(s):", failed.len()).as_str());
+        for name in &failed {
+            message = message.push_line(format!("- {name}").as_str());
+        }
+    }
+    let embed = embed_template(EmbedOperation::NativeSoundsImported).description(message.build());
+    ctx.send(poise::CreateReply::default().embed(embed))
+        .await
+        .context(GeneralSerenitySnafu)?;
+
+    Ok(())
+}
+
+/// Add one of your sounds, or a public one, to this server's native soundboard.
+#[poise::command(
+    slash_command,
+    prefix_command,
+    guild_only,
+    ephemeral,
+    required_permissions = "CREATE_GUILD_EXPRESSIONS",
+    required_bot_permissions = "CREATE_GUILD_EXPRESSIONS",
+    category = "Soundboard"
+)]
+pub async fn push_native_sound(
+    ctx: Context<'_>,
+    #[autocomplete = "autocomplete_play_sound"]
+    #[description = "The sound identifier. Refer to the autocomplete"]
+    sound_id: String,
+    #[description = "Name on the server soundboard. Defaults to the sound description"]
+    name: Option<String>,
+    #[description = "A unicode emoji shown next to the sound"] emoji: Option<String>,
+) -> CommandResult {
+    ctx.defer_ephemeral().await.context(GeneralSerenitySnafu)?;
+    let guild_id = crate::utils::get_guild_id(ctx)?;
+
+    let sound_id = uuid::Uuid::parse_str(&sound_id).map_err(|_| SoundboardError::SoundNotFound)?;
+    let sound = ctx
+        .data()
+        .data_manager
+        .sounds()
+        .get_sound_details(sound_id)
+        .await
+        // private sounds of other users are treated as non existent
+        .filter(|sound| sound.public || sound.user_id == ctx.author().id.get() as i64)
+        .ok_or(SoundboardError::SoundNotFound)?;
+
+    let sound_dir = ctx.data().data_dir.join("sounds");
+    let native = native_soundboard::push_guild_sound(
+        ctx.data().soundboard_http.as_ref(),
+        &sound_dir,
+        guild_id,
+        &sound,
+        name,
+        emoji,
+    )
+    .await?;
+    tracing::info!(
+        "Pushed sound {} as native sound {} to guild {guild_id} for user {}",
+        sound.sound_id,
+        native.sound_id,
+        ctx.author()
+    );
+
+    let embed = embed_template(EmbedOperation::NativeSoundPushed).description(format!(
+        "Added **{}** to the server soundboard.",
+        native.name
+    ));
+    ctx.send(poise::CreateReply::default().embed(embed))
+        .await
+        .context(GeneralSerenitySnafu)?;
+
+    Ok(())
+}
+
+/// Convert an audio file to mp3 with ffmpeg. Returns whether the conversion succeeded.
+pub async fn convert_to_mp3(input: &Path, output: &Path) -> std::io::Result<bool> {
+    let (mut recv, send) = std::io::pipe()?;
+
+    let mut command = tokio::process::Command::new("ffmpeg")
+        .args([
+            "-i",
+            input.display().to_string().as_str(),
+            output.display().to_string().as_str(),
+        ])
+        .stdout(send.try_clone()?)
+        .stderr(send)
+        .spawn()?;
+
+    let mut ffmpeg_output = Vec::new();
+    recv.read_to_end(&mut ffmpeg_output)?;
+    tracing::debug!(
+        "ffmpeg{}",
+        String::from_utf8(ffmpeg_output).unwrap_or_default()
+    );
+
+    Ok(command.wait().await?.success())
+}
+
+/// Derive the identifier of a sound from the hash of the originally uploaded file
+pub fn sound_id_from_bytes(file: &[u8]) -> uuid::Uuid {
+    let mut hasher = sha1_smol::Sha1::new();
+    hasher.update(file);
+    let mut bytes = [0; 16];
+    bytes.copy_from_slice(&hasher.digest().bytes()[..16]);
+    uuid::Builder::from_sha1_bytes(bytes).into_uuid()
+}
+
 async fn autocomplete_play_sound<'a>(
     ctx: Context<'_>,
     partial: &str,
@@ -465,6 +819,9 @@ pub mod error {
 
         #[snafu(display("File uploaded is not an audio file."))]
         NotAudioFile,
+
+        #[snafu(display("Ayaya can't find that sound."))]
+        SoundNotFound,
     }
 
     impl ErrorName for SoundboardError {
@@ -473,6 +830,7 @@ pub mod error {
                 SoundboardError::NoticeTimeout => "notice_timeout",
                 SoundboardError::PolicyDeclined => "policy_declined",
                 SoundboardError::NotAudioFile => "not_audio_file",
+                SoundboardError::SoundNotFound => "sound_not_found",
             };
             format!("soundboard::{str}")
         }
@@ -488,6 +846,7 @@ pub mod error {
                     "Rerun the command with the public argument set to false."
                 }
                 SoundboardError::NotAudioFile => "Upload a real audio file instead.",
+                SoundboardError::SoundNotFound => "Pick a sound from the autocomplete.",
             }
         }
 
@@ -4,7 +4,10 @@ use snafu::Snafu;
 use crate::{
     error::{ErrorName, UserFriendlyError},
     utils::{ChannelInfo, GuildInfo},
-    voice::commands::soundboard::error::SoundboardError,
+    voice::{
+        commands::soundboard::error::SoundboardError, native_soundboard::NativeSoundboardError,
+        sound_pack::SoundPackError,
+    },
 };
 
 #[derive(Debug, Snafu)]
@@ -246,6 +249,12 @@ pub enum MusicCommandError {
     // #[diagnostic(transparent)]
     SoundboardError { source: SoundboardError },
 
+    #[snafu(transparent)]
+    SoundPackError { source: SoundPackError },
+
+    #[snafu(transparent)]
+    NativeSoundboardError { source: NativeSoundboardError },
+
     #[snafu(display("Failed to add queue event: {error}"))]
     FailedAddEvent {
         error: songbird::error::ControlError,
@@ -285,6 +294,8 @@ impl ErrorName for MusicCommandError {
             MusicCommandError::FailedTrackLoop { .. } => "failed_track_loop",
             MusicCommandError::QueueMoveNoPos1 { .. } => "queue_move_no_pos1",
             MusicCommandError::SoundboardError { source } => &ErrorName::name(source),
+            MusicCommandError::SoundPackError { source } => &ErrorName::name(source),
+            MusicCommandError::NativeSoundboardError { source } => &ErrorName::name(source),
             MusicCommandError::FailedAddEvent { .. } => "failed_add_event",
         };
         format!("music::{name}")
@@ -330,6 +341,8 @@ impl UserFriendlyError for MusicCommandError {
                 "To move to the next song position, use position 2. Or leave the target empty."
             }
             Self::SoundboardError { source } => source.help_text(),
+            Self::SoundPackError { source } => source.help_text(),
+            Self::NativeSoundboardError { source } => source.help_text(),
             _ => DEFAULT,
         }
     }
@@ -341,6 +354,8 @@ impl UserFriendlyError for MusicCommandError {
                 crate::error::ErrorCategory::UserMistake
             }
             MusicCommandError::QueueMoveNoPos1 { .. } => crate::error::ErrorCategory::UserMistake,
+            MusicCommandError::SoundPackError { source } => source.category(),
+            MusicCommandError::NativeSoundboardError { source } => source.category(),
             _ => crate::error::ErrorCategory::BotIssue,
         }
     }
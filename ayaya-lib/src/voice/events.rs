@@ -3,17 +3,30 @@ use std::sync::{
     atomic::{AtomicBool, AtomicUsize, Ordering},
 };
 
+use ayaya_db::data::idle::IdlePolicy;
 use poise::serenity_prelude::{self as serenity, UserId};
 use serenity::{
     Context as SerenityContext, all::GenericChannelId, async_trait, http::Http, model::id::GuildId,
 };
 use songbird::{
-    Event, EventContext, EventHandler as VoiceEventHandler, Songbird, tracks::PlayMode,
+    Event, EventContext, EventHandler as VoiceEventHandler, Songbird,
+    events::context_data::DisconnectReason, tracks::PlayMode,
 };
 use tracing::{error, info};
 
-use super::utils::{EmbedOperation, YoutubeMetadata, metadata_to_embed};
-use crate::utils::check_msg;
+use super::{
+    always_on::spawn_rejoin,
+    bot_state::forget_call_state,
+    utils::{EmbedOperation, YoutubeMetadata, metadata_to_embed},
+};
+use crate::{
+    Data,
+    api::{
+        events::{DashboardEventKind, EventBus},
+        player::TrackResponse,
+    },
+    utils::check_msg,
+};
 
 pub struct _SongFader {
     pub chan_id: GenericChannelId,
@@ -41,8 +54,10 @@ impl VoiceEventHandler for _SongFader {
 }
 
 /// Bot inactive counter. Will start counting when song ends, is stopped or paused.
-/// The check is ran every 60 seconds, so the 5 minutes actually has a margin
-/// of 1 min. Also starts counting when the bot is alone in the voice channel
+/// The check is ran every [`IdlePolicy::check_interval`], so the timeout actually has a margin
+/// of one interval. Also starts counting when the bot is alone in the voice channel.
+///
+/// Whether being alone or paused counts as inactive is decided by the guild's [`IdlePolicy`].
 pub struct BotInactiveCounter {
     pub channel_id: GenericChannelId,
     pub guild_id: GuildId,
@@ -51,12 +66,16 @@ pub struct BotInactiveCounter {
     pub manager: Arc<Songbird>,
     pub counter: Arc<AtomicUsize>,
     pub linger: Arc<AtomicBool>,
+    /// Set in 24/7 mode, where the bot never leaves for inactivity
+    pub always_on: Arc<AtomicBool>,
+    pub policy: IdlePolicy,
 }
 
 #[derive(Debug)]
 enum Status {
     Alone,
     PlaybackFinished,
+    Paused,
     Playback,
     Inactive,
     Linger,
@@ -71,6 +90,7 @@ impl std::fmt::Display for Status {
             match self {
                 Status::Alone => "Bot is alone",
                 Status::PlaybackFinished => "Playback is finished",
+                Status::Paused => "Playback is paused",
                 Status::Playback => "Bot is playing music",
                 Status::Inactive => "Bot is inactive",
                 Status::Linger => "Linger is active.",
@@ -93,16 +113,17 @@ impl BotInactiveCounter {
                     None => true,
                 }
             };
+            let alone_is_inactive = alone_in_channel && self.policy.alone_is_inactive;
             let linger = self.linger.load(Ordering::Relaxed);
 
             // skip queue checks if linger is on
             if linger {
-                (alone_in_channel, Some(Status::Linger))
+                (alone_is_inactive, Some(Status::Linger))
             } else {
                 tracing::info!("linger is off");
 
                 // first check if we are alone
-                if alone_in_channel {
+                if alone_is_inactive {
                     return (true, Some(Status::Alone));
                 }
 
@@ -118,6 +139,9 @@ impl BotInactiveCounter {
 
                 // if linger is not on, we leave when the bot stops playing, or is inactive
                 match play_mode {
+                    Some(PlayMode::Pause) if !self.policy.paused_is_inactive => {
+                        (false, Some(Status::Paused))
+                    }
                     Some(PlayMode::Pause | PlayMode::Stop | PlayMode::End) => {
                         (true, Some(Status::PlaybackFinished))
                     }
@@ -138,6 +162,12 @@ impl BotInactiveCounter {
 #[async_trait]
 impl VoiceEventHandler for BotInactiveCounter {
     async fn act(&self, _ctx: &EventContext<'_>) -> Option<Event> {
+        if self.always_on.load(Ordering::Relaxed) {
+            self.counter.store(0, Ordering::Relaxed);
+            return None;
+        }
+
+        let max_checks = self.policy.max_checks();
         let check_inactive = self.check_inactive().await;
         match check_inactive {
             (true, status) => {
@@ -145,7 +175,7 @@ impl VoiceEventHandler for BotInactiveCounter {
 
                 let counter_before = self.counter.fetch_add(1, Ordering::Relaxed);
                 info!(
-                    "Counter for channel {} in guild {} is {}/5.{status}.",
+                    "Counter for channel {} in guild {} is {}/{max_checks}.{status}.",
                     self.channel_id,
                     self.guild_id,
                     counter_before + 1
@@ -156,7 +186,7 @@ impl VoiceEventHandler for BotInactiveCounter {
 
                 self.counter.store(0, Ordering::Relaxed);
                 info!(
-                    "Counter for channel {} in guild {} is reset to {}/5.{status}",
+                    "Counter for channel {} in guild {} is reset to {}/{max_checks}.{status}",
                     self.channel_id,
                     self.guild_id,
                     self.counter.load(Ordering::Relaxed)
@@ -165,10 +195,12 @@ impl VoiceEventHandler for BotInactiveCounter {
         }
 
         let counter = self.counter.load(Ordering::Relaxed);
-        if counter >= 5 {
+        if counter >= max_checks {
             // Leave the voice channel
             let manager = &self.manager;
 
+            let data: Arc<Data> = self.ctx.data();
+            data.voice_flags.request_leave(self.guild_id);
             if let Err(e) = manager.remove(self.guild_id).await {
                 check_msg(
                     self.channel_id
@@ -180,12 +212,12 @@ impl VoiceEventHandler for BotInactiveCounter {
 
             check_msg(
                 self.channel_id
-                    .say(&self.ctx.http, "Left voice channel after 5 minutes of inactivity. Ayaya got bored without you, you know")
+                    .say(&self.ctx.http, self.policy.render_leave_message())
                     .await,
             );
             info!(
-                "Left voice channel {} in guild {} for 5 minutes of inactivity",
-                self.channel_id, self.guild_id
+                "Left voice channel {} in guild {} for {:?} of inactivity",
+                self.channel_id, self.guild_id, self.policy.timeout
             );
             return None;
         }
@@ -227,37 +259,77 @@ impl VoiceEventHandler for TrackPlayNotifier {
     }
 }
 
-// pub struct VoiceLeaveCleanup {
-//     pub channel_id: ChannelId,
-//     pub guild_id: GuildId,
-//     pub ctx: SerenityContext,
-//     pub manager: Arc<Songbird>,
-//     pub counter: Arc<AtomicUsize>,
-//     pub linger_map: Arc<Mutex<HashMap<GuildId, Arc<AtomicBool>>>>,
-//     pub bot_user_id: UserId,
-// }
+/// Send track starts, or track ends, of a call to the dashboard event streams
+pub struct TrackEventPublisher {
+    pub guild_id: GuildId,
+    pub events: EventBus,
+    /// Whether this handles the end of tracks instead of their start
+    pub end: bool,
+}
+
+#[async_trait]
+impl VoiceEventHandler for TrackEventPublisher {
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+        if let EventContext::Track(tracks) = ctx {
+            for (_, handle) in tracks.iter() {
+                let track = TrackResponse::new(1, &handle.data::<YoutubeMetadata>());
+                let kind = if self.end {
+                    DashboardEventKind::TrackEnd { track }
+                } else {
+                    DashboardEventKind::TrackStart { track }
+                };
+                self.events.publish(self.guild_id, kind);
+            }
+        }
+        None
+    }
+}
+
+/// Cleanup after the voice driver disconnects. In 24/7 guilds, rejoin the channel instead unless
+/// the disconnect was requested.
+pub struct VoiceLeaveCleanup {
+    pub guild_id: GuildId,
+    pub ctx: SerenityContext,
+}
+
+#[async_trait]
+impl VoiceEventHandler for VoiceLeaveCleanup {
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+        let EventContext::DriverDisconnect(disconnect) = ctx else {
+            return None;
+        };
+        // no reason means the disconnect was requested, eg: by the leave command
+        let requested = matches!(disconnect.reason, None | Some(DisconnectReason::Requested));
+        info!(
+            "Voice driver disconnected in guild {} ({:?}, reason {:?})",
+            self.guild_id, disconnect.kind, disconnect.reason
+        );
+
+        let data: Arc<Data> = self.ctx.data();
+        if !requested {
+            match data
+                .data_manager
+                .always_on()
+                .get_always_on(self.guild_id.get())
+                .await
+            {
+                Ok(Some(_)) => {
+                    info!("Guild {} is in 24/7 mode, rejoining", self.guild_id);
+                    spawn_rejoin(&self.ctx, self.guild_id);
+                    return None;
+                }
+                Ok(None) => {}
+                Err(e) => error!("Failed to get 24/7 channel of guild {}: {e}", self.guild_id),
+            }
 
-// #[async_trait]
-// impl VoiceEventHandler for VoiceLeaveCleanup {
-//     async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
-//         if let EventContext::(ev) = ctx {
-//             if let Some(dis) = ev.reason {
-//                 match dis {
-//                     songbird::events::context_data::DisconnectReason::AttemptDiscarded => todo!(),
-//                     songbird::events::context_data::DisconnectReason::Internal => todo!(),
-//                     songbird::events::context_data::DisconnectReason::Io => todo!(),
-//                     songbird::events::context_data::DisconnectReason::ProtocolViolation => todo!(),
-//                     songbird::events::context_data::DisconnectReason::TimedOut => todo!(),
-//                     songbird::events::context_data::DisconnectReason::Requested => todo!(),
-//                     songbird::events::context_data::DisconnectReason::WsClosed(close_code) => {
-//                         todo!()
-//                     }
-//                     _ => todo!(),
-//                 }
-//             }
-//             tracing::info!("removed from voice channel");
-//             self.manager.remove(self.guild_id).await;
-//         }
-//         None
-//     }
-// }
+            data.voice_flags.request_leave(self.guild_id);
+            if let Err(e) = data.songbird.remove(self.guild_id).await {
+                error!("Failed to remove call in guild {}: {e:?}", self.guild_id);
+            }
+        }
+
+        tracing::info!("removed from voice channel");
+        forget_call_state(&data, self.guild_id).await;
+        None
+    }
+}
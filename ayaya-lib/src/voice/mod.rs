@@ -1,6 +1,10 @@
+pub mod always_on;
+pub mod bot_state;
 pub mod commands;
 pub mod error;
 pub mod events;
+pub mod native_soundboard;
+pub mod sound_pack;
 pub mod utils;
 
 pub use commands::voice_commands;
@@ -0,0 +1,445 @@
+//! Sync between the bot soundboard and Discord's native guild soundboard.
+//!
+//! The HTTP calls live behind [`GuildSoundboardHttp`] so the sync logic can be exercised without
+//! talking to Discord.
+use std::path::Path;
+
+use async_trait::async_trait;
+use base64::Engine as _;
+use poise::serenity_prelude as serenity;
+use serde::{Deserialize, Serialize};
+use snafu::{ResultExt, Snafu};
+
+use crate::error::{ErrorName, UserFriendlyError};
+
+const API_BASE: &str = "https://discord.com/api/v10";
+const CDN_BASE: &str = "https://cdn.discordapp.com";
+/// Largest file Discord accepts for a guild soundboard sound
+pub const MAX_NATIVE_SOUND_SIZE: usize = 512 * 1024;
+const MIN_NAME_LEN: usize = 2;
+const MAX_NAME_LEN: usize = 32;
+
+/// A sound of a guild's native soundboard, as returned by Discord
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NativeSound {
+    pub sound_id: String,
+    pub name: String,
+    #[serde(default)]
+    pub volume: Option<f64>,
+    #[serde(default)]
+    pub emoji_name: Option<String>,
+    #[serde(default = "default_available")]
+    pub available: bool,
+}
+
+fn default_available() -> bool {
+    true
+}
+
+#[derive(Debug, Deserialize)]
+struct NativeSoundList {
+    items: Vec<NativeSound>,
+}
+
+/// Body of a request creating a sound in a guild's native soundboard
+#[derive(Debug, Clone, Serialize)]
+pub struct CreateNativeSound {
+    pub name: String,
+    /// The audio as a `data:` URI
+    pub sound: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub emoji_name: Option<String>,
+}
+
+impl CreateNativeSound {
+    /// Validate the sound against Discord's limits and build the request body.
+    ///
+    /// Names longer than Discord allows are truncated rather than rejected, since bot sound names
+    /// are free form descriptions.
+    pub fn new(
+        name: &str,
+        data: &[u8],
+        emoji_name: Option<String>,
+    ) -> Result<Self, NativeSoundboardError> {
+        if data.len() > MAX_NATIVE_SOUND_SIZE {
+            return Err(NativeSoundboardError::TooLarge { size: data.len() });
+        }
+
+        let name = name.trim().chars().take(MAX_NAME_LEN).collect::<String>();
+        if name.chars().count() < MIN_NAME_LEN {
+            return Err(NativeSoundboardError::InvalidName { name });
+        }
+
+        let sound = format!(
+            "data:audio/mpeg;base64,{}",
+            base64::engine::general_purpose::STANDARD.encode(data)
+        );
+        Ok(Self {
+            name,
+            sound,
+            emoji_name,
+        })
+    }
+}
+
+/// The Discord endpoints used to sync soundboards
+#[async_trait]
+pub trait GuildSoundboardHttp: Send + Sync {
+    /// List the sounds of a guild's native soundboard
+    async fn list_guild_sounds(
+        &self,
+        guild_id: serenity::GuildId,
+    ) -> Result<Vec<NativeSound>, NativeSoundboardError>;
+
+    /// Download the audio of a native sound
+    async fn download_sound(&self, sound_id: &str) -> Result<Vec<u8>, NativeSoundboardError>;
+
+    /// Add a sound to a guild's native soundboard
+    async fn create_guild_sound(
+        &self,
+        guild_id: serenity::GuildId,
+        sound: &CreateNativeSound,
+    ) -> Result<NativeSound, NativeSoundboardError>;
+}
+
+/// [`GuildSoundboardHttp`] backed by the Discord REST API
+pub struct DiscordSoundboardHttp {
+    client: reqwest::Client,
+    token: String,
+}
+
+impl DiscordSoundboardHttp {
+    pub fn new(client: reqwest::Client, token: String) -> Self {
+        Self { client, token }
+    }
+
+    async fn check(
+        response: reqwest::Response,
+    ) -> Result<reqwest::Response, NativeSoundboardError> {
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+        let message = response.text().await.unwrap_or_default();
+        Err(NativeSoundboardError::Api {
+            status: status.as_u16(),
+            message,
+        })
+    }
+}
+
+#[async_trait]
+impl GuildSoundboardHttp for DiscordSoundboardHttp {
+    async fn list_guild_sounds(
+        &self,
+        guild_id: serenity::GuildId,
+    ) -> Result<Vec<NativeSound>, NativeSoundboardError> {
+        let response = self
+            .client
+            .get(format!("{API_BASE}/guilds/{guild_id}/soundboard-sounds"))
+            .header(
+                reqwest::header::AUTHORIZATION,
+                format!("Bot {}", self.token),
+            )
+            .send()
+            .await
+            .context(RequestSnafu)?;
+        let list: NativeSoundList = Self::check(response)
+            .await?
+            .json()
+            .await
+            .context(RequestSnafu)?;
+        Ok(list.items)
+    }
+
+    async fn download_sound(&self, sound_id: &str) -> Result<Vec<u8>, NativeSoundboardError> {
+        let response = self
+            .client
+            .get(format!("{CDN_BASE}/soundboard-sounds/{sound_id}"))
+            .send()
+            .await
+            .context(RequestSnafu)?;
+        let bytes = Self::check(response)
+            .await?
+            .bytes()
+            .await
+            .context(RequestSnafu)?;
+        Ok(bytes.to_vec())
+    }
+
+    async fn create_guild_sound(
+        &self,
+        guild_id: serenity::GuildId,
+        sound: &CreateNativeSound,
+    ) -> Result<NativeSound, NativeSoundboardError> {
+        let response = self
+            .client
+            .post(format!("{API_BASE}/guilds/{guild_id}/soundboard-sounds"))
+            .header(
+                reqwest::header::AUTHORIZATION,
+                format!("Bot {}", self.token),
+            )
+            .json(sound)
+            .send()
+            .await
+            .context(RequestSnafu)?;
+        Self::check(response)
+            .await?
+            .json()
+            .await
+            .context(RequestSnafu)
+    }
+}
+
+/// A native sound downloaded for import
+pub struct DownloadedNativeSound {
+    pub sound: NativeSound,
+    pub data: Vec<u8>,
+}
+
+/// Download every available sound of a guild's native soundboard
+pub async fn download_guild_sounds(
+    http: &dyn GuildSoundboardHttp,
+    guild_id: serenity::GuildId,
+) -> Result<Vec<DownloadedNativeSound>, NativeSoundboardError> {
+    let sounds = http.list_guild_sounds(guild_id).await?;
+    let mut downloaded = Vec::with_capacity(sounds.len());
+    for sound in sounds.into_iter().filter(|sound| sound.available) {
+        let data = http.download_sound(&sound.sound_id).await?;
+        downloaded.push(DownloadedNativeSound { sound, data });
+    }
+    Ok(downloaded)
+}
+
+/// Push a bot sound stored in `sound_dir` to a guild's native soundboard
+pub async fn push_guild_sound(
+    http: &dyn GuildSoundboardHttp,
+    sound_dir: &Path,
+    guild_id: serenity::GuildId,
+    sound: &ayaya_db::entity::sounds::Model,
+    name: Option<String>,
+    emoji_name: Option<String>,
+) -> Result<NativeSound, NativeSoundboardError> {
+    let path = sound_dir.join(format!("{}.mp3", sound.sound_id));
+    let data = std::fs::read(&path).context(FilesystemSnafu { path })?;
+    let request = CreateNativeSound::new(
+        name.as_deref().unwrap_or(&sound.sound_name),
+        &data,
+        emoji_name,
+    )?;
+    http.create_guild_sound(guild_id, &request).await
+}
+
+/// Whether the data looks like an MP3 file, either with an ID3 tag or starting on a frame sync
+pub fn is_mp3(data: &[u8]) -> bool {
+    data.starts_with(b"ID3") || (data.len() > 1 && data[0] == 0xFF && data[1] & 0xE0 == 0xE0)
+}
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub))]
+pub enum NativeSoundboardError {
+    #[snafu(display("Error talking to Discord: {source}"))]
+    Request { source: reqwest::Error },
+
+    #[snafu(display("Discord rejected the soundboard request ({status}): {message}"))]
+    Api { status: u16, message: String },
+
+    #[snafu(display("Error reading sound file {}: {source}", path.display()))]
+    Filesystem {
+        source: std::io::Error,
+        path: std::path::PathBuf,
+    },
+
+    #[snafu(display(
+        "The sound is {size} bytes, over Discord's limit of {MAX_NATIVE_SOUND_SIZE} bytes."
+    ))]
+    TooLarge { size: usize },
+
+    #[snafu(display("The name \"{name}\" is too short for the guild soundboard."))]
+    InvalidName { name: String },
+}
+
+impl ErrorName for NativeSoundboardError {
+    fn name(&self) -> String {
+        let name = match self {
+            NativeSoundboardError::Request { .. } => "request",
+            NativeSoundboardError::Api { .. } => "api",
+            NativeSoundboardError::Filesystem { .. } => "filesystem",
+            NativeSoundboardError::TooLarge { .. } => "too_large",
+            NativeSoundboardError::InvalidName { .. } => "invalid_name",
+        };
+        format!("native_soundboard::{name}")
+    }
+}
+
+impl UserFriendlyError for NativeSoundboardError {
+    fn help_text(&self) -> &str {
+        match self {
+            NativeSoundboardError::Api { .. } => {
+                "Discord refused. Check that the soundboard has free slots and the sound is short enough."
+            }
+            NativeSoundboardError::TooLarge { .. } => "Pick a shorter sound.",
+            NativeSoundboardError::InvalidName { .. } => {
+                "Give the sound a name of at least 2 characters."
+            }
+            _ => "Contact @solemnattic for assistance",
+        }
+    }
+
+    fn category(&self) -> crate::error::ErrorCategory {
+        match self {
+            NativeSoundboardError::Request { .. } | NativeSoundboardError::Api { .. } => {
+                crate::error::ErrorCategory::ExternalServiceIssue
+            }
+            NativeSoundboardError::Filesystem { .. } => crate::error::ErrorCategory::BotIssue,
+            NativeSoundboardError::TooLarge { .. } | NativeSoundboardError::InvalidName { .. } => {
+                crate::error::ErrorCategory::UserMistake
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    const GUILD_ID: serenity::GuildId = serenity::GuildId::new(1);
+
+    #[derive(Default)]
+    struct MockSoundboardHttp {
+        sounds: Vec<NativeSound>,
+        created: Mutex<Vec<CreateNativeSound>>,
+    }
+
+    #[async_trait]
+    impl GuildSoundboardHttp for MockSoundboardHttp {
+        async fn list_guild_sounds(
+            &self,
+            _guild_id: serenity::GuildId,
+        ) -> Result<Vec<NativeSound>, NativeSoundboardError> {
+            Ok(self.sounds.clone())
+        }
+
+        async fn download_sound(&self, sound_id: &str) -> Result<Vec<u8>, NativeSoundboardError> {
+            Ok(format!("ID3{sound_id}").into_bytes())
+        }
+
+        async fn create_guild_sound(
+            &self,
+            _guild_id: serenity::GuildId,
+            sound: &CreateNativeSound,
+        ) -> Result<NativeSound, NativeSoundboardError> {
+            self.created.lock().unwrap().push(sound.clone());
+            Ok(NativeSound {
+                sound_id: "100".to_string(),
+                name: sound.name.clone(),
+                volume: None,
+                emoji_name: sound.emoji_name.clone(),
+                available: true,
+            })
+        }
+    }
+
+    fn native_sound(sound_id: &str, available: bool) -> NativeSound {
+        NativeSound {
+            sound_id: sound_id.to_string(),
+            name: format!("sound {sound_id}"),
+            volume: Some(1.0),
+            emoji_name: None,
+            available,
+        }
+    }
+
+    fn bot_sound(sound_id: uuid::Uuid, name: &str) -> ayaya_db::entity::sounds::Model {
+        ayaya_db::entity::sounds::Model {
+            sound_id,
+            user_id: 1,
+            uploaded_server_id: 1,
+            sound_name: name.to_string(),
+            public: true,
+            uploaded_at: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn download_skips_unavailable() {
+        let http = MockSoundboardHttp {
+            sounds: vec![native_sound("1", true), native_sound("2", false)],
+            ..Default::default()
+        };
+
+        let downloaded = download_guild_sounds(&http, GUILD_ID).await.unwrap();
+
+        assert_eq!(downloaded.len(), 1);
+        assert_eq!(downloaded[0].sound.sound_id, "1");
+        assert!(is_mp3(&downloaded[0].data));
+    }
+
+    #[tokio::test]
+    async fn push_sends_data_uri() {
+        let http = MockSoundboardHttp::default();
+        let dir = tempfile::tempdir().unwrap();
+        let sound_id = uuid::Uuid::new_v4();
+        std::fs::write(dir.path().join(format!("{sound_id}.mp3")), b"ID3data").unwrap();
+
+        let pushed = push_guild_sound(
+            &http,
+            dir.path(),
+            GUILD_ID,
+            &bot_sound(
+                sound_id,
+                "a very long sound name that discord will not accept",
+            ),
+            None,
+            Some("🔊".to_string()),
+        )
+        .await
+        .unwrap();
+
+        let created = http.created.lock().unwrap();
+        assert_eq!(created.len(), 1);
+        assert_eq!(created[0].name.chars().count(), MAX_NAME_LEN);
+        assert_eq!(pushed.name, created[0].name);
+        assert!(created[0].sound.starts_with("data:audio/mpeg;base64,"));
+    }
+
+    #[tokio::test]
+    async fn push_rejects_large_sound() {
+        let http = MockSoundboardHttp::default();
+        let dir = tempfile::tempdir().unwrap();
+        let sound_id = uuid::Uuid::new_v4();
+        std::fs::write(
+            dir.path().join(format!("{sound_id}.mp3")),
+            vec![0; MAX_NATIVE_SOUND_SIZE + 1],
+        )
+        .unwrap();
+
+        let result = push_guild_sound(
+            &http,
+            dir.path(),
+            GUILD_ID,
+            &bot_sound(sound_id, "big"),
+            None,
+            None,
+        )
+        .await;
+
+        assert!(matches!(
+            result,
+            Err(NativeSoundboardError::TooLarge { .. })
+        ));
+        assert!(http.created.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_short_name() {
+        let result = CreateNativeSound::new(" a ", b"ID3", None);
+        assert!(matches!(
+            result,
+            Err(NativeSoundboardError::InvalidName { .. })
+        ));
+    }
+}
@@ -0,0 +1,435 @@
+//! Soundboard packs: a zip archive containing the mp3 files of a set of sounds together with a
+//! manifest describing them. Used to back up sounds or move them between bot instances.
+use std::{
+    collections::HashSet,
+    io::{Cursor, Read, Write},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+use snafu::{ResultExt, Snafu};
+
+use crate::error::{ErrorName, UserFriendlyError};
+
+/// Name of the manifest file inside the archive
+const MANIFEST_NAME: &str = "manifest.json";
+/// Directory inside the archive holding the sound files
+const SOUNDS_DIR: &str = "sounds";
+/// Bumped whenever the manifest format changes in an incompatible way
+const PACK_VERSION: u32 = 1;
+/// Most sounds a pack can hold
+const MAX_PACK_SOUNDS: usize = 500;
+/// Largest size of the manifest once decompressed
+const MAX_MANIFEST_SIZE: u64 = 1024 * 1024;
+/// Largest size of a sound file once decompressed
+const MAX_SOUND_SIZE: u64 = 10 * 1024 * 1024;
+/// Largest size of all the sound files of a pack once decompressed
+const MAX_PACK_TOTAL_SIZE: u64 = 200 * 1024 * 1024;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SoundPackManifest {
+    pub version: u32,
+    pub sounds: Vec<SoundPackEntry>,
+}
+
+/// Metadata of a single sound, mirroring the `sounds` table
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SoundPackEntry {
+    pub sound_id: uuid::Uuid,
+    pub sound_name: String,
+    pub public: bool,
+    pub user_id: i64,
+    pub uploaded_server_id: i64,
+}
+
+impl From<&ayaya_db::entity::sounds::Model> for SoundPackEntry {
+    fn from(value: &ayaya_db::entity::sounds::Model) -> Self {
+        Self {
+            sound_id: value.sound_id,
+            sound_name: value.sound_name.clone(),
+            public: value.public,
+            user_id: value.user_id,
+            uploaded_server_id: value.uploaded_server_id,
+        }
+    }
+}
+
+/// Builds a pack from the given sounds. Sounds without a file in `sound_dir` are skipped and
+/// returned separately so the caller can report them.
+pub fn build_sound_pack(
+    sound_dir: &Path,
+    sounds: &[ayaya_db::entity::sounds::Model],
+) -> Result<(Vec<u8>, Vec<SoundPackEntry>), SoundPackError> {
+    let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated);
+
+    let mut included = Vec::new();
+    let mut missing = Vec::new();
+    for sound in sounds {
+        let entry = SoundPackEntry::from(sound);
+        let path = sound_dir.join(format!("{}.mp3", sound.sound_id));
+        let data = match std::fs::read(&path) {
+            Ok(data) => data,
+            Err(error) => {
+                tracing::warn!("skipping sound file {}: {error}", path.display());
+                missing.push(entry);
+                continue;
+            }
+        };
+
+        writer
+            .start_file(sound_file_name(&entry), options)
+            .context(ZipSnafu)?;
+        writer.write_all(&data).context(IoSnafu)?;
+        included.push(entry);
+    }
+
+    let manifest = SoundPackManifest {
+        version: PACK_VERSION,
+        sounds: included,
+    };
+    writer
+        .start_file(MANIFEST_NAME, options)
+        .context(ZipSnafu)?;
+    let manifest = serde_json::to_vec_pretty(&manifest).context(ManifestSnafu)?;
+    writer.write_all(&manifest).context(IoSnafu)?;
+
+    let cursor = writer.finish().context(ZipSnafu)?;
+    Ok((cursor.into_inner(), missing))
+}
+
+/// Opens a pack and checks its manifest. The archive is untrusted, so its entries and their
+/// decompressed sizes are limited, and a sound can only be listed once. The sounds are then read
+/// one at a time from the returned reader.
+pub fn read_sound_pack(bytes: &[u8]) -> Result<SoundPackReader<'_>, SoundPackError> {
+    // the zip crate already refuses archives holding the same file name twice
+    let mut archive = zip::ZipArchive::new(Cursor::new(bytes)).context(ZipSnafu)?;
+    // the sounds and the manifest
+    if archive.len() > MAX_PACK_SOUNDS + 1 {
+        return Err(SoundPackError::TooManySounds {
+            count: archive.len().saturating_sub(1),
+        });
+    }
+
+    let manifest: SoundPackManifest = {
+        let file = archive
+            .by_name(MANIFEST_NAME)
+            .map_err(|_| SoundPackError::MissingManifest)?;
+        let buf = read_entry(file, MAX_MANIFEST_SIZE)?;
+        serde_json::from_slice(&buf).context(ManifestSnafu)?
+    };
+
+    if manifest.version > PACK_VERSION {
+        return Err(SoundPackError::UnsupportedVersion {
+            version: manifest.version,
+        });
+    }
+    if manifest.sounds.len() > MAX_PACK_SOUNDS {
+        return Err(SoundPackError::TooManySounds {
+            count: manifest.sounds.len(),
+        });
+    }
+
+    let mut sound_ids = HashSet::new();
+    let mut total_size: u64 = 0;
+    for entry in &manifest.sounds {
+        if !sound_ids.insert(entry.sound_id) {
+            return Err(SoundPackError::DuplicateSound {
+                sound_id: entry.sound_id,
+            });
+        }
+        let name = sound_file_name(entry);
+        let file = archive
+            .by_name(&name)
+            .map_err(|_| SoundPackError::MissingSoundFile { name })?;
+        total_size = total_size.saturating_add(file.size());
+    }
+    if total_size > MAX_PACK_TOTAL_SIZE {
+        return Err(SoundPackError::PackTooLarge);
+    }
+
+    Ok(SoundPackReader {
+        archive,
+        entries: manifest.sounds.into_iter(),
+        read_size: 0,
+    })
+}
+
+/// The sounds of an opened pack, each read when iterated so only one is in memory at a time
+pub struct SoundPackReader<'a> {
+    archive: zip::ZipArchive<Cursor<&'a [u8]>>,
+    entries: std::vec::IntoIter<SoundPackEntry>,
+    /// Decompressed bytes of the sounds read so far
+    read_size: u64,
+}
+
+impl Iterator for SoundPackReader<'_> {
+    type Item = Result<(SoundPackEntry, Vec<u8>), SoundPackError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = self.entries.next()?;
+        Some(self.read_sound(&entry).map(|data| (entry, data)))
+    }
+}
+
+impl SoundPackReader<'_> {
+    fn read_sound(&mut self, entry: &SoundPackEntry) -> Result<Vec<u8>, SoundPackError> {
+        let name = sound_file_name(entry);
+        let file = self
+            .archive
+            .by_name(&name)
+            .map_err(|_| SoundPackError::MissingSoundFile { name })?;
+        let data = read_entry(file, MAX_SOUND_SIZE)?;
+        // the sizes were checked when opening, but the archive may lie about them
+        self.read_size = self.read_size.saturating_add(data.len() as u64);
+        if self.read_size > MAX_PACK_TOTAL_SIZE {
+            return Err(SoundPackError::PackTooLarge);
+        }
+        Ok(data)
+    }
+}
+
+fn sound_file_name(entry: &SoundPackEntry) -> String {
+    format!("{SOUNDS_DIR}/{}.mp3", entry.sound_id)
+}
+
+/// Read an entry of the archive, failing when it decompresses to more than `limit` bytes. The
+/// size in the archive is checked first, and the read is capped in case it lies.
+fn read_entry(mut file: zip::read::ZipFile<'_>, limit: u64) -> Result<Vec<u8>, SoundPackError> {
+    let name = file.name().to_string();
+    if file.size() > limit {
+        return Err(SoundPackError::EntryTooLarge { name });
+    }
+    let mut data = Vec::with_capacity(file.size() as usize);
+    (&mut file)
+        .take(limit + 1)
+        .read_to_end(&mut data)
+        .context(IoSnafu)?;
+    if data.len() as u64 > limit {
+        return Err(SoundPackError::EntryTooLarge { name });
+    }
+    Ok(data)
+}
+
+#[derive(Debug, Snafu)]
+#[snafu(visibility(pub))]
+pub enum SoundPackError {
+    #[snafu(display("Error reading or writing the pack archive: {source}"))]
+    Zip { source: zip::result::ZipError },
+
+    #[snafu(display("Error reading or writing pack data: {source}"))]
+    Io { source: std::io::Error },
+
+    #[snafu(display("Error (de)serializing the pack manifest: {source}"))]
+    Manifest { source: serde_json::Error },
+
+    #[snafu(display("The pack has no {MANIFEST_NAME}."))]
+    MissingManifest,
+
+    #[snafu(display("The pack is missing the sound file {name}."))]
+    MissingSoundFile { name: String },
+
+    #[snafu(display("The pack version {version} is newer than what Ayaya understands."))]
+    UnsupportedVersion { version: u32 },
+
+    #[snafu(display(
+        "The pack has {count} sounds, at most {MAX_PACK_SOUNDS} can be imported at once."
+    ))]
+    TooManySounds { count: usize },
+
+    #[snafu(display("The file {name} in the pack is too large."))]
+    EntryTooLarge { name: String },
+
+    #[snafu(display("The pack lists the sound {sound_id} more than once."))]
+    DuplicateSound { sound_id: uuid::Uuid },
+
+    #[snafu(display(
+        "The sounds of the pack are larger than {} MB in total.",
+        MAX_PACK_TOTAL_SIZE / 1024 / 1024
+    ))]
+    PackTooLarge,
+}
+
+impl ErrorName for SoundPackError {
+    fn name(&self) -> String {
+        let name = match self {
+            SoundPackError::Zip { .. } => "zip",
+            SoundPackError::Io { .. } => "io",
+            SoundPackError::Manifest { .. } => "manifest",
+            SoundPackError::MissingManifest => "missing_manifest",
+            SoundPackError::MissingSoundFile { .. } => "missing_sound_file",
+            SoundPackError::UnsupportedVersion { .. } => "unsupported_version",
+            SoundPackError::TooManySounds { .. } => "too_many_sounds",
+            SoundPackError::EntryTooLarge { .. } => "entry_too_large",
+            SoundPackError::DuplicateSound { .. } => "duplicate_sound",
+            SoundPackError::PackTooLarge => "pack_too_large",
+        };
+        format!("sound_pack::{name}")
+    }
+}
+
+impl UserFriendlyError for SoundPackError {
+    fn help_text(&self) -> &str {
+        match self {
+            SoundPackError::Zip { .. }
+            | SoundPackError::MissingManifest
+            | SoundPackError::MissingSoundFile { .. }
+            | SoundPackError::EntryTooLarge { .. }
+            | SoundPackError::DuplicateSound { .. } => {
+                "Upload a pack made by the export_sounds command, without modifications."
+            }
+            SoundPackError::UnsupportedVersion { .. } => {
+                "This pack was made by a newer Ayaya. Ask her owner to update."
+            }
+            SoundPackError::TooManySounds { .. } | SoundPackError::PackTooLarge => {
+                "Split the pack into smaller ones."
+            }
+            _ => "Contact @solemnattic for assistance",
+        }
+    }
+
+    fn category(&self) -> crate::error::ErrorCategory {
+        match self {
+            SoundPackError::Io { .. } => crate::error::ErrorCategory::BotIssue,
+            _ => crate::error::ErrorCategory::UserMistake,
+        }
+    }
+}
+
+#[cfg(test)]
+#[expect(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    fn entry(sound_id: uuid::Uuid) -> SoundPackEntry {
+        SoundPackEntry {
+            sound_id,
+            sound_name: format!("sound {sound_id}"),
+            public: false,
+            user_id: 1,
+            uploaded_server_id: 1,
+        }
+    }
+
+    /// An archive of the manifest of the given sounds and the given files
+    fn pack_of(version: u32, sounds: Vec<SoundPackEntry>, files: &[(String, Vec<u8>)]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Deflated);
+        let manifest = SoundPackManifest { version, sounds };
+        writer.start_file(MANIFEST_NAME, options).unwrap();
+        writer
+            .write_all(&serde_json::to_vec(&manifest).unwrap())
+            .unwrap();
+        for (name, data) in files {
+            writer.start_file(name.as_str(), options).unwrap();
+            writer.write_all(data).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn packs_read_back_what_was_built() {
+        let sound_dir = tempfile::tempdir().unwrap();
+        let sounds = [uuid::Uuid::new_v4(), uuid::Uuid::new_v4()].map(|sound_id| {
+            ayaya_db::entity::sounds::Model {
+                sound_id,
+                user_id: 1,
+                uploaded_server_id: 2,
+                sound_name: format!("sound {sound_id}"),
+                public: true,
+                uploaded_at: None,
+            }
+        });
+        std::fs::write(
+            sound_dir.path().join(format!("{}.mp3", sounds[0].sound_id)),
+            b"ID3 first",
+        )
+        .unwrap();
+
+        let (pack, missing) = build_sound_pack(sound_dir.path(), &sounds).unwrap();
+        assert_eq!(missing.len(), 1);
+        assert_eq!(missing[0].sound_id, sounds[1].sound_id);
+
+        let read = read_sound_pack(&pack)
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(read.len(), 1);
+        let (entry, data) = &read[0];
+        assert_eq!(entry.sound_id, sounds[0].sound_id);
+        assert_eq!(entry.sound_name, sounds[0].sound_name);
+        assert!(entry.public);
+        assert_eq!(data, b"ID3 first");
+    }
+
+    #[test]
+    fn packs_need_a_manifest() {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .start_file("sounds/a.mp3", zip::write::SimpleFileOptions::default())
+            .unwrap();
+        let pack = writer.finish().unwrap().into_inner();
+
+        assert!(matches!(
+            read_sound_pack(&pack),
+            Err(SoundPackError::MissingManifest)
+        ));
+    }
+
+    #[test]
+    fn newer_packs_are_refused() {
+        let pack = pack_of(PACK_VERSION + 1, Vec::new(), &[]);
+
+        assert!(matches!(
+            read_sound_pack(&pack),
+            Err(SoundPackError::UnsupportedVersion { version }) if version == PACK_VERSION + 1
+        ));
+    }
+
+    #[test]
+    fn packs_hold_a_limited_number_of_sounds() {
+        let sounds = (0..=MAX_PACK_SOUNDS)
+            .map(|_| entry(uuid::Uuid::new_v4()))
+            .collect();
+        let pack = pack_of(PACK_VERSION, sounds, &[]);
+
+        assert!(matches!(
+            read_sound_pack(&pack),
+            Err(SoundPackError::TooManySounds { count }) if count == MAX_PACK_SOUNDS + 1
+        ));
+    }
+
+    #[test]
+    fn oversized_sounds_are_refused() {
+        let sound = entry(uuid::Uuid::new_v4());
+        let data = vec![0; MAX_SOUND_SIZE as usize + 1];
+        let pack = pack_of(
+            PACK_VERSION,
+            vec![sound.clone()],
+            &[(sound_file_name(&sound), data)],
+        );
+
+        let mut reader = read_sound_pack(&pack).unwrap();
+        assert!(matches!(
+            reader.next(),
+            Some(Err(SoundPackError::EntryTooLarge { .. }))
+        ));
+    }
+
+    #[test]
+    fn sounds_can_only_be_listed_once() {
+        let sound = entry(uuid::Uuid::new_v4());
+        let pack = pack_of(
+            PACK_VERSION,
+            vec![sound.clone(), sound.clone()],
+            &[(sound_file_name(&sound), b"ID3".to_vec())],
+        );
+
+        assert!(matches!(
+            read_sound_pack(&pack),
+            Err(SoundPackError::DuplicateSound { sound_id }) if sound_id == sound.sound_id
+        ));
+    }
+}
@@ -181,6 +181,10 @@ pub enum EmbedOperation {
     NewPlaylist,
     NewPlaylistNext,
     SoundPlayed,
+    SoundPackExported,
+    SoundPackImported,
+    NativeSoundsImported,
+    NativeSoundPushed,
 }
 
 impl std::fmt::Display for EmbedOperation {
@@ -201,6 +205,10 @@ impl std::fmt::Display for EmbedOperation {
             EmbedOperation::NewPlaylist => "Added New Playlist",
             EmbedOperation::NewPlaylistNext => "Added New Playlist - Next",
             EmbedOperation::SoundPlayed => "Sound Played",
+            EmbedOperation::SoundPackExported => "Sound Pack Exported",
+            EmbedOperation::SoundPackImported => "Sound Pack Imported",
+            EmbedOperation::NativeSoundsImported => "Guild Soundboard Imported",
+            EmbedOperation::NativeSoundPushed => "Added to Guild Soundboard",
         };
         write!(f, "{out}")
     }
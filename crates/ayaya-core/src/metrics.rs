@@ -20,6 +20,7 @@ pub trait MetricsSink: Send + Sync + 'static {
     async fn data_access(&self, name: &str, op: DataOperationType);
     async fn data_time(&self, name: &str, op: DataOperationType, time: f64);
     async fn cache_len(&self, name: &str, len: usize);
+    async fn cache_access(&self, name: &str, hit: bool);
 }
 
 #[derive(Debug, Default, Clone)]
@@ -32,4 +33,6 @@ impl MetricsSink for NoopMetrics {
     async fn data_time(&self, _name: &str, _op: DataOperationType, _time: f64) {}
 
     async fn cache_len(&self, _name: &str, _len: usize) {}
+
+    async fn cache_access(&self, _name: &str, _hit: bool) {}
 }
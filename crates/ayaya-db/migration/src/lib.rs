@@ -12,6 +12,17 @@ mod m20260207_060550_akend_pull_tracker;
 mod m20260220_020540_akend_pull_weapons;
 mod m20260221_152529_akend_numeric_seqid;
 mod m20260413_142658_voicechat_mon;
+mod m20261018_000001_guild_idle_settings;
+mod m20261018_000002_always_on_channels;
+mod m20261018_000003_voice_feed;
+mod m20261018_000004_voice_privacy;
+mod m20261018_000005_weekly_digest;
+mod m20261018_000006_command_call_log_index;
+mod m20261018_000007_permission_rules;
+mod m20261018_000008_guild_settings;
+mod m20261018_000009_audit_log;
+mod m20261018_000010_command_cooldowns;
+mod m20261018_000011_song_play_log;
 
 pub struct Migrator;
 
@@ -30,6 +41,17 @@ impl MigratorTrait for Migrator {
             Box::new(m20260220_020540_akend_pull_weapons::Migration),
             Box::new(m20260221_152529_akend_numeric_seqid::Migration),
             Box::new(m20260413_142658_voicechat_mon::Migration),
+            Box::new(m20261018_000001_guild_idle_settings::Migration),
+            Box::new(m20261018_000002_always_on_channels::Migration),
+            Box::new(m20261018_000003_voice_feed::Migration),
+            Box::new(m20261018_000004_voice_privacy::Migration),
+            Box::new(m20261018_000005_weekly_digest::Migration),
+            Box::new(m20261018_000006_command_call_log_index::Migration),
+            Box::new(m20261018_000007_permission_rules::Migration),
+            Box::new(m20261018_000008_guild_settings::Migration),
+            Box::new(m20261018_000009_audit_log::Migration),
+            Box::new(m20261018_000010_command_cooldowns::Migration),
+            Box::new(m20261018_000011_song_play_log::Migration),
         ]
     }
 }
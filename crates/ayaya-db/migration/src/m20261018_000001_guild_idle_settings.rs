@@ -0,0 +1,61 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(GuildIdleSettings::Table)
+                    .if_not_exists()
+                    .col(big_unsigned(GuildIdleSettings::GuildId).primary_key())
+                    .col(
+                        integer(GuildIdleSettings::TimeoutSecs)
+                            .not_null()
+                            .default(300),
+                    )
+                    .col(
+                        integer(GuildIdleSettings::CheckIntervalSecs)
+                            .not_null()
+                            .default(60),
+                    )
+                    .col(
+                        boolean(GuildIdleSettings::AloneIsInactive)
+                            .not_null()
+                            .default(true),
+                    )
+                    .col(
+                        boolean(GuildIdleSettings::PausedIsInactive)
+                            .not_null()
+                            .default(true),
+                    )
+                    .col(string_null(GuildIdleSettings::LeaveMessage))
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(GuildIdleSettings::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum GuildIdleSettings {
+    Table,
+    GuildId,
+    TimeoutSecs,
+    CheckIntervalSecs,
+    AloneIsInactive,
+    PausedIsInactive,
+    LeaveMessage,
+}
@@ -0,0 +1,43 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(AlwaysOnChannels::Table)
+                    .if_not_exists()
+                    .col(big_unsigned(AlwaysOnChannels::GuildId).primary_key())
+                    .col(big_unsigned(AlwaysOnChannels::VoiceChannelId).not_null())
+                    .col(big_unsigned(AlwaysOnChannels::TextChannelId).not_null())
+                    .col(big_unsigned(AlwaysOnChannels::EnabledBy).not_null())
+                    .col(timestamp_with_time_zone(AlwaysOnChannels::EnabledAt).not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(AlwaysOnChannels::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum AlwaysOnChannels {
+    Table,
+    GuildId,
+    VoiceChannelId,
+    TextChannelId,
+    EnabledBy,
+    EnabledAt,
+}
@@ -0,0 +1,111 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(VoiceFeedSettings::Table)
+                    .if_not_exists()
+                    .col(big_unsigned(VoiceFeedSettings::GuildId).primary_key())
+                    .col(big_unsigned(VoiceFeedSettings::ChannelId).not_null())
+                    .col(boolean(VoiceFeedSettings::Enabled).not_null().default(true))
+                    .col(
+                        boolean(VoiceFeedSettings::AnnounceJoin)
+                            .not_null()
+                            .default(true),
+                    )
+                    .col(
+                        boolean(VoiceFeedSettings::AnnounceLeave)
+                            .not_null()
+                            .default(true),
+                    )
+                    .col(
+                        boolean(VoiceFeedSettings::AnnounceMove)
+                            .not_null()
+                            .default(true),
+                    )
+                    .col(string_null(VoiceFeedSettings::JoinTemplate))
+                    .col(string_null(VoiceFeedSettings::LeaveTemplate))
+                    .col(string_null(VoiceFeedSettings::MoveTemplate))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(VoiceFeedIgnores::Table)
+                    .if_not_exists()
+                    .col(pk_uuid(VoiceFeedIgnores::EntryId))
+                    .col(big_unsigned(VoiceFeedIgnores::GuildId).not_null())
+                    .col(string(VoiceFeedIgnores::TargetKind).not_null())
+                    .col(big_unsigned(VoiceFeedIgnores::TargetId).not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_voice_feed_ignores_guild_kind_target")
+                    .table(VoiceFeedIgnores::Table)
+                    .col(VoiceFeedIgnores::GuildId)
+                    .col(VoiceFeedIgnores::TargetKind)
+                    .col(VoiceFeedIgnores::TargetId)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_voice_feed_ignores_guild_kind_target")
+                    .table(VoiceFeedIgnores::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(VoiceFeedIgnores::Table).to_owned())
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(VoiceFeedSettings::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum VoiceFeedSettings {
+    Table,
+    GuildId,
+    ChannelId,
+    Enabled,
+    AnnounceJoin,
+    AnnounceLeave,
+    AnnounceMove,
+    JoinTemplate,
+    LeaveTemplate,
+    MoveTemplate,
+}
+
+#[derive(DeriveIden)]
+enum VoiceFeedIgnores {
+    Table,
+    EntryId,
+    GuildId,
+    TargetKind,
+    TargetId,
+}
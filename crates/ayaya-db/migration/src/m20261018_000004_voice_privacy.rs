@@ -0,0 +1,68 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(VoiceRetentionSettings::Table)
+                    .if_not_exists()
+                    .col(big_unsigned(VoiceRetentionSettings::GuildId).primary_key())
+                    .col(
+                        integer(VoiceRetentionSettings::RawStateDays)
+                            .not_null()
+                            .default(30),
+                    )
+                    .col(integer_null(VoiceRetentionSettings::HistoryDays))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(VoiceOptOuts::Table)
+                    .if_not_exists()
+                    .col(big_unsigned(VoiceOptOuts::UserId).primary_key())
+                    .col(timestamp_with_time_zone(VoiceOptOuts::OptedOutAt).not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(VoiceOptOuts::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(
+                Table::drop()
+                    .table(VoiceRetentionSettings::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum VoiceRetentionSettings {
+    Table,
+    GuildId,
+    RawStateDays,
+    HistoryDays,
+}
+
+#[derive(DeriveIden)]
+enum VoiceOptOuts {
+    Table,
+    UserId,
+    OptedOutAt,
+}
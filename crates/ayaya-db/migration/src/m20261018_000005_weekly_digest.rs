@@ -0,0 +1,94 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(DigestSettings::Table)
+                    .if_not_exists()
+                    .col(big_unsigned(DigestSettings::GuildId).primary_key())
+                    .col(big_unsigned(DigestSettings::ChannelId).not_null())
+                    .col(boolean(DigestSettings::Enabled).not_null().default(true))
+                    // days from Monday, and hour of day in UTC+8
+                    .col(integer(DigestSettings::Weekday).not_null().default(0))
+                    .col(integer(DigestSettings::Hour).not_null().default(20))
+                    .col(
+                        boolean(DigestSettings::IncludeVoice)
+                            .not_null()
+                            .default(true),
+                    )
+                    .col(
+                        boolean(DigestSettings::IncludeSongs)
+                            .not_null()
+                            .default(true),
+                    )
+                    .col(
+                        boolean(DigestSettings::IncludeCommands)
+                            .not_null()
+                            .default(true),
+                    )
+                    .col(
+                        boolean(DigestSettings::IncludeSounds)
+                            .not_null()
+                            .default(true),
+                    )
+                    .col(timestamp_with_time_zone_null(DigestSettings::LastPostedAt))
+                    .to_owned(),
+            )
+            .await?;
+
+        // sounds uploaded before this have no upload time
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Sounds::Table)
+                    .add_column(timestamp_with_time_zone_null(Sounds::UploadedAt))
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Sounds::Table)
+                    .drop_column(Sounds::UploadedAt)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_table(Table::drop().table(DigestSettings::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum DigestSettings {
+    Table,
+    GuildId,
+    ChannelId,
+    Enabled,
+    Weekday,
+    Hour,
+    IncludeVoice,
+    IncludeSongs,
+    IncludeCommands,
+    IncludeSounds,
+    LastPostedAt,
+}
+
+#[derive(DeriveIden)]
+enum Sounds {
+    Table,
+    UploadedAt,
+}
@@ -0,0 +1,40 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // command usage reports read the log of a server over a time window
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_command_call_log_server_time_stamp")
+                    .table(CommandCallLog::Table)
+                    .col(CommandCallLog::ServerId)
+                    .col(CommandCallLog::CommandTimeStamp)
+                    .if_not_exists()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_command_call_log_server_time_stamp")
+                    .table(CommandCallLog::Table)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum CommandCallLog {
+    Table,
+    ServerId,
+    CommandTimeStamp,
+}
@@ -0,0 +1,119 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // a user or role denied a command or a command category
+        manager
+            .create_table(
+                Table::create()
+                    .table(CommandDenyRule::Table)
+                    .if_not_exists()
+                    .col(pk_uuid(CommandDenyRule::EntryId))
+                    .col(big_unsigned(CommandDenyRule::ServerId).not_null())
+                    .col(string(CommandDenyRule::TargetKind).not_null())
+                    .col(big_unsigned(CommandDenyRule::TargetId).not_null())
+                    .col(string(CommandDenyRule::ScopeKind).not_null())
+                    .col(string(CommandDenyRule::ScopeName).not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_command_deny_rule_server_scope")
+                    .table(CommandDenyRule::Table)
+                    .col(CommandDenyRule::ServerId)
+                    .col(CommandDenyRule::ScopeKind)
+                    .col(CommandDenyRule::ScopeName)
+                    .to_owned(),
+            )
+            .await?;
+
+        // a command or a command category only usable from some text or voice channels
+        manager
+            .create_table(
+                Table::create()
+                    .table(CommandChannelScope::Table)
+                    .if_not_exists()
+                    .col(pk_uuid(CommandChannelScope::EntryId))
+                    .col(big_unsigned(CommandChannelScope::ServerId).not_null())
+                    .col(string(CommandChannelScope::ScopeKind).not_null())
+                    .col(string(CommandChannelScope::ScopeName).not_null())
+                    .col(string(CommandChannelScope::ChannelKind).not_null())
+                    .col(big_unsigned(CommandChannelScope::ChannelId).not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_command_channel_scope_server_scope")
+                    .table(CommandChannelScope::Table)
+                    .col(CommandChannelScope::ServerId)
+                    .col(CommandChannelScope::ScopeKind)
+                    .col(CommandChannelScope::ScopeName)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_command_channel_scope_server_scope")
+                    .table(CommandChannelScope::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(CommandChannelScope::Table).to_owned())
+            .await?;
+
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_command_deny_rule_server_scope")
+                    .table(CommandDenyRule::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(CommandDenyRule::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum CommandDenyRule {
+    Table,
+    EntryId,
+    ServerId,
+    TargetKind,
+    TargetId,
+    ScopeKind,
+    ScopeName,
+}
+
+#[derive(DeriveIden)]
+enum CommandChannelScope {
+    Table,
+    EntryId,
+    ServerId,
+    ScopeKind,
+    ScopeName,
+    ChannelKind,
+    ChannelId,
+}
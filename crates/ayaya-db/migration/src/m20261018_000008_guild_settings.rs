@@ -0,0 +1,42 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // unset columns fall back to the bot's defaults
+        manager
+            .create_table(
+                Table::create()
+                    .table(GuildSettings::Table)
+                    .if_not_exists()
+                    .col(big_unsigned(GuildSettings::GuildId).primary_key())
+                    .col(string_null(GuildSettings::Prefix))
+                    .col(big_unsigned_null(GuildSettings::MusicChannelId))
+                    .col(boolean_null(GuildSettings::PublicUploads))
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(GuildSettings::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum GuildSettings {
+    Table,
+    GuildId,
+    Prefix,
+    MusicChannelId,
+    PublicUploads,
+}
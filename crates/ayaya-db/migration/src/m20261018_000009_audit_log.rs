@@ -0,0 +1,83 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // server id 0 for changes outside of a guild, eg: dashboard tokens
+        manager
+            .create_table(
+                Table::create()
+                    .table(AuditLog::Table)
+                    .if_not_exists()
+                    .col(pk_uuid(AuditLog::EntryId))
+                    .col(big_unsigned(AuditLog::ServerId).not_null())
+                    .col(big_unsigned(AuditLog::ActorId).not_null())
+                    .col(string(AuditLog::Action).not_null())
+                    .col(string_null(AuditLog::Before))
+                    .col(string_null(AuditLog::After))
+                    .col(timestamp_with_time_zone(AuditLog::CreatedAt).not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_audit_log_server_created")
+                    .table(AuditLog::Table)
+                    .col(AuditLog::ServerId)
+                    .col(AuditLog::CreatedAt)
+                    .to_owned(),
+            )
+            .await?;
+
+        // changes are mirrored to this channel, if set
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(GuildSettings::Table)
+                    .add_column(big_unsigned_null(GuildSettings::AuditChannelId))
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(GuildSettings::Table)
+                    .drop_column(GuildSettings::AuditChannelId)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_table(Table::drop().table(AuditLog::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum AuditLog {
+    Table,
+    EntryId,
+    ServerId,
+    ActorId,
+    Action,
+    Before,
+    After,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum GuildSettings {
+    Table,
+    AuditChannelId,
+}
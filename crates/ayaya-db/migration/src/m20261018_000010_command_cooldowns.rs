@@ -0,0 +1,71 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // how often a command or command category can be used per user, channel or guild
+        manager
+            .create_table(
+                Table::create()
+                    .table(CommandCooldown::Table)
+                    .if_not_exists()
+                    .col(pk_uuid(CommandCooldown::EntryId))
+                    .col(big_unsigned(CommandCooldown::ServerId).not_null())
+                    .col(string(CommandCooldown::ScopeKind).not_null())
+                    .col(string(CommandCooldown::ScopeName).not_null())
+                    .col(string(CommandCooldown::Bucket).not_null())
+                    .col(integer(CommandCooldown::Uses).not_null())
+                    .col(integer(CommandCooldown::PeriodSecs).not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_command_cooldown_server_scope_bucket")
+                    .table(CommandCooldown::Table)
+                    .col(CommandCooldown::ServerId)
+                    .col(CommandCooldown::ScopeKind)
+                    .col(CommandCooldown::ScopeName)
+                    .col(CommandCooldown::Bucket)
+                    .unique()
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_index(
+                Index::drop()
+                    .name("idx_command_cooldown_server_scope_bucket")
+                    .table(CommandCooldown::Table)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .drop_table(Table::drop().table(CommandCooldown::Table).to_owned())
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum CommandCooldown {
+    Table,
+    EntryId,
+    ServerId,
+    ScopeKind,
+    ScopeName,
+    Bucket,
+    Uses,
+    PeriodSecs,
+}
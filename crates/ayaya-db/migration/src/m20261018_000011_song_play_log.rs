@@ -0,0 +1,53 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // song_queues only has all time counts, the digest counts the plays of a week
+        manager
+            .create_table(
+                Table::create()
+                    .table(SongPlayLog::Table)
+                    .if_not_exists()
+                    .col(pk_uuid(SongPlayLog::PlayId))
+                    .col(big_unsigned(SongPlayLog::ServerId).not_null())
+                    .col(big_unsigned(SongPlayLog::UserId).not_null())
+                    .col(string(SongPlayLog::YoutubeId).not_null())
+                    .col(timestamp_with_time_zone(SongPlayLog::PlayedAt).not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_song_play_log_server_played")
+                    .table(SongPlayLog::Table)
+                    .col(SongPlayLog::ServerId)
+                    .col(SongPlayLog::PlayedAt)
+                    .to_owned(),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(SongPlayLog::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum SongPlayLog {
+    Table,
+    PlayId,
+    ServerId,
+    UserId,
+    YoutubeId,
+    PlayedAt,
+}
@@ -0,0 +1,175 @@
+//! Guilds where the bot stays connected to a voice channel around the clock (24/7 mode)
+use std::sync::Arc;
+
+use ayaya_core::metrics::{DataOperationType, MetricsSink};
+use poise::serenity_prelude as serenity;
+use sea_orm::{ActiveValue, DatabaseConnection, IntoActiveModel, prelude::*};
+use snafu::ResultExt;
+use time::OffsetDateTime;
+
+use super::{DataResult, utils::DataTiming};
+use crate::entity::{always_on_channels, prelude::*};
+use crate::error::DatabaseSnafu;
+
+#[derive(Clone)]
+pub struct AlwaysOnManager {
+    db: DatabaseConnection,
+    metrics_handler: Arc<dyn MetricsSink>,
+}
+
+impl AlwaysOnManager {
+    /// Create a new instance of [`Self`]
+    pub fn new(db: DatabaseConnection, metrics_handler: Arc<dyn MetricsSink>) -> Self {
+        Self {
+            db,
+            metrics_handler,
+        }
+    }
+
+    /// Enable 24/7 mode for a guild, or move it to another channel if already enabled
+    pub async fn enable_always_on(
+        &self,
+        guild_id: u64,
+        voice_channel_id: u64,
+        text_channel_id: u64,
+        enabled_by: &serenity::UserId,
+    ) -> DataResult<always_on_channels::Model> {
+        const OP: &str = "enable_always_on";
+        self.metrics_handler
+            .data_access(OP, DataOperationType::Write)
+            .await;
+        let _timing = DataTiming::new(
+            OP.to_string(),
+            DataOperationType::Write,
+            Some(self.metrics_handler.clone()),
+        );
+
+        let existing = AlwaysOnChannels::find_by_id(guild_id as i64)
+            .one(&self.db)
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+
+        let model = if let Some(model) = existing {
+            let mut active = model.into_active_model();
+            active.voice_channel_id = ActiveValue::Set(voice_channel_id as i64);
+            active.text_channel_id = ActiveValue::Set(text_channel_id as i64);
+            active.enabled_by = ActiveValue::Set(enabled_by.get() as i64);
+            active.enabled_at = ActiveValue::Set(OffsetDateTime::now_utc());
+            active.update(&self.db).await
+        } else {
+            always_on_channels::ActiveModel {
+                guild_id: ActiveValue::Set(guild_id as i64),
+                voice_channel_id: ActiveValue::Set(voice_channel_id as i64),
+                text_channel_id: ActiveValue::Set(text_channel_id as i64),
+                enabled_by: ActiveValue::Set(enabled_by.get() as i64),
+                enabled_at: ActiveValue::Set(OffsetDateTime::now_utc()),
+            }
+            .insert(&self.db)
+            .await
+        }
+        .context(DatabaseSnafu { operation: OP })?;
+
+        Ok(model)
+    }
+
+    /// Disable 24/7 mode for a guild. Returns whether it was enabled.
+    pub async fn disable_always_on(&self, guild_id: u64) -> DataResult<bool> {
+        const OP: &str = "disable_always_on";
+        self.metrics_handler
+            .data_access(OP, DataOperationType::Write)
+            .await;
+        let _timing = DataTiming::new(
+            OP.to_string(),
+            DataOperationType::Write,
+            Some(self.metrics_handler.clone()),
+        );
+
+        let result = AlwaysOnChannels::delete_by_id(guild_id as i64)
+            .exec(&self.db)
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+
+        Ok(result.rows_affected > 0)
+    }
+
+    /// Get the 24/7 channel of a guild, if enabled
+    pub async fn get_always_on(
+        &self,
+        guild_id: u64,
+    ) -> DataResult<Option<always_on_channels::Model>> {
+        const OP: &str = "get_always_on";
+        self.metrics_handler
+            .data_access(OP, DataOperationType::Read)
+            .await;
+        let _timing = DataTiming::new(
+            OP.to_string(),
+            DataOperationType::Read,
+            Some(self.metrics_handler.clone()),
+        );
+
+        AlwaysOnChannels::find_by_id(guild_id as i64)
+            .one(&self.db)
+            .await
+            .context(DatabaseSnafu { operation: OP })
+    }
+
+    /// All guilds with 24/7 mode enabled
+    pub async fn list_always_on(&self) -> DataResult<Vec<always_on_channels::Model>> {
+        const OP: &str = "list_always_on";
+        self.metrics_handler
+            .data_access(OP, DataOperationType::Read)
+            .await;
+        let _timing = DataTiming::new(
+            OP.to_string(),
+            DataOperationType::Read,
+            Some(self.metrics_handler.clone()),
+        );
+
+        AlwaysOnChannels::find()
+            .all(&self.db)
+            .await
+            .context(DatabaseSnafu { operation: OP })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::*;
+    use ayaya_core::metrics::NoopMetrics;
+
+    use migration::Migrator as SqliteMigrator;
+    use migration::MigratorTrait;
+    use sea_orm::Database;
+
+    async fn get_manager() -> AlwaysOnManager {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        SqliteMigrator::up(&db, None).await.unwrap();
+        AlwaysOnManager::new(db, Arc::new(NoopMetrics))
+    }
+
+    #[tokio::test]
+    async fn enable_move_disable() {
+        let manager = get_manager().await;
+        assert!(manager.get_always_on(GUILD_ID_1).await.unwrap().is_none());
+
+        manager
+            .enable_always_on(GUILD_ID_1, 1, 2, &USER_ID_1)
+            .await
+            .unwrap();
+        let moved = manager
+            .enable_always_on(GUILD_ID_1, 3, 4, &USER_ID_2)
+            .await
+            .unwrap();
+        assert_eq!(moved.voice_channel_id, 3);
+        assert_eq!(moved.text_channel_id, 4);
+        assert_eq!(moved.enabled_by, USER_ID_2.get() as i64);
+
+        let all = manager.list_always_on().await.unwrap();
+        assert_eq!(all, vec![moved]);
+
+        assert!(manager.disable_always_on(GUILD_ID_1).await.unwrap());
+        assert!(!manager.disable_always_on(GUILD_ID_1).await.unwrap());
+        assert!(manager.list_always_on().await.unwrap().is_empty());
+    }
+}
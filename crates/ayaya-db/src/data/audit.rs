@@ -0,0 +1,236 @@
+//! Audit log of configuration changes made through admin and owner commands
+use std::sync::Arc;
+
+use ayaya_core::metrics::{DataOperationType, MetricsSink};
+use sea_orm::{ActiveValue, DatabaseConnection, QueryOrder, prelude::*};
+use snafu::ResultExt;
+use time::OffsetDateTime;
+
+use super::{DataResult, utils::DataTiming};
+use crate::entity::{audit_log, prelude::*};
+use crate::error::DatabaseSnafu;
+
+/// Longest stored before or after value, longer ones are cut
+pub const MAX_AUDIT_VALUE_LEN: usize = 1000;
+
+/// A configuration change to record
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AuditRecord {
+    /// 0 for changes outside of a guild
+    pub server_id: u64,
+    pub actor_id: u64,
+    /// What was changed, eg: "permissions.command_role.add"
+    pub action: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+/// Filters of the audit log, unset fields match every entry
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AuditFilter {
+    pub server_id: u64,
+    pub actor_id: Option<u64>,
+    /// Matches actions starting with it, so "permissions" matches every permission change
+    pub action: Option<String>,
+    pub since: Option<OffsetDateTime>,
+}
+
+/// A page of the audit log, newest entries first
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuditPage {
+    pub entries: Vec<audit_log::Model>,
+    /// Entries matching the filter, over every page
+    pub total: u64,
+}
+
+#[derive(Clone)]
+pub struct AuditManager {
+    db: DatabaseConnection,
+    metrics_handler: Arc<dyn MetricsSink>,
+}
+
+impl AuditManager {
+    /// Create a new instance of [`Self`]
+    pub fn new(db: DatabaseConnection, metrics_handler: Arc<dyn MetricsSink>) -> Self {
+        Self {
+            db,
+            metrics_handler,
+        }
+    }
+
+    /// Record a change. Values longer than [`MAX_AUDIT_VALUE_LEN`] are cut.
+    pub async fn record(
+        &self,
+        record: &AuditRecord,
+        at: OffsetDateTime,
+    ) -> DataResult<audit_log::Model> {
+        const OP: &str = "record_audit";
+        self.metrics_handler
+            .data_access(OP, DataOperationType::Write)
+            .await;
+        let _timing = DataTiming::new(
+            OP.to_string(),
+            DataOperationType::Write,
+            Some(self.metrics_handler.clone()),
+        );
+
+        audit_log::ActiveModel {
+            entry_id: ActiveValue::Set(Uuid::now_v7()),
+            server_id: ActiveValue::Set(record.server_id as i64),
+            actor_id: ActiveValue::Set(record.actor_id as i64),
+            action: ActiveValue::Set(record.action.clone()),
+            before: ActiveValue::Set(record.before.as_deref().map(truncate_value)),
+            after: ActiveValue::Set(record.after.as_deref().map(truncate_value)),
+            created_at: ActiveValue::Set(at),
+        }
+        .insert(&self.db)
+        .await
+        .context(DatabaseSnafu { operation: OP })
+    }
+
+    /// A page of the entries matching the filter, newest first. Pages start at 0.
+    pub async fn get_audit_page(
+        &self,
+        filter: &AuditFilter,
+        page: u64,
+        page_size: u64,
+    ) -> DataResult<AuditPage> {
+        const OP: &str = "get_audit_page";
+        self.metrics_handler
+            .data_access(OP, DataOperationType::Read)
+            .await;
+        let _timing = DataTiming::new(
+            OP.to_string(),
+            DataOperationType::Read,
+            Some(self.metrics_handler.clone()),
+        );
+
+        let mut query =
+            AuditLog::find().filter(audit_log::Column::ServerId.eq(filter.server_id as i64));
+        if let Some(actor_id) = filter.actor_id {
+            query = query.filter(audit_log::Column::ActorId.eq(actor_id as i64));
+        }
+        if let Some(action) = &filter.action {
+            query = query.filter(audit_log::Column::Action.starts_with(action.as_str()));
+        }
+        if let Some(since) = filter.since {
+            query = query.filter(audit_log::Column::CreatedAt.gte(since));
+        }
+
+        let paginator = query
+            .order_by_desc(audit_log::Column::CreatedAt)
+            .order_by_desc(audit_log::Column::EntryId)
+            .paginate(&self.db, page_size.max(1));
+        let total = paginator
+            .num_items()
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+        let entries = paginator
+            .fetch_page(page)
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+
+        Ok(AuditPage { entries, total })
+    }
+}
+
+fn truncate_value(value: &str) -> String {
+    if value.chars().count() <= MAX_AUDIT_VALUE_LEN {
+        return value.to_string();
+    }
+    let mut value = value
+        .chars()
+        .take(MAX_AUDIT_VALUE_LEN - 1)
+        .collect::<String>();
+    value.push('…');
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::*;
+    use ayaya_core::metrics::NoopMetrics;
+
+    use migration::Migrator as SqliteMigrator;
+    use migration::MigratorTrait;
+    use sea_orm::Database;
+    use time::{Duration, macros::datetime};
+
+    async fn get_manager() -> AuditManager {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        SqliteMigrator::up(&db, None).await.unwrap();
+        AuditManager::new(db, Arc::new(NoopMetrics))
+    }
+
+    fn record(actor_id: u64, action: &str) -> AuditRecord {
+        AuditRecord {
+            server_id: GUILD_ID_1,
+            actor_id,
+            action: action.to_string(),
+            before: None,
+            after: Some("after".to_string()),
+        }
+    }
+
+    #[tokio::test]
+    async fn record_and_filter() {
+        let manager = get_manager().await;
+        let start = datetime!(2026-10-18 00:00 UTC);
+        let records = [
+            record(USER_ID_1.get(), "permissions.command_role.add"),
+            record(USER_ID_1.get(), "settings.idle"),
+            record(USER_ID_2.get(), "permissions.deny.add"),
+            AuditRecord {
+                server_id: 0,
+                ..record(USER_ID_1.get(), "dashboard.token.create")
+            },
+        ];
+        for (i, record) in records.iter().enumerate() {
+            manager
+                .record(record, start + Duration::minutes(i as i64))
+                .await
+                .unwrap();
+        }
+
+        let filter = AuditFilter {
+            server_id: GUILD_ID_1,
+            ..Default::default()
+        };
+        let page = manager.get_audit_page(&filter, 0, 2).await.unwrap();
+        assert_eq!(page.total, 3);
+        // newest first
+        assert_eq!(page.entries[0].action, "permissions.deny.add");
+        assert_eq!(page.entries[0].before, None);
+        assert_eq!(page.entries[0].after.as_deref(), Some("after"));
+
+        let filter = AuditFilter {
+            server_id: GUILD_ID_1,
+            action: Some("permissions".to_string()),
+            actor_id: Some(USER_ID_1.get()),
+            ..Default::default()
+        };
+        let page = manager.get_audit_page(&filter, 0, 10).await.unwrap();
+        assert_eq!(page.total, 1);
+        assert_eq!(page.entries[0].action, "permissions.command_role.add");
+
+        let filter = AuditFilter {
+            server_id: 0,
+            since: Some(start + Duration::minutes(3)),
+            ..Default::default()
+        };
+        assert_eq!(
+            manager.get_audit_page(&filter, 0, 10).await.unwrap().total,
+            1
+        );
+    }
+
+    #[test]
+    fn long_values_are_cut() {
+        assert_eq!(truncate_value("short"), "short");
+        let long = "a".repeat(MAX_AUDIT_VALUE_LEN + 10);
+        let cut = truncate_value(&long);
+        assert_eq!(cut.chars().count(), MAX_AUDIT_VALUE_LEN);
+        assert!(cut.ends_with('…'));
+    }
+}
@@ -0,0 +1,530 @@
+//! Command usage over time, built from the command call log. The calls are counted by the
+//! database, so only a row per command or per bar of a trend is loaded.
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use ayaya_core::metrics::{DataOperationType, MetricsSink};
+use sea_orm::{
+    Condition, ConnectionTrait, DatabaseConnection, QueryOrder, QuerySelect, QueryTrait,
+    prelude::*, sea_query::Func,
+};
+use snafu::ResultExt;
+use time::{Duration, OffsetDateTime};
+
+use super::{DataResult, utils::DataTiming};
+use crate::entity::{command_call_log, prelude::*};
+use crate::error::DatabaseSnafu;
+
+/// Time window of the command usage reports
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UsageWindow {
+    Day,
+    Week,
+    #[default]
+    Month,
+    Year,
+    AllTime,
+}
+
+impl UsageWindow {
+    /// Start of the window, None for all time
+    pub fn since(self, now: OffsetDateTime) -> Option<OffsetDateTime> {
+        let days = match self {
+            Self::Day => 1,
+            Self::Week => 7,
+            Self::Month => 30,
+            Self::Year => 365,
+            Self::AllTime => return None,
+        };
+        Some(now - Duration::days(days))
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Day => "last 24 hours",
+            Self::Week => "last 7 days",
+            Self::Month => "last 30 days",
+            Self::Year => "last 365 days",
+            Self::AllTime => "all time",
+        }
+    }
+
+    /// Start of the window before, to compare with. None for all time.
+    pub fn previous_since(self, now: OffsetDateTime) -> Option<OffsetDateTime> {
+        self.since(now).map(|since| since - (now - since))
+    }
+
+    /// Length of a bar of the trend chart, and how it is called
+    pub fn trend_bucket(self) -> (Duration, &'static str) {
+        match self {
+            Self::Day => (Duration::hours(1), "1 hour"),
+            Self::Week => (Duration::hours(6), "6 hours"),
+            Self::Month => (Duration::days(1), "1 day"),
+            Self::Year => (Duration::weeks(1), "1 week"),
+            Self::AllTime => (Duration::days(30), "30 days"),
+        }
+    }
+}
+
+/// Usage of a command in a time window
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CommandUsage {
+    pub command: String,
+    pub calls: u64,
+    pub unique_users: u64,
+    /// Calls in the window before, to compare with
+    pub previous_calls: u64,
+}
+
+/// Calls of a command, or of every command, over a time window
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CommandTrend {
+    /// Calls in each bar, oldest first
+    pub counts: Vec<u64>,
+    pub unique_users: u64,
+    /// Calls in the window before, to compare with
+    pub previous_calls: u64,
+}
+
+/// Start and end of each `bucket` long slice from `since` to `now`, the last one is cut at `now`
+pub fn trend_buckets(
+    since: OffsetDateTime,
+    now: OffsetDateTime,
+    bucket: Duration,
+) -> Vec<(OffsetDateTime, OffsetDateTime)> {
+    if now <= since || bucket <= Duration::ZERO {
+        return Vec::new();
+    }
+    let buckets = ((now - since) / bucket).ceil() as i32;
+    (0..buckets)
+        .map(|i| {
+            let start = since + bucket * i;
+            (start, (start + bucket).min(now))
+        })
+        .collect()
+}
+
+/// Filters of the command log, unset fields match every call
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CommandLogFilter {
+    /// 0 for calls in DMs
+    pub server_id: Option<u64>,
+    pub user_id: Option<u64>,
+    pub command: Option<String>,
+    pub since: Option<OffsetDateTime>,
+}
+
+/// A page of the command log, newest calls first
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CommandLogPage {
+    pub entries: Vec<command_call_log::Model>,
+    /// Calls matching the filter, over every page
+    pub total: u64,
+}
+
+#[derive(Clone)]
+pub struct CommandStatsManager {
+    db: DatabaseConnection,
+    metrics_handler: Arc<dyn MetricsSink>,
+}
+
+impl CommandStatsManager {
+    /// Create a new instance of [`Self`]
+    pub fn new(db: DatabaseConnection, metrics_handler: Arc<dyn MetricsSink>) -> Self {
+        Self {
+            db,
+            metrics_handler,
+        }
+    }
+
+    /// Usage of each command of a server called in the window, most called first
+    pub async fn get_command_usage(
+        &self,
+        server_id: u64,
+        window: UsageWindow,
+        now: OffsetDateTime,
+    ) -> DataResult<Vec<CommandUsage>> {
+        const OP: &str = "get_command_usage";
+        self.metrics_handler
+            .data_access(OP, DataOperationType::Read)
+            .await;
+        let _timing = DataTiming::new(
+            OP.to_string(),
+            DataOperationType::Read,
+            Some(self.metrics_handler.clone()),
+        );
+
+        let since = window.since(now);
+        let mut current =
+            CommandCallLog::find().filter(command_call_log::Column::ServerId.eq(server_id as i64));
+        if let Some(since) = since {
+            current = current.filter(command_call_log::Column::CommandTimeStamp.gte(since));
+        }
+        let current: Vec<(String, i64, i64)> = current
+            .select_only()
+            .column(command_call_log::Column::Command)
+            .expr(Func::count(Expr::col(command_call_log::Column::LogId)))
+            .expr(Func::count_distinct(Expr::col(
+                command_call_log::Column::UserId,
+            )))
+            .group_by(command_call_log::Column::Command)
+            .into_tuple()
+            .all(&self.db)
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+
+        let previous: HashMap<String, i64> = match (window.previous_since(now), since) {
+            (Some(previous_since), Some(since)) => CommandCallLog::find()
+                .filter(command_call_log::Column::ServerId.eq(server_id as i64))
+                .filter(command_call_log::Column::CommandTimeStamp.gte(previous_since))
+                .filter(command_call_log::Column::CommandTimeStamp.lt(since))
+                .select_only()
+                .column(command_call_log::Column::Command)
+                .expr(Func::count(Expr::col(command_call_log::Column::LogId)))
+                .group_by(command_call_log::Column::Command)
+                .into_tuple::<(String, i64)>()
+                .all(&self.db)
+                .await
+                .context(DatabaseSnafu { operation: OP })?
+                .into_iter()
+                .collect(),
+            _ => HashMap::new(),
+        };
+
+        let mut usage = current
+            .into_iter()
+            .map(|(command, calls, unique_users)| CommandUsage {
+                previous_calls: previous.get(&command).copied().unwrap_or_default() as u64,
+                command,
+                calls: calls as u64,
+                unique_users: unique_users as u64,
+            })
+            .collect::<Vec<_>>();
+        usage.sort_by(|a, b| b.calls.cmp(&a.calls).then(a.command.cmp(&b.command)));
+        Ok(usage)
+    }
+
+    /// Calls of a server in each bar of the window's trend, only of `command` if given. All time
+    /// starts from the first call, None when there is none.
+    pub async fn get_command_trend(
+        &self,
+        server_id: u64,
+        command: Option<&str>,
+        window: UsageWindow,
+        now: OffsetDateTime,
+    ) -> DataResult<Option<CommandTrend>> {
+        const OP: &str = "get_command_trend";
+        self.metrics_handler
+            .data_access(OP, DataOperationType::Read)
+            .await;
+        let _timing = DataTiming::new(
+            OP.to_string(),
+            DataOperationType::Read,
+            Some(self.metrics_handler.clone()),
+        );
+
+        let mut calls =
+            CommandCallLog::find().filter(command_call_log::Column::ServerId.eq(server_id as i64));
+        if let Some(command) = command {
+            calls = calls.filter(command_call_log::Column::Command.eq(command));
+        }
+
+        let start = match window.since(now) {
+            Some(since) => since,
+            None => match calls
+                .clone()
+                .order_by_asc(command_call_log::Column::CommandTimeStamp)
+                .one(&self.db)
+                .await
+                .context(DatabaseSnafu { operation: OP })?
+            {
+                Some(first) => first.command_time_stamp,
+                None => return Ok(None),
+            },
+        };
+        if let Some(previous_since) = window.previous_since(now) {
+            calls = calls.filter(command_call_log::Column::CommandTimeStamp.gte(previous_since));
+        }
+
+        // one row, with the users and previous calls first and then a count per bar
+        let in_window = Condition::all()
+            .add(command_call_log::Column::CommandTimeStamp.gte(start))
+            .add(command_call_log::Column::CommandTimeStamp.lt(now));
+        let buckets = trend_buckets(start, now, window.trend_bucket().0);
+        let mut query = calls
+            .select_only()
+            .expr(Func::count_distinct(Expr::case(
+                in_window,
+                Expr::col(command_call_log::Column::UserId),
+            )))
+            .expr(Func::count(Expr::case(
+                command_call_log::Column::CommandTimeStamp.lt(start),
+                Expr::val(1),
+            )));
+        for (bucket_start, bucket_end) in &buckets {
+            query = query.expr(Func::count(Expr::case(
+                Condition::all()
+                    .add(command_call_log::Column::CommandTimeStamp.gte(*bucket_start))
+                    .add(command_call_log::Column::CommandTimeStamp.lt(*bucket_end)),
+                Expr::val(1),
+            )));
+        }
+        let Some(row) = self
+            .db
+            .query_one(query.build(self.db.get_database_backend()))
+            .await
+            .context(DatabaseSnafu { operation: OP })?
+        else {
+            return Ok(None);
+        };
+        let count = |index: usize| {
+            row.try_get_by_index::<i64>(index)
+                .map(|count| count as u64)
+                .context(DatabaseSnafu { operation: OP })
+        };
+
+        Ok(Some(CommandTrend {
+            unique_users: count(0)?,
+            previous_calls: count(1)?,
+            counts: (0..buckets.len())
+                .map(|i| count(i + 2))
+                .collect::<DataResult<_>>()?,
+        }))
+    }
+
+    /// A page of the calls matching the filter, newest first. Pages start at 0.
+    pub async fn get_command_log_page(
+        &self,
+        filter: &CommandLogFilter,
+        page: u64,
+        page_size: u64,
+    ) -> DataResult<CommandLogPage> {
+        const OP: &str = "get_command_log_page";
+        self.metrics_handler
+            .data_access(OP, DataOperationType::Read)
+            .await;
+        let _timing = DataTiming::new(
+            OP.to_string(),
+            DataOperationType::Read,
+            Some(self.metrics_handler.clone()),
+        );
+
+        let mut query = CommandCallLog::find();
+        if let Some(server_id) = filter.server_id {
+            query = query.filter(command_call_log::Column::ServerId.eq(server_id as i64));
+        }
+        if let Some(user_id) = filter.user_id {
+            query = query.filter(command_call_log::Column::UserId.eq(user_id as i64));
+        }
+        if let Some(command) = &filter.command {
+            query = query.filter(command_call_log::Column::Command.eq(command.as_str()));
+        }
+        if let Some(since) = filter.since {
+            query = query.filter(command_call_log::Column::CommandTimeStamp.gte(since));
+        }
+
+        let paginator = query
+            .order_by_desc(command_call_log::Column::CommandTimeStamp)
+            .order_by_desc(command_call_log::Column::LogId)
+            .paginate(&self.db, page_size.max(1));
+        let total = paginator
+            .num_items()
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+        let entries = paginator
+            .fetch_page(page)
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+
+        Ok(CommandLogPage { entries, total })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::*;
+    use ayaya_core::metrics::NoopMetrics;
+
+    use migration::Migrator as SqliteMigrator;
+    use migration::MigratorTrait;
+    use sea_orm::{ActiveValue, Database};
+    use time::macros::datetime;
+    use uuid::Uuid;
+
+    async fn get_db() -> DatabaseConnection {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        SqliteMigrator::up(&db, None).await.unwrap();
+        db
+    }
+
+    fn call(user_id: u64, command: &str, at: OffsetDateTime) -> command_call_log::Model {
+        command_call_log::Model {
+            log_id: Uuid::now_v7(),
+            server_id: GUILD_ID_1 as i64,
+            user_id: user_id as i64,
+            command: command.to_string(),
+            command_time_stamp: at,
+        }
+    }
+
+    async fn insert_calls(db: &DatabaseConnection, calls: Vec<command_call_log::Model>) {
+        for call in calls {
+            command_call_log::ActiveModel {
+                log_id: ActiveValue::Set(call.log_id),
+                server_id: ActiveValue::Set(call.server_id),
+                user_id: ActiveValue::Set(call.user_id),
+                command: ActiveValue::Set(call.command),
+                command_time_stamp: ActiveValue::Set(call.command_time_stamp),
+            }
+            .insert(db)
+            .await
+            .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn usage_counts_users_and_previous_window() {
+        let db = get_db().await;
+        let manager = CommandStatsManager::new(db.clone(), Arc::new(NoopMetrics));
+        let now = datetime!(2026-10-18 00:00 UTC);
+        let since = UsageWindow::Week.since(now).unwrap();
+        insert_calls(
+            &db,
+            vec![
+                call(USER_ID_1.get(), "play", since - Duration::days(8)),
+                call(USER_ID_1.get(), "play", since - Duration::days(2)),
+                call(USER_ID_1.get(), "play", since + Duration::days(1)),
+                call(USER_ID_1.get(), "play", since + Duration::days(2)),
+                call(USER_ID_2.get(), "play", since + Duration::days(3)),
+                call(USER_ID_2.get(), "skip", since + Duration::days(3)),
+                call(USER_ID_2.get(), "gay", since - Duration::days(1)),
+            ],
+        )
+        .await;
+
+        assert_eq!(
+            manager
+                .get_command_usage(GUILD_ID_1, UsageWindow::Week, now)
+                .await
+                .unwrap(),
+            vec![
+                CommandUsage {
+                    command: "play".to_string(),
+                    calls: 3,
+                    unique_users: 2,
+                    previous_calls: 1,
+                },
+                CommandUsage {
+                    command: "skip".to_string(),
+                    calls: 1,
+                    unique_users: 1,
+                    previous_calls: 0,
+                },
+            ]
+        );
+        let all_time = manager
+            .get_command_usage(GUILD_ID_1, UsageWindow::AllTime, now)
+            .await
+            .unwrap();
+        assert_eq!(all_time.len(), 3);
+        assert_eq!(all_time[0].calls, 5);
+    }
+
+    #[test]
+    fn buckets_run_from_since_to_now() {
+        let since = datetime!(2026-10-18 00:00 UTC);
+        let now = since + Duration::hours(3) + Duration::minutes(30);
+
+        let buckets = trend_buckets(since, now, Duration::hours(1));
+        assert_eq!(buckets.len(), 4);
+        assert_eq!(buckets[0], (since, since + Duration::hours(1)));
+        assert_eq!(buckets[3], (since + Duration::hours(3), now));
+        assert!(trend_buckets(now, since, Duration::hours(1)).is_empty());
+    }
+
+    #[tokio::test]
+    async fn trends_count_calls_per_bucket() {
+        let db = get_db().await;
+        let manager = CommandStatsManager::new(db.clone(), Arc::new(NoopMetrics));
+        let now = datetime!(2026-10-18 00:00 UTC);
+        let since = UsageWindow::Day.since(now).unwrap();
+        insert_calls(
+            &db,
+            vec![
+                call(USER_ID_1.get(), "play", since - Duration::minutes(1)),
+                call(USER_ID_1.get(), "play", since),
+                call(USER_ID_2.get(), "play", since + Duration::minutes(59)),
+                call(USER_ID_1.get(), "skip", since + Duration::hours(3)),
+                call(USER_ID_1.get(), "play", now),
+            ],
+        )
+        .await;
+
+        let trend = manager
+            .get_command_trend(GUILD_ID_1, None, UsageWindow::Day, now)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(trend.counts.len(), 24);
+        assert_eq!(&trend.counts[..4], &[2, 0, 0, 1]);
+        assert_eq!(trend.counts.iter().sum::<u64>(), 3);
+        assert_eq!(trend.unique_users, 2);
+        assert_eq!(trend.previous_calls, 1);
+
+        let plays = manager
+            .get_command_trend(GUILD_ID_1, Some("play"), UsageWindow::AllTime, now)
+            .await
+            .unwrap()
+            .unwrap();
+        // all time starts from the first call
+        assert_eq!(plays.counts, vec![3]);
+        assert_eq!(plays.previous_calls, 0);
+
+        let none = manager
+            .get_command_trend(GUILD_ID_1 + 1, None, UsageWindow::AllTime, now)
+            .await
+            .unwrap();
+        assert!(none.is_none());
+    }
+
+    #[tokio::test]
+    async fn command_log_pages_with_filters() {
+        let db = get_db().await;
+        let manager = CommandStatsManager::new(db.clone(), Arc::new(NoopMetrics));
+        let start = datetime!(2026-10-18 00:00 UTC);
+        insert_calls(
+            &db,
+            (0..5)
+                .map(|i| {
+                    let command = if i % 2 == 0 { "play" } else { "skip" };
+                    call(USER_ID_1.get(), command, start + Duration::minutes(i))
+                })
+                .collect(),
+        )
+        .await;
+
+        let filter = CommandLogFilter {
+            command: Some("play".to_string()),
+            ..Default::default()
+        };
+        let page = manager.get_command_log_page(&filter, 0, 2).await.unwrap();
+        assert_eq!(page.total, 3);
+        assert_eq!(page.entries.len(), 2);
+        // newest first
+        assert_eq!(
+            page.entries[0].command_time_stamp,
+            start + Duration::minutes(4)
+        );
+        let page = manager.get_command_log_page(&filter, 1, 2).await.unwrap();
+        assert_eq!(page.entries.len(), 1);
+
+        let filter = CommandLogFilter {
+            user_id: Some(USER_ID_2.get()),
+            ..Default::default()
+        };
+        let page = manager.get_command_log_page(&filter, 0, 2).await.unwrap();
+        assert_eq!(page.total, 0);
+        assert!(page.entries.is_empty());
+    }
+}
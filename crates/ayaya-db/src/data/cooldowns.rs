@@ -0,0 +1,491 @@
+//! Cooldowns limiting how often a command or command category can be used.
+//!
+//! A cooldown allows a number of uses per period in a bucket: per user, per channel or for the
+//! whole guild. Uses are counted over a sliding window kept in memory, so they reset when the bot
+//! restarts. Like permission rules, a command's own cooldown replaces its category's one for the
+//! same bucket, and cooldowns of different buckets all apply.
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use ayaya_core::metrics::{DataOperationType, MetricsSink};
+use sea_orm::{ActiveValue, DatabaseConnection, prelude::*, sea_query::OnConflict};
+use snafu::ResultExt;
+use tokio::sync::RwLock;
+
+use super::{DataResult, permission_rules::RuleScope, utils::DataTiming};
+use crate::entity::{command_cooldown, prelude::*};
+use crate::error::DatabaseSnafu;
+
+/// Tracked buckets above which the ones without recent uses are dropped
+const MAX_TRACKED_BUCKETS: usize = 10_000;
+
+/// Who shares the uses of a cooldown
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum CooldownBucket {
+    User,
+    Channel,
+    Guild,
+}
+
+impl CooldownBucket {
+    pub const ALL: [Self; 3] = [Self::User, Self::Channel, Self::Guild];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::User => "user",
+            Self::Channel => "channel",
+            Self::Guild => "guild",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|bucket| bucket.as_str() == value)
+    }
+}
+
+/// At most `uses` calls of a command or category every `period`, per bucket. A zero period
+/// turns the cooldown off, which lifts a default one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CooldownRule {
+    pub scope: RuleScope,
+    /// The command or category
+    pub name: String,
+    pub bucket: CooldownBucket,
+    pub uses: u32,
+    pub period: Duration,
+}
+
+impl CooldownRule {
+    fn is_off(&self) -> bool {
+        self.period.is_zero()
+    }
+}
+
+impl TryFrom<command_cooldown::Model> for CooldownRule {
+    type Error = ();
+
+    fn try_from(value: command_cooldown::Model) -> Result<Self, Self::Error> {
+        Ok(Self {
+            scope: RuleScope::parse(&value.scope_kind).ok_or(())?,
+            name: value.scope_name,
+            bucket: CooldownBucket::parse(&value.bucket).ok_or(())?,
+            uses: value.uses.max(0) as u32,
+            period: Duration::from_secs(value.period_secs.max(0) as u64),
+        })
+    }
+}
+
+/// The cooldowns that apply to a command: for every bucket, the command's own rule if there is
+/// one, else its category's. Earlier rules win over later ones of the same scope, so guild rules
+/// listed before defaults replace them.
+pub fn applicable_cooldowns<'a>(
+    rules: &'a [CooldownRule],
+    command: &str,
+    category: &str,
+) -> Vec<&'a CooldownRule> {
+    CooldownBucket::ALL
+        .into_iter()
+        .filter_map(|bucket| {
+            let find = |scope: RuleScope, name: &str| {
+                rules
+                    .iter()
+                    .find(|rule| rule.bucket == bucket && rule.scope == scope && rule.name == name)
+            };
+            find(RuleScope::Command, command).or_else(|| find(RuleScope::Category, category))
+        })
+        .filter(|rule| !rule.is_off())
+        .collect()
+}
+
+/// Who is calling a command, and from where
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CooldownCaller {
+    pub guild_id: u64,
+    pub user_id: u64,
+    pub channel_id: u64,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct UsageKey {
+    guild_id: u64,
+    scope: RuleScope,
+    name: String,
+    bucket: CooldownBucket,
+    /// The user or channel of the bucket, 0 for the guild bucket
+    target_id: u64,
+}
+
+/// Recent uses of a bucket, oldest first
+struct Usage {
+    period: Duration,
+    uses: VecDeque<Instant>,
+}
+
+#[derive(Clone)]
+pub struct CooldownManager {
+    db: DatabaseConnection,
+    metrics_handler: Arc<dyn MetricsSink>,
+    /// Loaded cooldowns of each guild, including guilds without any
+    cache: Arc<RwLock<HashMap<u64, Vec<CooldownRule>>>>,
+    usage: Arc<Mutex<HashMap<UsageKey, Usage>>>,
+}
+
+impl CooldownManager {
+    /// Create a new instance of [`Self`]
+    pub fn new(db: DatabaseConnection, metrics_handler: Arc<dyn MetricsSink>) -> Self {
+        Self {
+            db,
+            metrics_handler,
+            cache: Default::default(),
+            usage: Default::default(),
+        }
+    }
+
+    /// Get the cooldowns a guild configured
+    pub async fn get_cooldowns(&self, guild_id: u64) -> DataResult<Vec<CooldownRule>> {
+        const OP: &str = "get_cooldowns";
+        let cached = self.cache.read().await.get(&guild_id).cloned();
+        self.metrics_handler
+            .cache_access("cooldown_cache", cached.is_some())
+            .await;
+        if let Some(rules) = cached {
+            return Ok(rules);
+        }
+
+        self.metrics_handler
+            .data_access(OP, DataOperationType::Read)
+            .await;
+        let _timing = DataTiming::new(
+            OP.to_string(),
+            DataOperationType::Read,
+            Some(self.metrics_handler.clone()),
+        );
+
+        let rules = CommandCooldown::find()
+            .filter(command_cooldown::Column::ServerId.eq(guild_id as i64))
+            .all(&self.db)
+            .await
+            .context(DatabaseSnafu { operation: OP })?
+            .into_iter()
+            .filter_map(|model| CooldownRule::try_from(model).ok())
+            .collect::<Vec<_>>();
+        self.cache.write().await.insert(guild_id, rules.clone());
+        Ok(rules)
+    }
+
+    /// Set the cooldown of a command or category for a bucket, returning the one it replaced
+    pub async fn set_cooldown(
+        &self,
+        guild_id: u64,
+        rule: &CooldownRule,
+    ) -> DataResult<Option<CooldownRule>> {
+        const OP: &str = "set_cooldown";
+        let previous = self
+            .get_cooldowns(guild_id)
+            .await?
+            .into_iter()
+            .find(|other| {
+                other.scope == rule.scope && other.name == rule.name && other.bucket == rule.bucket
+            });
+
+        self.metrics_handler
+            .data_access(OP, DataOperationType::Write)
+            .await;
+        let _timing = DataTiming::new(
+            OP.to_string(),
+            DataOperationType::Write,
+            Some(self.metrics_handler.clone()),
+        );
+
+        CommandCooldown::insert(command_cooldown::ActiveModel {
+            entry_id: ActiveValue::Set(Uuid::now_v7()),
+            server_id: ActiveValue::Set(guild_id as i64),
+            scope_kind: ActiveValue::Set(rule.scope.as_str().to_string()),
+            scope_name: ActiveValue::Set(rule.name.clone()),
+            bucket: ActiveValue::Set(rule.bucket.as_str().to_string()),
+            uses: ActiveValue::Set(rule.uses.min(i32::MAX as u32) as i32),
+            period_secs: ActiveValue::Set(rule.period.as_secs().min(i32::MAX as u64) as i32),
+        })
+        .on_conflict(
+            OnConflict::columns([
+                command_cooldown::Column::ServerId,
+                command_cooldown::Column::ScopeKind,
+                command_cooldown::Column::ScopeName,
+                command_cooldown::Column::Bucket,
+            ])
+            .update_columns([
+                command_cooldown::Column::Uses,
+                command_cooldown::Column::PeriodSecs,
+            ])
+            .to_owned(),
+        )
+        .exec(&self.db)
+        .await
+        .context(DatabaseSnafu { operation: OP })?;
+
+        self.cache.write().await.remove(&guild_id);
+        Ok(previous)
+    }
+
+    /// Remove the cooldown of a command or category for a bucket, returning it if there was one
+    pub async fn delete_cooldown(
+        &self,
+        guild_id: u64,
+        scope: RuleScope,
+        name: &str,
+        bucket: CooldownBucket,
+    ) -> DataResult<Option<CooldownRule>> {
+        const OP: &str = "delete_cooldown";
+        let previous = self
+            .get_cooldowns(guild_id)
+            .await?
+            .into_iter()
+            .find(|rule| rule.scope == scope && rule.name == name && rule.bucket == bucket);
+        if previous.is_none() {
+            return Ok(None);
+        }
+
+        self.metrics_handler
+            .data_access(OP, DataOperationType::Write)
+            .await;
+        let _timing = DataTiming::new(
+            OP.to_string(),
+            DataOperationType::Write,
+            Some(self.metrics_handler.clone()),
+        );
+
+        CommandCooldown::delete_many()
+            .filter(command_cooldown::Column::ServerId.eq(guild_id as i64))
+            .filter(command_cooldown::Column::ScopeKind.eq(scope.as_str()))
+            .filter(command_cooldown::Column::ScopeName.eq(name))
+            .filter(command_cooldown::Column::Bucket.eq(bucket.as_str()))
+            .exec(&self.db)
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+
+        self.cache.write().await.remove(&guild_id);
+        Ok(previous)
+    }
+
+    /// Count a use against the cooldowns, see [`applicable_cooldowns`]. If one of them is used
+    /// up, nothing is counted and the time until it frees up is returned.
+    pub fn try_use(
+        &self,
+        caller: CooldownCaller,
+        cooldowns: &[&CooldownRule],
+        now: Instant,
+    ) -> Option<Duration> {
+        let mut usage = self
+            .usage
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        if usage.len() > MAX_TRACKED_BUCKETS {
+            usage.retain(|_, usage| {
+                usage
+                    .uses
+                    .back()
+                    .is_some_and(|last| now.duration_since(*last) < usage.period)
+            });
+        }
+
+        let keys = cooldowns
+            .iter()
+            .map(|rule| UsageKey {
+                guild_id: caller.guild_id,
+                scope: rule.scope,
+                name: rule.name.clone(),
+                bucket: rule.bucket,
+                target_id: match rule.bucket {
+                    CooldownBucket::User => caller.user_id,
+                    CooldownBucket::Channel => caller.channel_id,
+                    CooldownBucket::Guild => 0,
+                },
+            })
+            .collect::<Vec<_>>();
+
+        // check every cooldown before counting, a refused call uses none of them
+        let mut retry_after = None;
+        for (rule, key) in cooldowns.iter().zip(&keys) {
+            let Some(entry) = usage.get_mut(key) else {
+                continue;
+            };
+            entry.period = rule.period;
+            while entry
+                .uses
+                .front()
+                .is_some_and(|used| now.duration_since(*used) >= rule.period)
+            {
+                entry.uses.pop_front();
+            }
+            if entry.uses.len() >= rule.uses as usize {
+                let wait = entry.uses.front().map_or(rule.period, |oldest| {
+                    rule.period.saturating_sub(now.duration_since(*oldest))
+                });
+                retry_after = retry_after.max(Some(wait));
+            }
+        }
+        if retry_after.is_some() {
+            return retry_after;
+        }
+
+        for (rule, key) in cooldowns.iter().zip(keys) {
+            usage
+                .entry(key)
+                .or_insert_with(|| Usage {
+                    period: rule.period,
+                    uses: VecDeque::new(),
+                })
+                .uses
+                .push_back(now);
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::*;
+    use ayaya_core::metrics::NoopMetrics;
+
+    use migration::Migrator as SqliteMigrator;
+    use migration::MigratorTrait;
+    use sea_orm::Database;
+
+    async fn get_manager() -> CooldownManager {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        SqliteMigrator::up(&db, None).await.unwrap();
+        CooldownManager::new(db, Arc::new(NoopMetrics))
+    }
+
+    fn rule(
+        scope: RuleScope,
+        name: &str,
+        bucket: CooldownBucket,
+        uses: u32,
+        secs: u64,
+    ) -> CooldownRule {
+        CooldownRule {
+            scope,
+            name: name.to_string(),
+            bucket,
+            uses,
+            period: Duration::from_secs(secs),
+        }
+    }
+
+    fn caller(user_id: u64, channel_id: u64) -> CooldownCaller {
+        CooldownCaller {
+            guild_id: GUILD_ID_1,
+            user_id,
+            channel_id,
+        }
+    }
+
+    #[test]
+    fn command_cooldown_replaces_category() {
+        let rules = vec![
+            rule(
+                RuleScope::Category,
+                COMMAND_CATEGORY_1,
+                CooldownBucket::User,
+                1,
+                10,
+            ),
+            rule(
+                RuleScope::Category,
+                COMMAND_CATEGORY_1,
+                CooldownBucket::Guild,
+                5,
+                10,
+            ),
+            rule(RuleScope::Command, COMMAND_1, CooldownBucket::User, 3, 10),
+            // a later default for the same command and bucket is ignored
+            rule(RuleScope::Command, COMMAND_1, CooldownBucket::User, 1, 60),
+        ];
+        let applicable = applicable_cooldowns(&rules, COMMAND_1, COMMAND_CATEGORY_1);
+        assert_eq!(applicable, vec![&rules[2], &rules[1]]);
+
+        // turned off for the command, the category's cooldown does not apply either
+        let rules = vec![
+            rule(RuleScope::Command, COMMAND_1, CooldownBucket::User, 0, 0),
+            rule(
+                RuleScope::Category,
+                COMMAND_CATEGORY_1,
+                CooldownBucket::User,
+                1,
+                10,
+            ),
+        ];
+        assert!(applicable_cooldowns(&rules, COMMAND_1, COMMAND_CATEGORY_1).is_empty());
+    }
+
+    #[tokio::test]
+    async fn uses_slide_out_of_the_window() {
+        let manager = get_manager().await;
+        let per_user = rule(RuleScope::Command, COMMAND_1, CooldownBucket::User, 2, 10);
+        let per_guild = rule(RuleScope::Command, COMMAND_1, CooldownBucket::Guild, 3, 10);
+        let cooldowns = [&per_user, &per_guild];
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+
+        assert_eq!(manager.try_use(caller(1, 1), &cooldowns, at(0)), None);
+        assert_eq!(manager.try_use(caller(1, 1), &cooldowns, at(4)), None);
+        assert_eq!(
+            manager.try_use(caller(1, 1), &cooldowns, at(5)),
+            Some(Duration::from_secs(5))
+        );
+        // the refused call was not counted against the guild
+        assert_eq!(manager.try_use(caller(2, 1), &cooldowns, at(5)), None);
+        assert_eq!(
+            manager.try_use(caller(3, 1), &cooldowns, at(6)),
+            Some(Duration::from_secs(4))
+        );
+        // the first use is out of the window
+        assert_eq!(manager.try_use(caller(1, 1), &cooldowns, at(10)), None);
+    }
+
+    #[tokio::test]
+    async fn set_and_delete_cooldowns() {
+        let manager = get_manager().await;
+        assert!(manager.get_cooldowns(GUILD_ID_1).await.unwrap().is_empty());
+
+        let first = rule(
+            RuleScope::Command,
+            COMMAND_1,
+            CooldownBucket::Channel,
+            2,
+            30,
+        );
+        assert_eq!(
+            manager.set_cooldown(GUILD_ID_1, &first).await.unwrap(),
+            None
+        );
+        let second = CooldownRule {
+            uses: 5,
+            ..first.clone()
+        };
+        assert_eq!(
+            manager.set_cooldown(GUILD_ID_1, &second).await.unwrap(),
+            Some(first)
+        );
+
+        // a new manager reads from the database instead of the cache
+        let uncached = CooldownManager::new(manager.db.clone(), Arc::new(NoopMetrics));
+        assert_eq!(
+            uncached.get_cooldowns(GUILD_ID_1).await.unwrap(),
+            vec![second.clone()]
+        );
+
+        let delete =
+            |bucket| manager.delete_cooldown(GUILD_ID_1, RuleScope::Command, COMMAND_1, bucket);
+        assert_eq!(delete(CooldownBucket::User).await.unwrap(), None);
+        assert_eq!(delete(CooldownBucket::Channel).await.unwrap(), Some(second));
+        assert!(manager.get_cooldowns(GUILD_ID_1).await.unwrap().is_empty());
+    }
+}
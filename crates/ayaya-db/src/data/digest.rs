@@ -0,0 +1,716 @@
+//! Weekly digest posted to a channel of each guild: top voice members, top songs, most used
+//! commands and new soundboard uploads. Holds the per guild schedule and the queries building a
+//! digest, posting it is left to the bot.
+use std::sync::Arc;
+
+use ayaya_core::metrics::{DataOperationType, MetricsSink};
+use sea_orm::{
+    ActiveValue, Condition, DatabaseConnection, IntoActiveModel, QueryOrder, prelude::*,
+};
+use snafu::ResultExt;
+use time::{Duration, OffsetDateTime, Time, UtcOffset, Weekday};
+
+use super::{
+    DataResult,
+    utils::DataTiming,
+    voice_analytics::{member_totals, session_spans},
+};
+use crate::entity::{
+    command_call_log, digest_settings, prelude::*, song_play_log, song_queues, sounds,
+    voice_sessions,
+};
+use crate::error::DatabaseSnafu;
+
+/// Entries listed in each section of a digest
+pub const DIGEST_SECTION_LEN: usize = 5;
+
+/// When a digest is posted, in a given timezone
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DigestSchedule {
+    pub weekday: Weekday,
+    /// Hour of the day, 0 to 23
+    pub hour: u8,
+}
+
+impl Default for DigestSchedule {
+    /// Monday at 20:00
+    fn default() -> Self {
+        Self {
+            weekday: Weekday::Monday,
+            hour: 20,
+        }
+    }
+}
+
+impl DigestSchedule {
+    /// The latest scheduled time not after `now`
+    pub fn last_due_at(&self, now: OffsetDateTime, offset: UtcOffset) -> OffsetDateTime {
+        let local = now.to_offset(offset);
+        let days_back = (local.weekday().number_days_from_monday() as i64
+            - self.weekday.number_days_from_monday() as i64)
+            .rem_euclid(7);
+        let hour = Time::from_hms(self.hour.min(23), 0, 0).unwrap_or(Time::MIDNIGHT);
+        let candidate = (local - Duration::days(days_back)).replace_time(hour);
+        if candidate > local {
+            candidate - Duration::weeks(1)
+        } else {
+            candidate
+        }
+    }
+
+    /// Whether a digest should be posted now, ie: the last scheduled time passed since the
+    /// last post
+    pub fn is_due(
+        &self,
+        now: OffsetDateTime,
+        last_posted_at: Option<OffsetDateTime>,
+        offset: UtcOffset,
+    ) -> bool {
+        let due_at = self.last_due_at(now, offset);
+        last_posted_at.is_none_or(|posted| posted < due_at)
+    }
+}
+
+/// Which sections a digest contains
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DigestSections {
+    pub voice: bool,
+    pub songs: bool,
+    pub commands: bool,
+    pub sounds: bool,
+}
+
+impl Default for DigestSections {
+    fn default() -> Self {
+        Self {
+            voice: true,
+            songs: true,
+            commands: true,
+            sounds: true,
+        }
+    }
+}
+
+/// Digest settings of a guild
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DigestConfig {
+    pub channel_id: u64,
+    pub enabled: bool,
+    pub schedule: DigestSchedule,
+    pub sections: DigestSections,
+    pub last_posted_at: Option<OffsetDateTime>,
+}
+
+impl DigestConfig {
+    /// An enabled digest with the default schedule and every section
+    pub fn new(channel_id: u64) -> Self {
+        Self {
+            channel_id,
+            enabled: true,
+            schedule: DigestSchedule::default(),
+            sections: DigestSections::default(),
+            last_posted_at: None,
+        }
+    }
+}
+
+impl From<digest_settings::Model> for DigestConfig {
+    fn from(value: digest_settings::Model) -> Self {
+        Self {
+            channel_id: value.channel_id as u64,
+            enabled: value.enabled,
+            schedule: DigestSchedule {
+                weekday: weekday_from_monday(value.weekday),
+                hour: value.hour.clamp(0, 23) as u8,
+            },
+            sections: DigestSections {
+                voice: value.include_voice,
+                songs: value.include_songs,
+                commands: value.include_commands,
+                sounds: value.include_sounds,
+            },
+            last_posted_at: value.last_posted_at,
+        }
+    }
+}
+
+fn weekday_from_monday(days: i32) -> Weekday {
+    let mut weekday = Weekday::Monday;
+    for _ in 0..days.rem_euclid(7) {
+        weekday = weekday.next();
+    }
+    weekday
+}
+
+/// A song of the digest
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DigestSong {
+    pub youtube_id: String,
+    pub description: Option<String>,
+    /// Plays by every member during the week
+    pub plays: i64,
+}
+
+/// Content of a digest. Sections that are turned off stay empty.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DigestContent {
+    /// Members with the most time in voice during the week
+    pub top_voice: Vec<(u64, Duration)>,
+    /// Songs played during the week, most played first
+    pub top_songs: Vec<DigestSong>,
+    /// Most used commands of the server during the week
+    pub top_commands: Vec<(String, i64)>,
+    /// Sounds uploaded in the server during the week, oldest first
+    pub new_sounds: Vec<sounds::Model>,
+}
+
+impl DigestContent {
+    pub fn is_empty(&self) -> bool {
+        self.top_voice.is_empty()
+            && self.top_songs.is_empty()
+            && self.top_commands.is_empty()
+            && self.new_sounds.is_empty()
+    }
+}
+
+#[derive(Clone)]
+pub struct DigestManager {
+    db: DatabaseConnection,
+    metrics_handler: Arc<dyn MetricsSink>,
+}
+
+impl DigestManager {
+    /// Create a new instance of [`Self`]
+    pub fn new(db: DatabaseConnection, metrics_handler: Arc<dyn MetricsSink>) -> Self {
+        Self {
+            db,
+            metrics_handler,
+        }
+    }
+
+    /// Get the digest settings of a guild, if it has a digest
+    pub async fn get_digest(&self, guild_id: u64) -> DataResult<Option<DigestConfig>> {
+        const OP: &str = "get_digest";
+        self.metrics_handler
+            .data_access(OP, DataOperationType::Read)
+            .await;
+        let _timing = DataTiming::new(
+            OP.to_string(),
+            DataOperationType::Read,
+            Some(self.metrics_handler.clone()),
+        );
+
+        let model = DigestSettings::find_by_id(guild_id as i64)
+            .one(&self.db)
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+
+        Ok(model.map(DigestConfig::from))
+    }
+
+    /// Store the digest settings of a guild, replacing any previous ones
+    pub async fn save_digest(&self, guild_id: u64, config: &DigestConfig) -> DataResult<()> {
+        const OP: &str = "save_digest";
+        self.metrics_handler
+            .data_access(OP, DataOperationType::Write)
+            .await;
+        let _timing = DataTiming::new(
+            OP.to_string(),
+            DataOperationType::Write,
+            Some(self.metrics_handler.clone()),
+        );
+
+        let existing = DigestSettings::find_by_id(guild_id as i64)
+            .one(&self.db)
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+
+        let mut active = match existing {
+            Some(model) => model.into_active_model(),
+            None => digest_settings::ActiveModel {
+                guild_id: ActiveValue::Set(guild_id as i64),
+                ..Default::default()
+            },
+        };
+        active.channel_id = ActiveValue::Set(config.channel_id as i64);
+        active.enabled = ActiveValue::Set(config.enabled);
+        active.weekday = ActiveValue::Set(config.schedule.weekday.number_days_from_monday() as i32);
+        active.hour = ActiveValue::Set(config.schedule.hour.min(23) as i32);
+        active.include_voice = ActiveValue::Set(config.sections.voice);
+        active.include_songs = ActiveValue::Set(config.sections.songs);
+        active.include_commands = ActiveValue::Set(config.sections.commands);
+        active.include_sounds = ActiveValue::Set(config.sections.sounds);
+        active.last_posted_at = ActiveValue::Set(config.last_posted_at);
+        active
+            .save(&self.db)
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+
+        Ok(())
+    }
+
+    /// Remove the digest of a guild
+    pub async fn delete_digest(&self, guild_id: u64) -> DataResult<()> {
+        const OP: &str = "delete_digest";
+        self.metrics_handler
+            .data_access(OP, DataOperationType::Write)
+            .await;
+        let _timing = DataTiming::new(
+            OP.to_string(),
+            DataOperationType::Write,
+            Some(self.metrics_handler.clone()),
+        );
+
+        DigestSettings::delete_by_id(guild_id as i64)
+            .exec(&self.db)
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+
+        Ok(())
+    }
+
+    /// Enabled digests whose scheduled time passed since their last post
+    pub async fn get_due_digests(
+        &self,
+        now: OffsetDateTime,
+        offset: UtcOffset,
+    ) -> DataResult<Vec<(u64, DigestConfig)>> {
+        const OP: &str = "get_due_digests";
+        self.metrics_handler
+            .data_access(OP, DataOperationType::Read)
+            .await;
+        let _timing = DataTiming::new(
+            OP.to_string(),
+            DataOperationType::Read,
+            Some(self.metrics_handler.clone()),
+        );
+
+        let models = DigestSettings::find()
+            .filter(digest_settings::Column::Enabled.eq(true))
+            .all(&self.db)
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+
+        Ok(models
+            .into_iter()
+            .map(|model| (model.guild_id as u64, DigestConfig::from(model)))
+            .filter(|(_, config)| config.schedule.is_due(now, config.last_posted_at, offset))
+            .collect())
+    }
+
+    /// Remember when the digest of a guild was last posted
+    pub async fn mark_digest_posted(
+        &self,
+        guild_id: u64,
+        posted_at: OffsetDateTime,
+    ) -> DataResult<()> {
+        const OP: &str = "mark_digest_posted";
+        self.metrics_handler
+            .data_access(OP, DataOperationType::Write)
+            .await;
+        let _timing = DataTiming::new(
+            OP.to_string(),
+            DataOperationType::Write,
+            Some(self.metrics_handler.clone()),
+        );
+
+        DigestSettings::update_many()
+            .col_expr(
+                digest_settings::Column::LastPostedAt,
+                sea_orm::sea_query::Expr::value(posted_at),
+            )
+            .filter(digest_settings::Column::GuildId.eq(guild_id as i64))
+            .exec(&self.db)
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+
+        Ok(())
+    }
+
+    /// Gather the sections of a digest covering `since` to `now`
+    pub async fn build_digest(
+        &self,
+        guild_id: u64,
+        sections: DigestSections,
+        since: OffsetDateTime,
+        now: OffsetDateTime,
+    ) -> DataResult<DigestContent> {
+        const OP: &str = "build_digest";
+        self.metrics_handler
+            .data_access(OP, DataOperationType::Read)
+            .await;
+        let _timing = DataTiming::new(
+            OP.to_string(),
+            DataOperationType::Read,
+            Some(self.metrics_handler.clone()),
+        );
+
+        let guild = guild_id as i64;
+        let mut content = DigestContent::default();
+
+        if sections.voice {
+            let sessions = VoiceSessions::find()
+                .filter(voice_sessions::Column::GuildId.eq(guild))
+                .filter(
+                    Condition::any()
+                        .add(voice_sessions::Column::LeftAt.is_null())
+                        .add(voice_sessions::Column::LeftAt.gte(since)),
+                )
+                .all(&self.db)
+                .await
+                .context(DatabaseSnafu { operation: OP })?;
+            content.top_voice = member_totals(&session_spans(&sessions, Some(since), now), None);
+            content.top_voice.truncate(DIGEST_SECTION_LEN);
+        }
+
+        if sections.songs {
+            let plays = SongPlayLog::find()
+                .filter(song_play_log::Column::ServerId.eq(guild))
+                .filter(song_play_log::Column::PlayedAt.gte(since))
+                .filter(song_play_log::Column::PlayedAt.lt(now))
+                .all(&self.db)
+                .await
+                .context(DatabaseSnafu { operation: OP })?;
+            let mut top_songs = count_by_name(plays.into_iter().map(|play| play.youtube_id));
+            top_songs.truncate(DIGEST_SECTION_LEN);
+
+            // the play log has no descriptions, the all time counts keep the latest one
+            let descriptions = SongQueues::find()
+                .filter(song_queues::Column::ServerId.eq(guild))
+                .filter(
+                    song_queues::Column::YoutubeId
+                        .is_in(top_songs.iter().map(|(youtube_id, _)| youtube_id.as_str())),
+                )
+                .filter(song_queues::Column::Description.is_not_null())
+                .all(&self.db)
+                .await
+                .context(DatabaseSnafu { operation: OP })?;
+            content.top_songs = top_songs
+                .into_iter()
+                .map(|(youtube_id, plays)| DigestSong {
+                    description: descriptions
+                        .iter()
+                        .find(|song| song.youtube_id == youtube_id)
+                        .and_then(|song| song.description.clone()),
+                    youtube_id,
+                    plays,
+                })
+                .collect();
+        }
+
+        if sections.commands {
+            let calls = CommandCallLog::find()
+                .filter(command_call_log::Column::ServerId.eq(guild))
+                .filter(command_call_log::Column::CommandTimeStamp.gte(since))
+                .filter(command_call_log::Column::CommandTimeStamp.lt(now))
+                .all(&self.db)
+                .await
+                .context(DatabaseSnafu { operation: OP })?;
+            content.top_commands = count_by_name(calls.into_iter().map(|call| call.command));
+            content.top_commands.truncate(DIGEST_SECTION_LEN);
+        }
+
+        if sections.sounds {
+            content.new_sounds = Sounds::find()
+                .filter(sounds::Column::UploadedServerId.eq(guild))
+                .filter(sounds::Column::UploadedAt.gte(since))
+                .order_by_asc(sounds::Column::UploadedAt)
+                .all(&self.db)
+                .await
+                .context(DatabaseSnafu { operation: OP })?;
+        }
+
+        Ok(content)
+    }
+}
+
+/// How often each name appears, most frequent first then by name
+fn count_by_name(names: impl Iterator<Item = String>) -> Vec<(String, i64)> {
+    let mut counts: Vec<(String, i64)> = Vec::new();
+    for name in names {
+        match counts.iter_mut().find(|(entry, _)| *entry == name) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((name, 1)),
+        }
+    }
+    counts.sort_by(|(a_name, a), (b_name, b)| b.cmp(a).then(a_name.cmp(b_name)));
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::*;
+    use crate::data::voice::{VoiceManager, VoiceStateUpdateInput};
+    use ayaya_core::metrics::NoopMetrics;
+
+    use migration::Migrator as SqliteMigrator;
+    use migration::MigratorTrait;
+    use sea_orm::Database;
+    use time::macros::{datetime, offset};
+    use uuid::Uuid;
+
+    async fn get_db() -> DatabaseConnection {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        SqliteMigrator::up(&db, None).await.unwrap();
+        db
+    }
+
+    #[test]
+    fn schedule_is_due_once_a_week() {
+        let schedule = DigestSchedule {
+            weekday: Weekday::Sunday,
+            hour: 20,
+        };
+        let offset = offset!(+8);
+
+        // Sunday 2026-10-18 20:00 in UTC+8
+        let due_at = datetime!(2026-10-18 12:00 UTC);
+        assert_eq!(schedule.last_due_at(due_at, offset), due_at);
+        assert_eq!(
+            schedule.last_due_at(due_at - Duration::minutes(1), offset),
+            due_at - Duration::weeks(1)
+        );
+        assert_eq!(
+            schedule.last_due_at(due_at + Duration::days(3), offset),
+            due_at
+        );
+
+        assert!(schedule.is_due(due_at, None, offset));
+        assert!(schedule.is_due(due_at, Some(due_at - Duration::days(7)), offset));
+        assert!(!schedule.is_due(
+            due_at + Duration::hours(1),
+            Some(due_at + Duration::minutes(1)),
+            offset
+        ));
+        assert!(!schedule.is_due(
+            due_at - Duration::hours(1),
+            Some(due_at - Duration::days(6)),
+            offset
+        ));
+    }
+
+    #[tokio::test]
+    async fn save_and_find_due_digests() {
+        let manager = DigestManager::new(get_db().await, Arc::new(NoopMetrics));
+        assert!(manager.get_digest(GUILD_ID_1).await.unwrap().is_none());
+
+        let config = DigestConfig {
+            schedule: DigestSchedule {
+                weekday: Weekday::Sunday,
+                hour: 20,
+            },
+            sections: DigestSections {
+                songs: false,
+                ..Default::default()
+            },
+            ..DigestConfig::new(1)
+        };
+        manager.save_digest(GUILD_ID_1, &config).await.unwrap();
+        assert_eq!(
+            manager.get_digest(GUILD_ID_1).await.unwrap(),
+            Some(config.clone())
+        );
+
+        let due_at = datetime!(2026-10-18 12:00 UTC);
+        let due = manager.get_due_digests(due_at, offset!(+8)).await.unwrap();
+        assert_eq!(due, vec![(GUILD_ID_1, config.clone())]);
+
+        manager
+            .mark_digest_posted(GUILD_ID_1, due_at)
+            .await
+            .unwrap();
+        assert!(
+            manager
+                .get_due_digests(due_at + Duration::hours(1), offset!(+8))
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        // disabled digests are never due
+        let config = DigestConfig {
+            enabled: false,
+            last_posted_at: None,
+            ..config
+        };
+        manager.save_digest(GUILD_ID_1, &config).await.unwrap();
+        assert!(
+            manager
+                .get_due_digests(due_at, offset!(+8))
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        manager.delete_digest(GUILD_ID_1).await.unwrap();
+        assert!(manager.get_digest(GUILD_ID_1).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn build_digest_sections() {
+        let db = get_db().await;
+        let manager = DigestManager::new(db.clone(), Arc::new(NoopMetrics));
+        let now = datetime!(2026-10-18 12:00 UTC);
+        let since = now - Duration::weeks(1);
+        let guild = GUILD_ID_1 as i64;
+
+        let voice = VoiceManager::new(db.clone(), Arc::new(NoopMetrics));
+        for (user_id, from, to, at) in [
+            (USER_ID_1, None, Some(1), now - Duration::hours(3)),
+            (USER_ID_2, None, Some(1), now - Duration::hours(1)),
+            (USER_ID_1, Some(1), None, now - Duration::hours(1)),
+        ] {
+            voice
+                .apply_voice_state_update(VoiceStateUpdateInput {
+                    guild_id: guild,
+                    user_id: user_id.get() as i64,
+                    from_channel_id: from,
+                    to_channel_id: to,
+                    occurred_at: at,
+                    self_mute: false,
+                    self_deaf: false,
+                    mute: false,
+                    deaf: false,
+                    self_stream: false,
+                    self_video: false,
+                    suppress: false,
+                    request_to_speak_at: None,
+                    raw_state_json: None,
+                    start_is_estimated: false,
+                })
+                .await
+                .unwrap();
+        }
+
+        // "b" has the most plays all time, but not this week
+        for (user_id, youtube_id, description, count) in [
+            (USER_ID_1, "a", Some("Song A"), 3),
+            (USER_ID_2, "b", None, 40),
+            (USER_ID_1, "c", None, 9),
+        ] {
+            song_queues::ActiveModel {
+                server_id: ActiveValue::Set(guild),
+                user_id: ActiveValue::Set(user_id.get() as i64),
+                youtube_id: ActiveValue::Set(youtube_id.to_string()),
+                description: ActiveValue::Set(description.map(str::to_string)),
+                count: ActiveValue::Set(count),
+                last_update: ActiveValue::Set(Some(now - Duration::days(1))),
+            }
+            .insert(&db)
+            .await
+            .unwrap();
+        }
+        for (user_id, youtube_id, played_at) in [
+            (USER_ID_1, "a", now - Duration::days(1)),
+            (USER_ID_2, "a", now - Duration::days(2)),
+            (USER_ID_2, "a", now - Duration::days(10)),
+            (USER_ID_2, "b", now - Duration::days(1)),
+            (USER_ID_2, "b", now - Duration::days(20)),
+            (USER_ID_2, "b", now - Duration::days(30)),
+            (USER_ID_1, "c", now - Duration::days(30)),
+        ] {
+            song_play_log::ActiveModel {
+                play_id: ActiveValue::Set(Uuid::now_v7()),
+                server_id: ActiveValue::Set(guild),
+                user_id: ActiveValue::Set(user_id.get() as i64),
+                youtube_id: ActiveValue::Set(youtube_id.to_string()),
+                played_at: ActiveValue::Set(played_at),
+            }
+            .insert(&db)
+            .await
+            .unwrap();
+        }
+
+        for (user_id, command, called_at) in [
+            (USER_ID_1, "play", now - Duration::days(1)),
+            (USER_ID_2, "play", now - Duration::days(3)),
+            (USER_ID_2, "skip", now - Duration::days(2)),
+            (USER_ID_2, "skip", now - Duration::days(9)),
+            (USER_ID_2, "skip", now - Duration::days(10)),
+            (USER_ID_1, "join", now - Duration::days(4)),
+        ] {
+            command_call_log::ActiveModel {
+                log_id: ActiveValue::Set(Uuid::now_v7()),
+                server_id: ActiveValue::Set(guild),
+                user_id: ActiveValue::Set(user_id.get() as i64),
+                command: ActiveValue::Set(command.to_string()),
+                command_time_stamp: ActiveValue::Set(called_at),
+            }
+            .insert(&db)
+            .await
+            .unwrap();
+        }
+
+        for (name, uploaded_at) in [
+            ("new", Some(now - Duration::days(1))),
+            ("old", Some(now - Duration::days(8))),
+            ("unknown", None),
+        ] {
+            sounds::ActiveModel {
+                sound_id: ActiveValue::Set(Uuid::now_v7()),
+                user_id: ActiveValue::Set(USER_ID_1.get() as i64),
+                uploaded_server_id: ActiveValue::Set(guild),
+                sound_name: ActiveValue::Set(name.to_string()),
+                public: ActiveValue::Set(true),
+                uploaded_at: ActiveValue::Set(uploaded_at),
+            }
+            .insert(&db)
+            .await
+            .unwrap();
+        }
+
+        let content = manager
+            .build_digest(GUILD_ID_1, DigestSections::default(), since, now)
+            .await
+            .unwrap();
+        assert_eq!(
+            content.top_voice,
+            vec![
+                (USER_ID_1.get(), Duration::hours(2)),
+                (USER_ID_2.get(), Duration::hours(1)),
+            ]
+        );
+        assert_eq!(
+            content.top_songs,
+            vec![
+                DigestSong {
+                    youtube_id: "a".to_string(),
+                    description: Some("Song A".to_string()),
+                    plays: 2,
+                },
+                DigestSong {
+                    youtube_id: "b".to_string(),
+                    description: None,
+                    plays: 1,
+                },
+            ]
+        );
+        assert_eq!(
+            content.top_commands,
+            vec![
+                ("play".to_string(), 2),
+                ("join".to_string(), 1),
+                ("skip".to_string(), 1),
+            ]
+        );
+        assert_eq!(content.new_sounds.len(), 1);
+        assert_eq!(content.new_sounds[0].sound_name, "new");
+
+        let content = manager
+            .build_digest(
+                GUILD_ID_1,
+                DigestSections {
+                    voice: false,
+                    songs: false,
+                    commands: false,
+                    sounds: false,
+                },
+                since,
+                now,
+            )
+            .await
+            .unwrap();
+        assert!(content.is_empty());
+    }
+}
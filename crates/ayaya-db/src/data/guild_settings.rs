@@ -0,0 +1,262 @@
+//! General per guild settings, such as the command prefix. Prefix commands read them on every
+//! message, so they are kept in memory once loaded.
+use std::{collections::HashMap, sync::Arc};
+
+use ayaya_core::metrics::{DataOperationType, MetricsSink};
+use sea_orm::{ActiveValue, DatabaseConnection, prelude::*, sea_query::OnConflict};
+use snafu::ResultExt;
+use tokio::sync::RwLock;
+
+use super::{DataResult, utils::DataTiming};
+use crate::entity::{guild_settings, prelude::*};
+use crate::error::DatabaseSnafu;
+
+/// A setting of [`GuildConfig`], as named in commands
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum GuildSettingKey {
+    Prefix,
+    MusicChannel,
+    PublicUploads,
+    AuditChannel,
+}
+
+impl GuildSettingKey {
+    pub const ALL: [Self; 4] = [
+        Self::Prefix,
+        Self::MusicChannel,
+        Self::PublicUploads,
+        Self::AuditChannel,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Prefix => "prefix",
+            Self::MusicChannel => "music_channel",
+            Self::PublicUploads => "public_uploads",
+            Self::AuditChannel => "audit_channel",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::Prefix => "Prefix of prefix commands",
+            Self::MusicChannel => "Channel music notices are posted to",
+            Self::PublicUploads => "Whether uploaded sounds are public by default",
+            Self::AuditChannel => "Channel configuration changes are mirrored to",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|key| key.name() == name)
+    }
+}
+
+/// General settings of a guild. Unset values fall back to the bot's defaults.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GuildConfig {
+    /// Prefix of prefix commands
+    pub prefix: Option<String>,
+    /// Channel music notices are posted to, instead of the channel playback was started from
+    pub music_channel_id: Option<u64>,
+    /// Whether uploaded sounds are public when the uploader does not say
+    pub public_uploads: Option<bool>,
+    /// Channel audit log entries of the guild are mirrored to
+    pub audit_channel_id: Option<u64>,
+}
+
+impl GuildConfig {
+    /// Unset a setting, going back to its default
+    pub fn reset(&mut self, key: GuildSettingKey) {
+        match key {
+            GuildSettingKey::Prefix => self.prefix = None,
+            GuildSettingKey::MusicChannel => self.music_channel_id = None,
+            GuildSettingKey::PublicUploads => self.public_uploads = None,
+            GuildSettingKey::AuditChannel => self.audit_channel_id = None,
+        }
+    }
+
+    /// Whether the setting is set
+    pub fn is_set(&self, key: GuildSettingKey) -> bool {
+        match key {
+            GuildSettingKey::Prefix => self.prefix.is_some(),
+            GuildSettingKey::MusicChannel => self.music_channel_id.is_some(),
+            GuildSettingKey::PublicUploads => self.public_uploads.is_some(),
+            GuildSettingKey::AuditChannel => self.audit_channel_id.is_some(),
+        }
+    }
+}
+
+impl From<guild_settings::Model> for GuildConfig {
+    fn from(value: guild_settings::Model) -> Self {
+        Self {
+            prefix: value.prefix,
+            music_channel_id: value.music_channel_id.map(|id| id as u64),
+            public_uploads: value.public_uploads,
+            audit_channel_id: value.audit_channel_id.map(|id| id as u64),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct GuildSettingsManager {
+    db: DatabaseConnection,
+    metrics_handler: Arc<dyn MetricsSink>,
+    /// Loaded settings of each guild, including guilds without any
+    cache: Arc<RwLock<HashMap<u64, GuildConfig>>>,
+}
+
+impl GuildSettingsManager {
+    /// Create a new instance of [`Self`]
+    pub fn new(db: DatabaseConnection, metrics_handler: Arc<dyn MetricsSink>) -> Self {
+        Self {
+            db,
+            metrics_handler,
+            cache: Default::default(),
+        }
+    }
+
+    /// Get the settings of a guild, the defaults if it never changed any
+    pub async fn get_settings(&self, guild_id: u64) -> DataResult<GuildConfig> {
+        const OP: &str = "get_guild_settings";
+        let cached = self.cache.read().await.get(&guild_id).cloned();
+        self.metrics_handler
+            .cache_access("guild_settings_cache", cached.is_some())
+            .await;
+        if let Some(config) = cached {
+            return Ok(config);
+        }
+
+        self.metrics_handler
+            .data_access(OP, DataOperationType::Read)
+            .await;
+        let _timing = DataTiming::new(
+            OP.to_string(),
+            DataOperationType::Read,
+            Some(self.metrics_handler.clone()),
+        );
+
+        let config = GuildSettings::find_by_id(guild_id as i64)
+            .one(&self.db)
+            .await
+            .context(DatabaseSnafu { operation: OP })?
+            .map(GuildConfig::from)
+            .unwrap_or_default();
+        self.cache.write().await.insert(guild_id, config.clone());
+        Ok(config)
+    }
+
+    /// Store the settings of a guild, replacing the previous ones. Settings equal to the defaults
+    /// are removed.
+    pub async fn save_settings(&self, guild_id: u64, config: &GuildConfig) -> DataResult<()> {
+        const OP: &str = "save_guild_settings";
+        self.metrics_handler
+            .data_access(OP, DataOperationType::Write)
+            .await;
+        let _timing = DataTiming::new(
+            OP.to_string(),
+            DataOperationType::Write,
+            Some(self.metrics_handler.clone()),
+        );
+
+        if *config == GuildConfig::default() {
+            GuildSettings::delete_by_id(guild_id as i64)
+                .exec(&self.db)
+                .await
+                .context(DatabaseSnafu { operation: OP })?;
+        } else {
+            GuildSettings::insert(guild_settings::ActiveModel {
+                guild_id: ActiveValue::Set(guild_id as i64),
+                prefix: ActiveValue::Set(config.prefix.clone()),
+                music_channel_id: ActiveValue::Set(config.music_channel_id.map(|id| id as i64)),
+                public_uploads: ActiveValue::Set(config.public_uploads),
+                audit_channel_id: ActiveValue::Set(config.audit_channel_id.map(|id| id as i64)),
+            })
+            .on_conflict(
+                OnConflict::column(guild_settings::Column::GuildId)
+                    .update_columns([
+                        guild_settings::Column::Prefix,
+                        guild_settings::Column::MusicChannelId,
+                        guild_settings::Column::PublicUploads,
+                        guild_settings::Column::AuditChannelId,
+                    ])
+                    .to_owned(),
+            )
+            .exec(&self.db)
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+        }
+
+        self.cache.write().await.insert(guild_id, config.clone());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::*;
+    use ayaya_core::metrics::NoopMetrics;
+
+    use migration::Migrator as SqliteMigrator;
+    use migration::MigratorTrait;
+    use sea_orm::Database;
+
+    async fn get_manager() -> GuildSettingsManager {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        SqliteMigrator::up(&db, None).await.unwrap();
+        GuildSettingsManager::new(db, Arc::new(NoopMetrics))
+    }
+
+    #[test]
+    fn keys_round_trip() {
+        for key in GuildSettingKey::ALL {
+            assert_eq!(GuildSettingKey::parse(key.name()), Some(key));
+        }
+        assert_eq!(GuildSettingKey::parse("unknown"), None);
+    }
+
+    #[tokio::test]
+    async fn save_and_reset_settings() {
+        let manager = get_manager().await;
+        assert_eq!(
+            manager.get_settings(GUILD_ID_1).await.unwrap(),
+            GuildConfig::default()
+        );
+
+        let mut config = GuildConfig {
+            prefix: Some("!".to_string()),
+            music_channel_id: Some(42),
+            public_uploads: Some(false),
+            audit_channel_id: Some(43),
+        };
+        manager.save_settings(GUILD_ID_1, &config).await.unwrap();
+        assert_eq!(manager.get_settings(GUILD_ID_1).await.unwrap(), config);
+
+        // a new manager reads from the database instead of the cache
+        let uncached = GuildSettingsManager::new(manager.db.clone(), Arc::new(NoopMetrics));
+        assert_eq!(uncached.get_settings(GUILD_ID_1).await.unwrap(), config);
+
+        config.reset(GuildSettingKey::Prefix);
+        assert!(!config.is_set(GuildSettingKey::Prefix));
+        manager.save_settings(GUILD_ID_1, &config).await.unwrap();
+        let uncached = GuildSettingsManager::new(manager.db.clone(), Arc::new(NoopMetrics));
+        assert_eq!(uncached.get_settings(GUILD_ID_1).await.unwrap(), config);
+
+        // back to the defaults, the row is removed
+        config.reset(GuildSettingKey::MusicChannel);
+        config.reset(GuildSettingKey::PublicUploads);
+        config.reset(GuildSettingKey::AuditChannel);
+        manager.save_settings(GUILD_ID_1, &config).await.unwrap();
+        assert!(
+            GuildSettings::find_by_id(GUILD_ID_1 as i64)
+                .one(&manager.db)
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert_eq!(
+            manager.get_settings(GUILD_ID_1).await.unwrap(),
+            GuildConfig::default()
+        );
+    }
+}
@@ -0,0 +1,251 @@
+//! Per guild settings deciding when the bot leaves an idle voice channel
+use std::{sync::Arc, time::Duration};
+
+use ayaya_core::metrics::{DataOperationType, MetricsSink};
+use sea_orm::{ActiveValue, DatabaseConnection, IntoActiveModel, prelude::*};
+use snafu::ResultExt;
+
+use super::{DataResult, utils::DataTiming};
+use crate::entity::{guild_idle_settings, prelude::*};
+use crate::error::DatabaseSnafu;
+
+/// Placeholder in the leave message replaced with the configured timeout
+pub const TIMEOUT_PLACEHOLDER: &str = "{timeout}";
+
+const DEFAULT_LEAVE_MESSAGE: &str =
+    "Left voice channel after {timeout} of inactivity. Ayaya got bored without you, you know";
+
+/// When the bot considers itself idle, and what it says when leaving
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IdlePolicy {
+    /// How long the bot stays inactive before leaving
+    pub timeout: Duration,
+    /// How often the inactivity check runs
+    pub check_interval: Duration,
+    /// Whether being alone in the channel counts as inactive
+    pub alone_is_inactive: bool,
+    /// Whether a paused track counts as inactive
+    pub paused_is_inactive: bool,
+    /// Custom leave message, [`TIMEOUT_PLACEHOLDER`] is replaced with the timeout
+    pub leave_message: Option<String>,
+}
+
+impl Default for IdlePolicy {
+    /// 5 checks of 60 seconds
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(300),
+            check_interval: Duration::from_secs(60),
+            alone_is_inactive: true,
+            paused_is_inactive: true,
+            leave_message: None,
+        }
+    }
+}
+
+impl IdlePolicy {
+    /// Number of consecutive inactive checks before leaving. Rounded up, and at least 1.
+    pub fn max_checks(&self) -> usize {
+        let interval = self.check_interval.as_secs().max(1);
+        self.timeout.as_secs().div_ceil(interval).max(1) as usize
+    }
+
+    /// The leave message with its placeholders filled in
+    pub fn render_leave_message(&self) -> String {
+        let timeout = humantime_secs(self.timeout);
+        self.leave_message
+            .as_deref()
+            .unwrap_or(DEFAULT_LEAVE_MESSAGE)
+            .replace(TIMEOUT_PLACEHOLDER, &timeout)
+    }
+}
+
+impl From<guild_idle_settings::Model> for IdlePolicy {
+    fn from(value: guild_idle_settings::Model) -> Self {
+        Self {
+            timeout: Duration::from_secs(value.timeout_secs.max(0) as u64),
+            check_interval: Duration::from_secs(value.check_interval_secs.max(0) as u64),
+            alone_is_inactive: value.alone_is_inactive,
+            paused_is_inactive: value.paused_is_inactive,
+            leave_message: value.leave_message,
+        }
+    }
+}
+
+/// Format whole seconds as "5m" or "1m 30s"
+fn humantime_secs(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match (secs / 60, secs % 60) {
+        (0, secs) => format!("{secs}s"),
+        (mins, 0) => format!("{mins}m"),
+        (mins, secs) => format!("{mins}m {secs}s"),
+    }
+}
+
+#[derive(Clone)]
+pub struct IdleSettingsManager {
+    db: DatabaseConnection,
+    metrics_handler: Arc<dyn MetricsSink>,
+}
+
+impl IdleSettingsManager {
+    /// Create a new instance of [`Self`]
+    pub fn new(db: DatabaseConnection, metrics_handler: Arc<dyn MetricsSink>) -> Self {
+        Self {
+            db,
+            metrics_handler,
+        }
+    }
+
+    /// Get the idle policy of a guild, or the default if it was never configured
+    pub async fn get_idle_policy(&self, guild_id: u64) -> DataResult<IdlePolicy> {
+        const OP: &str = "get_idle_policy";
+        self.metrics_handler
+            .data_access(OP, DataOperationType::Read)
+            .await;
+        let _timing = DataTiming::new(
+            OP.to_string(),
+            DataOperationType::Read,
+            Some(self.metrics_handler.clone()),
+        );
+
+        let model = GuildIdleSettings::find_by_id(guild_id as i64)
+            .one(&self.db)
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+
+        Ok(model.map(IdlePolicy::from).unwrap_or_default())
+    }
+
+    /// Store the idle policy of a guild, replacing any previous one
+    pub async fn set_idle_policy(&self, guild_id: u64, policy: &IdlePolicy) -> DataResult<()> {
+        const OP: &str = "set_idle_policy";
+        self.metrics_handler
+            .data_access(OP, DataOperationType::Write)
+            .await;
+        let _timing = DataTiming::new(
+            OP.to_string(),
+            DataOperationType::Write,
+            Some(self.metrics_handler.clone()),
+        );
+
+        let existing = GuildIdleSettings::find_by_id(guild_id as i64)
+            .one(&self.db)
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+
+        let timeout_secs = ActiveValue::Set(policy.timeout.as_secs().min(i32::MAX as u64) as i32);
+        let check_interval_secs =
+            ActiveValue::Set(policy.check_interval.as_secs().min(i32::MAX as u64) as i32);
+
+        if let Some(model) = existing {
+            let mut active = model.into_active_model();
+            active.timeout_secs = timeout_secs;
+            active.check_interval_secs = check_interval_secs;
+            active.alone_is_inactive = ActiveValue::Set(policy.alone_is_inactive);
+            active.paused_is_inactive = ActiveValue::Set(policy.paused_is_inactive);
+            active.leave_message = ActiveValue::Set(policy.leave_message.clone());
+            active
+                .save(&self.db)
+                .await
+                .context(DatabaseSnafu { operation: OP })?;
+        } else {
+            guild_idle_settings::ActiveModel {
+                guild_id: ActiveValue::Set(guild_id as i64),
+                timeout_secs,
+                check_interval_secs,
+                alone_is_inactive: ActiveValue::Set(policy.alone_is_inactive),
+                paused_is_inactive: ActiveValue::Set(policy.paused_is_inactive),
+                leave_message: ActiveValue::Set(policy.leave_message.clone()),
+            }
+            .insert(&self.db)
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+        }
+
+        Ok(())
+    }
+
+    /// Drop the guild's idle policy, going back to the default
+    pub async fn reset_idle_policy(&self, guild_id: u64) -> DataResult<()> {
+        const OP: &str = "reset_idle_policy";
+        self.metrics_handler
+            .data_access(OP, DataOperationType::Write)
+            .await;
+        let _timing = DataTiming::new(
+            OP.to_string(),
+            DataOperationType::Write,
+            Some(self.metrics_handler.clone()),
+        );
+
+        GuildIdleSettings::delete_by_id(guild_id as i64)
+            .exec(&self.db)
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::*;
+    use ayaya_core::metrics::NoopMetrics;
+
+    use migration::Migrator as SqliteMigrator;
+    use migration::MigratorTrait;
+    use sea_orm::Database;
+
+    async fn get_manager() -> IdleSettingsManager {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        SqliteMigrator::up(&db, None).await.unwrap();
+        IdleSettingsManager::new(db, Arc::new(NoopMetrics))
+    }
+
+    #[tokio::test]
+    async fn default_policy() {
+        let manager = get_manager().await;
+
+        let policy = manager.get_idle_policy(GUILD_ID_1).await.unwrap();
+
+        assert_eq!(policy, IdlePolicy::default());
+        assert_eq!(policy.max_checks(), 5);
+        assert_eq!(
+            policy.render_leave_message(),
+            "Left voice channel after 5m of inactivity. Ayaya got bored without you, you know"
+        );
+    }
+
+    #[tokio::test]
+    async fn set_and_reset_policy() {
+        let manager = get_manager().await;
+        let policy = IdlePolicy {
+            timeout: Duration::from_secs(90),
+            check_interval: Duration::from_secs(20),
+            alone_is_inactive: false,
+            paused_is_inactive: false,
+            leave_message: Some("bye after {timeout}".to_string()),
+        };
+
+        manager.set_idle_policy(GUILD_ID_1, &policy).await.unwrap();
+        let stored = manager.get_idle_policy(GUILD_ID_1).await.unwrap();
+        assert_eq!(stored, policy);
+        assert_eq!(stored.max_checks(), 5);
+        assert_eq!(stored.render_leave_message(), "bye after 1m 30s");
+
+        // overwrite
+        let policy = IdlePolicy {
+            leave_message: None,
+            ..policy
+        };
+        manager.set_idle_policy(GUILD_ID_1, &policy).await.unwrap();
+        assert_eq!(manager.get_idle_policy(GUILD_ID_1).await.unwrap(), policy);
+
+        manager.reset_idle_policy(GUILD_ID_1).await.unwrap();
+        assert_eq!(
+            manager.get_idle_policy(GUILD_ID_1).await.unwrap(),
+            IdlePolicy::default()
+        );
+    }
+}
@@ -1,18 +1,37 @@
 //! Manage database connection and caching
 //!
 pub mod akend_tracker;
+pub mod always_on;
+pub mod audit;
+pub mod command_stats;
+pub mod cooldowns;
 pub mod dashboard;
+pub mod digest;
+pub mod guild_settings;
+pub mod idle;
+pub mod permission_rules;
+pub mod permission_transfer;
 pub mod permissions;
 pub mod sounds;
 pub mod stats;
 mod utils;
 pub mod voice;
+pub mod voice_analytics;
+pub mod voice_feed;
+pub mod voice_privacy;
 pub mod wuwa_tracker;
 
 use std::sync::{Arc, Mutex};
 
 use crate::error::DataError;
 use crate::{data::akend_tracker::AkEndTracker, entity::prelude::*};
+use always_on::AlwaysOnManager;
+use audit::AuditManager;
+use command_stats::CommandStatsManager;
+use cooldowns::CooldownManager;
+use digest::DigestManager;
+use guild_settings::GuildSettingsManager;
+use idle::IdleSettingsManager;
 use lru_mem::LruCache;
 use migration::{Migrator as SqliteMigrator, MigratorTrait};
 use permissions::Permissions;
@@ -28,6 +47,8 @@ use stats::StatsManager;
 use time::UtcOffset;
 use utils::DataTiming;
 use voice::VoiceManager;
+use voice_feed::VoiceFeedManager;
+use voice_privacy::VoicePrivacyManager;
 
 use crate::data::wuwa_tracker::WuwaPullsManager;
 use crate::error::{
@@ -59,6 +80,15 @@ pub struct DataManager {
     voice: VoiceManager,
     wuwa_tracker: WuwaPullsManager,
     akend_tracker: AkEndTracker,
+    idle: IdleSettingsManager,
+    always_on: AlwaysOnManager,
+    voice_feed: VoiceFeedManager,
+    voice_privacy: VoicePrivacyManager,
+    digest: DigestManager,
+    command_stats: CommandStatsManager,
+    guild_settings: GuildSettingsManager,
+    audit: AuditManager,
+    cooldowns: CooldownManager,
     autocomplete_cache: Autocomplete,
 }
 
@@ -85,6 +115,15 @@ impl DataManager {
         let voice = VoiceManager::new(db.clone(), metrics_handler.clone());
         let wuwa_tracker = WuwaPullsManager::new(db.clone(), metrics_handler.clone());
         let akend_tracker = AkEndTracker::new(db.clone(), metrics_handler.clone());
+        let idle = IdleSettingsManager::new(db.clone(), metrics_handler.clone());
+        let always_on = AlwaysOnManager::new(db.clone(), metrics_handler.clone());
+        let voice_feed = VoiceFeedManager::new(db.clone(), metrics_handler.clone());
+        let voice_privacy = VoicePrivacyManager::new(db.clone(), metrics_handler.clone());
+        let digest = DigestManager::new(db.clone(), metrics_handler.clone());
+        let command_stats = CommandStatsManager::new(db.clone(), metrics_handler.clone());
+        let guild_settings = GuildSettingsManager::new(db.clone(), metrics_handler.clone());
+        let audit = AuditManager::new(db.clone(), metrics_handler.clone());
+        let cooldowns = CooldownManager::new(db.clone(), metrics_handler.clone());
         Ok(Self {
             db,
             metrics_handler,
@@ -94,6 +133,15 @@ impl DataManager {
             voice,
             wuwa_tracker,
             akend_tracker,
+            idle,
+            always_on,
+            voice_feed,
+            voice_privacy,
+            digest,
+            command_stats,
+            guild_settings,
+            audit,
+            cooldowns,
             autocomplete_cache: Arc::new(Mutex::new(LruCache::new(1000 * 1024))),
         })
     }
@@ -122,6 +170,42 @@ impl DataManager {
         self.akend_tracker.clone()
     }
 
+    pub fn idle(&self) -> IdleSettingsManager {
+        self.idle.clone()
+    }
+
+    pub fn always_on(&self) -> AlwaysOnManager {
+        self.always_on.clone()
+    }
+
+    pub fn voice_feed(&self) -> VoiceFeedManager {
+        self.voice_feed.clone()
+    }
+
+    pub fn voice_privacy(&self) -> VoicePrivacyManager {
+        self.voice_privacy.clone()
+    }
+
+    pub fn digest(&self) -> DigestManager {
+        self.digest.clone()
+    }
+
+    pub fn command_stats(&self) -> CommandStatsManager {
+        self.command_stats.clone()
+    }
+
+    pub fn guild_settings(&self) -> GuildSettingsManager {
+        self.guild_settings.clone()
+    }
+
+    pub fn audit(&self) -> AuditManager {
+        self.audit.clone()
+    }
+
+    pub fn cooldowns(&self) -> CooldownManager {
+        self.cooldowns.clone()
+    }
+
     /// Log command calls to the database. Will also increment the command counter.
     pub async fn log_command_call(
         &mut self,
@@ -0,0 +1,410 @@
+//! Rules deciding whether a member may call a command, and the order they are checked in.
+//!
+//! Rules are stored per command or per command category. A command's own role and channel rules
+//! replace its category's ones, while deny rules of both apply. The first matching step decides:
+//!
+//! 1. The user is denied the command or its category.
+//! 2. The command can only be used from some text channels, and this is not one of them.
+//! 3. The command can only be used from some voice channels, and the user is not in one of them.
+//! 4. The user is explicitly allowed the command.
+//! 5. One of the user's roles is denied the command or its category.
+//! 6. The command requires a role: allowed with one of them, denied otherwise.
+//! 7. The category requires a role: allowed with one of them, denied otherwise.
+//! 8. Nothing restricts the command, it is allowed.
+//!
+//! Channel scopes apply to explicitly allowed users too: they restrict where a command is used,
+//! not who uses it.
+
+/// Whether a rule is about a single command or a whole command category
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RuleScope {
+    Command,
+    Category,
+}
+
+impl RuleScope {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Command => "command",
+            Self::Category => "category",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "command" => Some(Self::Command),
+            "category" => Some(Self::Category),
+            _ => None,
+        }
+    }
+}
+
+/// Who a deny rule applies to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum DenyTarget {
+    User(u64),
+    Role(u64),
+}
+
+impl DenyTarget {
+    pub fn kind(self) -> &'static str {
+        match self {
+            Self::User(_) => "user",
+            Self::Role(_) => "role",
+        }
+    }
+
+    pub fn id(self) -> u64 {
+        match self {
+            Self::User(id) | Self::Role(id) => id,
+        }
+    }
+
+    pub fn from_parts(kind: &str, id: u64) -> Option<Self> {
+        match kind {
+            "user" => Some(Self::User(id)),
+            "role" => Some(Self::Role(id)),
+            _ => None,
+        }
+    }
+}
+
+/// The kind of channel a channel scope checks
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ChannelKind {
+    /// The channel the command is called from
+    Text,
+    /// The voice channel the user is in
+    Voice,
+}
+
+impl ChannelKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Text => "text",
+            Self::Voice => "voice",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "text" => Some(Self::Text),
+            "voice" => Some(Self::Voice),
+            _ => None,
+        }
+    }
+}
+
+/// A user or role denied a command or category
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DenyRule {
+    pub target: DenyTarget,
+    pub scope: RuleScope,
+}
+
+/// A channel a command or category can be used from
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChannelScope {
+    pub scope: RuleScope,
+    pub kind: ChannelKind,
+    pub channel_id: u64,
+}
+
+/// Every rule of a guild that applies to one command and one user
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PermissionRules {
+    pub user_allowed: bool,
+    pub command_roles: Vec<u64>,
+    pub category_roles: Vec<u64>,
+    pub denies: Vec<DenyRule>,
+    pub channel_scopes: Vec<ChannelScope>,
+}
+
+impl PermissionRules {
+    /// Whether the caller's roles can change the decision. If not, they don't need to be looked up.
+    pub fn uses_roles(&self) -> bool {
+        !self.command_roles.is_empty()
+            || !self.category_roles.is_empty()
+            || self
+                .denies
+                .iter()
+                .any(|deny| matches!(deny.target, DenyTarget::Role(_)))
+    }
+}
+
+/// Who calls a command, and from where
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Caller {
+    pub user_id: u64,
+    pub role_ids: Vec<u64>,
+    pub channel_id: u64,
+    pub voice_channel_id: Option<u64>,
+}
+
+/// The rule that decided, see the module documentation for the order
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DecidingRule {
+    UserDenied(RuleScope),
+    ChannelOutOfScope { scope: RuleScope, allowed: Vec<u64> },
+    VoiceChannelOutOfScope { scope: RuleScope, allowed: Vec<u64> },
+    UserAllowed,
+    RoleDenied { role_id: u64, scope: RuleScope },
+    CommandRole(u64),
+    MissingCommandRole(Vec<u64>),
+    CategoryRole(u64),
+    MissingCategoryRole(Vec<u64>),
+    Unrestricted,
+}
+
+impl DecidingRule {
+    pub fn allows(&self) -> bool {
+        matches!(
+            self,
+            Self::UserAllowed | Self::CommandRole(_) | Self::CategoryRole(_) | Self::Unrestricted
+        )
+    }
+}
+
+/// Decide whether the caller may use the command the rules were loaded for
+pub fn evaluate(rules: &PermissionRules, caller: &Caller) -> DecidingRule {
+    // 1. user denies, the command's before the category's
+    for scope in [RuleScope::Command, RuleScope::Category] {
+        if rules
+            .denies
+            .iter()
+            .any(|deny| deny.scope == scope && deny.target == DenyTarget::User(caller.user_id))
+        {
+            return DecidingRule::UserDenied(scope);
+        }
+    }
+
+    // 2. and 3. channel scopes
+    if let Some((scope, allowed)) = scoped_channels(rules, ChannelKind::Text)
+        && !allowed.contains(&caller.channel_id)
+    {
+        return DecidingRule::ChannelOutOfScope { scope, allowed };
+    }
+    if let Some((scope, allowed)) = scoped_channels(rules, ChannelKind::Voice)
+        && caller
+            .voice_channel_id
+            .is_none_or(|channel_id| !allowed.contains(&channel_id))
+    {
+        return DecidingRule::VoiceChannelOutOfScope { scope, allowed };
+    }
+
+    // 4. user allowance
+    if rules.user_allowed {
+        return DecidingRule::UserAllowed;
+    }
+
+    // 5. role denies
+    for scope in [RuleScope::Command, RuleScope::Category] {
+        if let Some(role_id) = rules.denies.iter().find_map(|deny| match deny.target {
+            DenyTarget::Role(role_id)
+                if deny.scope == scope && caller.role_ids.contains(&role_id) =>
+            {
+                Some(role_id)
+            }
+            _ => None,
+        }) {
+            return DecidingRule::RoleDenied { role_id, scope };
+        }
+    }
+
+    // 6. and 7. required roles
+    if !rules.command_roles.is_empty() {
+        return match matching_role(&rules.command_roles, caller) {
+            Some(role_id) => DecidingRule::CommandRole(role_id),
+            None => DecidingRule::MissingCommandRole(rules.command_roles.clone()),
+        };
+    }
+    if !rules.category_roles.is_empty() {
+        return match matching_role(&rules.category_roles, caller) {
+            Some(role_id) => DecidingRule::CategoryRole(role_id),
+            None => DecidingRule::MissingCategoryRole(rules.category_roles.clone()),
+        };
+    }
+
+    DecidingRule::Unrestricted
+}
+
+/// Channels of a kind the command is scoped to, the command's own if it has any
+fn scoped_channels(rules: &PermissionRules, kind: ChannelKind) -> Option<(RuleScope, Vec<u64>)> {
+    [RuleScope::Command, RuleScope::Category]
+        .into_iter()
+        .map(|scope| {
+            let channels = rules
+                .channel_scopes
+                .iter()
+                .filter(|channel| channel.scope == scope && channel.kind == kind)
+                .map(|channel| channel.channel_id)
+                .collect::<Vec<_>>();
+            (scope, channels)
+        })
+        .find(|(_, channels)| !channels.is_empty())
+}
+
+fn matching_role(roles: &[u64], caller: &Caller) -> Option<u64> {
+    roles
+        .iter()
+        .copied()
+        .find(|role_id| caller.role_ids.contains(role_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const USER: u64 = 1;
+    const ROLE_A: u64 = 10;
+    const ROLE_B: u64 = 11;
+    const MUSIC: u64 = 100;
+    const GENERAL: u64 = 101;
+    const VOICE: u64 = 200;
+
+    fn caller() -> Caller {
+        Caller {
+            user_id: USER,
+            role_ids: vec![ROLE_A],
+            channel_id: GENERAL,
+            voice_channel_id: None,
+        }
+    }
+
+    fn deny(target: DenyTarget, scope: RuleScope) -> DenyRule {
+        DenyRule { target, scope }
+    }
+
+    fn channel(scope: RuleScope, kind: ChannelKind, channel_id: u64) -> ChannelScope {
+        ChannelScope {
+            scope,
+            kind,
+            channel_id,
+        }
+    }
+
+    #[test]
+    fn unrestricted_and_roles() {
+        assert_eq!(
+            evaluate(&PermissionRules::default(), &caller()),
+            DecidingRule::Unrestricted
+        );
+
+        let rules = PermissionRules {
+            command_roles: vec![ROLE_B],
+            category_roles: vec![ROLE_A],
+            ..Default::default()
+        };
+        // the command's roles replace the category's
+        assert_eq!(
+            evaluate(&rules, &caller()),
+            DecidingRule::MissingCommandRole(vec![ROLE_B])
+        );
+
+        let rules = PermissionRules {
+            category_roles: vec![ROLE_B, ROLE_A],
+            ..Default::default()
+        };
+        assert_eq!(
+            evaluate(&rules, &caller()),
+            DecidingRule::CategoryRole(ROLE_A)
+        );
+    }
+
+    #[test]
+    fn denies_take_precedence() {
+        let rules = PermissionRules {
+            user_allowed: true,
+            denies: vec![deny(DenyTarget::User(USER), RuleScope::Category)],
+            ..Default::default()
+        };
+        assert_eq!(
+            evaluate(&rules, &caller()),
+            DecidingRule::UserDenied(RuleScope::Category)
+        );
+
+        // a user allowance beats role rules
+        let rules = PermissionRules {
+            user_allowed: true,
+            command_roles: vec![ROLE_B],
+            denies: vec![deny(DenyTarget::Role(ROLE_A), RuleScope::Command)],
+            ..Default::default()
+        };
+        assert_eq!(evaluate(&rules, &caller()), DecidingRule::UserAllowed);
+
+        let rules = PermissionRules {
+            command_roles: vec![ROLE_A],
+            denies: vec![deny(DenyTarget::Role(ROLE_A), RuleScope::Category)],
+            ..Default::default()
+        };
+        assert_eq!(
+            evaluate(&rules, &caller()),
+            DecidingRule::RoleDenied {
+                role_id: ROLE_A,
+                scope: RuleScope::Category
+            }
+        );
+
+        // denies of other roles don't apply
+        let rules = PermissionRules {
+            denies: vec![deny(DenyTarget::Role(ROLE_B), RuleScope::Command)],
+            ..Default::default()
+        };
+        assert_eq!(evaluate(&rules, &caller()), DecidingRule::Unrestricted);
+        assert!(rules.uses_roles());
+        assert!(
+            !PermissionRules {
+                user_allowed: true,
+                denies: vec![deny(DenyTarget::User(USER), RuleScope::Command)],
+                ..Default::default()
+            }
+            .uses_roles()
+        );
+    }
+
+    #[test]
+    fn channel_scopes() {
+        let rules = PermissionRules {
+            user_allowed: true,
+            channel_scopes: vec![
+                channel(RuleScope::Category, ChannelKind::Text, MUSIC),
+                channel(RuleScope::Category, ChannelKind::Voice, VOICE),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(
+            evaluate(&rules, &caller()),
+            DecidingRule::ChannelOutOfScope {
+                scope: RuleScope::Category,
+                allowed: vec![MUSIC]
+            }
+        );
+
+        let in_music = Caller {
+            channel_id: MUSIC,
+            ..caller()
+        };
+        assert_eq!(
+            evaluate(&rules, &in_music),
+            DecidingRule::VoiceChannelOutOfScope {
+                scope: RuleScope::Category,
+                allowed: vec![VOICE]
+            }
+        );
+        let in_voice = Caller {
+            voice_channel_id: Some(VOICE),
+            ..in_music
+        };
+        assert_eq!(evaluate(&rules, &in_voice), DecidingRule::UserAllowed);
+
+        // the command's channels replace the category's
+        let rules = PermissionRules {
+            channel_scopes: vec![
+                channel(RuleScope::Category, ChannelKind::Text, MUSIC),
+                channel(RuleScope::Command, ChannelKind::Text, GENERAL),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(evaluate(&rules, &caller()), DecidingRule::Unrestricted);
+    }
+}
@@ -0,0 +1,262 @@
+//! Moving the role restrictions and user allowances of a guild to another guild.
+//!
+//! Exports name the roles and users of every rule next to their ids. On import, roles and users
+//! are matched by name in the target guild first, then by id so an export can be restored into
+//! the guild it came from. Imports only add rules, they never remove any.
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+/// Bumped whenever the export format changes in an incompatible way
+pub const PERMISSION_EXPORT_VERSION: u32 = 1;
+
+/// A rule of the `require_command_role`, `require_category_role` or `command_allow_user` tables
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PermissionRow {
+    CommandRole { command: String, role_id: u64 },
+    CategoryRole { category: String, role_id: u64 },
+    AllowedUser { command: String, user_id: u64 },
+}
+
+/// The permission configuration of a guild, as written to the export file
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PermissionExport {
+    pub version: u32,
+    #[serde(default)]
+    pub command_roles: Vec<ExportedRoleRule>,
+    #[serde(default)]
+    pub category_roles: Vec<ExportedRoleRule>,
+    #[serde(default)]
+    pub allowed_users: Vec<ExportedUserRule>,
+}
+
+/// A command or category requiring a role
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportedRoleRule {
+    /// The command or category
+    pub name: String,
+    pub role_id: u64,
+    pub role_name: String,
+}
+
+/// A user always allowed a command
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportedUserRule {
+    pub command: String,
+    pub user_id: u64,
+    pub user_name: String,
+}
+
+/// The roles and members of a guild, by id and name
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GuildNames {
+    pub roles: Vec<(u64, String)>,
+    pub users: Vec<(u64, String)>,
+}
+
+impl GuildNames {
+    fn role_name(&self, role_id: u64) -> String {
+        find_name(&self.roles, role_id).unwrap_or_else(|| "unknown role".to_string())
+    }
+
+    fn user_name(&self, user_id: u64) -> String {
+        find_name(&self.users, user_id).unwrap_or_else(|| "unknown user".to_string())
+    }
+}
+
+/// Rules an import would add, and the ones it can't
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ImportPlan {
+    pub to_add: Vec<PermissionRow>,
+    /// Rules the target guild already has
+    pub already_present: usize,
+    /// Descriptions of rules whose role or user has no match in the target guild
+    pub unresolved: Vec<String>,
+}
+
+impl PermissionExport {
+    /// Export the rules of a guild, naming their roles and users with `names`
+    pub fn from_rows(rows: &[PermissionRow], names: &GuildNames) -> Self {
+        let mut export = Self {
+            version: PERMISSION_EXPORT_VERSION,
+            ..Default::default()
+        };
+        for row in rows {
+            match row {
+                PermissionRow::CommandRole { command, role_id } => {
+                    export.command_roles.push(ExportedRoleRule {
+                        name: command.clone(),
+                        role_id: *role_id,
+                        role_name: names.role_name(*role_id),
+                    })
+                }
+                PermissionRow::CategoryRole { category, role_id } => {
+                    export.category_roles.push(ExportedRoleRule {
+                        name: category.clone(),
+                        role_id: *role_id,
+                        role_name: names.role_name(*role_id),
+                    })
+                }
+                PermissionRow::AllowedUser { command, user_id } => {
+                    export.allowed_users.push(ExportedUserRule {
+                        command: command.clone(),
+                        user_id: *user_id,
+                        user_name: names.user_name(*user_id),
+                    })
+                }
+            }
+        }
+        export
+    }
+
+    /// The rules importing the export into a guild would add. `existing` are the rules the
+    /// guild already has, `target` its roles and members.
+    pub fn plan_import(&self, target: &GuildNames, existing: &[PermissionRow]) -> ImportPlan {
+        let mut plan = ImportPlan::default();
+        let mut seen = existing.iter().cloned().collect::<HashSet<_>>();
+        let mut push = |row: Option<PermissionRow>, description: String| match row {
+            Some(row) if seen.contains(&row) => plan.already_present += 1,
+            Some(row) => {
+                seen.insert(row.clone());
+                plan.to_add.push(row);
+            }
+            None => plan.unresolved.push(description),
+        };
+
+        for rule in &self.command_roles {
+            let row = resolve(&target.roles, &rule.role_name, rule.role_id).map(|role_id| {
+                PermissionRow::CommandRole {
+                    command: rule.name.clone(),
+                    role_id,
+                }
+            });
+            push(
+                row,
+                format!("command `{}` requires role {}", rule.name, rule.role_name),
+            );
+        }
+        for rule in &self.category_roles {
+            let row = resolve(&target.roles, &rule.role_name, rule.role_id).map(|role_id| {
+                PermissionRow::CategoryRole {
+                    category: rule.name.clone(),
+                    role_id,
+                }
+            });
+            push(
+                row,
+                format!("category `{}` requires role {}", rule.name, rule.role_name),
+            );
+        }
+        for rule in &self.allowed_users {
+            let row = resolve(&target.users, &rule.user_name, rule.user_id).map(|user_id| {
+                PermissionRow::AllowedUser {
+                    command: rule.command.clone(),
+                    user_id,
+                }
+            });
+            push(
+                row,
+                format!("command `{}` allows user {}", rule.command, rule.user_name),
+            );
+        }
+        plan
+    }
+}
+
+fn find_name(names: &[(u64, String)], id: u64) -> Option<String> {
+    names
+        .iter()
+        .find(|(other_id, _)| *other_id == id)
+        .map(|(_, name)| name.clone())
+}
+
+/// The id of the only role or user with the name, else the exported id if the target has it
+fn resolve(names: &[(u64, String)], name: &str, id: u64) -> Option<u64> {
+    let mut by_name = names.iter().filter(|(_, other)| other == name);
+    match (by_name.next(), by_name.next()) {
+        (Some((id, _)), None) => Some(*id),
+        _ => names
+            .iter()
+            .any(|(other_id, _)| *other_id == id)
+            .then_some(id),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source_names() -> GuildNames {
+        GuildNames {
+            roles: vec![(1, "DJ".to_string()), (2, "Mods".to_string())],
+            users: vec![(10, "alice".to_string())],
+        }
+    }
+
+    fn source_rows() -> Vec<PermissionRow> {
+        vec![
+            PermissionRow::CommandRole {
+                command: "play".to_string(),
+                role_id: 1,
+            },
+            PermissionRow::CategoryRole {
+                category: "Admin Commands".to_string(),
+                role_id: 2,
+            },
+            PermissionRow::AllowedUser {
+                command: "play".to_string(),
+                user_id: 10,
+            },
+        ]
+    }
+
+    #[test]
+    fn export_round_trips() {
+        let export = PermissionExport::from_rows(&source_rows(), &source_names());
+        assert_eq!(export.version, PERMISSION_EXPORT_VERSION);
+        assert_eq!(export.command_roles[0].role_name, "DJ");
+        assert_eq!(export.allowed_users[0].user_name, "alice");
+
+        let json = serde_json::to_string(&export).unwrap();
+        let parsed: PermissionExport = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed, export);
+
+        // restoring into the same guild adds nothing
+        let plan = export.plan_import(&source_names(), &source_rows());
+        assert!(plan.to_add.is_empty());
+        assert_eq!(plan.already_present, 3);
+    }
+
+    #[test]
+    fn import_maps_by_name() {
+        let export = PermissionExport::from_rows(&source_rows(), &source_names());
+        let target = GuildNames {
+            // a different DJ id, two roles named Mods, and nobody named alice
+            roles: vec![
+                (100, "DJ".to_string()),
+                (101, "Mods".to_string()),
+                (102, "Mods".to_string()),
+            ],
+            users: vec![(10, "alice_alt".to_string())],
+        };
+        let plan = export.plan_import(&target, &[]);
+        assert_eq!(
+            plan.to_add,
+            vec![
+                PermissionRow::CommandRole {
+                    command: "play".to_string(),
+                    role_id: 100,
+                },
+                // no single match by name, but the same user id is in the target
+                PermissionRow::AllowedUser {
+                    command: "play".to_string(),
+                    user_id: 10,
+                },
+            ]
+        );
+        assert_eq!(
+            plan.unresolved,
+            vec!["category `Admin Commands` requires role Mods".to_string()]
+        );
+    }
+}
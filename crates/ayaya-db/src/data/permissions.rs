@@ -2,19 +2,23 @@ use std::sync::Arc;
 
 use lru_mem::{HeapSize, LruCache};
 use poise::serenity_prelude as serenity;
-use sea_orm::{DatabaseConnection, prelude::*};
+use sea_orm::{DatabaseConnection, IntoActiveModel, prelude::*};
 use serenity::futures::TryFutureExt;
 use snafu::ResultExt;
 use tokio::sync::Mutex;
 
 use crate::entity::prelude::*;
 use crate::error::{
-    FindAllAllowedUserDatabaseSnafu, FindCategoryRolesAllowedDatabaseSnafu,
+    DatabaseSnafu, FindAllAllowedUserDatabaseSnafu, FindCategoryRolesAllowedDatabaseSnafu,
     NewCategoryRoleRestrictionDatabaseSnafu, NewCommandAllowedUserDatabaseSnafu,
     NewCommandRoleRestrictionDatabaseSnafu,
 };
 use ayaya_core::metrics::{DataOperationType, MetricsSink};
 
+use super::permission_rules::{
+    ChannelKind, ChannelScope, DenyRule, DenyTarget, PermissionRules, RuleScope,
+};
+use super::permission_transfer::PermissionRow;
 use super::{DataResult, utils::DataTiming};
 use crate::error::DataError;
 
@@ -48,11 +52,15 @@ impl Permissions {
     pub async fn permission_cache_access(&mut self, key: &PermissionCacheKey) -> Option<Vec<u8>> {
         let mut cache = self.cache.lock().await;
         let value = cache.get(key).cloned();
+        drop(cache);
         if value.is_some() {
             tracing::debug!("found in cache");
         } else {
             tracing::debug!("cache miss");
         }
+        self.metrics_handler
+            .cache_access("permission_cache", value.is_some())
+            .await;
         value
     }
 
@@ -416,154 +424,921 @@ impl Permissions {
             .await
             .context(FindAllAllowedUserDatabaseSnafu)
     }
-}
 
-impl Permissions {
-    pub async fn setup_cache_metrics(
-        metrics_handler: Arc<dyn MetricsSink>,
-        permission_cache: PermissionCache,
-    ) {
-        tokio::spawn(async move {
-            loop {
-                metrics_handler
-                    .cache_len("permission_cache", permission_cache.lock().await.len())
-                    .await;
-                tokio::time::sleep(std::time::Duration::from_secs(30)).await;
-            }
-        });
-    }
-}
+    /// Removes a command role restriction. Returns `false` if the role was not required for the
+    /// command.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if an error occured with the database.
+    pub async fn delete_command_role_restriction(
+        &mut self,
+        guild_id: u64,
+        role_id: &serenity::RoleId,
+        command: &str,
+    ) -> DataResult<bool> {
+        const OP: &str = "delete_command_role_restriction";
+        let _timing = DataTiming::new(
+            OP.to_string(),
+            DataOperationType::Write,
+            Some(self.metrics_handler.clone()),
+        );
+        self.metrics_handler
+            .data_access(OP, DataOperationType::Write)
+            .await;
 
-/// The cache key for the permission cache. Each detail is split into a field for easy comparison
-#[derive(Debug, Eq, Hash, PartialEq, Clone)]
-pub struct PermissionCacheKey {
-    /// The user id in u64, if any
-    pub user_id: Option<u64>,
-    /// The guild id in u64
-    pub guild_id: u64,
-    /// The name of the function calling
-    pub operation: &'static str,
-    /// The name of the bot command or command category
-    pub comorcat: String,
-}
+        use crate::entity::require_command_role;
+        let result = RequireCommandRole::delete_many()
+            .filter(require_command_role::Column::ServerId.eq(guild_id as i64))
+            .filter(require_command_role::Column::RoleId.eq(role_id.get() as i64))
+            .filter(require_command_role::Column::Command.eq(command))
+            .exec(&self.db)
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
 
-impl HeapSize for PermissionCacheKey {
-    fn heap_size(&self) -> usize {
-        self.comorcat.heap_size() + self.operation.heap_size()
+        // cache invalidation
+        self.permission_cache_invalidate(PermissionCacheKey {
+            user_id: None,
+            guild_id,
+            operation: "",
+            comorcat: command.to_string(),
+        })
+        .await;
+        Ok(result.rows_affected > 0)
     }
-}
-
-#[cfg(test)]
-mod tests {
 
-    use super::*;
-    use crate::constants::*;
-    use ayaya_core::metrics::NoopMetrics;
+    /// Removes a category role restriction. Returns `false` if the role was not required for the
+    /// category.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if an error occured with the database.
+    pub async fn delete_category_role_restriction(
+        &mut self,
+        guild_id: u64,
+        role_id: &serenity::RoleId,
+        command_category: &str,
+    ) -> DataResult<bool> {
+        const OP: &str = "delete_category_role_restriction";
+        let _timing = DataTiming::new(
+            OP.to_string(),
+            DataOperationType::Write,
+            Some(self.metrics_handler.clone()),
+        );
+        self.metrics_handler
+            .data_access(OP, DataOperationType::Write)
+            .await;
 
-    use migration::{Migrator as SqliteMigrator, MigratorTrait};
-    use sea_orm::Database;
+        use crate::entity::require_category_role;
+        let result = RequireCategoryRole::delete_many()
+            .filter(require_category_role::Column::ServerId.eq(guild_id as i64))
+            .filter(require_category_role::Column::RoleId.eq(role_id.get() as i64))
+            .filter(require_category_role::Column::Category.eq(command_category))
+            .exec(&self.db)
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
 
-    async fn get_manager() -> Permissions {
-        let db = Database::connect("sqlite::memory:").await.unwrap();
-        SqliteMigrator::up(&db, None).await.unwrap();
-        Permissions::new(db, Arc::new(NoopMetrics)).await.unwrap()
+        // cache invalidation
+        self.permission_cache_invalidate(PermissionCacheKey {
+            user_id: None,
+            guild_id,
+            operation: "",
+            comorcat: command_category.to_string(),
+        })
+        .await;
+        Ok(result.rows_affected > 0)
     }
 
-    // TODO: simulate add data
-    async fn simulate_add_user_allowed(manager: &mut Permissions) {
-        manager
-            .new_command_user_allowed(GUILD_ID_1, USER_ID_1.get(), COMMAND_1)
-            .await
-            .unwrap();
-        manager
-            .new_command_user_allowed(GUILD_ID_1, USER_ID_2.get(), COMMAND_1)
-            .await
-            .unwrap();
-    }
+    /// Removes a user allowed to use a command. Returns `false` if the user was not allowed.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if an error occured with the database.
+    pub async fn delete_command_user_allowed(
+        &mut self,
+        guild_id: u64,
+        user_id: u64,
+        command: &str,
+    ) -> DataResult<bool> {
+        const OP: &str = "delete_command_user_allowed";
+        let _timing = DataTiming::new(
+            OP.to_string(),
+            DataOperationType::Write,
+            Some(self.metrics_handler.clone()),
+        );
+        self.metrics_handler
+            .data_access(OP, DataOperationType::Write)
+            .await;
 
-    async fn simulate_new_command_role_restriction(manager: &mut Permissions) {
-        manager
-            .new_command_role_restriction(GUILD_ID_1, &ROLE_ID_1, COMMAND_1)
+        use crate::entity::command_allow_user;
+        let result = CommandAllowUser::delete_many()
+            .filter(command_allow_user::Column::ServerId.eq(guild_id as i64))
+            .filter(command_allow_user::Column::UserId.eq(user_id as i64))
+            .filter(command_allow_user::Column::Command.eq(command))
+            .exec(&self.db)
             .await
-            .unwrap();
-    }
+            .context(DatabaseSnafu { operation: OP })?;
 
-    async fn simulate_new_command_category(manager: &mut Permissions) {
-        manager
-            .new_category_role_restriction(GUILD_ID_1, &ROLE_ID_1, COMMAND_CATEGORY_1)
-            .await
-            .unwrap();
+        // cache invalidation
+        self.permission_cache_invalidate(PermissionCacheKey {
+            user_id: Some(user_id),
+            guild_id,
+            operation: "",
+            comorcat: command.to_string(),
+        })
+        .await;
+        Ok(result.rows_affected > 0)
     }
 
-    #[tokio::test]
-    async fn find_user_allowed() {
-        let mut manager = get_manager().await;
-        simulate_add_user_allowed(&mut manager).await;
-
-        let res1 = manager
-            .find_user_allowed(GUILD_ID_1, USER_ID_1.get(), COMMAND_1)
-            .await
-            .unwrap();
+    /// Replaces the role of a command role restriction.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the old role is not required for the command, the
+    /// new role already is, or an error occured with the database.
+    pub async fn edit_command_role_restriction(
+        &mut self,
+        guild_id: u64,
+        command: &str,
+        old_role_id: &serenity::RoleId,
+        new_role_id: &serenity::RoleId,
+    ) -> DataResult<crate::entity::require_command_role::Model> {
+        const OP: &str = "edit_command_role_restriction";
+        let _timing = DataTiming::new(
+            OP.to_string(),
+            DataOperationType::Write,
+            Some(self.metrics_handler.clone()),
+        );
+        self.metrics_handler
+            .data_access(OP, DataOperationType::Write)
+            .await;
 
-        let res2 = manager
-            .find_user_allowed(GUILD_ID_1, USER_ID_3.get(), COMMAND_1)
-            .await
-            .unwrap();
+        let roles = self.find_command_roles_allowed(guild_id, command).await?;
+        let Some(existing) = roles.iter().find(|e| e.role_id == old_role_id.get() as i64) else {
+            return Err(DataError::NotFound {
+                err: format!("role {old_role_id} is not required for command {command}"),
+            });
+        };
+        if old_role_id != new_role_id && roles.iter().any(|e| e.role_id == new_role_id.get() as i64)
+        {
+            return Err(DataError::NewCommandRoleRestrictionDuplicate);
+        }
 
-        let res3 = manager
-            .find_user_allowed(GUILD_ID_1, USER_ID_2.get(), COMMAND_1)
+        let mut model = existing.clone().into_active_model();
+        model.role_id = sea_orm::ActiveValue::Set(new_role_id.get() as i64);
+        let model = model
+            .update(&self.db)
             .await
-            .unwrap();
+            .context(DatabaseSnafu { operation: OP })?;
 
-        assert!(res1.is_some());
-        assert!(res2.is_none());
-        assert!(res3.is_some());
+        // cache invalidation
+        self.permission_cache_invalidate(PermissionCacheKey {
+            user_id: None,
+            guild_id,
+            operation: "",
+            comorcat: command.to_string(),
+        })
+        .await;
+        Ok(model)
     }
 
-    #[tokio::test]
-    async fn find_command_roles_allowed() {
-        let mut manager = get_manager().await;
-        simulate_new_command_role_restriction(&mut manager).await;
-
-        let res = manager
-            .find_command_roles_allowed(GUILD_ID_1, COMMAND_1)
-            .await
-            .unwrap();
-
-        assert!(res.len() == 1);
-        assert!(res.first().unwrap().role_id != ROLE_ID_2.get() as i64);
-    }
+    /// Replaces the role of a category role restriction.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the old role is not required for the category, the
+    /// new role already is, or an error occured with the database.
+    pub async fn edit_category_role_restriction(
+        &mut self,
+        guild_id: u64,
+        command_category: &str,
+        old_role_id: &serenity::RoleId,
+        new_role_id: &serenity::RoleId,
+    ) -> DataResult<crate::entity::require_category_role::Model> {
+        const OP: &str = "edit_category_role_restriction";
+        let _timing = DataTiming::new(
+            OP.to_string(),
+            DataOperationType::Write,
+            Some(self.metrics_handler.clone()),
+        );
+        self.metrics_handler
+            .data_access(OP, DataOperationType::Write)
+            .await;
 
-    #[tokio::test]
-    async fn find_category_roles_allowed() {
-        let mut manager = get_manager().await;
-        simulate_new_command_category(&mut manager).await;
+        let roles = self
+            .find_category_roles_allowed(guild_id, command_category)
+            .await?;
+        let Some(existing) = roles.iter().find(|e| e.role_id == old_role_id.get() as i64) else {
+            return Err(DataError::NotFound {
+                err: format!("role {old_role_id} is not required for category {command_category}"),
+            });
+        };
+        if old_role_id != new_role_id && roles.iter().any(|e| e.role_id == new_role_id.get() as i64)
+        {
+            return Err(DataError::NewCategoryRoleRestrictionDuplicate);
+        }
 
-        let res = manager
-            .find_category_roles_allowed(GUILD_ID_1, COMMAND_CATEGORY_1)
+        let mut model = existing.clone().into_active_model();
+        model.role_id = sea_orm::ActiveValue::Set(new_role_id.get() as i64);
+        let model = model
+            .update(&self.db)
             .await
-            .unwrap();
+            .context(DatabaseSnafu { operation: OP })?;
 
-        assert!(res.len() == 1);
-        assert!(res.first().unwrap().role_id == ROLE_ID_1.get() as i64);
-        assert!(res.first().unwrap().category == COMMAND_CATEGORY_1);
+        // cache invalidation
+        self.permission_cache_invalidate(PermissionCacheKey {
+            user_id: None,
+            guild_id,
+            operation: "",
+            comorcat: command_category.to_string(),
+        })
+        .await;
+        Ok(model)
     }
 
-    #[tokio::test]
-    async fn new_command_role_restriction() {
-        let mut manager = get_manager().await;
+    /// Moves a user allowance from one command to another.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the user is not allowed to use the old command, is
+    /// already allowed to use the new one, or an error occured with the database.
+    pub async fn edit_command_user_allowed(
+        &mut self,
+        guild_id: u64,
+        user_id: u64,
+        old_command: &str,
+        new_command: &str,
+    ) -> DataResult<crate::entity::command_allow_user::Model> {
+        const OP: &str = "edit_command_user_allowed";
+        let _timing = DataTiming::new(
+            OP.to_string(),
+            DataOperationType::Write,
+            Some(self.metrics_handler.clone()),
+        );
+        self.metrics_handler
+            .data_access(OP, DataOperationType::Write)
+            .await;
 
-        let res = manager
-            .new_command_role_restriction(GUILD_ID_1, &ROLE_ID_1, COMMAND_1)
+        let Some(existing) = self
+            .find_user_allowed(guild_id, user_id, old_command)
+            .await?
+        else {
+            return Err(DataError::NotFound {
+                err: format!("user {user_id} is not allowed to use command {old_command}"),
+            });
+        };
+        if old_command != new_command
+            && self
+                .find_user_allowed(guild_id, user_id, new_command)
+                .await?
+                .is_some()
+        {
+            return Err(DataError::NewCommandAllowedUserDuplicate);
+        }
+
+        let mut model = existing.into_active_model();
+        model.command = sea_orm::ActiveValue::Set(new_command.to_string());
+        let model = model
+            .update(&self.db)
             .await
-            .unwrap();
+            .context(DatabaseSnafu { operation: OP })?;
 
-        assert!(res.command == COMMAND_1);
+        // cache invalidation, for both commands
+        for command in [old_command, new_command] {
+            self.permission_cache_invalidate(PermissionCacheKey {
+                user_id: Some(user_id),
+                guild_id,
+                operation: "",
+                comorcat: command.to_string(),
+            })
+            .await;
+        }
+        Ok(model)
     }
 
-    #[tokio::test]
-    async fn new_category_role_restriction() {
-        let mut manager = get_manager().await;
+    /// Finds the deny rules of a command or category.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if there is an error accessing the database.
+    pub async fn find_deny_rules(
+        &mut self,
+        guild_id: u64,
+        scope: RuleScope,
+        name: &str,
+    ) -> DataResult<Vec<crate::entity::command_deny_rule::Model>> {
+        const OP: &str = "find_deny_rules";
+        let _timing = DataTiming::new(
+            OP.to_string(),
+            DataOperationType::Read,
+            Some(self.metrics_handler.clone()),
+        );
+
+        use crate::entity::command_deny_rule;
+        self.metrics_handler
+            .data_access(OP, DataOperationType::Read)
+            .await;
+
+        // a command and a category may share a name
+        let cache_key = PermissionCacheKey {
+            user_id: None,
+            guild_id,
+            operation: match scope {
+                RuleScope::Command => "find_command_deny_rules",
+                RuleScope::Category => "find_category_deny_rules",
+            },
+            comorcat: name.to_string(),
+        };
+
+        if let Some(entry) = self.permission_cache_access(&cache_key).await {
+            let (decoded, _): (Vec<command_deny_rule::Model>, _) =
+                bincode::decode_from_slice(&entry, bincode::config::standard())?;
+            return Ok(decoded);
+        }
+        let model = CommandDenyRule::find()
+            .filter(command_deny_rule::Column::ServerId.eq(guild_id as i64))
+            .filter(command_deny_rule::Column::ScopeKind.eq(scope.as_str()))
+            .filter(command_deny_rule::Column::ScopeName.eq(name))
+            .all(&self.db)
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+        if let Ok(encoded) = bincode::encode_to_vec(&model, bincode::config::standard()) {
+            self.permission_cache_insert(cache_key, encoded).await;
+        };
+        Ok(model)
+    }
+
+    /// Denies a user or role a command or category.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the rule already exists or an error is returned from
+    /// the database.
+    pub async fn new_deny_rule(
+        &mut self,
+        guild_id: u64,
+        target: DenyTarget,
+        scope: RuleScope,
+        name: &str,
+    ) -> DataResult<crate::entity::command_deny_rule::Model> {
+        const OP: &str = "new_deny_rule";
+        let _timing = DataTiming::new(
+            OP.to_string(),
+            DataOperationType::Write,
+            Some(self.metrics_handler.clone()),
+        );
+        self.metrics_handler
+            .data_access(OP, DataOperationType::Write)
+            .await;
+
+        use crate::entity::command_deny_rule;
+        let existing = self.find_deny_rules(guild_id, scope, name).await?;
+        if existing
+            .iter()
+            .any(|e| e.target_kind == target.kind() && e.target_id == target.id() as i64)
+        {
+            return Err(DataError::DuplicateEntry {
+                object: format!("{} deny rule for {} {name}", target.kind(), scope.as_str()),
+            });
+        }
+
+        let model = command_deny_rule::ActiveModel {
+            entry_id: sea_orm::ActiveValue::Set(Uuid::now_v7()),
+            server_id: sea_orm::ActiveValue::Set(guild_id as i64),
+            target_kind: sea_orm::ActiveValue::Set(target.kind().to_string()),
+            target_id: sea_orm::ActiveValue::Set(target.id() as i64),
+            scope_kind: sea_orm::ActiveValue::Set(scope.as_str().to_string()),
+            scope_name: sea_orm::ActiveValue::Set(name.to_string()),
+        }
+        .insert(&self.db)
+        .await
+        .context(DatabaseSnafu { operation: OP })?;
+
+        // cache invalidation
+        self.permission_cache_invalidate(PermissionCacheKey {
+            user_id: None,
+            guild_id,
+            operation: "",
+            comorcat: name.to_string(),
+        })
+        .await;
+        Ok(model)
+    }
+
+    /// Removes a deny rule. Returns `false` if the rule did not exist.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if an error occured with the database.
+    pub async fn delete_deny_rule(
+        &mut self,
+        guild_id: u64,
+        target: DenyTarget,
+        scope: RuleScope,
+        name: &str,
+    ) -> DataResult<bool> {
+        const OP: &str = "delete_deny_rule";
+        let _timing = DataTiming::new(
+            OP.to_string(),
+            DataOperationType::Write,
+            Some(self.metrics_handler.clone()),
+        );
+        self.metrics_handler
+            .data_access(OP, DataOperationType::Write)
+            .await;
+
+        use crate::entity::command_deny_rule;
+        let result = CommandDenyRule::delete_many()
+            .filter(command_deny_rule::Column::ServerId.eq(guild_id as i64))
+            .filter(command_deny_rule::Column::TargetKind.eq(target.kind()))
+            .filter(command_deny_rule::Column::TargetId.eq(target.id() as i64))
+            .filter(command_deny_rule::Column::ScopeKind.eq(scope.as_str()))
+            .filter(command_deny_rule::Column::ScopeName.eq(name))
+            .exec(&self.db)
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+
+        // cache invalidation
+        self.permission_cache_invalidate(PermissionCacheKey {
+            user_id: None,
+            guild_id,
+            operation: "",
+            comorcat: name.to_string(),
+        })
+        .await;
+        Ok(result.rows_affected > 0)
+    }
+
+    /// Finds the channels a command or category is limited to. Returns an empty [`Vec`] if it can
+    /// be used anywhere.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if there is an error accessing the database.
+    pub async fn find_channel_scopes(
+        &mut self,
+        guild_id: u64,
+        scope: RuleScope,
+        name: &str,
+    ) -> DataResult<Vec<crate::entity::command_channel_scope::Model>> {
+        const OP: &str = "find_channel_scopes";
+        let _timing = DataTiming::new(
+            OP.to_string(),
+            DataOperationType::Read,
+            Some(self.metrics_handler.clone()),
+        );
+
+        use crate::entity::command_channel_scope;
+        self.metrics_handler
+            .data_access(OP, DataOperationType::Read)
+            .await;
+
+        // a command and a category may share a name
+        let cache_key = PermissionCacheKey {
+            user_id: None,
+            guild_id,
+            operation: match scope {
+                RuleScope::Command => "find_command_channel_scopes",
+                RuleScope::Category => "find_category_channel_scopes",
+            },
+            comorcat: name.to_string(),
+        };
+
+        if let Some(entry) = self.permission_cache_access(&cache_key).await {
+            let (decoded, _): (Vec<command_channel_scope::Model>, _) =
+                bincode::decode_from_slice(&entry, bincode::config::standard())?;
+            return Ok(decoded);
+        }
+        let model = CommandChannelScope::find()
+            .filter(command_channel_scope::Column::ServerId.eq(guild_id as i64))
+            .filter(command_channel_scope::Column::ScopeKind.eq(scope.as_str()))
+            .filter(command_channel_scope::Column::ScopeName.eq(name))
+            .all(&self.db)
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+        if let Ok(encoded) = bincode::encode_to_vec(&model, bincode::config::standard()) {
+            self.permission_cache_insert(cache_key, encoded).await;
+        };
+        Ok(model)
+    }
+
+    /// Limits a command or category to a channel, in addition to the channels it is already
+    /// limited to.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the channel was already added or an error is returned
+    /// from the database.
+    pub async fn new_channel_scope(
+        &mut self,
+        guild_id: u64,
+        scope: RuleScope,
+        name: &str,
+        kind: ChannelKind,
+        channel_id: u64,
+    ) -> DataResult<crate::entity::command_channel_scope::Model> {
+        const OP: &str = "new_channel_scope";
+        let _timing = DataTiming::new(
+            OP.to_string(),
+            DataOperationType::Write,
+            Some(self.metrics_handler.clone()),
+        );
+        self.metrics_handler
+            .data_access(OP, DataOperationType::Write)
+            .await;
+
+        use crate::entity::command_channel_scope;
+        let existing = self.find_channel_scopes(guild_id, scope, name).await?;
+        if existing.iter().any(|e| e.channel_id == channel_id as i64) {
+            return Err(DataError::DuplicateEntry {
+                object: format!("channel {channel_id} for {} {name}", scope.as_str()),
+            });
+        }
+
+        let model = command_channel_scope::ActiveModel {
+            entry_id: sea_orm::ActiveValue::Set(Uuid::now_v7()),
+            server_id: sea_orm::ActiveValue::Set(guild_id as i64),
+            scope_kind: sea_orm::ActiveValue::Set(scope.as_str().to_string()),
+            scope_name: sea_orm::ActiveValue::Set(name.to_string()),
+            channel_kind: sea_orm::ActiveValue::Set(kind.as_str().to_string()),
+            channel_id: sea_orm::ActiveValue::Set(channel_id as i64),
+        }
+        .insert(&self.db)
+        .await
+        .context(DatabaseSnafu { operation: OP })?;
+
+        // cache invalidation
+        self.permission_cache_invalidate(PermissionCacheKey {
+            user_id: None,
+            guild_id,
+            operation: "",
+            comorcat: name.to_string(),
+        })
+        .await;
+        Ok(model)
+    }
+
+    /// Removes a channel from the channels a command or category is limited to. Returns `false`
+    /// if it was not one of them.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if an error occured with the database.
+    pub async fn delete_channel_scope(
+        &mut self,
+        guild_id: u64,
+        scope: RuleScope,
+        name: &str,
+        channel_id: u64,
+    ) -> DataResult<bool> {
+        const OP: &str = "delete_channel_scope";
+        let _timing = DataTiming::new(
+            OP.to_string(),
+            DataOperationType::Write,
+            Some(self.metrics_handler.clone()),
+        );
+        self.metrics_handler
+            .data_access(OP, DataOperationType::Write)
+            .await;
+
+        use crate::entity::command_channel_scope;
+        let result = CommandChannelScope::delete_many()
+            .filter(command_channel_scope::Column::ServerId.eq(guild_id as i64))
+            .filter(command_channel_scope::Column::ScopeKind.eq(scope.as_str()))
+            .filter(command_channel_scope::Column::ScopeName.eq(name))
+            .filter(command_channel_scope::Column::ChannelId.eq(channel_id as i64))
+            .exec(&self.db)
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+
+        // cache invalidation
+        self.permission_cache_invalidate(PermissionCacheKey {
+            user_id: None,
+            guild_id,
+            operation: "",
+            comorcat: name.to_string(),
+        })
+        .await;
+        Ok(result.rows_affected > 0)
+    }
+
+    /// Gathers every rule deciding whether the user may call the command, to be checked with
+    /// [`evaluate`](super::permission_rules::evaluate).
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if there is an error accessing the database.
+    pub async fn find_permission_rules(
+        &mut self,
+        guild_id: u64,
+        user_id: u64,
+        command: &str,
+        command_category: Option<&str>,
+    ) -> DataResult<PermissionRules> {
+        let user_allowed = self
+            .find_user_allowed(guild_id, user_id, command)
+            .await?
+            .is_some();
+        let command_roles = self
+            .find_command_roles_allowed(guild_id, command)
+            .await?
+            .into_iter()
+            .map(|e| e.role_id as u64)
+            .collect();
+
+        let mut scopes = vec![(RuleScope::Command, command)];
+        if let Some(command_category) = command_category {
+            scopes.push((RuleScope::Category, command_category));
+        }
+        let category_roles = match command_category {
+            Some(command_category) => self
+                .find_category_roles_allowed(guild_id, command_category)
+                .await?
+                .into_iter()
+                .map(|e| e.role_id as u64)
+                .collect(),
+            None => Vec::new(),
+        };
+
+        let mut denies = Vec::new();
+        let mut channel_scopes = Vec::new();
+        for (scope, name) in scopes {
+            denies.extend(
+                self.find_deny_rules(guild_id, scope, name)
+                    .await?
+                    .into_iter()
+                    .filter_map(|e| {
+                        DenyTarget::from_parts(&e.target_kind, e.target_id as u64)
+                            .map(|target| DenyRule { target, scope })
+                    }),
+            );
+            channel_scopes.extend(
+                self.find_channel_scopes(guild_id, scope, name)
+                    .await?
+                    .into_iter()
+                    .filter_map(|e| {
+                        ChannelKind::parse(&e.channel_kind).map(|kind| ChannelScope {
+                            scope,
+                            kind,
+                            channel_id: e.channel_id as u64,
+                        })
+                    }),
+            );
+        }
+
+        Ok(PermissionRules {
+            user_allowed,
+            command_roles,
+            category_roles,
+            denies,
+            channel_scopes,
+        })
+    }
+
+    /// Every role restriction and user allowance of a guild, for exports. Deny rules and channel
+    /// scopes are not included.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if an error occured with the database.
+    pub async fn guild_permission_rows(&self, guild_id: u64) -> DataResult<Vec<PermissionRow>> {
+        const OP: &str = "guild_permission_rows";
+        let _timing = DataTiming::new(
+            OP.to_string(),
+            DataOperationType::Read,
+            Some(self.metrics_handler.clone()),
+        );
+        self.metrics_handler
+            .data_access(OP, DataOperationType::Read)
+            .await;
+
+        use crate::entity::{command_allow_user, require_category_role, require_command_role};
+        let command_roles = RequireCommandRole::find()
+            .filter(require_command_role::Column::ServerId.eq(guild_id))
+            .all(&self.db)
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+        let category_roles = RequireCategoryRole::find()
+            .filter(require_category_role::Column::ServerId.eq(guild_id))
+            .all(&self.db)
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+        let allowed_users = CommandAllowUser::find()
+            .filter(command_allow_user::Column::ServerId.eq(guild_id))
+            .all(&self.db)
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+
+        let mut rows = Vec::new();
+        rows.extend(
+            command_roles
+                .into_iter()
+                .map(|e| PermissionRow::CommandRole {
+                    command: e.command,
+                    role_id: e.role_id as u64,
+                }),
+        );
+        rows.extend(
+            category_roles
+                .into_iter()
+                .map(|e| PermissionRow::CategoryRole {
+                    category: e.category,
+                    role_id: e.role_id as u64,
+                }),
+        );
+        rows.extend(
+            allowed_users
+                .into_iter()
+                .map(|e| PermissionRow::AllowedUser {
+                    command: e.command,
+                    user_id: e.user_id as u64,
+                }),
+        );
+        Ok(rows)
+    }
+
+    /// Add a rule of an import. Returns `false` if the guild already had it.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if an error occured with the database.
+    pub async fn add_permission_row(
+        &mut self,
+        guild_id: u64,
+        row: &PermissionRow,
+    ) -> DataResult<bool> {
+        let result = match row {
+            PermissionRow::CommandRole { command, role_id } => self
+                .new_command_role_restriction(guild_id, &serenity::RoleId::new(*role_id), command)
+                .await
+                .map(|_| ()),
+            PermissionRow::CategoryRole { category, role_id } => self
+                .new_category_role_restriction(guild_id, &serenity::RoleId::new(*role_id), category)
+                .await
+                .map(|_| ()),
+            PermissionRow::AllowedUser { command, user_id } => self
+                .new_command_user_allowed(guild_id, *user_id, command)
+                .await
+                .map(|_| ()),
+        };
+        match result {
+            Ok(()) => Ok(true),
+            Err(
+                DataError::NewCommandRoleRestrictionDuplicate
+                | DataError::NewCategoryRoleRestrictionDuplicate
+                | DataError::NewCommandAllowedUserDuplicate,
+            ) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+impl Permissions {
+    pub async fn setup_cache_metrics(
+        metrics_handler: Arc<dyn MetricsSink>,
+        permission_cache: PermissionCache,
+    ) {
+        tokio::spawn(async move {
+            loop {
+                metrics_handler
+                    .cache_len("permission_cache", permission_cache.lock().await.len())
+                    .await;
+                tokio::time::sleep(std::time::Duration::from_secs(30)).await;
+            }
+        });
+    }
+}
+
+/// The cache key for the permission cache. Each detail is split into a field for easy comparison
+#[derive(Debug, Eq, Hash, PartialEq, Clone)]
+pub struct PermissionCacheKey {
+    /// The user id in u64, if any
+    pub user_id: Option<u64>,
+    /// The guild id in u64
+    pub guild_id: u64,
+    /// The name of the function calling
+    pub operation: &'static str,
+    /// The name of the bot command or command category
+    pub comorcat: String,
+}
+
+impl HeapSize for PermissionCacheKey {
+    fn heap_size(&self) -> usize {
+        self.comorcat.heap_size() + self.operation.heap_size()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::constants::*;
+    use ayaya_core::metrics::NoopMetrics;
+
+    use migration::{Migrator as SqliteMigrator, MigratorTrait};
+    use sea_orm::Database;
+
+    async fn get_manager() -> Permissions {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        SqliteMigrator::up(&db, None).await.unwrap();
+        Permissions::new(db, Arc::new(NoopMetrics)).await.unwrap()
+    }
+
+    // TODO: simulate add data
+    async fn simulate_add_user_allowed(manager: &mut Permissions) {
+        manager
+            .new_command_user_allowed(GUILD_ID_1, USER_ID_1.get(), COMMAND_1)
+            .await
+            .unwrap();
+        manager
+            .new_command_user_allowed(GUILD_ID_1, USER_ID_2.get(), COMMAND_1)
+            .await
+            .unwrap();
+    }
+
+    async fn simulate_new_command_role_restriction(manager: &mut Permissions) {
+        manager
+            .new_command_role_restriction(GUILD_ID_1, &ROLE_ID_1, COMMAND_1)
+            .await
+            .unwrap();
+    }
+
+    async fn simulate_new_command_category(manager: &mut Permissions) {
+        manager
+            .new_category_role_restriction(GUILD_ID_1, &ROLE_ID_1, COMMAND_CATEGORY_1)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn find_user_allowed() {
+        let mut manager = get_manager().await;
+        simulate_add_user_allowed(&mut manager).await;
+
+        let res1 = manager
+            .find_user_allowed(GUILD_ID_1, USER_ID_1.get(), COMMAND_1)
+            .await
+            .unwrap();
+
+        let res2 = manager
+            .find_user_allowed(GUILD_ID_1, USER_ID_3.get(), COMMAND_1)
+            .await
+            .unwrap();
+
+        let res3 = manager
+            .find_user_allowed(GUILD_ID_1, USER_ID_2.get(), COMMAND_1)
+            .await
+            .unwrap();
+
+        assert!(res1.is_some());
+        assert!(res2.is_none());
+        assert!(res3.is_some());
+    }
+
+    #[tokio::test]
+    async fn find_command_roles_allowed() {
+        let mut manager = get_manager().await;
+        simulate_new_command_role_restriction(&mut manager).await;
+
+        let res = manager
+            .find_command_roles_allowed(GUILD_ID_1, COMMAND_1)
+            .await
+            .unwrap();
+
+        assert!(res.len() == 1);
+        assert!(res.first().unwrap().role_id != ROLE_ID_2.get() as i64);
+    }
+
+    #[tokio::test]
+    async fn find_category_roles_allowed() {
+        let mut manager = get_manager().await;
+        simulate_new_command_category(&mut manager).await;
+
+        let res = manager
+            .find_category_roles_allowed(GUILD_ID_1, COMMAND_CATEGORY_1)
+            .await
+            .unwrap();
+
+        assert!(res.len() == 1);
+        assert!(res.first().unwrap().role_id == ROLE_ID_1.get() as i64);
+        assert!(res.first().unwrap().category == COMMAND_CATEGORY_1);
+    }
+
+    #[tokio::test]
+    async fn new_command_role_restriction() {
+        let mut manager = get_manager().await;
+
+        let res = manager
+            .new_command_role_restriction(GUILD_ID_1, &ROLE_ID_1, COMMAND_1)
+            .await
+            .unwrap();
+
+        assert!(res.command == COMMAND_1);
+    }
+
+    #[tokio::test]
+    async fn new_category_role_restriction() {
+        let mut manager = get_manager().await;
 
         let res = manager
             .new_category_role_restriction(GUILD_ID_1, &ROLE_ID_1, COMMAND_CATEGORY_1)
@@ -584,6 +1359,406 @@ mod tests {
         assert!(res.command == COMMAND_1);
     }
 
+    #[tokio::test]
+    async fn delete_restrictions() {
+        let mut manager = get_manager().await;
+        simulate_add_user_allowed(&mut manager).await;
+        simulate_new_command_role_restriction(&mut manager).await;
+        simulate_new_command_category(&mut manager).await;
+
+        // fill the cache, the deletes must invalidate it
+        assert!(
+            manager
+                .find_user_allowed(GUILD_ID_1, USER_ID_1.get(), COMMAND_1)
+                .await
+                .unwrap()
+                .is_some()
+        );
+        assert!(
+            manager
+                .delete_command_user_allowed(GUILD_ID_1, USER_ID_1.get(), COMMAND_1)
+                .await
+                .unwrap()
+        );
+        assert!(
+            !manager
+                .delete_command_user_allowed(GUILD_ID_1, USER_ID_1.get(), COMMAND_1)
+                .await
+                .unwrap()
+        );
+        assert!(
+            manager
+                .find_user_allowed(GUILD_ID_1, USER_ID_1.get(), COMMAND_1)
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            manager
+                .find_user_allowed(GUILD_ID_1, USER_ID_2.get(), COMMAND_1)
+                .await
+                .unwrap()
+                .is_some()
+        );
+
+        assert!(
+            manager
+                .delete_command_role_restriction(GUILD_ID_1, &ROLE_ID_1, COMMAND_1)
+                .await
+                .unwrap()
+        );
+        assert!(
+            manager
+                .find_command_roles_allowed(GUILD_ID_1, COMMAND_1)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        assert!(
+            !manager
+                .delete_category_role_restriction(GUILD_ID_1, &ROLE_ID_2, COMMAND_CATEGORY_1)
+                .await
+                .unwrap()
+        );
+        assert!(
+            manager
+                .delete_category_role_restriction(GUILD_ID_1, &ROLE_ID_1, COMMAND_CATEGORY_1)
+                .await
+                .unwrap()
+        );
+        assert!(
+            manager
+                .find_category_roles_allowed(GUILD_ID_1, COMMAND_CATEGORY_1)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn edit_restrictions() {
+        let mut manager = get_manager().await;
+        simulate_add_user_allowed(&mut manager).await;
+        simulate_new_command_role_restriction(&mut manager).await;
+        simulate_new_command_category(&mut manager).await;
+
+        let res = manager
+            .edit_command_role_restriction(GUILD_ID_1, COMMAND_1, &ROLE_ID_1, &ROLE_ID_2)
+            .await
+            .unwrap();
+        assert!(res.role_id == ROLE_ID_2.get() as i64);
+        let roles = manager
+            .find_command_roles_allowed(GUILD_ID_1, COMMAND_1)
+            .await
+            .unwrap();
+        assert!(roles.len() == 1);
+        assert!(roles.first().unwrap().role_id == ROLE_ID_2.get() as i64);
+        assert!(
+            manager
+                .edit_command_role_restriction(GUILD_ID_1, COMMAND_1, &ROLE_ID_1, &ROLE_ID_2)
+                .await
+                .is_err()
+        );
+
+        manager
+            .edit_category_role_restriction(GUILD_ID_1, COMMAND_CATEGORY_1, &ROLE_ID_1, &ROLE_ID_2)
+            .await
+            .unwrap();
+        let roles = manager
+            .find_category_roles_allowed(GUILD_ID_1, COMMAND_CATEGORY_1)
+            .await
+            .unwrap();
+        assert!(roles.first().unwrap().role_id == ROLE_ID_2.get() as i64);
+
+        let other_command = "other_command";
+        let res = manager
+            .edit_command_user_allowed(GUILD_ID_1, USER_ID_1.get(), COMMAND_1, other_command)
+            .await
+            .unwrap();
+        assert!(res.command == other_command);
+        assert!(
+            manager
+                .find_user_allowed(GUILD_ID_1, USER_ID_1.get(), COMMAND_1)
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert!(
+            manager
+                .find_user_allowed(GUILD_ID_1, USER_ID_1.get(), other_command)
+                .await
+                .unwrap()
+                .is_some()
+        );
+        // moving to the same command changes nothing
+        assert!(
+            manager
+                .edit_command_user_allowed(GUILD_ID_1, USER_ID_2.get(), COMMAND_1, COMMAND_1)
+                .await
+                .is_ok()
+        );
+        manager
+            .new_command_user_allowed(GUILD_ID_1, USER_ID_2.get(), other_command)
+            .await
+            .unwrap();
+        assert!(
+            manager
+                .edit_command_user_allowed(GUILD_ID_1, USER_ID_2.get(), COMMAND_1, other_command)
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn deny_rules_and_channel_scopes() {
+        let mut manager = get_manager().await;
+        simulate_add_user_allowed(&mut manager).await;
+        let channel_id = 42;
+
+        manager
+            .new_deny_rule(
+                GUILD_ID_1,
+                DenyTarget::Role(ROLE_ID_1.get()),
+                RuleScope::Category,
+                COMMAND_CATEGORY_1,
+            )
+            .await
+            .unwrap();
+        assert!(
+            manager
+                .new_deny_rule(
+                    GUILD_ID_1,
+                    DenyTarget::Role(ROLE_ID_1.get()),
+                    RuleScope::Category,
+                    COMMAND_CATEGORY_1,
+                )
+                .await
+                .is_err()
+        );
+        manager
+            .new_channel_scope(
+                GUILD_ID_1,
+                RuleScope::Command,
+                COMMAND_1,
+                ChannelKind::Text,
+                channel_id,
+            )
+            .await
+            .unwrap();
+
+        let rules = manager
+            .find_permission_rules(
+                GUILD_ID_1,
+                USER_ID_1.get(),
+                COMMAND_1,
+                Some(COMMAND_CATEGORY_1),
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            rules,
+            PermissionRules {
+                user_allowed: true,
+                command_roles: Vec::new(),
+                category_roles: Vec::new(),
+                denies: vec![DenyRule {
+                    target: DenyTarget::Role(ROLE_ID_1.get()),
+                    scope: RuleScope::Category,
+                }],
+                channel_scopes: vec![ChannelScope {
+                    scope: RuleScope::Command,
+                    kind: ChannelKind::Text,
+                    channel_id,
+                }],
+            }
+        );
+        // a category with the name of the command has its own rules
+        assert!(
+            manager
+                .find_channel_scopes(GUILD_ID_1, RuleScope::Category, COMMAND_1)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        // the cached rules are invalidated
+        assert!(
+            manager
+                .delete_deny_rule(
+                    GUILD_ID_1,
+                    DenyTarget::Role(ROLE_ID_1.get()),
+                    RuleScope::Category,
+                    COMMAND_CATEGORY_1,
+                )
+                .await
+                .unwrap()
+        );
+        assert!(
+            manager
+                .delete_channel_scope(GUILD_ID_1, RuleScope::Command, COMMAND_1, channel_id)
+                .await
+                .unwrap()
+        );
+        assert!(
+            !manager
+                .delete_channel_scope(GUILD_ID_1, RuleScope::Command, COMMAND_1, channel_id)
+                .await
+                .unwrap()
+        );
+        let rules = manager
+            .find_permission_rules(
+                GUILD_ID_1,
+                USER_ID_3.get(),
+                COMMAND_1,
+                Some(COMMAND_CATEGORY_1),
+            )
+            .await
+            .unwrap();
+        assert_eq!(rules, PermissionRules::default());
+    }
+
+    /// Counts permission cache hits and misses
+    #[derive(Default)]
+    struct CacheCounter {
+        hits: std::sync::atomic::AtomicUsize,
+        misses: std::sync::atomic::AtomicUsize,
+    }
+
+    impl CacheCounter {
+        fn counts(&self) -> (usize, usize) {
+            use std::sync::atomic::Ordering;
+            (
+                self.hits.load(Ordering::SeqCst),
+                self.misses.load(Ordering::SeqCst),
+            )
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MetricsSink for CacheCounter {
+        async fn data_access(&self, _name: &str, _op: DataOperationType) {}
+
+        async fn data_time(&self, _name: &str, _op: DataOperationType, _time: f64) {}
+
+        async fn cache_len(&self, _name: &str, _len: usize) {}
+
+        async fn cache_access(&self, _name: &str, hit: bool) {
+            use std::sync::atomic::Ordering;
+            if hit {
+                self.hits.fetch_add(1, Ordering::SeqCst);
+            } else {
+                self.misses.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn lookups_use_the_cache() {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        SqliteMigrator::up(&db, None).await.unwrap();
+        let counter = Arc::new(CacheCounter::default());
+        let mut manager = Permissions::new(db, counter.clone()).await.unwrap();
+
+        manager
+            .find_command_roles_allowed(GUILD_ID_1, COMMAND_1)
+            .await
+            .unwrap();
+        assert_eq!(counter.counts(), (0, 1));
+        // empty results are cached too
+        assert!(
+            manager
+                .find_command_roles_allowed(GUILD_ID_1, COMMAND_1)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(counter.counts(), (1, 1));
+
+        // the write looks the roles up once more, then invalidates them
+        simulate_new_command_role_restriction(&mut manager).await;
+        assert_eq!(counter.counts(), (2, 1));
+        let roles = manager
+            .find_command_roles_allowed(GUILD_ID_1, COMMAND_1)
+            .await
+            .unwrap();
+        assert_eq!(roles.len(), 1);
+        assert_eq!(counter.counts(), (2, 2));
+
+        // every rule of a command is a hit once loaded
+        let rules = manager
+            .find_permission_rules(
+                GUILD_ID_1,
+                USER_ID_1.get(),
+                COMMAND_1,
+                Some(COMMAND_CATEGORY_1),
+            )
+            .await
+            .unwrap();
+        let (hits, misses) = counter.counts();
+        assert_eq!(
+            manager
+                .find_permission_rules(
+                    GUILD_ID_1,
+                    USER_ID_1.get(),
+                    COMMAND_1,
+                    Some(COMMAND_CATEGORY_1),
+                )
+                .await
+                .unwrap(),
+            rules
+        );
+        let (new_hits, new_misses) = counter.counts();
+        assert_eq!(new_misses, misses);
+        assert!(new_hits > hits);
+    }
+
+    #[tokio::test]
+    async fn export_and_import_rows() {
+        let mut manager = get_manager().await;
+        simulate_add_user_allowed(&mut manager).await;
+        simulate_new_command_role_restriction(&mut manager).await;
+        simulate_new_command_category(&mut manager).await;
+
+        let rows = manager.guild_permission_rows(GUILD_ID_1).await.unwrap();
+        assert_eq!(rows.len(), 4);
+        assert!(rows.contains(&PermissionRow::CategoryRole {
+            category: COMMAND_CATEGORY_1.to_string(),
+            role_id: ROLE_ID_1.get(),
+        }));
+        assert!(
+            manager
+                .guild_permission_rows(GUILD_ID_1 + 1)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        // importing into another guild adds every row once
+        for row in &rows {
+            assert!(
+                manager
+                    .add_permission_row(GUILD_ID_1 + 1, row)
+                    .await
+                    .unwrap()
+            );
+            assert!(
+                !manager
+                    .add_permission_row(GUILD_ID_1 + 1, row)
+                    .await
+                    .unwrap()
+            );
+        }
+        assert_eq!(
+            manager
+                .guild_permission_rows(GUILD_ID_1 + 1)
+                .await
+                .unwrap()
+                .len(),
+            4
+        );
+    }
+
     #[tokio::test]
     async fn findall_user_allowed() {
         let mut manager = get_manager().await;
@@ -2,6 +2,7 @@ use crate::entity::prelude::*;
 use poise::serenity_prelude as serenity;
 use sea_orm::{ActiveValue, DatabaseConnection, IntoActiveModel, prelude::*};
 use snafu::ResultExt;
+use time::OffsetDateTime;
 
 use crate::data::utils::DataTiming;
 use crate::error::{DataError, DatabaseSnafu};
@@ -67,6 +68,7 @@ impl SoundsManager {
                 uploaded_server_id: ActiveValue::Set(uploaded_server_id as i64),
                 sound_name: ActiveValue::Set(sound_name),
                 public: ActiveValue::Set(public.unwrap_or(true)),
+                uploaded_at: ActiveValue::Set(Some(OffsetDateTime::now_utc())),
             }
             .insert(&self.sounds_db)
             .await
@@ -189,6 +191,72 @@ impl SoundsManager {
         Ok(sounds)
     }
 
+    /// Get every sound in the database, regardless of owner or publicity
+    pub async fn get_all_sounds(&self) -> DataResult<Vec<crate::entity::sounds::Model>> {
+        const OP: &str = "get_all_sounds";
+        self.metrics_handler
+            .data_access(OP, DataOperationType::Read)
+            .await;
+        let _timing = DataTiming::new(
+            OP.to_string(),
+            DataOperationType::Read,
+            Some(self.metrics_handler.clone()),
+        );
+
+        let sounds = Sounds::find()
+            .all(&self.sounds_db)
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+
+        Ok(sounds)
+    }
+
+    /// Add a sound coming from an imported pack. Sound ids are derived from the file hash, so a
+    /// sound already present under any user is skipped.
+    ///
+    /// Returns `true` if the sound was inserted, `false` if it already existed.
+    pub async fn import_sound(
+        &self,
+        user_id: &serenity::UserId,
+        uploaded_server_id: u64,
+        sound_id: uuid::Uuid,
+        sound_name: String,
+        public: bool,
+    ) -> DataResult<bool> {
+        const OP: &str = "import_sound";
+        self.metrics_handler
+            .data_access(OP, DataOperationType::Write)
+            .await;
+        let _timing = DataTiming::new(
+            OP.to_string(),
+            DataOperationType::Write,
+            Some(self.metrics_handler.clone()),
+        );
+
+        use crate::entity::sounds;
+        let existing = Sounds::find_by_id(sound_id)
+            .one(&self.sounds_db)
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+        if existing.is_some() {
+            return Ok(false);
+        }
+
+        sounds::ActiveModel {
+            user_id: ActiveValue::Set(user_id.get() as i64),
+            sound_id: ActiveValue::Set(sound_id),
+            uploaded_server_id: ActiveValue::Set(uploaded_server_id as i64),
+            sound_name: ActiveValue::Set(sound_name),
+            public: ActiveValue::Set(public),
+            uploaded_at: ActiveValue::Set(Some(OffsetDateTime::now_utc())),
+        }
+        .insert(&self.sounds_db)
+        .await
+        .context(DatabaseSnafu { operation: OP })?;
+
+        Ok(true)
+    }
+
     pub async fn set_user_public_upload_policy(
         &self,
         user_id: &serenity::UserId,
@@ -315,6 +383,39 @@ mod tests {
         assert!(sounds.len() > 0, "{sounds:?}");
     }
 
+    #[tokio::test]
+    async fn import_sound_dedup() {
+        let manager = get_manager().await;
+
+        let sound_id = uuid::Uuid::new_v4();
+
+        manager
+            .add_sound(&USER_ID_1, GUILD_ID_1, sound_id, "Ex".to_string(), None)
+            .await
+            .unwrap();
+
+        let inserted = manager
+            .import_sound(&USER_ID_2, GUILD_ID_1, sound_id, "Ex".to_string(), true)
+            .await
+            .unwrap();
+        assert!(!inserted);
+
+        let inserted = manager
+            .import_sound(
+                &USER_ID_2,
+                GUILD_ID_1,
+                uuid::Uuid::new_v4(),
+                "Ex 2".to_string(),
+                true,
+            )
+            .await
+            .unwrap();
+        assert!(inserted);
+
+        let sounds = manager.get_all_sounds().await.unwrap();
+        assert!(sounds.len() == 2, "{sounds:?}");
+    }
+
     #[tokio::test]
     async fn private_sounds() {
         let manager = get_manager().await;
@@ -50,7 +50,7 @@ impl StatsManager {
             Some(self.metrics_handler.clone()),
         );
 
-        use crate::entity::song_queues;
+        use crate::entity::{song_play_log, song_queues};
         let count = SongQueues::find()
             .filter(song_queues::Column::ServerId.eq(guild_id))
             .filter(song_queues::Column::UserId.eq(user.id.get()))
@@ -77,7 +77,7 @@ impl StatsManager {
             song_queues::ActiveModel {
                 server_id: ActiveValue::set(guild_id as i64),
                 user_id: ActiveValue::set(user.id.get() as i64),
-                youtube_id: ActiveValue::set(song_id),
+                youtube_id: ActiveValue::set(song_id.clone()),
                 description: ActiveValue::Set(description),
                 count: ActiveValue::set(1),
                 last_update: ActiveValue::Set(Some(now_odt)),
@@ -87,6 +87,17 @@ impl StatsManager {
             .context(DatabaseSnafu { operation: OP })?;
         }
 
+        song_play_log::ActiveModel {
+            play_id: ActiveValue::Set(Uuid::now_v7()),
+            server_id: ActiveValue::Set(guild_id as i64),
+            user_id: ActiveValue::Set(user.id.get() as i64),
+            youtube_id: ActiveValue::Set(song_id),
+            played_at: ActiveValue::Set(now_odt),
+        }
+        .insert(&self.stats_db)
+        .await
+        .context(DatabaseSnafu { operation: OP })?;
+
         Ok(())
     }
 }
@@ -2,14 +2,20 @@ use std::sync::Arc;
 
 use ayaya_core::metrics::{DataOperationType, MetricsSink};
 use sea_orm::{
-    ActiveModelTrait, ActiveValue, ColumnTrait, DatabaseConnection, DatabaseTransaction,
-    EntityTrait, IntoActiveModel, QueryFilter, QueryOrder, QuerySelect, TransactionTrait,
+    ActiveModelTrait, ActiveValue, ColumnTrait, Condition, DatabaseConnection, DatabaseTransaction,
+    EntityTrait, IntoActiveModel, QueryFilter, QueryOrder, TransactionTrait,
 };
 use snafu::ResultExt;
 use time::OffsetDateTime;
 use uuid::Uuid;
 
-use super::{DataResult, utils::DataTiming};
+use super::{
+    DataResult,
+    utils::DataTiming,
+    voice_analytics::{
+        CoPresence, VoiceStateTotals, pairwise_overlaps, session_spans, voice_state_totals,
+    },
+};
 use crate::entity::{prelude::*, voice_sessions, voice_state_events};
 use crate::error::DatabaseSnafu;
 
@@ -83,6 +89,21 @@ impl VoiceStateUpdateInput {
     }
 }
 
+/// A member seen in a voice channel, eg: in the gateway cache
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ObservedVoiceState {
+    pub user_id: i64,
+    pub channel_id: i64,
+    pub raw_state_json: Option<String>,
+}
+
+/// Sessions changed by [`VoiceManager::reconcile_guild_voice_states`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ReconcileReport {
+    pub closed: u64,
+    pub opened: u64,
+}
+
 #[derive(Clone)]
 pub struct VoiceManager {
     db: DatabaseConnection,
@@ -262,6 +283,87 @@ impl VoiceManager {
         Ok(Some(session_id))
     }
 
+    /// Match the open sessions of a guild with the members currently in voice, eg: after a
+    /// gateway gap where leave or join events may have been missed. Sessions of members gone or
+    /// in another channel are closed with `reason`, and members without a matching session get
+    /// one with an estimated start.
+    pub async fn reconcile_guild_voice_states(
+        &self,
+        guild_id: i64,
+        observed: &[ObservedVoiceState],
+        observed_at: OffsetDateTime,
+        reason: VoiceSessionEndReason,
+    ) -> DataResult<ReconcileReport> {
+        const OP: &str = "reconcile_guild_voice_states";
+        self.metrics_handler
+            .data_access(OP, DataOperationType::Write)
+            .await;
+        let _timing = DataTiming::new(
+            OP.to_string(),
+            DataOperationType::Write,
+            Some(self.metrics_handler.clone()),
+        );
+
+        let txn = self
+            .db
+            .begin()
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+
+        let open_sessions = voice_sessions::Entity::find()
+            .filter(voice_sessions::Column::GuildId.eq(guild_id))
+            .filter(voice_sessions::Column::LeftAt.is_null())
+            .all(&txn)
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+
+        let mut report = ReconcileReport::default();
+        let mut still_open = Vec::new();
+        for session in open_sessions {
+            let matches = observed.iter().any(|state| {
+                state.user_id == session.user_id && state.channel_id == session.channel_id
+            });
+            if matches {
+                still_open.push((session.user_id, session.channel_id));
+                continue;
+            }
+
+            let mut active_model = session.into_active_model();
+            active_model.left_at = ActiveValue::Set(Some(observed_at));
+            active_model.ended_reason = ActiveValue::Set(Some(reason.as_str().to_string()));
+            active_model
+                .update(&txn)
+                .await
+                .context(DatabaseSnafu { operation: OP })?;
+            report.closed += 1;
+        }
+
+        for state in observed {
+            if still_open.contains(&(state.user_id, state.channel_id)) {
+                continue;
+            }
+            insert_voice_session(
+                &txn,
+                guild_id,
+                state.user_id,
+                state.channel_id,
+                Uuid::now_v7(),
+                observed_at,
+                true,
+                state.raw_state_json.clone(),
+                OP,
+            )
+            .await?;
+            report.opened += 1;
+        }
+
+        txn.commit()
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+
+        Ok(report)
+    }
+
     pub async fn close_all_open_voice_sessions(
         &self,
         observed_at: OffsetDateTime,
@@ -300,29 +402,112 @@ impl VoiceManager {
         Ok(count)
     }
 
+    /// All voice sessions of a server, open ones included
     pub async fn get_server_voice_sessions(
         &self,
         server_id: u64,
     ) -> DataResult<Vec<voice_sessions::Model>> {
-        const OP: &str = "close_all_open_voice_sessions";
+        self.get_server_voice_sessions_since(server_id, None).await
+    }
+
+    /// Voice sessions of a server overlapping the time since `since`, oldest first. Open
+    /// sessions are included. None returns every session.
+    pub async fn get_server_voice_sessions_since(
+        &self,
+        server_id: u64,
+        since: Option<OffsetDateTime>,
+    ) -> DataResult<Vec<voice_sessions::Model>> {
+        const OP: &str = "get_server_voice_sessions";
         self.metrics_handler
-            .data_access(OP, DataOperationType::Write)
+            .data_access(OP, DataOperationType::Read)
             .await;
         let _timing = DataTiming::new(
             OP.to_string(),
-            DataOperationType::Write,
+            DataOperationType::Read,
             Some(self.metrics_handler.clone()),
         );
 
-        let models = VoiceSessions::find()
+        let mut query = VoiceSessions::find()
             .filter(voice_sessions::Column::GuildId.eq(server_id))
-            .group_by(voice_sessions::Column::UserId)
+            .order_by_asc(voice_sessions::Column::JoinedAt);
+        if let Some(since) = since {
+            query = query.filter(
+                Condition::any()
+                    .add(voice_sessions::Column::LeftAt.is_null())
+                    .add(voice_sessions::Column::LeftAt.gte(since)),
+            );
+        }
+
+        let models = query
             .all(&self.db)
             .await
             .context(DatabaseSnafu { operation: OP })?;
 
         Ok(models)
     }
+
+    /// Time every pair of members of a server spent in the same channel since `since`, most
+    /// first. Open sessions count up to `now`.
+    pub async fn get_co_presence(
+        &self,
+        server_id: u64,
+        since: Option<OffsetDateTime>,
+        now: OffsetDateTime,
+    ) -> DataResult<Vec<CoPresence>> {
+        let sessions = self
+            .get_server_voice_sessions_since(server_id, since)
+            .await?;
+        Ok(pairwise_overlaps(&session_spans(&sessions, since, now)))
+    }
+
+    /// Voice state events of a server since `since`, oldest first
+    pub async fn get_server_voice_state_events_since(
+        &self,
+        server_id: u64,
+        since: OffsetDateTime,
+    ) -> DataResult<Vec<voice_state_events::Model>> {
+        const OP: &str = "get_server_voice_state_events_since";
+        self.metrics_handler
+            .data_access(OP, DataOperationType::Read)
+            .await;
+        let _timing = DataTiming::new(
+            OP.to_string(),
+            DataOperationType::Read,
+            Some(self.metrics_handler.clone()),
+        );
+
+        VoiceStateEvents::find()
+            .filter(voice_state_events::Column::GuildId.eq(server_id))
+            .filter(voice_state_events::Column::OccurredAt.gte(since))
+            .order_by_asc(voice_state_events::Column::OccurredAt)
+            .all(&self.db)
+            .await
+            .context(DatabaseSnafu { operation: OP })
+    }
+
+    /// Time every member of a server spent muted, deafened, streaming, on camera or suppressed
+    /// since `since`, most time in voice first. Open sessions count up to `now`.
+    pub async fn get_voice_state_totals(
+        &self,
+        server_id: u64,
+        since: Option<OffsetDateTime>,
+        now: OffsetDateTime,
+    ) -> DataResult<Vec<(u64, VoiceStateTotals)>> {
+        let sessions = self
+            .get_server_voice_sessions_since(server_id, since)
+            .await?;
+        // sessions already open at `since` need the events from their start
+        let Some(first_join) = sessions.iter().map(|session| session.joined_at).min() else {
+            return Ok(Vec::new());
+        };
+        let events = self
+            .get_server_voice_state_events_since(server_id, first_join)
+            .await?;
+        Ok(voice_state_totals(
+            &events,
+            &session_spans(&sessions, since, now),
+        ))
+    }
 }
 
 async fn insert_voice_state_event(
@@ -454,3 +639,228 @@ async fn close_voice_session(
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::*;
+    use ayaya_core::metrics::NoopMetrics;
+
+    use migration::Migrator as SqliteMigrator;
+    use migration::MigratorTrait;
+    use sea_orm::Database;
+    use time::{Duration, macros::datetime};
+
+    async fn get_manager() -> VoiceManager {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        SqliteMigrator::up(&db, None).await.unwrap();
+        VoiceManager::new(db, Arc::new(NoopMetrics))
+    }
+
+    fn update(
+        user_id: u64,
+        from: Option<i64>,
+        to: Option<i64>,
+        occurred_at: OffsetDateTime,
+    ) -> VoiceStateUpdateInput {
+        VoiceStateUpdateInput {
+            guild_id: GUILD_ID_1 as i64,
+            user_id: user_id as i64,
+            from_channel_id: from,
+            to_channel_id: to,
+            occurred_at,
+            self_mute: false,
+            self_deaf: false,
+            mute: false,
+            deaf: false,
+            self_stream: false,
+            self_video: false,
+            suppress: false,
+            request_to_speak_at: None,
+            raw_state_json: None,
+            start_is_estimated: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn server_sessions_in_window() {
+        let manager = get_manager().await;
+        let start = datetime!(2026-10-01 00:00 UTC);
+        let user = USER_ID_1.get();
+
+        // two sessions of the same user, then a third still open
+        for input in [
+            update(user, None, Some(1), start),
+            update(user, Some(1), Some(2), start + Duration::hours(1)),
+            update(user, Some(2), None, start + Duration::hours(2)),
+            update(USER_ID_2.get(), None, Some(1), start + Duration::days(10)),
+        ] {
+            manager.apply_voice_state_update(input).await.unwrap();
+        }
+
+        let all = manager.get_server_voice_sessions(GUILD_ID_1).await.unwrap();
+        assert_eq!(all.len(), 3);
+        assert_eq!(
+            all.iter()
+                .map(|session| session.channel_id)
+                .collect::<Vec<_>>(),
+            vec![1, 2, 1]
+        );
+
+        let recent = manager
+            .get_server_voice_sessions_since(GUILD_ID_1, Some(start + Duration::minutes(90)))
+            .await
+            .unwrap();
+        assert_eq!(recent.len(), 2);
+        assert_eq!(recent[0].channel_id, 2);
+        assert!(recent[1].left_at.is_none());
+    }
+
+    #[tokio::test]
+    async fn co_presence_of_a_server() {
+        let manager = get_manager().await;
+        let start = datetime!(2026-10-01 00:00 UTC);
+        let (user_1, user_2) = (USER_ID_1.get(), USER_ID_2.get());
+
+        for input in [
+            update(user_1, None, Some(1), start),
+            update(user_2, None, Some(1), start + Duration::minutes(30)),
+            update(user_1, Some(1), Some(2), start + Duration::hours(1)),
+            update(user_2, Some(1), Some(2), start + Duration::hours(2)),
+        ] {
+            manager.apply_voice_state_update(input).await.unwrap();
+        }
+
+        // both still connected in channel 2
+        let now = start + Duration::hours(3);
+        let pairs = manager
+            .get_co_presence(GUILD_ID_1, None, now)
+            .await
+            .unwrap();
+        assert_eq!(pairs.len(), 1);
+        assert!(pairs[0].involves(user_1) && pairs[0].involves(user_2));
+        assert_eq!(pairs[0].overlap, Duration::minutes(90));
+
+        let recent = manager
+            .get_co_presence(GUILD_ID_1, Some(start + Duration::hours(2)), now)
+            .await
+            .unwrap();
+        assert_eq!(recent[0].overlap, Duration::hours(1));
+    }
+
+    #[tokio::test]
+    async fn voice_state_totals_of_a_server() {
+        let manager = get_manager().await;
+        let start = datetime!(2026-10-01 00:00 UTC);
+        let user = USER_ID_1.get();
+
+        for input in [
+            update(user, None, Some(1), start),
+            VoiceStateUpdateInput {
+                self_mute: true,
+                ..update(user, Some(1), Some(1), start + Duration::hours(1))
+            },
+            VoiceStateUpdateInput {
+                self_mute: true,
+                self_video: true,
+                ..update(user, Some(1), Some(2), start + Duration::hours(2))
+            },
+            update(user, Some(2), None, start + Duration::hours(3)),
+        ] {
+            manager.apply_voice_state_update(input).await.unwrap();
+        }
+
+        let now = start + Duration::days(1);
+        let totals = manager
+            .get_voice_state_totals(GUILD_ID_1, None, now)
+            .await
+            .unwrap();
+        assert_eq!(totals.len(), 1);
+        assert_eq!(totals[0].0, user);
+        assert_eq!(totals[0].1.in_voice, Duration::hours(3));
+        assert_eq!(totals[0].1.muted, Duration::hours(2));
+        assert_eq!(totals[0].1.video, Duration::hours(1));
+
+        // the mute from before the window still counts
+        let recent = manager
+            .get_voice_state_totals(GUILD_ID_1, Some(start + Duration::minutes(90)), now)
+            .await
+            .unwrap();
+        assert_eq!(recent[0].1.muted, Duration::minutes(90));
+
+        assert!(
+            manager
+                .get_voice_state_totals(GUILD_ID_1, Some(now), now)
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn reconcile_after_gateway_gap() {
+        let manager = get_manager().await;
+        let start = datetime!(2026-10-01 00:00 UTC);
+        let (user_1, user_2) = (USER_ID_1.get(), USER_ID_2.get());
+        let guild_id = GUILD_ID_1 as i64;
+
+        for input in [
+            update(user_1, None, Some(1), start),
+            update(user_2, None, Some(1), start),
+        ] {
+            manager.apply_voice_state_update(input).await.unwrap();
+        }
+
+        // during the gap user 1 moved to channel 2, user 2 left, and user 1 is seen again
+        let observed_at = start + Duration::hours(1);
+        let observed = [ObservedVoiceState {
+            user_id: user_1 as i64,
+            channel_id: 2,
+            raw_state_json: None,
+        }];
+        let report = manager
+            .reconcile_guild_voice_states(
+                guild_id,
+                &observed,
+                observed_at,
+                VoiceSessionEndReason::Disconnect,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            report,
+            ReconcileReport {
+                closed: 2,
+                opened: 1
+            }
+        );
+
+        let sessions = manager.get_server_voice_sessions(GUILD_ID_1).await.unwrap();
+        assert_eq!(sessions.len(), 3);
+        let open = sessions
+            .iter()
+            .filter(|session| session.left_at.is_none())
+            .collect::<Vec<_>>();
+        assert_eq!(open.len(), 1);
+        assert_eq!(open[0].channel_id, 2);
+        assert!(open[0].start_is_estimated);
+        assert!(
+            sessions
+                .iter()
+                .filter(|session| session.left_at == Some(observed_at))
+                .all(|session| session.ended_reason.as_deref() == Some("disconnect"))
+        );
+
+        // nothing changed since
+        let report = manager
+            .reconcile_guild_voice_states(
+                guild_id,
+                &observed,
+                observed_at + Duration::minutes(5),
+                VoiceSessionEndReason::Disconnect,
+            )
+            .await
+            .unwrap();
+        assert_eq!(report, ReconcileReport::default());
+    }
+}
@@ -0,0 +1,889 @@
+//! Reports built from voice sessions: activity by hour of day and day of week, busiest channels,
+//! peaks of concurrent members, member profiles and who shares channels with whom. These work on
+//! sessions already loaded by [`super::voice::VoiceManager`], so they don't touch the database.
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use time::{Date, Duration, OffsetDateTime, Time, UtcOffset, macros::offset};
+
+use crate::entity::{voice_sessions, voice_state_events};
+
+/// Timezone the reports are bucketed in, same as the command stats
+pub const REPORT_OFFSET: UtcOffset = offset!(+8);
+
+/// Minutes spent in voice, summed over members, by weekday (Monday first) and hour of day
+pub type Heatmap = [[f64; 24]; 7];
+
+/// Time window of a voice report, ending now
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VoiceWindow {
+    Day,
+    Week,
+    #[default]
+    Month,
+    Year,
+    AllTime,
+}
+
+impl VoiceWindow {
+    /// Start of the window, None for all time
+    pub fn since(self, now: OffsetDateTime) -> Option<OffsetDateTime> {
+        let days = match self {
+            Self::Day => 1,
+            Self::Week => 7,
+            Self::Month => 30,
+            Self::Year => 365,
+            Self::AllTime => return None,
+        };
+        Some(now - Duration::days(days))
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Day => "last 24 hours",
+            Self::Week => "last 7 days",
+            Self::Month => "last 30 days",
+            Self::Year => "last 365 days",
+            Self::AllTime => "all time",
+        }
+    }
+}
+
+/// A voice session clipped to a report window
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SessionSpan {
+    pub user_id: u64,
+    pub channel_id: u64,
+    pub start: OffsetDateTime,
+    pub end: OffsetDateTime,
+}
+
+impl SessionSpan {
+    pub fn duration(&self) -> Duration {
+        self.end - self.start
+    }
+}
+
+/// Clip sessions to the window between `since` and `now`. Open sessions last until `now`, and
+/// sessions falling outside the window are dropped.
+pub fn session_spans(
+    sessions: &[voice_sessions::Model],
+    since: Option<OffsetDateTime>,
+    now: OffsetDateTime,
+) -> Vec<SessionSpan> {
+    sessions
+        .iter()
+        .filter_map(|session| {
+            let start = match since {
+                Some(since) => session.joined_at.max(since),
+                None => session.joined_at,
+            };
+            let end = session.left_at.unwrap_or(now).min(now);
+            (start < end).then_some(SessionSpan {
+                user_id: session.user_id as u64,
+                channel_id: session.channel_id as u64,
+                start,
+                end,
+            })
+        })
+        .collect()
+}
+
+/// Cut spans to start no earlier than `since`, dropping the ones ending before it
+pub fn clip_spans(spans: &[SessionSpan], since: Option<OffsetDateTime>) -> Vec<SessionSpan> {
+    let Some(since) = since else {
+        return spans.to_vec();
+    };
+    spans
+        .iter()
+        .filter(|span| span.end > since)
+        .map(|span| SessionSpan {
+            start: span.start.max(since),
+            ..*span
+        })
+        .collect()
+}
+
+/// Midnight starting the local week (Monday) of `now`
+pub fn week_start(now: OffsetDateTime, offset: UtcOffset) -> OffsetDateTime {
+    let local = now.to_offset(offset);
+    let days = local.weekday().number_days_from_monday() as i64;
+    (local - Duration::days(days)).replace_time(Time::MIDNIGHT)
+}
+
+/// Midnight starting the local month of `now`
+pub fn month_start(now: OffsetDateTime, offset: UtcOffset) -> OffsetDateTime {
+    let local = now.to_offset(offset);
+    let days = local.day() as i64 - 1;
+    (local - Duration::days(days)).replace_time(Time::MIDNIGHT)
+}
+
+/// Split every span on the hour boundaries of `offset` and sum the minutes per weekday and hour
+pub fn activity_heatmap(spans: &[SessionSpan], offset: UtcOffset) -> Heatmap {
+    let offset_secs = offset.whole_seconds() as i64;
+    let mut heatmap = [[0.0; 24]; 7];
+
+    for span in spans {
+        let mut cursor = span.start;
+        while cursor < span.end {
+            let local_secs = cursor.unix_timestamp() + offset_secs;
+            let next_hour = (local_secs.div_euclid(3600) + 1) * 3600 - offset_secs;
+            let next = OffsetDateTime::from_unix_timestamp(next_hour)
+                .map_or(span.end, |next| next.min(span.end));
+
+            let local = cursor.to_offset(offset);
+            let weekday = local.weekday().number_days_from_monday() as usize;
+            heatmap[weekday][local.hour() as usize] += (next - cursor).as_seconds_f64() / 60.0;
+            cursor = next;
+        }
+    }
+
+    heatmap
+}
+
+/// Minutes per hour of day, over all weekdays
+pub fn hourly_totals(heatmap: &Heatmap) -> [f64; 24] {
+    let mut totals = [0.0; 24];
+    for day in heatmap {
+        for (hour, minutes) in day.iter().enumerate() {
+            totals[hour] += minutes;
+        }
+    }
+    totals
+}
+
+/// Minutes per weekday, Monday first
+pub fn weekday_totals(heatmap: &Heatmap) -> [f64; 7] {
+    heatmap.map(|day| day.iter().sum())
+}
+
+/// Index of the largest value, None if all are zero
+pub fn busiest_index(totals: &[f64]) -> Option<usize> {
+    totals
+        .iter()
+        .enumerate()
+        .filter(|(_, minutes)| **minutes > 0.0)
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(index, _)| index)
+}
+
+/// Total time spent in each channel, busiest first
+pub fn busiest_channels(spans: &[SessionSpan]) -> Vec<(u64, Duration)> {
+    let mut totals: HashMap<u64, Duration> = HashMap::new();
+    for span in spans {
+        *totals.entry(span.channel_id).or_default() += span.duration();
+    }
+
+    let mut totals = totals.into_iter().collect::<Vec<_>>();
+    totals.sort_by(|(a_id, a), (b_id, b)| b.cmp(a).then(a_id.cmp(b_id)));
+    totals
+}
+
+/// Time each member spent in voice, optionally in a single channel, most first
+pub fn member_totals(spans: &[SessionSpan], channel_id: Option<u64>) -> Vec<(u64, Duration)> {
+    let mut totals: HashMap<u64, Duration> = HashMap::new();
+    for span in spans
+        .iter()
+        .filter(|span| channel_id.is_none_or(|channel_id| span.channel_id == channel_id))
+    {
+        *totals.entry(span.user_id).or_default() += span.duration();
+    }
+
+    let mut totals = totals.into_iter().collect::<Vec<_>>();
+    totals.sort_by(|(a_id, a), (b_id, b)| b.cmp(a).then(a_id.cmp(b_id)));
+    totals
+}
+
+/// Number of members in voice over time, with a point at every change
+pub fn concurrency_timeline(spans: &[SessionSpan]) -> Vec<(OffsetDateTime, usize)> {
+    let mut changes = spans
+        .iter()
+        .flat_map(|span| [(span.start, 1), (span.end, -1)])
+        .collect::<Vec<(OffsetDateTime, i64)>>();
+    // at the same instant, leaves come before joins so back to back sessions don't overlap
+    changes.sort();
+
+    let mut timeline: Vec<(OffsetDateTime, usize)> = Vec::new();
+    let mut current: i64 = 0;
+    for (time, change) in changes {
+        current += change;
+        let count = current.max(0) as usize;
+        match timeline.last_mut() {
+            Some((last_time, last_count)) if *last_time == time => *last_count = count,
+            _ => timeline.push((time, count)),
+        }
+    }
+    timeline
+}
+
+/// The first moment with the most members in voice at once
+pub fn peak_concurrency(timeline: &[(OffsetDateTime, usize)]) -> Option<(OffsetDateTime, usize)> {
+    timeline
+        .iter()
+        .copied()
+        .filter(|(_, count)| *count > 0)
+        .reduce(|peak, point| if point.1 > peak.1 { point } else { peak })
+}
+
+/// Most members in voice at once for every local day of the timeline. Days without any change
+/// carry over the count of the day before.
+pub fn daily_peaks(timeline: &[(OffsetDateTime, usize)], offset: UtcOffset) -> Vec<(Date, usize)> {
+    let mut peaks: BTreeMap<Date, usize> = BTreeMap::new();
+    let mut previous: Option<(Date, usize)> = None;
+
+    for (time, count) in timeline {
+        let day = time.to_offset(offset).date();
+        if let Some((mut previous_day, previous_count)) = previous {
+            // the count before this change lasted through the days in between
+            while previous_day < day {
+                previous_day = match previous_day.next_day() {
+                    Some(next) => next,
+                    None => break,
+                };
+                let peak = peaks.entry(previous_day).or_default();
+                *peak = (*peak).max(previous_count);
+            }
+        }
+        let peak = peaks.entry(day).or_default();
+        *peak = (*peak).max(*count);
+        previous = Some((day, *count));
+    }
+
+    peaks.into_iter().collect()
+}
+
+/// A member's voice habits, see [`voice_profile`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VoiceProfile {
+    pub total: Duration,
+    pub session_count: usize,
+    pub longest_session: Option<SessionSpan>,
+    /// Channel with the most time and its total
+    pub favorite_channel: Option<(u64, Duration)>,
+    /// Members sharing a channel the longest, most time first
+    pub companions: Vec<(u64, Duration)>,
+    /// Local days with any time in voice
+    pub active_days: usize,
+    /// Consecutive active days up to today, or up to yesterday if not yet active today
+    pub current_streak: usize,
+    pub longest_streak: usize,
+}
+
+/// Build the voice profile of a member from the spans of the whole server. Streaks only see
+/// the days covered by the spans.
+pub fn voice_profile(
+    spans: &[SessionSpan],
+    user_id: u64,
+    today: Date,
+    offset: UtcOffset,
+) -> VoiceProfile {
+    let own = spans
+        .iter()
+        .filter(|span| span.user_id == user_id)
+        .copied()
+        .collect::<Vec<_>>();
+    let days = active_days(&own, offset);
+    let (current_streak, longest_streak) = streaks(&days, today);
+
+    VoiceProfile {
+        total: own.iter().map(SessionSpan::duration).sum(),
+        session_count: own.len(),
+        longest_session: own.iter().copied().max_by_key(SessionSpan::duration),
+        favorite_channel: busiest_channels(&own).first().copied(),
+        companions: companions(spans, user_id),
+        active_days: days.len(),
+        current_streak,
+        longest_streak,
+    }
+}
+
+/// Time other members spent in the same channel at the same time as the member, most first
+pub fn companions(spans: &[SessionSpan], user_id: u64) -> Vec<(u64, Duration)> {
+    let mut totals = pairwise_overlaps(spans)
+        .into_iter()
+        .filter(|pair| pair.involves(user_id) && pair.overlap.is_positive())
+        .map(|pair| (pair.other(user_id), pair.overlap))
+        .collect::<Vec<_>>();
+    totals.sort_by(|(a_id, a), (b_id, b)| b.cmp(a).then(a_id.cmp(b_id)));
+    totals
+}
+
+/// Time two members spent in the same channel at the same time
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CoPresence {
+    /// The lower of the two ids
+    pub user_a: u64,
+    pub user_b: u64,
+    pub overlap: Duration,
+}
+
+impl CoPresence {
+    pub fn involves(&self, user_id: u64) -> bool {
+        self.user_a == user_id || self.user_b == user_id
+    }
+
+    /// The member paired with `user_id`
+    pub fn other(&self, user_id: u64) -> u64 {
+        if self.user_a == user_id {
+            self.user_b
+        } else {
+            self.user_a
+        }
+    }
+}
+
+/// Overlap time of every pair of members who shared a channel, most first
+pub fn pairwise_overlaps(spans: &[SessionSpan]) -> Vec<CoPresence> {
+    let mut by_channel: HashMap<u64, Vec<&SessionSpan>> = HashMap::new();
+    for span in spans {
+        by_channel.entry(span.channel_id).or_default().push(span);
+    }
+
+    let mut totals: HashMap<(u64, u64), Duration> = HashMap::new();
+    for mut channel_spans in by_channel.into_values() {
+        channel_spans.sort_by_key(|span| span.start);
+        for (i, first) in channel_spans.iter().enumerate() {
+            // sorted by start, so nothing after a span starting past our end can overlap
+            for second in channel_spans[i + 1..]
+                .iter()
+                .take_while(|second| second.start < first.end)
+            {
+                if first.user_id == second.user_id {
+                    continue;
+                }
+                let overlap = first.end.min(second.end) - second.start;
+                let pair = (
+                    first.user_id.min(second.user_id),
+                    first.user_id.max(second.user_id),
+                );
+                *totals.entry(pair).or_default() += overlap;
+            }
+        }
+    }
+
+    let mut pairs = totals
+        .into_iter()
+        .map(|((user_a, user_b), overlap)| CoPresence {
+            user_a,
+            user_b,
+            overlap,
+        })
+        .collect::<Vec<_>>();
+    pairs.sort_by(|a, b| {
+        b.overlap
+            .cmp(&a.overlap)
+            .then((a.user_a, a.user_b).cmp(&(b.user_a, b.user_b)))
+    });
+    pairs
+}
+
+/// Time a member spent in each voice state, see [`voice_state_totals`]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct VoiceStateTotals {
+    pub in_voice: Duration,
+    /// Muted by themselves or by a moderator
+    pub muted: Duration,
+    /// Deafened by themselves or by a moderator
+    pub deafened: Duration,
+    pub streaming: Duration,
+    pub video: Duration,
+    /// In a stage channel without permission to speak
+    pub suppressed: Duration,
+}
+
+impl VoiceStateTotals {
+    /// Add the totals of another member, for server wide numbers
+    pub fn merge(&mut self, other: &Self) {
+        self.in_voice += other.in_voice;
+        self.muted += other.muted;
+        self.deafened += other.deafened;
+        self.streaming += other.streaming;
+        self.video += other.video;
+        self.suppressed += other.suppressed;
+    }
+}
+
+/// Time each member spent muted, deafened, streaming, on camera or suppressed, most time in
+/// voice first. A state lasts from its event until the member's next event, but only counts
+/// while one of their spans is open, so a lost leave event doesn't count forever. `events` must
+/// include the last event before each span starts.
+pub fn voice_state_totals(
+    events: &[voice_state_events::Model],
+    spans: &[SessionSpan],
+) -> Vec<(u64, VoiceStateTotals)> {
+    let mut events_by_user: HashMap<u64, Vec<&voice_state_events::Model>> = HashMap::new();
+    for event in events {
+        events_by_user
+            .entry(event.user_id as u64)
+            .or_default()
+            .push(event);
+    }
+    let mut spans_by_user: HashMap<u64, Vec<&SessionSpan>> = HashMap::new();
+    for span in spans {
+        spans_by_user.entry(span.user_id).or_default().push(span);
+    }
+
+    let mut totals = Vec::with_capacity(spans_by_user.len());
+    for (user_id, user_spans) in spans_by_user {
+        let mut user_totals = VoiceStateTotals {
+            in_voice: user_spans.iter().map(|span| span.duration()).sum(),
+            ..Default::default()
+        };
+
+        let mut user_events = events_by_user.remove(&user_id).unwrap_or_default();
+        user_events.sort_by_key(|event| event.occurred_at);
+        for (i, event) in user_events.iter().enumerate() {
+            let until = user_events.get(i + 1).map(|next| next.occurred_at);
+            let covered: Duration = user_spans
+                .iter()
+                .map(|span| {
+                    let start = span.start.max(event.occurred_at);
+                    let end = until.map_or(span.end, |until| span.end.min(until));
+                    (end - start).max(Duration::ZERO)
+                })
+                .sum();
+            if covered.is_zero() {
+                continue;
+            }
+
+            if event.self_mute || event.mute {
+                user_totals.muted += covered;
+            }
+            if event.self_deaf || event.deaf {
+                user_totals.deafened += covered;
+            }
+            if event.self_stream {
+                user_totals.streaming += covered;
+            }
+            if event.self_video {
+                user_totals.video += covered;
+            }
+            if event.suppress {
+                user_totals.suppressed += covered;
+            }
+        }
+        totals.push((user_id, user_totals));
+    }
+
+    totals.sort_by(|(a_id, a), (b_id, b)| b.in_voice.cmp(&a.in_voice).then(a_id.cmp(b_id)));
+    totals
+}
+
+/// Local days touched by the spans
+pub fn active_days(spans: &[SessionSpan], offset: UtcOffset) -> BTreeSet<Date> {
+    let mut days = BTreeSet::new();
+    for span in spans {
+        let mut day = span.start.to_offset(offset).date();
+        // a span ending at midnight doesn't touch the next day
+        let last = (span.end - Duration::NANOSECOND)
+            .max(span.start)
+            .to_offset(offset)
+            .date();
+        while day <= last {
+            days.insert(day);
+            day = match day.next_day() {
+                Some(next) => next,
+                None => break,
+            };
+        }
+    }
+    days
+}
+
+/// The current and the longest run of consecutive days. The current run may end yesterday, so
+/// it isn't lost before the member joins voice today.
+pub fn streaks(days: &BTreeSet<Date>, today: Date) -> (usize, usize) {
+    let mut longest = 0;
+    let mut run = 0;
+    let mut previous: Option<Date> = None;
+    for day in days {
+        run = match previous {
+            Some(previous) if previous.next_day() == Some(*day) => run + 1,
+            _ => 1,
+        };
+        longest = longest.max(run);
+        previous = Some(*day);
+    }
+
+    let current = match previous {
+        Some(last) if last == today || last.next_day() == Some(today) => run,
+        _ => 0,
+    };
+    (current, longest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use time::macros::{date, datetime};
+    use uuid::Uuid;
+
+    fn session(
+        user_id: i64,
+        channel_id: i64,
+        joined_at: OffsetDateTime,
+        left_at: Option<OffsetDateTime>,
+    ) -> voice_sessions::Model {
+        voice_sessions::Model {
+            session_id: Uuid::now_v7(),
+            guild_id: 1,
+            user_id,
+            channel_id,
+            joined_at,
+            start_is_estimated: false,
+            left_at,
+            join_event_id: None,
+            leave_event_id: None,
+            ended_reason: None,
+            join_state_json: None,
+            leave_state_json: None,
+        }
+    }
+
+    fn span(user_id: u64, start: OffsetDateTime, end: OffsetDateTime) -> SessionSpan {
+        SessionSpan {
+            user_id,
+            channel_id: 10,
+            start,
+            end,
+        }
+    }
+
+    #[test]
+    fn spans_are_clipped_to_the_window() {
+        let now = datetime!(2026-10-18 12:00 UTC);
+        let sessions = [
+            session(1, 10, datetime!(2026-10-18 09:00 UTC), None),
+            session(
+                2,
+                10,
+                datetime!(2026-10-16 23:00 UTC),
+                Some(datetime!(2026-10-17 13:00 UTC)),
+            ),
+            session(
+                3,
+                11,
+                datetime!(2026-10-01 00:00 UTC),
+                Some(datetime!(2026-10-01 01:00 UTC)),
+            ),
+        ];
+
+        let spans = session_spans(&sessions, VoiceWindow::Day.since(now), now);
+
+        assert_eq!(spans.len(), 2);
+        assert_eq!(spans[0].duration(), Duration::hours(3));
+        assert_eq!(spans[1].start, datetime!(2026-10-17 12:00 UTC));
+        assert_eq!(spans[1].duration(), Duration::hours(1));
+        assert_eq!(session_spans(&sessions, None, now).len(), 3);
+    }
+
+    #[test]
+    fn heatmap_splits_on_local_hours() {
+        // 2026-10-18 is a Sunday, 23:30 UTC+8 to 00:45 UTC+8 the next day
+        let spans = [span(
+            1,
+            datetime!(2026-10-18 15:30 UTC),
+            datetime!(2026-10-18 16:45 UTC),
+        )];
+
+        let heatmap = activity_heatmap(&spans, REPORT_OFFSET);
+
+        assert_eq!(heatmap[6][23], 30.0);
+        assert_eq!(heatmap[0][0], 45.0);
+        assert_eq!(weekday_totals(&heatmap).iter().sum::<f64>(), 75.0);
+        assert_eq!(busiest_index(&hourly_totals(&heatmap)), Some(0));
+        assert_eq!(busiest_index(&[0.0; 24]), None);
+    }
+
+    #[test]
+    fn channels_sorted_by_time() {
+        let start = datetime!(2026-10-18 00:00 UTC);
+        let mut spans = vec![span(1, start, start + Duration::hours(1))];
+        spans.push(SessionSpan {
+            channel_id: 11,
+            ..span(2, start, start + Duration::hours(2))
+        });
+        spans.push(span(3, start, start + Duration::minutes(30)));
+
+        assert_eq!(
+            busiest_channels(&spans),
+            vec![(11, Duration::hours(2)), (10, Duration::minutes(90))]
+        );
+    }
+
+    #[test]
+    fn concurrency_and_peaks() {
+        let day_1 = datetime!(2026-10-17 02:00 UTC);
+        let spans = [
+            // lasts over two local days
+            span(1, day_1, day_1 + Duration::hours(30)),
+            span(2, day_1 + Duration::hours(1), day_1 + Duration::hours(2)),
+            // starts when the previous one ends
+            span(3, day_1 + Duration::hours(2), day_1 + Duration::hours(3)),
+        ];
+
+        let timeline = concurrency_timeline(&spans);
+        assert_eq!(
+            timeline,
+            vec![
+                (day_1, 1),
+                (day_1 + Duration::hours(1), 2),
+                (day_1 + Duration::hours(2), 2),
+                (day_1 + Duration::hours(3), 1),
+                (day_1 + Duration::hours(30), 0),
+            ]
+        );
+        assert_eq!(
+            peak_concurrency(&timeline),
+            Some((day_1 + Duration::hours(1), 2))
+        );
+        assert_eq!(peak_concurrency(&[]), None);
+
+        let peaks = daily_peaks(&timeline, REPORT_OFFSET);
+        assert_eq!(
+            peaks,
+            vec![(date!(2026 - 10 - 17), 2), (date!(2026 - 10 - 18), 1)]
+        );
+    }
+
+    #[test]
+    fn clip_and_calendar() {
+        let start = datetime!(2026-10-18 00:00 UTC);
+        let spans = [
+            span(1, start, start + Duration::hours(2)),
+            span(2, start - Duration::hours(3), start - Duration::hours(1)),
+        ];
+        let clipped = clip_spans(&spans, Some(start + Duration::hours(1)));
+        assert_eq!(clipped.len(), 1);
+        assert_eq!(clipped[0].duration(), Duration::hours(1));
+        assert_eq!(clip_spans(&spans, None).len(), 2);
+
+        // Sunday 2026-10-18 08:00 in UTC+8
+        assert_eq!(
+            week_start(start, REPORT_OFFSET),
+            datetime!(2026-10-12 00:00 +8)
+        );
+        assert_eq!(
+            month_start(start, REPORT_OFFSET),
+            datetime!(2026-10-01 00:00 +8)
+        );
+    }
+
+    #[test]
+    fn profile_of_a_member() {
+        let start = datetime!(2026-10-16 12:00 UTC);
+        let spans = [
+            span(1, start, start + Duration::hours(2)),
+            span(2, start + Duration::hours(1), start + Duration::hours(3)),
+            span(3, start, start + Duration::minutes(30)),
+            // the next two days
+            span(
+                1,
+                start + Duration::days(1),
+                start + Duration::days(1) + Duration::hours(1),
+            ),
+            SessionSpan {
+                channel_id: 11,
+                ..span(
+                    1,
+                    start + Duration::days(2),
+                    start + Duration::days(2) + Duration::hours(4),
+                )
+            },
+            SessionSpan {
+                channel_id: 11,
+                ..span(
+                    2,
+                    start + Duration::days(2),
+                    start + Duration::days(2) + Duration::hours(1),
+                )
+            },
+        ];
+
+        let profile = voice_profile(&spans, 1, date!(2026 - 10 - 19), REPORT_OFFSET);
+
+        assert_eq!(profile.total, Duration::hours(7));
+        assert_eq!(profile.session_count, 3);
+        assert_eq!(profile.longest_session, Some(spans[4]));
+        assert_eq!(profile.favorite_channel, Some((11, Duration::hours(4))));
+        assert_eq!(
+            profile.companions,
+            vec![(2, Duration::hours(2)), (3, Duration::minutes(30))]
+        );
+        assert_eq!(profile.active_days, 3);
+        assert_eq!(profile.current_streak, 3);
+        assert_eq!(profile.longest_streak, 3);
+    }
+
+    #[test]
+    fn streak_edges() {
+        let days = [
+            date!(2026 - 10 - 01),
+            date!(2026 - 10 - 02),
+            date!(2026 - 10 - 05),
+            date!(2026 - 10 - 06),
+            date!(2026 - 10 - 07),
+        ]
+        .into_iter()
+        .collect::<BTreeSet<_>>();
+
+        assert_eq!(streaks(&days, date!(2026 - 10 - 07)), (3, 3));
+        assert_eq!(streaks(&days, date!(2026 - 10 - 08)), (3, 3));
+        assert_eq!(streaks(&days, date!(2026 - 10 - 09)), (0, 3));
+        assert_eq!(streaks(&BTreeSet::new(), date!(2026 - 10 - 09)), (0, 0));
+    }
+
+    #[test]
+    fn member_totals_are_sorted() {
+        let start = datetime!(2026-10-18 00:00 UTC);
+        let spans = [
+            span(1, start, start + Duration::hours(1)),
+            span(2, start, start + Duration::hours(3)),
+            span(3, start, start + Duration::hours(1)),
+            SessionSpan {
+                channel_id: 11,
+                ..span(1, start + Duration::hours(2), start + Duration::hours(4))
+            },
+        ];
+
+        assert_eq!(
+            member_totals(&spans, None),
+            vec![
+                (1, Duration::hours(3)),
+                (2, Duration::hours(3)),
+                (3, Duration::hours(1)),
+            ]
+        );
+        assert_eq!(
+            member_totals(&spans, Some(10)),
+            vec![
+                (2, Duration::hours(3)),
+                (1, Duration::hours(1)),
+                (3, Duration::hours(1)),
+            ]
+        );
+        assert!(member_totals(&spans, Some(12)).is_empty());
+    }
+
+    #[test]
+    fn pairs_share_a_channel() {
+        let start = datetime!(2026-10-18 00:00 UTC);
+        let spans = [
+            span(2, start, start + Duration::hours(3)),
+            span(1, start + Duration::hours(1), start + Duration::hours(2)),
+            span(3, start + Duration::hours(2), start + Duration::hours(4)),
+            // same time, other channel
+            SessionSpan {
+                channel_id: 11,
+                ..span(4, start, start + Duration::hours(4))
+            },
+        ];
+
+        let pairs = pairwise_overlaps(&spans);
+        assert_eq!(
+            pairs,
+            vec![
+                CoPresence {
+                    user_a: 1,
+                    user_b: 2,
+                    overlap: Duration::hours(1),
+                },
+                CoPresence {
+                    user_a: 2,
+                    user_b: 3,
+                    overlap: Duration::hours(1),
+                },
+            ]
+        );
+        assert!(pairs[1].involves(3));
+        assert_eq!(pairs[1].other(3), 2);
+    }
+
+    fn event(user_id: i64, occurred_at: OffsetDateTime) -> voice_state_events::Model {
+        voice_state_events::Model {
+            event_id: Uuid::now_v7(),
+            guild_id: 1,
+            user_id,
+            event_kind: "state_change".to_string(),
+            from_channel_id: Some(10),
+            to_channel_id: Some(10),
+            occurred_at,
+            session_id: None,
+            self_mute: false,
+            self_deaf: false,
+            mute: false,
+            deaf: false,
+            self_stream: false,
+            self_video: false,
+            suppress: false,
+            request_to_speak_at: None,
+            raw_state_json: None,
+        }
+    }
+
+    #[test]
+    fn state_totals_follow_events() {
+        let start = datetime!(2026-10-18 00:00 UTC);
+        let events = [
+            event(1, start),
+            voice_state_events::Model {
+                self_mute: true,
+                ..event(1, start + Duration::hours(1))
+            },
+            voice_state_events::Model {
+                self_mute: true,
+                self_deaf: true,
+                self_stream: true,
+                ..event(1, start + Duration::hours(2))
+            },
+            // left without a leave event, only the span limits it
+            voice_state_events::Model {
+                self_video: true,
+                ..event(2, start)
+            },
+        ];
+        let spans = [
+            span(1, start, start + Duration::hours(3)),
+            span(2, start, start + Duration::hours(4)),
+            span(3, start, start + Duration::hours(1)),
+        ];
+
+        let totals = voice_state_totals(&events, &spans);
+        assert_eq!(
+            totals
+                .iter()
+                .map(|(user_id, _)| *user_id)
+                .collect::<Vec<_>>(),
+            vec![2, 1, 3]
+        );
+        assert_eq!(
+            totals[1].1,
+            VoiceStateTotals {
+                in_voice: Duration::hours(3),
+                muted: Duration::hours(2),
+                deafened: Duration::hours(1),
+                streaming: Duration::hours(1),
+                ..Default::default()
+            }
+        );
+        assert_eq!(totals[0].1.video, Duration::hours(4));
+        assert_eq!(totals[2].1.muted, Duration::ZERO);
+
+        // a span starting after the event still gets its state
+        let late = voice_state_totals(
+            &events,
+            &[span(
+                1,
+                start + Duration::minutes(90),
+                start + Duration::hours(3),
+            )],
+        );
+        assert_eq!(late[0].1.muted, Duration::minutes(90));
+
+        let mut server = VoiceStateTotals::default();
+        for (_, user_totals) in &totals {
+            server.merge(user_totals);
+        }
+        assert_eq!(server.in_voice, Duration::hours(8));
+        assert_eq!(server.video, Duration::hours(4));
+    }
+}
@@ -0,0 +1,475 @@
+//! Per guild voice activity feed: join, leave and move notices posted to a chosen text channel
+use std::sync::Arc;
+
+use ayaya_core::metrics::{DataOperationType, MetricsSink};
+use sea_orm::{ActiveValue, DatabaseConnection, IntoActiveModel, prelude::*};
+use snafu::ResultExt;
+
+use super::voice::{VoiceEventKind, VoiceStateUpdateInput};
+use super::{DataResult, utils::DataTiming};
+use crate::entity::{prelude::*, voice_feed_ignores, voice_feed_settings};
+use crate::error::DatabaseSnafu;
+
+/// Placeholders available in feed templates
+pub const FEED_PLACEHOLDERS: [&str; 5] = ["{user}", "{channel}", "{from}", "{to}", "{guild}"];
+
+const DEFAULT_JOIN_TEMPLATE: &str = "`{user}` joined the channel `{channel}` in guild `{guild}`";
+const DEFAULT_LEAVE_TEMPLATE: &str = "`{user}` left the channel `{channel}` in guild `{guild}`";
+const DEFAULT_MOVE_TEMPLATE: &str = "`{user}` moved from `{from}` to `{to}` in guild `{guild}`";
+
+/// What an ignore entry of the feed refers to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FeedIgnoreKind {
+    Channel,
+    User,
+}
+
+impl FeedIgnoreKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Channel => "channel",
+            Self::User => "user",
+        }
+    }
+}
+
+/// Voice feed configuration of a guild
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VoiceFeedConfig {
+    /// Text channel the notices are posted to
+    pub channel_id: u64,
+    pub enabled: bool,
+    pub announce_join: bool,
+    pub announce_leave: bool,
+    pub announce_move: bool,
+    /// Custom templates, see [`FEED_PLACEHOLDERS`]
+    pub join_template: Option<String>,
+    pub leave_template: Option<String>,
+    pub move_template: Option<String>,
+    /// Voice channels whose activity is not announced
+    pub ignored_channels: Vec<u64>,
+    /// Users whose activity is not announced
+    pub ignored_users: Vec<u64>,
+}
+
+impl VoiceFeedConfig {
+    /// An enabled feed posting every kind of notice to the channel, with the default templates
+    pub fn new(channel_id: u64) -> Self {
+        Self {
+            channel_id,
+            enabled: true,
+            announce_join: true,
+            announce_leave: true,
+            announce_move: true,
+            join_template: None,
+            leave_template: None,
+            move_template: None,
+            ignored_channels: Vec::new(),
+            ignored_users: Vec::new(),
+        }
+    }
+
+    /// The kind of notice to post for a voice state update, if one should be posted at all
+    pub fn notice_kind(&self, input: &VoiceStateUpdateInput) -> Option<VoiceEventKind> {
+        if !self.enabled || self.ignored_users.contains(&(input.user_id as u64)) {
+            return None;
+        }
+        let ignored_channel = |channel_id: Option<i64>| {
+            channel_id
+                .is_some_and(|channel_id| self.ignored_channels.contains(&(channel_id as u64)))
+        };
+
+        let kind = input.classify();
+        let announce = match kind {
+            VoiceEventKind::Join => self.announce_join && !ignored_channel(input.to_channel_id),
+            VoiceEventKind::Leave => self.announce_leave && !ignored_channel(input.from_channel_id),
+            // a move is only hidden if both ends are ignored
+            VoiceEventKind::Move => {
+                self.announce_move
+                    && !(ignored_channel(input.from_channel_id)
+                        && ignored_channel(input.to_channel_id))
+            }
+            VoiceEventKind::StateChange => false,
+        };
+        announce.then_some(kind)
+    }
+
+    /// The template used for a kind of notice
+    pub fn template(&self, kind: VoiceEventKind) -> Option<&str> {
+        let template = match kind {
+            VoiceEventKind::Join => self
+                .join_template
+                .as_deref()
+                .unwrap_or(DEFAULT_JOIN_TEMPLATE),
+            VoiceEventKind::Leave => self
+                .leave_template
+                .as_deref()
+                .unwrap_or(DEFAULT_LEAVE_TEMPLATE),
+            VoiceEventKind::Move => self
+                .move_template
+                .as_deref()
+                .unwrap_or(DEFAULT_MOVE_TEMPLATE),
+            VoiceEventKind::StateChange => return None,
+        };
+        Some(template)
+    }
+}
+
+/// Values filled into a feed template
+#[derive(Clone, Debug, Default)]
+pub struct FeedMessageValues<'a> {
+    pub user: &'a str,
+    pub guild: &'a str,
+    pub from: Option<&'a str>,
+    pub to: Option<&'a str>,
+}
+
+/// Fill the placeholders of a feed template. `{channel}` is the joined channel, or the left one
+/// if there is none.
+pub fn render_feed_message(template: &str, values: &FeedMessageValues<'_>) -> String {
+    let channel = values.to.or(values.from).unwrap_or_default();
+    template
+        .replace("{user}", values.user)
+        .replace("{guild}", values.guild)
+        .replace("{channel}", channel)
+        .replace("{from}", values.from.unwrap_or_default())
+        .replace("{to}", values.to.unwrap_or_default())
+}
+
+#[derive(Clone)]
+pub struct VoiceFeedManager {
+    db: DatabaseConnection,
+    metrics_handler: Arc<dyn MetricsSink>,
+}
+
+impl VoiceFeedManager {
+    /// Create a new instance of [`Self`]
+    pub fn new(db: DatabaseConnection, metrics_handler: Arc<dyn MetricsSink>) -> Self {
+        Self {
+            db,
+            metrics_handler,
+        }
+    }
+
+    /// Get the voice feed of a guild, with its ignore lists. None if never configured.
+    pub async fn get_voice_feed(&self, guild_id: u64) -> DataResult<Option<VoiceFeedConfig>> {
+        const OP: &str = "get_voice_feed";
+        self.metrics_handler
+            .data_access(OP, DataOperationType::Read)
+            .await;
+        let _timing = DataTiming::new(
+            OP.to_string(),
+            DataOperationType::Read,
+            Some(self.metrics_handler.clone()),
+        );
+
+        let Some(settings) = VoiceFeedSettings::find_by_id(guild_id as i64)
+            .one(&self.db)
+            .await
+            .context(DatabaseSnafu { operation: OP })?
+        else {
+            return Ok(None);
+        };
+
+        let ignores = VoiceFeedIgnores::find()
+            .filter(voice_feed_ignores::Column::GuildId.eq(guild_id as i64))
+            .all(&self.db)
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+        let ignored = |kind: FeedIgnoreKind| {
+            ignores
+                .iter()
+                .filter(|ignore| ignore.target_kind == kind.as_str())
+                .map(|ignore| ignore.target_id as u64)
+                .collect::<Vec<_>>()
+        };
+
+        Ok(Some(VoiceFeedConfig {
+            channel_id: settings.channel_id as u64,
+            enabled: settings.enabled,
+            announce_join: settings.announce_join,
+            announce_leave: settings.announce_leave,
+            announce_move: settings.announce_move,
+            join_template: settings.join_template,
+            leave_template: settings.leave_template,
+            move_template: settings.move_template,
+            ignored_channels: ignored(FeedIgnoreKind::Channel),
+            ignored_users: ignored(FeedIgnoreKind::User),
+        }))
+    }
+
+    /// Store the settings of a guild's voice feed. The ignore lists are changed with
+    /// [`Self::set_feed_ignored`].
+    pub async fn save_voice_feed(&self, guild_id: u64, config: &VoiceFeedConfig) -> DataResult<()> {
+        const OP: &str = "save_voice_feed";
+        self.metrics_handler
+            .data_access(OP, DataOperationType::Write)
+            .await;
+        let _timing = DataTiming::new(
+            OP.to_string(),
+            DataOperationType::Write,
+            Some(self.metrics_handler.clone()),
+        );
+
+        let existing = VoiceFeedSettings::find_by_id(guild_id as i64)
+            .one(&self.db)
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+
+        if let Some(model) = existing {
+            let mut active = model.into_active_model();
+            active.channel_id = ActiveValue::Set(config.channel_id as i64);
+            active.enabled = ActiveValue::Set(config.enabled);
+            active.announce_join = ActiveValue::Set(config.announce_join);
+            active.announce_leave = ActiveValue::Set(config.announce_leave);
+            active.announce_move = ActiveValue::Set(config.announce_move);
+            active.join_template = ActiveValue::Set(config.join_template.clone());
+            active.leave_template = ActiveValue::Set(config.leave_template.clone());
+            active.move_template = ActiveValue::Set(config.move_template.clone());
+            active
+                .update(&self.db)
+                .await
+                .context(DatabaseSnafu { operation: OP })?;
+        } else {
+            voice_feed_settings::ActiveModel {
+                guild_id: ActiveValue::Set(guild_id as i64),
+                channel_id: ActiveValue::Set(config.channel_id as i64),
+                enabled: ActiveValue::Set(config.enabled),
+                announce_join: ActiveValue::Set(config.announce_join),
+                announce_leave: ActiveValue::Set(config.announce_leave),
+                announce_move: ActiveValue::Set(config.announce_move),
+                join_template: ActiveValue::Set(config.join_template.clone()),
+                leave_template: ActiveValue::Set(config.leave_template.clone()),
+                move_template: ActiveValue::Set(config.move_template.clone()),
+            }
+            .insert(&self.db)
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+        }
+
+        Ok(())
+    }
+
+    /// Add or remove a channel or user from the feed's ignore list. Returns whether anything
+    /// changed.
+    pub async fn set_feed_ignored(
+        &self,
+        guild_id: u64,
+        kind: FeedIgnoreKind,
+        target_id: u64,
+        ignored: bool,
+    ) -> DataResult<bool> {
+        const OP: &str = "set_feed_ignored";
+        self.metrics_handler
+            .data_access(OP, DataOperationType::Write)
+            .await;
+        let _timing = DataTiming::new(
+            OP.to_string(),
+            DataOperationType::Write,
+            Some(self.metrics_handler.clone()),
+        );
+
+        let existing = VoiceFeedIgnores::find()
+            .filter(voice_feed_ignores::Column::GuildId.eq(guild_id as i64))
+            .filter(voice_feed_ignores::Column::TargetKind.eq(kind.as_str()))
+            .filter(voice_feed_ignores::Column::TargetId.eq(target_id as i64))
+            .one(&self.db)
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+
+        match (existing, ignored) {
+            (None, true) => {
+                voice_feed_ignores::ActiveModel {
+                    entry_id: ActiveValue::Set(Uuid::new_v4()),
+                    guild_id: ActiveValue::Set(guild_id as i64),
+                    target_kind: ActiveValue::Set(kind.as_str().to_string()),
+                    target_id: ActiveValue::Set(target_id as i64),
+                }
+                .insert(&self.db)
+                .await
+                .context(DatabaseSnafu { operation: OP })?;
+                Ok(true)
+            }
+            (Some(model), false) => {
+                model
+                    .delete(&self.db)
+                    .await
+                    .context(DatabaseSnafu { operation: OP })?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Remove the voice feed of a guild with its ignore lists. Returns whether it was configured.
+    pub async fn delete_voice_feed(&self, guild_id: u64) -> DataResult<bool> {
+        const OP: &str = "delete_voice_feed";
+        self.metrics_handler
+            .data_access(OP, DataOperationType::Write)
+            .await;
+        let _timing = DataTiming::new(
+            OP.to_string(),
+            DataOperationType::Write,
+            Some(self.metrics_handler.clone()),
+        );
+
+        VoiceFeedIgnores::delete_many()
+            .filter(voice_feed_ignores::Column::GuildId.eq(guild_id as i64))
+            .exec(&self.db)
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+        let result = VoiceFeedSettings::delete_by_id(guild_id as i64)
+            .exec(&self.db)
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+
+        Ok(result.rows_affected > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::*;
+    use ayaya_core::metrics::NoopMetrics;
+
+    use migration::Migrator as SqliteMigrator;
+    use migration::MigratorTrait;
+    use sea_orm::Database;
+    use time::OffsetDateTime;
+
+    async fn get_manager() -> VoiceFeedManager {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        SqliteMigrator::up(&db, None).await.unwrap();
+        VoiceFeedManager::new(db, Arc::new(NoopMetrics))
+    }
+
+    fn update(user_id: u64, from: Option<i64>, to: Option<i64>) -> VoiceStateUpdateInput {
+        VoiceStateUpdateInput {
+            guild_id: GUILD_ID_1 as i64,
+            user_id: user_id as i64,
+            from_channel_id: from,
+            to_channel_id: to,
+            occurred_at: OffsetDateTime::now_utc(),
+            self_mute: false,
+            self_deaf: false,
+            mute: false,
+            deaf: false,
+            self_stream: false,
+            self_video: false,
+            suppress: false,
+            request_to_speak_at: None,
+            raw_state_json: None,
+            start_is_estimated: false,
+        }
+    }
+
+    #[test]
+    fn notice_kind_filters() {
+        let user = USER_ID_1.get();
+        let mut config = VoiceFeedConfig::new(1);
+        assert_eq!(
+            config.notice_kind(&update(user, None, Some(10))),
+            Some(VoiceEventKind::Join)
+        );
+        assert_eq!(config.notice_kind(&update(user, Some(10), Some(10))), None);
+
+        config.announce_leave = false;
+        assert_eq!(config.notice_kind(&update(user, Some(10), None)), None);
+
+        config.ignored_channels = vec![10];
+        assert_eq!(config.notice_kind(&update(user, None, Some(10))), None);
+        assert_eq!(
+            config.notice_kind(&update(user, Some(10), Some(11))),
+            Some(VoiceEventKind::Move)
+        );
+        config.ignored_channels.push(11);
+        assert_eq!(config.notice_kind(&update(user, Some(10), Some(11))), None);
+
+        config.ignored_users = vec![USER_ID_2.get()];
+        assert_eq!(
+            config.notice_kind(&update(USER_ID_2.get(), None, Some(12))),
+            None
+        );
+
+        config.enabled = false;
+        assert_eq!(config.notice_kind(&update(user, None, Some(12))), None);
+    }
+
+    #[test]
+    fn render_templates() {
+        let config = VoiceFeedConfig::new(1);
+        let values = FeedMessageValues {
+            user: "ayaya",
+            guild: "hololive",
+            from: Some("lobby"),
+            to: Some("karaoke"),
+        };
+        assert_eq!(
+            render_feed_message(config.template(VoiceEventKind::Move).unwrap(), &values),
+            "`ayaya` moved from `lobby` to `karaoke` in guild `hololive`"
+        );
+
+        let values = FeedMessageValues { to: None, ..values };
+        assert_eq!(
+            render_feed_message("{user} left {channel}", &values),
+            "ayaya left lobby"
+        );
+        assert_eq!(config.template(VoiceEventKind::StateChange), None);
+    }
+
+    #[tokio::test]
+    async fn save_ignore_delete() {
+        let manager = get_manager().await;
+        assert!(manager.get_voice_feed(GUILD_ID_1).await.unwrap().is_none());
+
+        let mut config = VoiceFeedConfig::new(1);
+        config.announce_move = false;
+        config.join_template = Some("{user} is here".to_string());
+        manager.save_voice_feed(GUILD_ID_1, &config).await.unwrap();
+        assert_eq!(
+            manager.get_voice_feed(GUILD_ID_1).await.unwrap(),
+            Some(config.clone())
+        );
+
+        config.channel_id = 2;
+        manager.save_voice_feed(GUILD_ID_1, &config).await.unwrap();
+
+        assert!(
+            manager
+                .set_feed_ignored(GUILD_ID_1, FeedIgnoreKind::Channel, 10, true)
+                .await
+                .unwrap()
+        );
+        assert!(
+            !manager
+                .set_feed_ignored(GUILD_ID_1, FeedIgnoreKind::Channel, 10, true)
+                .await
+                .unwrap()
+        );
+        assert!(
+            manager
+                .set_feed_ignored(GUILD_ID_1, FeedIgnoreKind::User, USER_ID_1.get(), true)
+                .await
+                .unwrap()
+        );
+
+        let stored = manager.get_voice_feed(GUILD_ID_1).await.unwrap().unwrap();
+        assert_eq!(stored.channel_id, 2);
+        assert_eq!(stored.ignored_channels, vec![10]);
+        assert_eq!(stored.ignored_users, vec![USER_ID_1.get()]);
+
+        assert!(
+            manager
+                .set_feed_ignored(GUILD_ID_1, FeedIgnoreKind::Channel, 10, false)
+                .await
+                .unwrap()
+        );
+        let stored = manager.get_voice_feed(GUILD_ID_1).await.unwrap().unwrap();
+        assert!(stored.ignored_channels.is_empty());
+
+        assert!(manager.delete_voice_feed(GUILD_ID_1).await.unwrap());
+        assert!(!manager.delete_voice_feed(GUILD_ID_1).await.unwrap());
+        assert!(manager.get_voice_feed(GUILD_ID_1).await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,619 @@
+//! What the voice monitor keeps and for how long: per guild retention of the raw gateway state
+//! and of the history itself, and members who opted out of being recorded at all
+use std::sync::Arc;
+
+use ayaya_core::metrics::{DataOperationType, MetricsSink};
+use sea_orm::{
+    ActiveValue, Condition, DatabaseConnection, DatabaseTransaction, IntoActiveModel, QueryOrder,
+    TransactionTrait, prelude::*, sea_query::Expr,
+};
+use snafu::ResultExt;
+use time::{Duration, OffsetDateTime};
+
+use super::{DataResult, utils::DataTiming};
+use crate::entity::{
+    prelude::*, voice_opt_outs, voice_retention_settings, voice_sessions, voice_state_events,
+};
+use crate::error::DatabaseSnafu;
+
+/// How long voice data of a guild is kept. Nothing is pruned until a guild sets a policy.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// Days before the raw gateway state JSON is cleared, None keeps it forever. Sessions and
+    /// their flags are kept.
+    pub raw_state_days: Option<u32>,
+    /// Days before sessions and events are deleted, None keeps them forever
+    pub history_days: Option<u32>,
+}
+
+impl From<voice_retention_settings::Model> for RetentionPolicy {
+    fn from(value: voice_retention_settings::Model) -> Self {
+        Self {
+            // stored as 0, the column is not nullable
+            raw_state_days: (value.raw_state_days > 0).then_some(value.raw_state_days as u32),
+            history_days: value.history_days.map(|days| days.max(0) as u32),
+        }
+    }
+}
+
+/// Rows touched by a prune
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PruneReport {
+    /// Events and sessions whose raw JSON was cleared
+    pub raw_cleared: u64,
+    pub events_deleted: u64,
+    pub sessions_deleted: u64,
+}
+
+impl PruneReport {
+    fn merge(&mut self, other: PruneReport) {
+        self.raw_cleared += other.raw_cleared;
+        self.events_deleted += other.events_deleted;
+        self.sessions_deleted += other.sessions_deleted;
+    }
+}
+
+/// Everything stored about a member by the voice monitor
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UserVoiceData {
+    pub opted_out_at: Option<OffsetDateTime>,
+    pub sessions: Vec<voice_sessions::Model>,
+    pub events: Vec<voice_state_events::Model>,
+}
+
+#[derive(Clone)]
+pub struct VoicePrivacyManager {
+    db: DatabaseConnection,
+    metrics_handler: Arc<dyn MetricsSink>,
+}
+
+impl VoicePrivacyManager {
+    /// Create a new instance of [`Self`]
+    pub fn new(db: DatabaseConnection, metrics_handler: Arc<dyn MetricsSink>) -> Self {
+        Self {
+            db,
+            metrics_handler,
+        }
+    }
+
+    /// Get the retention policy of a guild, or the default if it was never configured
+    pub async fn get_retention_policy(&self, guild_id: u64) -> DataResult<RetentionPolicy> {
+        const OP: &str = "get_retention_policy";
+        self.metrics_handler
+            .data_access(OP, DataOperationType::Read)
+            .await;
+        let _timing = DataTiming::new(
+            OP.to_string(),
+            DataOperationType::Read,
+            Some(self.metrics_handler.clone()),
+        );
+
+        let model = VoiceRetentionSettings::find_by_id(guild_id as i64)
+            .one(&self.db)
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+
+        Ok(model.map(RetentionPolicy::from).unwrap_or_default())
+    }
+
+    /// Store the retention policy of a guild, replacing any previous one
+    pub async fn set_retention_policy(
+        &self,
+        guild_id: u64,
+        policy: &RetentionPolicy,
+    ) -> DataResult<()> {
+        const OP: &str = "set_retention_policy";
+        self.metrics_handler
+            .data_access(OP, DataOperationType::Write)
+            .await;
+        let _timing = DataTiming::new(
+            OP.to_string(),
+            DataOperationType::Write,
+            Some(self.metrics_handler.clone()),
+        );
+
+        let existing = VoiceRetentionSettings::find_by_id(guild_id as i64)
+            .one(&self.db)
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+
+        let raw_state_days = ActiveValue::Set(
+            policy
+                .raw_state_days
+                .map_or(0, |days| days.min(i32::MAX as u32) as i32),
+        );
+        let history_days = ActiveValue::Set(
+            policy
+                .history_days
+                .map(|days| days.min(i32::MAX as u32) as i32),
+        );
+
+        if let Some(model) = existing {
+            let mut active = model.into_active_model();
+            active.raw_state_days = raw_state_days;
+            active.history_days = history_days;
+            active
+                .update(&self.db)
+                .await
+                .context(DatabaseSnafu { operation: OP })?;
+        } else {
+            voice_retention_settings::ActiveModel {
+                guild_id: ActiveValue::Set(guild_id as i64),
+                raw_state_days,
+                history_days,
+            }
+            .insert(&self.db)
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+        }
+
+        Ok(())
+    }
+
+    /// Apply the retention policy of every guild that set one, the others keep everything
+    pub async fn prune_voice_data(&self, now: OffsetDateTime) -> DataResult<PruneReport> {
+        const OP: &str = "prune_voice_data";
+        self.metrics_handler
+            .data_access(OP, DataOperationType::Write)
+            .await;
+        let _timing = DataTiming::new(
+            OP.to_string(),
+            DataOperationType::Write,
+            Some(self.metrics_handler.clone()),
+        );
+
+        let configured = VoiceRetentionSettings::find()
+            .all(&self.db)
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+        let txn = self
+            .db
+            .begin()
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+
+        let mut report = PruneReport::default();
+        for model in configured {
+            let guild_id = model.guild_id;
+            let policy = RetentionPolicy::from(model);
+            report.merge(prune_guild(&txn, guild_id, &policy, now, OP).await?);
+        }
+
+        txn.commit()
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+
+        Ok(report)
+    }
+
+    /// Whether the member asked not to be recorded
+    pub async fn is_opted_out(&self, user_id: u64) -> DataResult<bool> {
+        const OP: &str = "is_voice_opted_out";
+        self.metrics_handler
+            .data_access(OP, DataOperationType::Read)
+            .await;
+        let _timing = DataTiming::new(
+            OP.to_string(),
+            DataOperationType::Read,
+            Some(self.metrics_handler.clone()),
+        );
+
+        let model = VoiceOptOuts::find_by_id(user_id as i64)
+            .one(&self.db)
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+
+        Ok(model.is_some())
+    }
+
+    /// Stop recording a member and delete their voice history in every guild. Returns the
+    /// number of deleted sessions and events.
+    pub async fn opt_out(&self, user_id: u64, now: OffsetDateTime) -> DataResult<u64> {
+        const OP: &str = "voice_opt_out";
+        self.metrics_handler
+            .data_access(OP, DataOperationType::Write)
+            .await;
+        let _timing = DataTiming::new(
+            OP.to_string(),
+            DataOperationType::Write,
+            Some(self.metrics_handler.clone()),
+        );
+
+        let txn = self
+            .db
+            .begin()
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+
+        let opted_out = VoiceOptOuts::find_by_id(user_id as i64)
+            .one(&txn)
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+        if opted_out.is_none() {
+            voice_opt_outs::ActiveModel {
+                user_id: ActiveValue::Set(user_id as i64),
+                opted_out_at: ActiveValue::Set(now),
+            }
+            .insert(&txn)
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+        }
+
+        let events = VoiceStateEvents::delete_many()
+            .filter(voice_state_events::Column::UserId.eq(user_id as i64))
+            .exec(&txn)
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+        let sessions = VoiceSessions::delete_many()
+            .filter(voice_sessions::Column::UserId.eq(user_id as i64))
+            .exec(&txn)
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+
+        txn.commit()
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+
+        Ok(events.rows_affected + sessions.rows_affected)
+    }
+
+    /// Record the member again from now on. Returns whether they were opted out.
+    pub async fn opt_in(&self, user_id: u64) -> DataResult<bool> {
+        const OP: &str = "voice_opt_in";
+        self.metrics_handler
+            .data_access(OP, DataOperationType::Write)
+            .await;
+        let _timing = DataTiming::new(
+            OP.to_string(),
+            DataOperationType::Write,
+            Some(self.metrics_handler.clone()),
+        );
+
+        let result = VoiceOptOuts::delete_by_id(user_id as i64)
+            .exec(&self.db)
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+
+        Ok(result.rows_affected > 0)
+    }
+
+    /// Everything stored about a member by the voice monitor, oldest first
+    pub async fn get_user_voice_data(&self, user_id: u64) -> DataResult<UserVoiceData> {
+        const OP: &str = "get_user_voice_data";
+        self.metrics_handler
+            .data_access(OP, DataOperationType::Read)
+            .await;
+        let _timing = DataTiming::new(
+            OP.to_string(),
+            DataOperationType::Read,
+            Some(self.metrics_handler.clone()),
+        );
+
+        let opted_out = VoiceOptOuts::find_by_id(user_id as i64)
+            .one(&self.db)
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+        let sessions = VoiceSessions::find()
+            .filter(voice_sessions::Column::UserId.eq(user_id as i64))
+            .order_by_asc(voice_sessions::Column::JoinedAt)
+            .all(&self.db)
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+        let events = VoiceStateEvents::find()
+            .filter(voice_state_events::Column::UserId.eq(user_id as i64))
+            .order_by_asc(voice_state_events::Column::OccurredAt)
+            .all(&self.db)
+            .await
+            .context(DatabaseSnafu { operation: OP })?;
+
+        Ok(UserVoiceData {
+            opted_out_at: opted_out.map(|model| model.opted_out_at),
+            sessions,
+            events,
+        })
+    }
+}
+
+/// Clear old raw JSON and delete old history of a guild. Open sessions, and the events since
+/// they started, are never deleted.
+async fn prune_guild(
+    txn: &DatabaseTransaction,
+    guild_id: i64,
+    policy: &RetentionPolicy,
+    now: OffsetDateTime,
+    operation: &str,
+) -> DataResult<PruneReport> {
+    let mut report = PruneReport::default();
+
+    if let Some(raw_state_days) = policy.raw_state_days {
+        report.raw_cleared += clear_raw_state(
+            txn,
+            guild_id,
+            now - Duration::days(raw_state_days as i64),
+            operation,
+        )
+        .await?;
+    }
+
+    if let Some(history_days) = policy.history_days {
+        let history_cutoff = now - Duration::days(history_days as i64);
+        // the states of open sessions are worked out from every event since they started
+        let open_sessions = VoiceSessions::find()
+            .filter(voice_sessions::Column::GuildId.eq(guild_id))
+            .filter(voice_sessions::Column::LeftAt.is_null())
+            .filter(voice_sessions::Column::JoinedAt.lt(history_cutoff))
+            .all(txn)
+            .await
+            .context(DatabaseSnafu { operation })?;
+        let mut events = VoiceStateEvents::delete_many()
+            .filter(voice_state_events::Column::GuildId.eq(guild_id))
+            .filter(voice_state_events::Column::OccurredAt.lt(history_cutoff));
+        for session in open_sessions {
+            events = events.filter(
+                Condition::any()
+                    .add(voice_state_events::Column::UserId.ne(session.user_id))
+                    .add(voice_state_events::Column::OccurredAt.lt(session.joined_at)),
+            );
+        }
+        report.events_deleted += events
+            .exec(txn)
+            .await
+            .context(DatabaseSnafu { operation })?
+            .rows_affected;
+        report.sessions_deleted += VoiceSessions::delete_many()
+            .filter(voice_sessions::Column::GuildId.eq(guild_id))
+            .filter(voice_sessions::Column::LeftAt.lt(history_cutoff))
+            .exec(txn)
+            .await
+            .context(DatabaseSnafu { operation })?
+            .rows_affected;
+    }
+
+    Ok(report)
+}
+
+/// Clear the raw JSON of a guild's events and sessions from before `raw_cutoff`
+async fn clear_raw_state(
+    txn: &DatabaseTransaction,
+    guild_id: i64,
+    raw_cutoff: OffsetDateTime,
+    operation: &str,
+) -> DataResult<u64> {
+    let mut raw_cleared = 0;
+    raw_cleared += VoiceStateEvents::update_many()
+        .col_expr(
+            voice_state_events::Column::RawStateJson,
+            Expr::value(Option::<String>::None),
+        )
+        .filter(voice_state_events::Column::GuildId.eq(guild_id))
+        .filter(voice_state_events::Column::OccurredAt.lt(raw_cutoff))
+        .filter(voice_state_events::Column::RawStateJson.is_not_null())
+        .exec(txn)
+        .await
+        .context(DatabaseSnafu { operation })?
+        .rows_affected;
+    raw_cleared += VoiceSessions::update_many()
+        .col_expr(
+            voice_sessions::Column::JoinStateJson,
+            Expr::value(Option::<String>::None),
+        )
+        .filter(voice_sessions::Column::GuildId.eq(guild_id))
+        .filter(voice_sessions::Column::JoinedAt.lt(raw_cutoff))
+        .filter(voice_sessions::Column::JoinStateJson.is_not_null())
+        .exec(txn)
+        .await
+        .context(DatabaseSnafu { operation })?
+        .rows_affected;
+    raw_cleared += VoiceSessions::update_many()
+        .col_expr(
+            voice_sessions::Column::LeaveStateJson,
+            Expr::value(Option::<String>::None),
+        )
+        .filter(voice_sessions::Column::GuildId.eq(guild_id))
+        .filter(voice_sessions::Column::LeftAt.lt(raw_cutoff))
+        .filter(voice_sessions::Column::LeaveStateJson.is_not_null())
+        .exec(txn)
+        .await
+        .context(DatabaseSnafu { operation })?
+        .rows_affected;
+    Ok(raw_cleared)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::*;
+    use crate::data::voice::{VoiceManager, VoiceStateUpdateInput};
+    use ayaya_core::metrics::NoopMetrics;
+
+    use migration::Migrator as SqliteMigrator;
+    use migration::MigratorTrait;
+    use sea_orm::Database;
+    use time::macros::datetime;
+
+    async fn get_managers() -> (VoicePrivacyManager, VoiceManager) {
+        let db = Database::connect("sqlite::memory:").await.unwrap();
+        SqliteMigrator::up(&db, None).await.unwrap();
+        (
+            VoicePrivacyManager::new(db.clone(), Arc::new(NoopMetrics)),
+            VoiceManager::new(db, Arc::new(NoopMetrics)),
+        )
+    }
+
+    fn update(
+        guild_id: u64,
+        user_id: u64,
+        from: Option<i64>,
+        to: Option<i64>,
+        occurred_at: OffsetDateTime,
+    ) -> VoiceStateUpdateInput {
+        VoiceStateUpdateInput {
+            guild_id: guild_id as i64,
+            user_id: user_id as i64,
+            from_channel_id: from,
+            to_channel_id: to,
+            occurred_at,
+            self_mute: false,
+            self_deaf: false,
+            mute: false,
+            deaf: false,
+            self_stream: false,
+            self_video: false,
+            suppress: false,
+            request_to_speak_at: None,
+            raw_state_json: Some("{}".to_string()),
+            start_is_estimated: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn retention_policy() {
+        let (manager, _) = get_managers().await;
+        assert_eq!(
+            manager.get_retention_policy(GUILD_ID_1).await.unwrap(),
+            RetentionPolicy::default()
+        );
+
+        let policy = RetentionPolicy {
+            raw_state_days: Some(7),
+            history_days: Some(365),
+        };
+        manager
+            .set_retention_policy(GUILD_ID_1, &policy)
+            .await
+            .unwrap();
+        assert_eq!(
+            manager.get_retention_policy(GUILD_ID_1).await.unwrap(),
+            policy
+        );
+
+        let policy = RetentionPolicy {
+            raw_state_days: None,
+            history_days: None,
+        };
+        manager
+            .set_retention_policy(GUILD_ID_1, &policy)
+            .await
+            .unwrap();
+        assert_eq!(
+            manager.get_retention_policy(GUILD_ID_1).await.unwrap(),
+            policy
+        );
+    }
+
+    #[tokio::test]
+    async fn prune_by_policy() {
+        let (manager, voice) = get_managers().await;
+        let start = datetime!(2026-01-01 00:00 UTC);
+        let user = USER_ID_1.get();
+        let other_guild = GUILD_ID_1 + 1;
+
+        for guild_id in [GUILD_ID_1, other_guild] {
+            for input in [
+                update(guild_id, user, None, Some(1), start),
+                update(guild_id, user, Some(1), None, start + Duration::hours(1)),
+                // still open, never deleted
+                update(guild_id, user, None, Some(1), start + Duration::days(2)),
+            ] {
+                voice.apply_voice_state_update(input).await.unwrap();
+            }
+        }
+        manager
+            .set_retention_policy(
+                GUILD_ID_1,
+                &RetentionPolicy {
+                    raw_state_days: Some(1),
+                    history_days: Some(1),
+                },
+            )
+            .await
+            .unwrap();
+
+        // guilds without a policy keep everything
+        let now = start + Duration::days(10);
+        let report = manager.prune_voice_data(now).await.unwrap();
+        assert_eq!(report.sessions_deleted, 1);
+        assert_eq!(report.events_deleted, 2);
+
+        // the join event of the open session is kept
+        let events = manager.get_user_voice_data(user).await.unwrap().events;
+        let kept = events
+            .iter()
+            .filter(|event| event.guild_id == GUILD_ID_1 as i64)
+            .collect::<Vec<_>>();
+        assert_eq!(kept.len(), 1);
+        assert_eq!(kept[0].occurred_at, start + Duration::days(2));
+
+        let sessions = voice.get_server_voice_sessions(GUILD_ID_1).await.unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert!(sessions[0].left_at.is_none());
+        assert!(sessions[0].join_state_json.is_none());
+        let untouched = voice.get_server_voice_sessions(other_guild).await.unwrap();
+        assert_eq!(untouched.len(), 2);
+        assert!(
+            untouched
+                .iter()
+                .all(|session| session.join_state_json.is_some())
+        );
+
+        let report = manager
+            .prune_voice_data(start + Duration::days(400))
+            .await
+            .unwrap();
+        assert_eq!(report.sessions_deleted, 0);
+        assert_eq!(report.events_deleted, 0);
+        assert_eq!(report.raw_cleared, 0);
+        let untouched = voice.get_server_voice_sessions(other_guild).await.unwrap();
+        assert_eq!(untouched.len(), 2);
+        assert!(
+            untouched
+                .iter()
+                .all(|session| session.join_state_json.is_some())
+        );
+    }
+
+    #[tokio::test]
+    async fn opt_out_deletes_history() {
+        let (manager, voice) = get_managers().await;
+        let start = datetime!(2026-01-01 00:00 UTC);
+        let (user_1, user_2) = (USER_ID_1.get(), USER_ID_2.get());
+        for input in [
+            update(GUILD_ID_1, user_1, None, Some(1), start),
+            update(GUILD_ID_1, user_2, None, Some(1), start),
+            update(
+                GUILD_ID_1,
+                user_1,
+                Some(1),
+                None,
+                start + Duration::hours(1),
+            ),
+        ] {
+            voice.apply_voice_state_update(input).await.unwrap();
+        }
+
+        let data = manager.get_user_voice_data(user_1).await.unwrap();
+        assert_eq!(data.sessions.len(), 1);
+        assert_eq!(data.events.len(), 2);
+        assert!(data.opted_out_at.is_none());
+
+        assert!(!manager.is_opted_out(user_1).await.unwrap());
+        assert_eq!(manager.opt_out(user_1, start).await.unwrap(), 3);
+        assert!(manager.is_opted_out(user_1).await.unwrap());
+
+        let data = manager.get_user_voice_data(user_1).await.unwrap();
+        assert!(data.sessions.is_empty() && data.events.is_empty());
+        assert_eq!(data.opted_out_at, Some(start));
+        assert_eq!(
+            voice
+                .get_server_voice_sessions(GUILD_ID_1)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+
+        assert!(manager.opt_in(user_1).await.unwrap());
+        assert!(!manager.opt_in(user_1).await.unwrap());
+        assert!(!manager.is_opted_out(user_1).await.unwrap());
+    }
+}
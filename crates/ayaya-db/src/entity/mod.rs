@@ -4,19 +4,32 @@ pub mod ak_end_char_pull;
 pub mod ak_end_import_state;
 pub mod ak_end_user;
 pub mod ak_end_weap_pull;
+pub mod always_on_channels;
+pub mod audit_log;
 pub mod ban_shit_music;
 pub mod ban_user_command_use;
 pub mod command_allow_user;
 pub mod command_call_log;
+pub mod command_channel_scope;
+pub mod command_cooldown;
+pub mod command_deny_rule;
 pub mod dashboard_allowlist;
 pub mod dashboard_tokens;
+pub mod digest_settings;
+pub mod guild_idle_settings;
+pub mod guild_settings;
 pub mod require_category_role;
 pub mod require_command_role;
+pub mod song_play_log;
 pub mod song_queues;
 pub mod sounds;
 pub mod upload_noticed;
 pub mod user_command_all_time_statistics;
 pub mod user_play_queries;
+pub mod voice_feed_ignores;
+pub mod voice_feed_settings;
+pub mod voice_opt_outs;
+pub mod voice_retention_settings;
 pub mod voice_sessions;
 pub mod voice_state_events;
 pub mod wuwa_import_state;
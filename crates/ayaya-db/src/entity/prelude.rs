@@ -4,17 +4,30 @@ pub use super::ak_end_char_pull::Entity as AkEndCharPull;
 pub use super::ak_end_import_state::Entity as AkEndImportState;
 pub use super::ak_end_user::Entity as AkEndUser;
 pub use super::ak_end_weap_pull::Entity as AkEndWeapPull;
+pub use super::always_on_channels::Entity as AlwaysOnChannels;
+pub use super::audit_log::Entity as AuditLog;
 pub use super::ban_shit_music::Entity as BanShitMusic;
 pub use super::ban_user_command_use::Entity as BanUserCommandUse;
 pub use super::command_allow_user::Entity as CommandAllowUser;
 pub use super::command_call_log::Entity as CommandCallLog;
+pub use super::command_channel_scope::Entity as CommandChannelScope;
+pub use super::command_cooldown::Entity as CommandCooldown;
+pub use super::command_deny_rule::Entity as CommandDenyRule;
+pub use super::digest_settings::Entity as DigestSettings;
+pub use super::guild_idle_settings::Entity as GuildIdleSettings;
+pub use super::guild_settings::Entity as GuildSettings;
 pub use super::require_category_role::Entity as RequireCategoryRole;
 pub use super::require_command_role::Entity as RequireCommandRole;
+pub use super::song_play_log::Entity as SongPlayLog;
 pub use super::song_queues::Entity as SongQueues;
 pub use super::sounds::Entity as Sounds;
 pub use super::upload_noticed::Entity as UploadNoticed;
 pub use super::user_command_all_time_statistics::Entity as UserCommandAllTimeStatistics;
 pub use super::user_play_queries::Entity as UserPlayQueries;
+pub use super::voice_feed_ignores::Entity as VoiceFeedIgnores;
+pub use super::voice_feed_settings::Entity as VoiceFeedSettings;
+pub use super::voice_opt_outs::Entity as VoiceOptOuts;
+pub use super::voice_retention_settings::Entity as VoiceRetentionSettings;
 pub use super::voice_sessions::Entity as VoiceSessions;
 pub use super::voice_state_events::Entity as VoiceStateEvents;
 pub use super::wuwa_import_state::Entity as WuwaImportState;
@@ -27,17 +40,30 @@ pub use super::ak_end_char_pull::Model as AkEndCharPullModel;
 pub use super::ak_end_import_state::Model as AkEndImportStateModel;
 pub use super::ak_end_user::Model as AkEndUserModel;
 pub use super::ak_end_weap_pull::Model as AkEndWeapPullModel;
+pub use super::always_on_channels::Model as AlwaysOnChannelsModel;
+pub use super::audit_log::Model as AuditLogModel;
 pub use super::ban_shit_music::Model as BanShitMusicModel;
 pub use super::ban_user_command_use::Model as BanUserCommandUseModel;
 pub use super::command_allow_user::Model as CommandAllowUserModel;
 pub use super::command_call_log::Model as CommandCallLogModel;
+pub use super::command_channel_scope::Model as CommandChannelScopeModel;
+pub use super::command_cooldown::Model as CommandCooldownModel;
+pub use super::command_deny_rule::Model as CommandDenyRuleModel;
+pub use super::digest_settings::Model as DigestSettingsModel;
+pub use super::guild_idle_settings::Model as GuildIdleSettingsModel;
+pub use super::guild_settings::Model as GuildSettingsModel;
 pub use super::require_category_role::Model as RequireCategoryRoleModel;
 pub use super::require_command_role::Model as RequireCommandRoleModel;
+pub use super::song_play_log::Model as SongPlayLogModel;
 pub use super::song_queues::Model as SongQueuesModel;
 pub use super::sounds::Model as SoundsModel;
 pub use super::upload_noticed::Model as UploadNoticedModel;
 pub use super::user_command_all_time_statistics::Model as UserCommandAllTimeStatisticsModel;
 pub use super::user_play_queries::Model as UserPlayQueriesModel;
+pub use super::voice_feed_ignores::Model as VoiceFeedIgnoresModel;
+pub use super::voice_feed_settings::Model as VoiceFeedSettingsModel;
+pub use super::voice_opt_outs::Model as VoiceOptOutsModel;
+pub use super::voice_retention_settings::Model as VoiceRetentionSettingsModel;
 pub use super::voice_sessions::Model as VoiceSessionsModel;
 pub use super::voice_state_events::Model as VoiceStateEventsModel;
 pub use super::wuwa_import_state::Model as WuwaImportStateModel;
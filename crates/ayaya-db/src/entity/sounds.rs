@@ -11,6 +11,7 @@ pub struct Model {
     pub uploaded_server_id: i64,
     pub sound_name: String,
     pub public: bool,
+    pub uploaded_at: Option<TimeDateTimeWithTimeZone>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]